@@ -0,0 +1,106 @@
+//! Memory retrieval: the indexed path vs the original linear scan.
+//!
+//!     cargo bench -p anemone-core --bench retrieval -- [memories] [dimensions]
+//!
+//! Builds a synthetic stream (default 20000 memories of 256 dimensions) in
+//! the legacy single-file format, times migrating and loading it, then times both retrieval paths over the same queries
+//! and reports how often the indexed path returns the exact top result.
+
+use std::io::Write;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anemone_core::config::Config;
+use anemone_core::memory::MemoryStream;
+use anemone_core::providers::{LlmProvider, MockProvider};
+use anemone_core::types::Memory;
+use rand::{Rng, SeedableRng};
+
+const QUERIES: usize = 200;
+const TOP_K: usize = 5;
+
+fn main() {
+    let numbers: Vec<usize> = std::env::args().filter_map(|a| a.parse().ok()).collect();
+    let count = numbers.first().copied().unwrap_or(20_000);
+    let dim = numbers.get(1).copied().unwrap_or(256);
+    let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+
+    let dir = tempfile::tempdir().expect("tempdir");
+    let start = chrono::Utc::now() - chrono::TimeDelta::minutes(count as i64);
+    let mut file = std::fs::File::create(dir.path().join("memory_stream.jsonl")).unwrap();
+    let mut embeddings = Vec::with_capacity(count);
+    for i in 0..count {
+        let embedding: Vec<f64> = (0..dim).map(|_| rng.gen_range(-1.0..1.0)).collect();
+        let memory = Memory {
+            id: format!("m_{:04}", i),
+            timestamp: (start + chrono::TimeDelta::minutes(i as i64)).to_rfc3339(),
+            kind: "thought".to_string(),
+            content: format!("synthetic memory {}", i),
+            importance: rng.gen_range(1..=10),
+            depth: 0,
+            references: Vec::new(),
+            embedding: embedding.clone(),
+            pinned: false,
+            embedding_model: String::new(),
+            embedding_dim: 0,
+        };
+        writeln!(file, "{}", serde_json::to_string(&memory).unwrap()).unwrap();
+        embeddings.push(embedding);
+    }
+    drop(file);
+    println!("{} memories, {} dimensions", count, dim);
+
+    let provider: Arc<dyn LlmProvider> = Arc::new(MockProvider::new());
+    let timed = Instant::now();
+    let stream = MemoryStream::new(dir.path(), Config::default(), Arc::clone(&provider));
+    println!("migrate + index build  {:>10.1?}", timed.elapsed());
+    let timed = Instant::now();
+    drop(MemoryStream::new(dir.path(), Config::default(), provider));
+    println!("reload (saved index)   {:>10.1?}", timed.elapsed());
+
+    // Queries near existing memories, like a thought about something remembered
+    let queries: Vec<Vec<f64>> = (0..QUERIES)
+        .map(|_| {
+            let source = &embeddings[rng.gen_range(0..count)];
+            source.iter().map(|x| x + rng.gen_range(-0.3..0.3)).collect()
+        })
+        .collect();
+
+    let mut linear = Duration::ZERO;
+    let mut indexed = Duration::ZERO;
+    let mut same_top = 0;
+    let mut overlap = 0;
+    for query in &queries {
+        let timed = Instant::now();
+        let expected = stream.retrieve_exhaustive(query, TOP_K);
+        linear += timed.elapsed();
+
+        let timed = Instant::now();
+        let found = stream.retrieve_by_embedding(query, TOP_K);
+        indexed += timed.elapsed();
+
+        if found.first().map(|m| &m.id) == expected.first().map(|m| &m.id) {
+            same_top += 1;
+        }
+        overlap += found
+            .iter()
+            .filter(|m| expected.iter().any(|e| e.id == m.id))
+            .count();
+    }
+
+    println!("linear scan / query    {:>10.1?}", linear / QUERIES as u32);
+    println!("indexed / query        {:>10.1?}", indexed / QUERIES as u32);
+    println!(
+        "speedup                {:>9.1}x",
+        linear.as_secs_f64() / indexed.as_secs_f64().max(f64::EPSILON)
+    );
+    println!(
+        "same top result        {:>9.1}%",
+        100.0 * same_top as f64 / QUERIES as f64
+    );
+    println!(
+        "top-{} overlap          {:>9.1}%",
+        TOP_K,
+        100.0 * overlap as f64 / (QUERIES * TOP_K) as f64
+    );
+}
@@ -2,25 +2,52 @@
 
 use std::collections::HashSet;
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
 
 use serde_json::json;
-use tokio::sync::{broadcast, mpsc};
+use tokio::sync::{broadcast, mpsc, oneshot, watch};
 use tracing::{error, info, warn};
 
 use crate::config::Config;
 use crate::events::BrainEvent;
-use crate::memory::MemoryStream;
+use crate::journal::{self, Journal, RingBuffer};
+use crate::mcp;
+use crate::memory::inspect::{MemoryReply, MemoryRequest};
+use crate::memory::reflection::{parse_insight, strip_list_marker};
+use crate::memory::{MemoryQuery, MemoryStream};
 use crate::prompts::{
-    main_system_prompt, FOCUS_NUDGE, PLANNING_PROMPT, REFLECTION_PROMPT,
+    main_system_prompt, BELIEFS_PROMPT, DREAM_MERGE_PROMPT, FOCUS_NUDGE, PLANNING_PROMPT,
+    REFLECTION_PROMPT, REFLECTION_QUESTIONS_PROMPT,
 };
-use crate::providers;
-use crate::tools;
-use crate::tools::shell::{IGNORE_FILES, INTERNAL_ROOT_FILES};
+use crate::providers::{self, LlmProvider};
+use crate::tools::{Tool, ToolContext, ToolOutput, ToolRegistry};
+use crate::tools::egress::EgressMode;
+use crate::tools::shell::{IGNORE_DIRS, IGNORE_FILES, INTERNAL_ROOT_FILES};
 use crate::types::*;
+use crate::usage::{BudgetStatus, UsageCategory, UsageMeter, UsageReport};
 
 /// Planning frequency — plan every N think cycles
 pub const PLAN_INTERVAL: u32 = 10;
 
+/// Memories retrieved as evidence for each reflection question
+const REFLECTION_EVIDENCE: usize = 8;
+
+/// Most memories re-embedded in one cycle, however long the pause before it
+const REEMBED_MAX_BATCH: usize = 100;
+
+/// Near-duplicate groups merged per dream — each merge is an LLM call
+const DREAM_MAX_MERGES: usize = 5;
+
+/// Insights the anemone reviews when rewriting beliefs.md
+const BELIEF_INSIGHTS: usize = 15;
+
+/// How long [`BrainHandle::memory`] waits — the brain answers between
+/// steps, so a request can sit behind a whole think cycle
+const MEMORY_REQUEST_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(120);
+
+/// What the wake nudge recalls memories about
+const WAKE_QUERY: &str = "what was I working on and thinking about";
+
 /// Messages that can be sent TO the brain (from API/TUI)
 #[derive(Debug)]
 pub enum BrainCommand {
@@ -28,86 +55,367 @@ pub enum BrainCommand {
     ConversationReply(String),
     SetFocusMode(bool),
     Snapshot(String),
+    /// Inspect or edit the memory stream; the reply comes back on the sender
+    Memory(MemoryRequest, oneshot::Sender<anyhow::Result<MemoryReply>>),
     Stop,
 }
 
-/// The Brain — runs as an independent tokio task.
+/// Recent event and API-call history, shared read-only with every
+/// [`BrainHandle`]. The full record lives in the on-disk journals.
+///
+/// Only the brain task writes to it, and never across an `.await`, so readers
+/// are blocked for at most a single push.
+#[derive(Debug)]
+struct History {
+    events: RingBuffer<EventEntry>,
+    api_calls: RingBuffer<ApiCallRecord>,
+}
+
+type SharedHistory = Arc<RwLock<History>>;
+
+fn read_history(history: &SharedHistory) -> RwLockReadGuard<'_, History> {
+    history.read().unwrap_or_else(|e| e.into_inner())
+}
+
+fn write_history(history: &SharedHistory) -> RwLockWriteGuard<'_, History> {
+    history.write().unwrap_or_else(|e| e.into_inner())
+}
+
+fn collect_files(root: &Path, current: &Path, out: &mut Vec<String>) {
+    if let Ok(entries) = std::fs::read_dir(current) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if entry.file_name().to_string_lossy().starts_with('.') {
+                continue;
+            }
+            if path.is_dir() {
+                collect_files(root, &path, out);
+            } else if let Ok(rel) = path.strip_prefix(root) {
+                out.push(rel.to_string_lossy().to_string());
+            }
+        }
+    }
+}
+
+/// Cheap, cloneable handle to a running [`Brain`].
+///
+/// Frontends talk to the brain only through this: commands go in over an mpsc
+/// channel, events come out over broadcast, and the latest state is published
+/// on a watch channel — no lock on the brain itself is ever taken.
+#[derive(Clone)]
+pub struct BrainHandle {
+    env_path: PathBuf,
+    command_tx: mpsc::Sender<BrainCommand>,
+    event_tx: broadcast::Sender<BrainEvent>,
+    snapshot_rx: watch::Receiver<BrainSnapshot>,
+    history: SharedHistory,
+    usage: UsageMeter,
+}
+
+impl BrainHandle {
+    /// Subscribe to live events from the brain.
+    pub fn subscribe(&self) -> broadcast::Receiver<BrainEvent> {
+        self.event_tx.subscribe()
+    }
+
+    /// Send a command to the brain task.
+    pub async fn send(
+        &self,
+        command: BrainCommand,
+    ) -> Result<(), mpsc::error::SendError<BrainCommand>> {
+        self.command_tx.send(command).await
+    }
+
+    /// Ask the brain to inspect or edit its memory stream.
+    pub async fn memory(&self, request: MemoryRequest) -> anyhow::Result<MemoryReply> {
+        let (reply_tx, reply_rx) = oneshot::channel();
+        self.command_tx
+            .send(BrainCommand::Memory(request, reply_tx))
+            .await
+            .map_err(|_| anyhow::anyhow!("brain is not running"))?;
+        tokio::time::timeout(MEMORY_REQUEST_TIMEOUT, reply_rx)
+            .await
+            .map_err(|_| anyhow::anyhow!("brain is busy, try again"))?
+            .map_err(|_| anyhow::anyhow!("brain stopped before replying"))?
+    }
+
+    /// Latest published state.
+    pub fn snapshot(&self) -> BrainSnapshot {
+        self.snapshot_rx.borrow().clone()
+    }
+
+    /// A receiver that is notified whenever the snapshot changes.
+    pub fn watch(&self) -> watch::Receiver<BrainSnapshot> {
+        self.snapshot_rx.clone()
+    }
+
+    pub fn identity(&self) -> Identity {
+        self.snapshot_rx.borrow().identity.clone()
+    }
+
+    pub fn env_path(&self) -> &Path {
+        &self.env_path
+    }
+
+    pub fn is_waiting_for_reply(&self) -> bool {
+        self.snapshot_rx.borrow().waiting_for_reply
+    }
+
+    /// Send the owner's message: the reply it's waiting for, if it asked
+    /// something, otherwise a new message.
+    pub async fn message(&self, text: String) -> anyhow::Result<()> {
+        let command = if self.is_waiting_for_reply() {
+            BrainCommand::ConversationReply(text)
+        } else {
+            BrainCommand::UserMessage(text)
+        };
+        self.send(command)
+            .await
+            .map_err(|_| anyhow::anyhow!("brain is not running"))
+    }
+
+    /// Every file in the box, relative to it and sorted. Hidden files and
+    /// directories are left out.
+    pub fn files(&self) -> Vec<String> {
+        let root = self.box_root();
+        let mut files = Vec::new();
+        collect_files(&root, &root, &mut files);
+        files.sort();
+        files
+    }
+
+    /// Where `path` (relative to the box) really is, or `None` if it leads
+    /// outside the box.
+    pub fn box_path(&self, path: &str) -> Option<PathBuf> {
+        let root = self.box_root();
+        let full = root.join(path);
+        let real = full.canonicalize().unwrap_or(full);
+        real.starts_with(&root).then_some(real)
+    }
+
+    fn box_root(&self) -> PathBuf {
+        self.env_path
+            .canonicalize()
+            .unwrap_or_else(|_| self.env_path.clone())
+    }
+
+    /// The most recent `limit` event entries, oldest first.
+    pub fn recent_events(&self, limit: usize) -> Vec<EventEntry> {
+        read_history(&self.history).events.recent(limit)
+    }
+
+    /// The most recent `limit` API call records, oldest first.
+    pub fn recent_api_calls(&self, limit: usize) -> Vec<ApiCallRecord> {
+        read_history(&self.history).api_calls.recent(limit)
+    }
+
+    /// Cumulative token usage and cost.
+    pub fn usage(&self) -> UsageReport {
+        self.usage.report()
+    }
+
+    /// Where usage stands against the configured budgets.
+    pub fn budget(&self) -> BudgetStatus {
+        self.usage.budget_status()
+    }
+}
+
+/// The Brain — an actor that owns all of its state and runs as an
+/// independent tokio task. Create it, grab a [`BrainHandle`], then
+/// `tokio::spawn(brain.run())`.
 pub struct Brain {
-    pub identity: Identity,
-    pub env_path: PathBuf,
-    pub events: Vec<EventEntry>,
-    pub api_calls: Vec<ApiCallRecord>,
-    pub thought_count: u32,
-    pub state: BrainState,
-    pub position: Position,
-    pub latest_snapshot: Option<String>,
-
-    pub event_tx: broadcast::Sender<BrainEvent>,
-    pub command_tx: mpsc::Sender<BrainCommand>,
-    command_rx: Option<mpsc::Receiver<BrainCommand>>,
+    identity: Identity,
+    env_path: PathBuf,
+    history: SharedHistory,
+    usage: UsageMeter,
+    events_journal: Journal,
+    api_calls_journal: Journal,
+    thought_count: u32,
+    state: BrainState,
+    position: Position,
+    latest_snapshot: Option<String>,
+
+    event_tx: broadcast::Sender<BrainEvent>,
+    command_tx: mpsc::Sender<BrainCommand>,
+    command_rx: mpsc::Receiver<BrainCommand>,
+    snapshot_tx: watch::Sender<BrainSnapshot>,
 
     stream: Option<MemoryStream>,
     config: Config,
+    provider: Arc<dyn LlmProvider>,
+    /// The tools this anemone may call
+    tools: ToolRegistry,
 
     seen_env_files: HashSet<String>,
     inbox_pending: Vec<NewFileInfo>,
     cycles_since_plan: u32,
+    cycles_since_dream: u32,
+    /// When the last re-embedding batch ran
+    reembedded_at: Option<std::time::Instant>,
     current_focus: String,
     focus_mode: bool,
     consecutive_research_cycles: u32,
 
     user_message: Option<String>,
     waiting_for_reply: bool,
-    conversation_reply: Option<tokio::sync::oneshot::Sender<String>>,
+    running: bool,
+    woken: bool,
+    /// Budget level last announced to frontends
+    budget: BudgetStatus,
+    /// Stop on its own after this many cycles (tests and one-shot runs)
+    cycle_limit: Option<u32>,
 }
 
 impl Brain {
+    /// Create a brain for the box at `env_path`, replaying its event and
+    /// API-call journals so history and the thought count survive restarts.
+    /// The LLM provider is picked from `config.provider`.
     pub fn new(identity: Identity, env_path: PathBuf, config: Config) -> Self {
-        let (event_tx, _) = broadcast::channel(256);
+        let provider = providers::from_config(&config);
+        Self::with_provider(identity, env_path, config, provider)
+    }
+
+    /// Like [`Brain::new`], with an explicit provider — e.g. one built from a
+    /// custom [`providers::ProviderRegistry`], or a mock in tests.
+    pub fn with_provider(
+        identity: Identity,
+        env_path: PathBuf,
+        config: Config,
+        provider: Arc<dyn LlmProvider>,
+    ) -> Self {
+        // Roomy enough for a burst of streamed thought deltas
+        let (event_tx, _) = broadcast::channel(1024);
         let (command_tx, command_rx) = mpsc::channel(32);
 
+        let events_journal = Journal::open(
+            &env_path,
+            journal::EVENTS_FILENAME,
+            config.journal_max_bytes,
+            config.journal_max_files,
+        );
+        let api_calls_journal = Journal::open(
+            &env_path,
+            journal::API_CALLS_FILENAME,
+            config.journal_max_bytes,
+            config.journal_max_files,
+        );
+        let events: Vec<EventEntry> = events_journal.replay(config.event_history_limit);
+        let api_calls: Vec<ApiCallRecord> =
+            api_calls_journal.replay(config.api_call_history_limit);
+        let thought_count = events.iter().map(|e| e.thought_number).max().unwrap_or(0);
+        if !events.is_empty() {
+            info!(
+                "{}: replayed {} events, {} API calls (thought #{})",
+                identity.name,
+                events.len(),
+                api_calls.len(),
+                thought_count
+            );
+        }
+        let history = History {
+            events: RingBuffer::from_vec(events, config.event_history_limit),
+            api_calls: RingBuffer::from_vec(api_calls, config.api_call_history_limit),
+        };
+
+        let position = Position { x: 5, y: 5 };
+        let (snapshot_tx, _) = watch::channel(BrainSnapshot {
+            identity: identity.clone(),
+            state: BrainState::Idle,
+            thought_count,
+            position: position.clone(),
+            focus_mode: false,
+            waiting_for_reply: false,
+        });
+
+        let usage = UsageMeter::load(&env_path, &config);
+        let tools = ToolRegistry::default().configured(&config, &identity.name);
+
         Self {
             identity,
             env_path,
-            events: Vec::new(),
-            api_calls: Vec::new(),
-            thought_count: 0,
+            history: Arc::new(RwLock::new(history)),
+            usage,
+            events_journal,
+            api_calls_journal,
+            thought_count,
             state: BrainState::Idle,
-            position: Position { x: 5, y: 5 },
+            position,
             latest_snapshot: None,
             event_tx,
             command_tx,
-            command_rx: Some(command_rx),
+            command_rx,
+            snapshot_tx,
             stream: None,
             config,
+            provider,
+            tools,
             seen_env_files: HashSet::new(),
             inbox_pending: Vec::new(),
             cycles_since_plan: 0,
+            cycles_since_dream: 0,
+            reembedded_at: None,
             current_focus: String::new(),
             focus_mode: false,
             consecutive_research_cycles: 0,
             user_message: None,
             waiting_for_reply: false,
-            conversation_reply: None,
+            running: true,
+            woken: false,
+            budget: BudgetStatus::Ok,
+            cycle_limit: None,
         }
     }
 
-    pub fn subscribe(&self) -> broadcast::Receiver<BrainEvent> {
-        self.event_tx.subscribe()
+    /// Make `run` return after `cycles` think cycles, skipping the final idle.
+    pub fn with_cycle_limit(mut self, cycles: u32) -> Self {
+        self.cycle_limit = Some(cycles);
+        self
     }
 
-    pub fn command_sender(&self) -> mpsc::Sender<BrainCommand> {
-        self.command_tx.clone()
+    /// Offer the tools in `tools` — e.g. the built-ins plus some of your
+    /// own — narrowed to what this anemone's tool settings allow.
+    pub fn with_tools(mut self, tools: ToolRegistry) -> Self {
+        self.tools = tools.configured(&self.config, &self.identity.name);
+        self
     }
 
-    pub fn is_waiting_for_reply(&self) -> bool {
-        self.waiting_for_reply
+    /// Create a handle for frontends. Can be called any number of times,
+    /// before or after the brain is spawned.
+    pub fn handle(&self) -> BrainHandle {
+        BrainHandle {
+            env_path: self.env_path.clone(),
+            command_tx: self.command_tx.clone(),
+            event_tx: self.event_tx.clone(),
+            snapshot_rx: self.snapshot_tx.subscribe(),
+            history: Arc::clone(&self.history),
+            usage: self.usage.clone(),
+        }
     }
 
     fn broadcast(&self, event: BrainEvent) {
         let _ = self.event_tx.send(event);
     }
 
+    /// Publish the current state to every handle's watch channel.
+    fn publish_snapshot(&self) {
+        self.snapshot_tx.send_modify(|snap| {
+            snap.state = self.state;
+            snap.thought_count = self.thought_count;
+            snap.position = self.position.clone();
+            snap.focus_mode = self.focus_mode;
+            snap.waiting_for_reply = self.waiting_for_reply;
+        });
+    }
+
+    fn set_state(&mut self, state: BrainState) {
+        self.state = state;
+        self.publish_snapshot();
+        self.broadcast(BrainEvent::Status(StatusData {
+            state,
+            thought_count: self.thought_count,
+        }));
+    }
+
     fn stream(&self) -> &MemoryStream {
         self.stream.as_ref().expect("stream not initialized")
     }
@@ -125,7 +433,10 @@ impl Brain {
             thought_number: self.thought_count,
             data: data.clone(),
         };
-        self.events.push(entry.clone());
+        write_history(&self.history).events.push(entry.clone());
+        if let Err(e) = self.events_journal.append(&entry) {
+            error!("Failed to journal event: {}", e);
+        }
         self.broadcast(BrainEvent::Entry(entry));
 
         let text = data
@@ -153,94 +464,170 @@ impl Brain {
             output: response.output.clone(),
             is_reflection,
             is_planning,
+            usage: response.usage,
         };
-        self.api_calls.push(record.clone());
+        write_history(&self.history).api_calls.push(record.clone());
+        if let Err(e) = self.api_calls_journal.append(&record) {
+            error!("Failed to journal API call: {}", e);
+        }
         self.broadcast(BrainEvent::ApiCall(record));
     }
 
-    // ── Activity classification (1:1 with Python) ──
+    // ── Commands ──
 
-    fn classify_activity(tool_name: &str, tool_args: &serde_json::Value) -> ActivityData {
-        match tool_name {
-            "move" => {
-                let loc = tool_args
-                    .get("location")
-                    .and_then(|v| v.as_str())
-                    .unwrap_or("");
-                ActivityData {
-                    activity_type: "moving".to_string(),
-                    detail: format!("Going to {}", loc),
-                }
+    /// Apply a command. Conversation replies are only meaningful while
+    /// `handle_respond` is waiting, which consumes them itself.
+    async fn handle_command(&mut self, command: BrainCommand) {
+        match command {
+            BrainCommand::UserMessage(text) => {
+                self.user_message = Some(text);
             }
-            "respond" => ActivityData {
-                activity_type: "conversing".to_string(),
-                detail: "Talking to someone...".to_string(),
-            },
-            "fetch_url" | "web_search" | "web_fetch" => ActivityData {
-                activity_type: "searching".to_string(),
-                detail: format!("{}...", tool_name.replace('_', " ")),
-            },
-            "shell" => {
-                let cmd = tool_args
-                    .get("command")
-                    .and_then(|v| v.as_str())
-                    .unwrap_or("")
-                    .trim();
-                if cmd.starts_with("python") {
-                    let detail: String = cmd.chars().take(60).collect();
-                    ActivityData {
-                        activity_type: "python".to_string(),
-                        detail: if cmd.len() > 60 {
-                            format!("{}...", detail)
-                        } else {
-                            detail
-                        },
-                    }
-                } else if cmd.contains('>') || cmd.starts_with("cat >") || cmd.starts_with("tee ") {
-                    let fname = cmd
-                        .split('>')
-                        .last()
-                        .and_then(|s| s.trim().split_whitespace().next())
-                        .unwrap_or("file");
-                    ActivityData {
-                        activity_type: "writing".to_string(),
-                        detail: format!("Writing {}", fname),
-                    }
-                } else if cmd.starts_with("cat ")
-                    || cmd.starts_with("head ")
-                    || cmd.starts_with("tail ")
-                    || cmd.starts_with("ls")
-                    || cmd.starts_with("find ")
-                    || cmd.starts_with("grep ")
-                {
-                    let detail: String = cmd.chars().take(50).collect();
-                    ActivityData {
-                        activity_type: "reading".to_string(),
-                        detail,
-                    }
-                } else {
-                    let detail: String = cmd.chars().take(50).collect();
-                    ActivityData {
-                        activity_type: "shell".to_string(),
-                        detail,
-                    }
+            BrainCommand::ConversationReply(text) => {
+                // Arrived after the conversation ended — treat as a new message.
+                self.user_message = Some(text);
+            }
+            BrainCommand::SetFocusMode(enabled) => {
+                self.focus_mode = enabled;
+                self.publish_snapshot();
+                self.broadcast(BrainEvent::FocusMode(FocusModeData { enabled }));
+            }
+            BrainCommand::Snapshot(data) => {
+                self.latest_snapshot = Some(data);
+            }
+            BrainCommand::Memory(request, reply_tx) => {
+                let reply = match self.stream.as_mut() {
+                    Some(stream) => stream.handle(request).await,
+                    None => Err(anyhow::anyhow!("memory stream not loaded yet")),
+                };
+                let change = match &reply {
+                    Ok(MemoryReply::Updated(memory)) => Some(("updated", memory.clone())),
+                    Ok(MemoryReply::Deleted(memory)) => Some(("deleted", memory.clone())),
+                    _ => None,
+                };
+                if let Some((action, memory)) = change {
+                    self.broadcast(BrainEvent::Memory(MemoryChangeData {
+                        action: action.to_string(),
+                        memory,
+                    }));
                 }
+                let _ = reply_tx.send(reply);
             }
-            _ => ActivityData {
-                activity_type: "working".to_string(),
-                detail: tool_name.to_string(),
-            },
+            BrainCommand::Stop => {
+                self.running = false;
+            }
+        }
+    }
+
+    /// Apply every command already queued, without waiting.
+    async fn drain_commands(&mut self) {
+        while let Ok(cmd) = self.command_rx.try_recv() {
+            self.handle_command(cmd).await;
+        }
+    }
+
+    /// Sleep between cycles while still applying commands as they arrive.
+    /// Returns early if the brain is told to stop.
+    async fn idle_for(&mut self, duration: std::time::Duration) {
+        let deadline = tokio::time::sleep(duration);
+        tokio::pin!(deadline);
+        while self.running {
+            tokio::select! {
+                _ = &mut deadline => break,
+                cmd = self.command_rx.recv() => match cmd {
+                    Some(cmd) => self.handle_command(cmd).await,
+                    None => self.running = false,
+                },
+            }
+        }
+    }
+
+    // ── Budget ──
+
+    /// Check usage against the budgets, announcing level changes. On
+    /// exhaustion the anemone goes to bed and the state becomes `Sleeping`.
+    fn check_budget(&mut self) -> BudgetStatus {
+        let status = self.usage.budget_status();
+        if std::mem::discriminant(&status) == std::mem::discriminant(&self.budget) {
+            return status;
+        }
+
+        let waking = if self.budget.is_exhausted() { "Waking up. " } else { "" };
+        match &status {
+            BudgetStatus::Ok => {
+                self.emit(
+                    "budget",
+                    json!({"text": format!("{}Budget back under its limits.", waking)}),
+                );
+            }
+            BudgetStatus::Soft { reason } => {
+                self.emit(
+                    "budget",
+                    json!({"text": format!(
+                        "{}Budget running low ({}) — thinking more slowly.",
+                        waking, reason
+                    )}),
+                );
+            }
+            BudgetStatus::Exhausted { reason, resume_at } => {
+                let until = chrono::DateTime::parse_from_rfc3339(resume_at)
+                    .map(|t| t.with_timezone(&chrono::Local).format("%I:%M %p").to_string())
+                    .unwrap_or_else(|_| resume_at.clone());
+                self.emit(
+                    "budget",
+                    json!({"text": format!("Budget exhausted ({}) — sleeping until {}.", reason, until)}),
+                );
+                crate::tools::movement::handle_move(&mut self.position, "bed");
+                self.broadcast(BrainEvent::Position(self.position.clone()));
+                self.set_state(BrainState::Sleeping);
+            }
+        }
+        self.broadcast(BrainEvent::Budget(status.clone()));
+        self.budget = status.clone();
+        status
+    }
+
+    /// Stay asleep until the budget recovers, still applying commands.
+    async fn sleep_off_budget(&mut self) {
+        while self.running {
+            let status = self.check_budget();
+            let BudgetStatus::Exhausted { resume_at, .. } = status else {
+                break;
+            };
+            let remaining = chrono::DateTime::parse_from_rfc3339(&resume_at)
+                .ok()
+                .and_then(|t| (t.with_timezone(&chrono::Utc) - chrono::Utc::now()).to_std().ok())
+                .unwrap_or_default();
+            // Re-check at least every minute, and never spin
+            let wait = remaining.clamp(
+                std::time::Duration::from_secs(1),
+                std::time::Duration::from_secs(60),
+            );
+            self.idle_for(wait).await;
+        }
+        if self.running {
+            self.set_state(BrainState::Idle);
+        }
+    }
+
+    /// Pause between cycles — stretched by `budget_slowdown` past the soft limit.
+    fn cycle_pace(&self) -> std::time::Duration {
+        let pace = std::time::Duration::from_secs(self.config.thinking_pace_seconds);
+        match self.budget {
+            BudgetStatus::Soft { .. } => pace.mul_f64(self.config.budget_slowdown.max(1.0)),
+            _ => pace,
         }
     }
 
     // ── Input building (1:1 with Python) ──
 
     fn build_input(&self) -> (String, Vec<serde_json::Value>) {
-        let instructions = main_system_prompt(&self.identity, &self.current_focus);
+        let shell_offline = matches!(EgressMode::parse(&self.config.shell_network.mode), Ok(EgressMode::Proxy));
+        let instructions = main_system_prompt(&self.identity, &self.current_focus, shell_offline);
         let mut input_list: Vec<serde_json::Value> = Vec::new();
 
         // Recent events as context
-        let recent: Vec<&EventEntry> = self
+        let history = read_history(&self.history);
+        let recent: Vec<&EventEntry> = history
             .events
             .iter()
             .filter(|e| {
@@ -269,7 +656,10 @@ impl Brain {
             }
         }
 
-        let nudge = if self.thought_count == 0 && recent.is_empty() {
+        drop(history);
+
+        // First cycle of this run — including after a restart with replayed history
+        let nudge = if !self.woken {
             self.build_wake_nudge()
         } else {
             self.build_continue_nudge()
@@ -301,14 +691,27 @@ impl Brain {
                 Spend your next several think cycles on this. Don't just glance at it and move on.",
                 names.join(", ")
             )];
+            let vision = self.provider.capabilities().vision;
+            let mut images = Vec::new();
             for f in &self.inbox_pending {
-                if f.image.is_some() {
-                    parts.push(format!("\n📎 {} (image attached below)", f.name));
+                if let Some(ref data_url) = f.image {
+                    if vision {
+                        parts.push(format!("\n📎 {} (image attached below)", f.name));
+                        images.push(json!({"type": "input_image", "image_url": data_url}));
+                    } else {
+                        parts.push(format!("\n📎 {} (an image — you can't view images)", f.name));
+                    }
                 } else if !f.content.is_empty() {
                     parts.push(format!("\n📎 {}:\n{}", f.name, f.content));
                 }
             }
-            input_list.push(json!({"role": "user", "content": parts.join("\n")}));
+            if images.is_empty() {
+                input_list.push(json!({"role": "user", "content": parts.join("\n")}));
+            } else {
+                let mut content = vec![json!({"type": "input_text", "text": parts.join("\n")})];
+                content.extend(images);
+                input_list.push(json!({"role": "user", "content": content}));
+            }
         } else {
             input_list.push(json!({"role": "user", "content": final_nudge}));
         }
@@ -341,8 +744,24 @@ impl Brain {
             parts.push(format!("**Files in your world:**\n{}", listing));
         }
 
+        // Pinned memories always come back
+        let pinned = self.stream().pinned();
+        if !pinned.is_empty() {
+            let pinned_text = pinned
+                .iter()
+                .map(|m| format!("- {}", m.content))
+                .collect::<Vec<_>>()
+                .join("\n");
+            parts.push(format!("**Memories you keep close:**\n{}", pinned_text));
+        }
+
         // Retrieve memories
-        let memories = self.stream().retrieve_sync("what was I working on and thinking about", Some(5));
+        let memories: Vec<&Memory> = self
+            .stream()
+            .search_sync(&MemoryQuery::about(WAKE_QUERY).with_top_k(5))
+            .into_iter()
+            .filter(|m| !m.pinned)
+            .collect();
         if !memories.is_empty() {
             let mem_text = memories
                 .iter()
@@ -389,15 +808,16 @@ impl Brain {
         }
 
         // Related memories from last thought
-        let last_thought = self
+        let last_thought = read_history(&self.history)
             .events
             .iter()
             .rev()
             .find(|e| e.event_type == "thought")
-            .and_then(|e| e.data.get("text").and_then(|v| v.as_str()));
+            .and_then(|e| e.data.get("text").and_then(|v| v.as_str()))
+            .map(String::from);
 
         if let Some(thought) = last_thought {
-            let memories = self.stream().retrieve_sync(thought, Some(3));
+            let memories = self.stream().retrieve_sync(&thought, Some(3));
             let now = chrono::Utc::now();
             let older: Vec<&&Memory> = memories
                 .iter()
@@ -427,26 +847,29 @@ impl Brain {
     // ── Think cycle (1:1 with Python _think_once) ──
 
     async fn think_once(&mut self) {
-        self.state = BrainState::Thinking;
-        self.broadcast(BrainEvent::Status(StatusData {
-            state: BrainState::Thinking,
-            thought_count: self.thought_count,
-        }));
+        self.set_state(BrainState::Thinking);
 
+        // Nudges retrieve synchronously; embed the wake query up front so it
+        // ranks by relevance. The continue nudge reuses the last thought's
+        // stored embedding.
+        if !self.woken {
+            self.stream_mut().cache_query(WAKE_QUERY).await;
+        }
         let (instructions, mut input_list) = self.build_input();
+        self.woken = true;
 
         // Clear user message after building input
         self.user_message = None;
 
         let max_tokens = self.config.max_output_tokens;
-        let response = match providers::chat(
-            &self.config,
-            &input_list,
-            true,
-            Some(&instructions),
-            max_tokens,
-        )
-        .await
+        let tools = if self.provider.capabilities().tools {
+            self.tools.definitions()
+        } else {
+            Vec::new()
+        };
+        let response = match self
+            .call_llm(&input_list, &tools, &instructions, max_tokens)
+            .await
         {
             Ok(r) => r,
             Err(e) => {
@@ -480,7 +903,8 @@ impl Brain {
             input_list.extend(current_response.output.clone());
 
             for tc in &current_response.tool_calls {
-                if matches!(tc.name.as_str(), "web_search" | "web_fetch" | "fetch_url") {
+                let tool = self.tools.get(&tc.name).cloned();
+                if tool.as_ref().is_some_and(|t| t.is_research()) {
                     did_research = true;
                 }
 
@@ -489,36 +913,27 @@ impl Brain {
                     json!({"tool": &tc.name, "args": &tc.arguments}),
                 );
 
-                let activity = Self::classify_activity(&tc.name, &tc.arguments);
-                self.broadcast(BrainEvent::Activity(activity));
+                if let Some(tool) = &tool {
+                    self.broadcast(BrainEvent::Activity(tool.activity(&tc.arguments)));
+                }
 
                 let pre_tool_files = self.scan_env_files();
 
-                let result = match tc.name.as_str() {
-                    "move" => {
-                        let location = tc
-                            .arguments
-                            .get("location")
-                            .and_then(|v| v.as_str())
-                            .unwrap_or("center");
-                        let result =
-                            crate::tools::movement::handle_move(&mut self.position, location);
-                        self.broadcast(BrainEvent::Position(self.position.clone()));
-                        result
-                    }
-                    "respond" => {
-                        let message = tc
-                            .arguments
-                            .get("message")
-                            .and_then(|v| v.as_str())
-                            .unwrap_or("");
-                        self.handle_respond(message).await
-                    }
-                    _ => match tools::execute_tool(&tc.name, &tc.arguments, &self.env_path).await {
-                        Ok(r) => r,
-                        Err(e) => format!("Error: {}", e),
-                    },
+                let position = self.position.clone();
+                let mut ctx = ToolContext {
+                    anemone: &self.identity.name,
+                    env_path: &self.env_path,
+                    position: &mut self.position,
+                    events: &self.event_tx,
+                    config: &self.config,
+                };
+                let result = match self.tools.execute(&tc.name, &tc.arguments, &mut ctx).await {
+                    ToolOutput::Text(text) => text,
+                    ToolOutput::Converse(message) => self.handle_respond(&message).await,
                 };
+                if self.position != position {
+                    self.publish_snapshot();
+                }
 
                 self.broadcast(BrainEvent::Activity(ActivityData {
                     activity_type: "idle".to_string(),
@@ -543,43 +958,16 @@ impl Brain {
                 }));
             }
 
-            // Follow-up LLM call
-            current_response = match providers::chat(
-                &self.config,
-                &input_list,
-                true,
-                Some(&instructions),
-                max_tokens,
-            )
-            .await
+            // Follow-up LLM call (transient failures are retried by the provider)
+            current_response = match self
+                .call_llm(&input_list, &tools, &instructions, max_tokens)
+                .await
             {
                 Ok(r) => r,
                 Err(e) => {
-                    // Retry on 500
-                    if e.to_string().contains("500") {
-                        warn!("LLM 500, retrying: {}", e);
-                        tokio::time::sleep(std::time::Duration::from_secs(2)).await;
-                        match providers::chat(
-                            &self.config,
-                            &input_list,
-                            true,
-                            Some(&instructions),
-                            max_tokens,
-                        )
-                        .await
-                        {
-                            Ok(r) => r,
-                            Err(e2) => {
-                                error!("LLM follow-up failed after retry: {}", e2);
-                                self.emit("error", json!({"text": e2.to_string()}));
-                                break;
-                            }
-                        }
-                    } else {
-                        error!("LLM follow-up call failed: {}", e);
-                        self.emit("error", json!({"text": e.to_string()}));
-                        break;
-                    }
+                    error!("LLM follow-up call failed: {}", e);
+                    self.emit("error", json!({"text": e.to_string()}));
+                    break;
                 }
             };
 
@@ -602,48 +990,110 @@ impl Brain {
 
         if let Some(ref text) = current_response.text {
             self.thought_count += 1;
+            self.publish_snapshot();
             self.emit("thought", json!({"text": text}));
 
-            // Store in memory stream
-            if let Err(e) = self
-                .stream_mut()
-                .add(text, "thought", 0, Vec::new())
-                .await
-            {
-                error!("Memory add failed: {}", e);
-            }
+            // Store in memory stream; it's scored and embedded while the
+            // anemone idles, and committed at the start of the next cycle
+            let stream = self.stream_mut();
+            stream.enqueue(text, "thought", 0, Vec::new());
+            stream.write_queued();
+        }
+    }
+
+    /// Main-loop LLM call. Streams fragments to frontends as `ThoughtDelta`
+    /// events when enabled and supported.
+    async fn call_llm(
+        &self,
+        input_list: &[serde_json::Value],
+        tools: &[serde_json::Value],
+        instructions: &str,
+        max_tokens: u32,
+    ) -> anyhow::Result<LlmResponse> {
+        let response = self
+            .request_llm(input_list, tools, instructions, max_tokens)
+            .await?;
+        self.usage
+            .record(UsageCategory::Think, &self.config.model, response.usage.as_ref());
+        Ok(response)
+    }
+
+    async fn request_llm(
+        &self,
+        input_list: &[serde_json::Value],
+        tools: &[serde_json::Value],
+        instructions: &str,
+        max_tokens: u32,
+    ) -> anyhow::Result<LlmResponse> {
+        if !(self.config.streaming && self.provider.capabilities().streaming) {
+            return self
+                .provider
+                .chat(input_list, tools, Some(instructions), max_tokens)
+                .await;
         }
+
+        let event_tx = self.event_tx.clone();
+        let on_delta = move |delta: providers::StreamDelta| {
+            let data = match delta {
+                providers::StreamDelta::Text(text) => ThoughtDeltaData {
+                    kind: "text".to_string(),
+                    delta: text,
+                    tool: None,
+                },
+                providers::StreamDelta::ToolArguments { tool, delta } => ThoughtDeltaData {
+                    kind: "tool_call".to_string(),
+                    delta,
+                    tool: Some(tool),
+                },
+            };
+            let _ = event_tx.send(BrainEvent::ThoughtDelta(data));
+        };
+        self.provider
+            .chat_stream(input_list, tools, Some(instructions), max_tokens, &on_delta)
+            .await
     }
 
     // ── Conversation ──
 
+    /// Speak to the owner and wait for a reply. Commands keep flowing while
+    /// we wait: any message that arrives is taken as their answer.
     async fn handle_respond(&mut self, message: &str) -> String {
         self.waiting_for_reply = true;
+        self.publish_snapshot();
         self.broadcast(BrainEvent::Conversation(ConversationData {
             state: "waiting".to_string(),
             message: Some(message.to_string()),
-            timeout: Some(15),
+            timeout: Some(crate::tools::respond::CONVERSATION_TIMEOUT_SECS as u32),
         }));
 
-        // Create oneshot channel for reply
-        let (tx, rx) = tokio::sync::oneshot::channel();
-        self.conversation_reply = Some(tx);
+        let deadline = tokio::time::sleep(std::time::Duration::from_secs(
+            crate::tools::respond::CONVERSATION_TIMEOUT_SECS,
+        ));
+        tokio::pin!(deadline);
+
+        let mut reply_text = None;
+        while self.running && reply_text.is_none() {
+            tokio::select! {
+                _ = &mut deadline => break,
+                cmd = self.command_rx.recv() => match cmd {
+                    Some(BrainCommand::ConversationReply(text))
+                    | Some(BrainCommand::UserMessage(text)) => reply_text = Some(text),
+                    Some(other) => self.handle_command(other).await,
+                    None => self.running = false,
+                },
+            }
+        }
 
-        let reply = match tokio::time::timeout(
-            std::time::Duration::from_secs(crate::tools::respond::CONVERSATION_TIMEOUT_SECS),
-            rx,
-        )
-        .await
-        {
-            Ok(Ok(text)) => format!(
+        let reply = match reply_text {
+            Some(text) => format!(
                 "They say: \"{}\"\n(Use respond again to reply, or go back to what you were doing.)",
                 text
             ),
-            _ => "(They didn't say anything else. You can get back to what you were doing.)".to_string(),
+            None => "(They didn't say anything else. You can get back to what you were doing.)".to_string(),
         };
 
         self.waiting_for_reply = false;
-        self.conversation_reply = None;
+        self.publish_snapshot();
         self.broadcast(BrainEvent::Conversation(ConversationData {
             state: "ended".to_string(),
             message: None,
@@ -653,14 +1103,14 @@ impl Brain {
         reply
     }
 
-    // ── Reflection (1:1 with Python) ──
+    // ── Reflection ──
 
+    /// Generative-agents reflection: ask salient questions of the recent
+    /// memories, then answer each from the memories retrieved for it. Each
+    /// insight cites its evidence and sits one level above the deepest
+    /// memory it cites, so reflections build on reflections.
     async fn reflect(&mut self) {
-        self.state = BrainState::Reflecting;
-        self.broadcast(BrainEvent::Status(StatusData {
-            state: BrainState::Reflecting,
-            thought_count: self.thought_count,
-        }));
+        self.set_state(BrainState::Reflecting);
         self.emit("reflection_start", json!({}));
 
         let recent_memories: Vec<Memory> = self
@@ -675,65 +1125,152 @@ impl Brain {
             return;
         }
 
+        let questions = self.reflection_questions(&recent_memories).await;
+        let mut insights = Vec::new();
+        for question in &questions {
+            insights.extend(self.answer_reflection_question(question).await);
+        }
+
+        if !questions.is_empty() {
+            self.emit(
+                "reflection",
+                json!({"text": insights.join("\n"), "questions": questions}),
+            );
+        }
+        self.stream_mut().reset_importance_sum();
+    }
+
+    /// The salient questions raised by `recent_memories`.
+    async fn reflection_questions(&mut self, recent_memories: &[Memory]) -> Vec<String> {
         let memories_text = recent_memories
             .iter()
             .map(|m| format!("[{}] (importance {}): {}", m.kind, m.importance, m.content))
             .collect::<Vec<_>>()
             .join("\n\n");
+        let content = format!(
+            "Your recent memories:\n\n{}\n\nAsk at most {} questions.",
+            memories_text, self.config.reflection_questions
+        );
+        let input = vec![json!({"role": "user", "content": content})];
 
-        let reflect_input = vec![json!({
-            "role": "user",
-            "content": format!("Your recent memories:\n\n{}", memories_text)
-        })];
-
-        match providers::chat(
-            &self.config,
-            &reflect_input,
-            false,
-            Some(REFLECTION_PROMPT),
-            300,
-        )
-        .await
+        match self
+            .provider
+            .chat(&input, &[], Some(REFLECTION_QUESTIONS_PROMPT), 200)
+            .await
         {
             Ok(response) => {
-                self.emit_api_call(REFLECTION_PROMPT, &reflect_input, &response, true, false);
-
-                let reflection_text = response.text.unwrap_or_default();
-                let source_ids: Vec<String> =
-                    recent_memories.iter().map(|m| m.id.clone()).collect();
-
-                for line in reflection_text.lines() {
-                    let insight = line.trim();
-                    if !insight.is_empty() {
-                        if let Err(e) = self
-                            .stream_mut()
-                            .add(insight, "reflection", 1, source_ids.clone())
-                            .await
-                        {
-                            error!("Failed to store reflection: {}", e);
-                        }
-                    }
-                }
-
-                self.emit("reflection", json!({"text": &reflection_text}));
+                self.emit_api_call(REFLECTION_QUESTIONS_PROMPT, &input, &response, true, false);
+                self.usage.record(
+                    UsageCategory::Reflection,
+                    &self.config.model,
+                    response.usage.as_ref(),
+                );
+                response
+                    .text
+                    .unwrap_or_default()
+                    .lines()
+                    .map(strip_list_marker)
+                    .filter(|q| !q.is_empty())
+                    .take(self.config.reflection_questions)
+                    .map(String::from)
+                    .collect()
             }
             Err(e) => {
                 error!("Reflection failed: {}", e);
                 self.emit("error", json!({"text": format!("Reflection failed: {}", e)}));
+                Vec::new()
             }
         }
+    }
 
-        self.stream_mut().reset_importance_sum();
+    /// Answer one reflection question from retrieved evidence, storing each
+    /// insight as a reflection. Returns the insights.
+    async fn answer_reflection_question(&mut self, question: &str) -> Vec<String> {
+        // Evidence stops short of the deepest level, so insights stay within it
+        let query = MemoryQuery::about(question)
+            .with_depth(None, Some(self.config.reflection_max_depth - 1))
+            .with_top_k(REFLECTION_EVIDENCE);
+        let evidence: Vec<Memory> = self.stream().search(&query).await.into_iter().cloned().collect();
+        if evidence.is_empty() {
+            return Vec::new();
+        }
+
+        let evidence_text = evidence
+            .iter()
+            .map(|m| format!("[{}] ({}, importance {}): {}", m.id, m.kind, m.importance, m.content))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let content = format!("Question: {}\n\nEvidence:\n{}", question, evidence_text);
+        let input = vec![json!({"role": "user", "content": content})];
+
+        let response = match self.provider.chat(&input, &[], Some(REFLECTION_PROMPT), 300).await {
+            Ok(response) => response,
+            Err(e) => {
+                error!("Reflection failed: {}", e);
+                self.emit("error", json!({"text": format!("Reflection failed: {}", e)}));
+                return Vec::new();
+            }
+        };
+        self.emit_api_call(REFLECTION_PROMPT, &input, &response, true, false);
+        self.usage.record(
+            UsageCategory::Reflection,
+            &self.config.model,
+            response.usage.as_ref(),
+        );
+
+        // The insights are written as one batch, in time to be evidence for
+        // the next question
+        let mut insights = Vec::new();
+        for line in response.text.unwrap_or_default().lines() {
+            let Some(insight) = parse_insight(line, &evidence) else {
+                continue;
+            };
+            self.stream_mut()
+                .enqueue(&insight.content, "reflection", insight.depth, insight.references);
+            insights.push(insight.content);
+        }
+        self.stream_mut().flush().await;
+        insights
+    }
+
+    // ── Re-embedding ──
+
+    /// Re-embed a batch of memories whose vectors are from another model,
+    /// sized so no more than `reembed_per_minute` go out on average.
+    async fn reembed_step(&mut self) {
+        let rate = self.config.reembed_per_minute;
+        if rate == 0 || self.stream().stale_count() == 0 {
+            self.reembedded_at = None;
+            return;
+        }
+        let now = std::time::Instant::now();
+        // The first batch gets a minute's worth
+        let elapsed = self
+            .reembedded_at
+            .map_or(std::time::Duration::from_secs(60), |at| now - at);
+        let batch = ((elapsed.as_secs_f64() / 60.0 * rate as f64) as usize).min(REEMBED_MAX_BATCH);
+        if batch == 0 {
+            return;
+        }
+        self.reembedded_at = Some(now);
+
+        match self.stream_mut().reembed(batch).await {
+            Ok(progress) => {
+                info!(
+                    "Re-embedded {} memories with {}, {} to go",
+                    progress.reembedded, progress.model, progress.remaining
+                );
+                self.broadcast(BrainEvent::Reembed(progress));
+            }
+            Err(e) => error!("Re-embedding failed: {}", e),
+        }
     }
 
     // ── Planning (1:1 with Python) ──
 
     async fn plan(&mut self) {
-        self.state = BrainState::Planning;
-        self.broadcast(BrainEvent::Status(StatusData {
-            state: BrainState::Planning,
-            thought_count: self.thought_count,
-        }));
+        self.set_state(BrainState::Planning);
+        self.stream_mut().flush().await;
 
         let projects = std::fs::read_to_string(self.env_path.join("projects.md"))
             .unwrap_or_else(|_| "(no projects.md yet)".to_string());
@@ -758,20 +1295,49 @@ impl Brain {
             recent_memories.join("\n")
         };
 
+        // Insights that bear on the current focus
+        let focus = if self.current_focus.is_empty() {
+            MemoryQuery::default()
+        } else {
+            MemoryQuery::about(self.current_focus.clone())
+        };
+        let insights: Vec<String> = self
+            .stream()
+            .search(&focus.with_kind("reflection").with_top_k(5))
+            .await
+            .iter()
+            .map(|m| format!("- {}", m.content))
+            .collect();
+        let insights_text = if insights.is_empty() {
+            "(none yet)".to_string()
+        } else {
+            insights.join("\n")
+        };
+
         let plan_input = vec![json!({
             "role": "user",
             "content": format!(
                 "Time to plan. Here's your current state:\n\n\
                 ## Current projects.md:\n{}\n\n\
                 ## Files in your world:\n{}\n\n\
-                ## Recent thoughts:\n{}",
-                projects_truncated, files_str, memories_text
+                ## Recent thoughts:\n{}\n\n\
+                ## Insights:\n{}",
+                projects_truncated, files_str, memories_text, insights_text
             )
         })];
 
-        match providers::chat(&self.config, &plan_input, false, Some(PLANNING_PROMPT), 1000).await {
+        match self
+            .provider
+            .chat(&plan_input, &[], Some(PLANNING_PROMPT), 1000)
+            .await
+        {
             Ok(response) => {
                 self.emit_api_call(PLANNING_PROMPT, &plan_input, &response, false, true);
+                self.usage.record(
+                    UsageCategory::Planning,
+                    &self.config.model,
+                    response.usage.as_ref(),
+                );
 
                 let plan_text = response.text.unwrap_or_default();
                 if plan_text.is_empty() {
@@ -822,13 +1388,229 @@ impl Brain {
         }
     }
 
+    // ── Dreaming ──
+
+    /// Dream every `dream_interval_cycles`, or sooner once the anemone has
+    /// taken itself to bed.
+    fn should_dream(&self) -> bool {
+        let in_bed = room_location("bed").is_some_and(|bed| bed == self.position);
+        let interval = self.config.dream_interval_cycles;
+        (interval > 0 && self.cycles_since_dream >= interval)
+            || (in_bed && self.cycles_since_dream >= self.config.dream_min_cycles)
+    }
+
+    /// Consolidate memories in bed: merge near-duplicates, archive stale
+    /// low-importance thoughts, compact storage, then rewrite beliefs.md.
+    async fn dream(&mut self) {
+        self.set_state(BrainState::Dreaming);
+        self.cycles_since_dream = 0;
+        if room_location("bed").is_some_and(|bed| bed != self.position) {
+            crate::tools::movement::handle_move(&mut self.position, "bed");
+            self.publish_snapshot();
+            self.broadcast(BrainEvent::Position(self.position.clone()));
+        }
+        self.emit("dream_start", json!({}));
+        self.stream_mut().flush().await;
+
+        let merged = self.merge_near_duplicates().await;
+
+        let stale_age = chrono::TimeDelta::seconds((self.config.dream_stale_hours * 3600.0) as i64);
+        let stale: HashSet<String> = self
+            .stream()
+            .stale_thoughts(chrono::Utc::now() - stale_age, self.config.dream_stale_importance)
+            .iter()
+            .map(|m| m.id.clone())
+            .collect();
+        let mut archived = match self.stream_mut().archive(&stale) {
+            Ok(count) => count,
+            Err(e) => {
+                error!("Failed to archive stale memories: {}", e);
+                0
+            }
+        };
+        match self.stream_mut().compact() {
+            Ok(count) => archived += count,
+            Err(e) => error!("Failed to compact memory: {}", e),
+        }
+
+        let beliefs = self.rewrite_beliefs().await;
+
+        let mut parts = Vec::new();
+        if merged > 0 {
+            parts.push(format!("merged {} near-duplicate memories", merged));
+        }
+        if archived > 0 {
+            parts.push(format!("archived {} faded memories", archived));
+        }
+        if beliefs {
+            parts.push("rewrote beliefs.md".to_string());
+        }
+        let summary = if parts.is_empty() {
+            "Dreamt, and woke with nothing to tidy up.".to_string()
+        } else {
+            format!("Dreamt: {}.", parts.join(", "))
+        };
+        self.emit(
+            "dream",
+            json!({"text": summary, "merged": merged, "archived": archived, "beliefs": beliefs}),
+        );
+    }
+
+    /// Merge the biggest groups of near-duplicate memories, one LLM call
+    /// each. Returns how many memories were merged away.
+    async fn merge_near_duplicates(&mut self) -> usize {
+        let groups: Vec<Vec<Memory>> = self
+            .stream()
+            .near_duplicates(self.config.dream_similarity)
+            .into_iter()
+            .take(DREAM_MAX_MERGES)
+            .map(|group| group.into_iter().cloned().collect())
+            .collect();
+
+        let mut merged = 0;
+        for group in groups {
+            let memories_text = group
+                .iter()
+                .map(|m| format!("- {}", m.content))
+                .collect::<Vec<_>>()
+                .join("\n");
+            let merge_input = vec![json!({
+                "role": "user",
+                "content": format!("Memories that say nearly the same thing:\n\n{}", memories_text)
+            })];
+
+            let response = match self
+                .provider
+                .chat(&merge_input, &[], Some(DREAM_MERGE_PROMPT), 300)
+                .await
+            {
+                Ok(response) => response,
+                Err(e) => {
+                    error!("Dream merge failed: {}", e);
+                    self.emit("error", json!({"text": format!("Dreaming failed: {}", e)}));
+                    break;
+                }
+            };
+            self.emit_api_call(DREAM_MERGE_PROMPT, &merge_input, &response, true, false);
+            self.usage.record(
+                UsageCategory::Dreaming,
+                &self.config.model,
+                response.usage.as_ref(),
+            );
+
+            let content = response.text.unwrap_or_default().trim().to_string();
+            if content.is_empty() {
+                continue;
+            }
+            let ids: Vec<String> = group.iter().map(|m| m.id.clone()).collect();
+            match self.stream_mut().consolidate(&ids, &content).await {
+                Ok(_) => merged += ids.len(),
+                Err(e) => error!("Failed to merge memories: {}", e),
+            }
+        }
+        merged
+    }
+
+    /// Rewrite beliefs.md from the current one and the strongest insights.
+    /// Returns whether it was written.
+    async fn rewrite_beliefs(&mut self) -> bool {
+        let insights: Vec<String> = self
+            .stream()
+            .search_sync(
+                &MemoryQuery::default()
+                    .with_kind("reflection")
+                    .with_top_k(BELIEF_INSIGHTS),
+            )
+            .iter()
+            .map(|m| format!("- {}", m.content))
+            .collect();
+        if insights.is_empty() {
+            return false;
+        }
+
+        let beliefs_path = self.env_path.join("beliefs.md");
+        let beliefs = std::fs::read_to_string(&beliefs_path)
+            .unwrap_or_else(|_| "(no beliefs.md yet)".to_string());
+        let beliefs_truncated: String = beliefs.chars().take(3000).collect();
+
+        let beliefs_input = vec![json!({
+            "role": "user",
+            "content": format!(
+                "## Current beliefs.md:\n{}\n\n## Insights from reflection:\n{}",
+                beliefs_truncated,
+                insights.join("\n")
+            )
+        })];
+
+        match self
+            .provider
+            .chat(&beliefs_input, &[], Some(BELIEFS_PROMPT), 1000)
+            .await
+        {
+            Ok(response) => {
+                self.emit_api_call(BELIEFS_PROMPT, &beliefs_input, &response, true, false);
+                self.usage.record(
+                    UsageCategory::Dreaming,
+                    &self.config.model,
+                    response.usage.as_ref(),
+                );
+
+                let text = response.text.unwrap_or_default();
+                if text.trim().is_empty() {
+                    return false;
+                }
+                if let Err(e) = std::fs::write(&beliefs_path, text.trim()) {
+                    error!("Failed to write beliefs.md: {}", e);
+                    return false;
+                }
+                self.seen_env_files.insert("beliefs.md".to_string());
+                true
+            }
+            Err(e) => {
+                error!("Rewriting beliefs failed: {}", e);
+                self.emit("error", json!({"text": format!("Rewriting beliefs failed: {}", e)}));
+                false
+            }
+        }
+    }
+
+    /// Launch this anemone's MCP servers and offer their tools next to the
+    /// built-ins. A server that won't start is reported and skipped.
+    async fn connect_mcp_servers(&mut self) {
+        let settings = self.config.tool_settings(&self.identity.name).clone();
+        for (server, server_config) in &settings.mcp_servers {
+            match mcp::connect(server, server_config, &self.env_path).await {
+                Ok(tools) => {
+                    let count = tools.len();
+                    for tool in tools {
+                        if settings.allows(tool.name()) {
+                            self.tools.register(Arc::new(tool));
+                        }
+                    }
+                    info!("{}: MCP server {} offers {} tools", self.identity.name, server, count);
+                }
+                Err(e) => {
+                    error!("{}: MCP server {} failed: {:#}", self.identity.name, server, e);
+                    self.emit("error", json!({"text": format!("MCP server {} failed: {:#}", server, e)}));
+                }
+            }
+        }
+    }
+
     // ── Main loop ──
 
-    pub async fn run(&mut self) {
+    /// Run the brain until it receives [`BrainCommand::Stop`]. Consumes the
+    /// brain — all further interaction goes through a [`BrainHandle`].
+    pub async fn run(mut self) {
         info!("{} is waking up...", self.identity.name);
 
         crate::tools::shell::ensure_venv(&self.env_path);
-        self.stream = Some(MemoryStream::new(&self.env_path, self.config.clone()));
+        self.stream = Some(MemoryStream::new(
+            &self.env_path,
+            self.config.clone(),
+            Arc::clone(&self.provider),
+        ).with_usage(self.usage.clone()));
+        self.connect_mcp_servers().await;
 
         // Initial file scan — mark subdirectory files as "seen" but leave root-level
         // user files unseen so they trigger inbox alerts
@@ -841,37 +1623,26 @@ impl Brain {
 
         info!("{} is ready.", self.identity.name);
 
-        let mut command_rx = self.command_rx.take().expect("command_rx already taken");
-        let mut running = true;
-
-        while running {
+        let mut cycles = 0;
+        loop {
             // Process commands
-            while let Ok(cmd) = command_rx.try_recv() {
-                match cmd {
-                    BrainCommand::UserMessage(text) => {
-                        self.user_message = Some(text);
-                    }
-                    BrainCommand::ConversationReply(text) => {
-                        if let Some(tx) = self.conversation_reply.take() {
-                            let _ = tx.send(text);
-                        }
-                    }
-                    BrainCommand::SetFocusMode(enabled) => {
-                        self.focus_mode = enabled;
-                        self.broadcast(BrainEvent::FocusMode(FocusModeData { enabled }));
-                    }
-                    BrainCommand::Snapshot(data) => {
-                        self.latest_snapshot = Some(data);
-                    }
-                    BrainCommand::Stop => {
-                        running = false;
-                        break;
-                    }
-                }
+            self.drain_commands().await;
+            if !self.running {
+                break;
             }
 
-            if !running {
-                break;
+            // Commit last cycle's thought
+            self.stream_mut().flush().await;
+
+            // Sleep off an exhausted budget before spending any more of it
+            if self.check_budget().is_exhausted() {
+                self.sleep_off_budget().await;
+                continue;
+            }
+
+            // Reflect if the last thought tipped the importance sum
+            if self.stream().should_reflect() {
+                self.reflect().await;
             }
 
             // Check for new files
@@ -887,36 +1658,52 @@ impl Brain {
             // Clear inbox after thinking
             self.inbox_pending.clear();
 
-            // Reflect if needed
-            if self.stream().should_reflect() {
-                self.reflect().await;
-            }
+            // Plan, dream and re-embed only within budget — each waits for a
+            // cycle that has some left
 
             // Plan periodically
             self.cycles_since_plan += 1;
-            if self.cycles_since_plan >= PLAN_INTERVAL {
+            if self.cycles_since_plan >= PLAN_INTERVAL && !self.check_budget().is_exhausted() {
                 self.plan().await;
             }
 
+            // Dream on schedule, or once the anemone goes to bed
+            self.cycles_since_dream += 1;
+            if self.should_dream() && !self.check_budget().is_exhausted() {
+                self.dream().await;
+            }
+
+            // Catch up memories embedded by an earlier model
+            if !self.check_budget().is_exhausted() {
+                self.reembed_step().await;
+            }
+
+            self.broadcast(BrainEvent::Usage(self.usage.report()));
+
+            // Run out during the cycle — straight to sleep, not up and about
+            if self.budget.is_exhausted() {
+                continue;
+            }
+
             // Idle
-            self.state = BrainState::Idle;
-            self.broadcast(BrainEvent::Status(StatusData {
-                state: BrainState::Idle,
-                thought_count: self.thought_count,
-            }));
+            self.set_state(BrainState::Idle);
 
             // Idle wander
             crate::tools::movement::idle_wander(&mut self.position);
+            self.publish_snapshot();
             self.broadcast(BrainEvent::Position(self.position.clone()));
 
-            tokio::time::sleep(std::time::Duration::from_secs(
-                self.config.thinking_pace_seconds,
-            ))
-            .await;
+            cycles += 1;
+            if self.cycle_limit.is_some_and(|limit| cycles >= limit) {
+                break;
+            }
+
+            self.idle_for(self.cycle_pace()).await;
         }
 
         info!("{} is shutting down.", self.identity.name);
-        self.state = BrainState::Idle;
+        self.stream_mut().flush().await;
+        self.set_state(BrainState::Idle);
     }
 
     // ── File helpers ──
@@ -933,7 +1720,16 @@ impl Brain {
                     .file_name()
                     .and_then(|n| n.to_str())
                     .unwrap_or("");
-                if fname.starts_with('.') || IGNORE_FILES.contains(&fname) {
+                let top_dir = rel
+                    .components()
+                    .next()
+                    .filter(|_| rel.components().count() > 1)
+                    .and_then(|c| c.as_os_str().to_str());
+                if fname.starts_with('.')
+                    || IGNORE_FILES.contains(&fname)
+                    || top_dir.is_some_and(|d| IGNORE_DIRS.contains(&d))
+                    || journal::is_journal_file(fname)
+                {
                     continue;
                 }
                 files.insert(rel_str);
@@ -1053,3 +1849,442 @@ fn walkdir(root: &Path) -> std::io::Result<Vec<PathBuf>> {
     }
     Ok(files)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_brain(env: &Path) -> Brain {
+        let identity = crate::identity::create_identity("TestAnemone", b"brain_test_seed");
+        Brain::new(identity, env.to_path_buf(), Config::default())
+    }
+
+    #[test]
+    fn test_handle_initial_snapshot() {
+        let tmp = tempfile::tempdir().unwrap();
+        let brain = test_brain(tmp.path());
+        let snapshot = brain.handle().snapshot();
+
+        assert_eq!(snapshot.identity.name, "TestAnemone");
+        assert_eq!(snapshot.state, BrainState::Idle);
+        assert_eq!(snapshot.thought_count, 0);
+        assert!(!snapshot.focus_mode);
+    }
+
+    #[tokio::test]
+    async fn test_commands_applied_while_idle() {
+        let tmp = tempfile::tempdir().unwrap();
+        let mut brain = test_brain(tmp.path());
+        let handle = brain.handle();
+
+        handle.send(BrainCommand::SetFocusMode(true)).await.unwrap();
+        handle.send(BrainCommand::Stop).await.unwrap();
+
+        // Stop must cut the idle period short
+        brain.idle_for(std::time::Duration::from_secs(3600)).await;
+
+        assert!(!brain.running);
+        assert!(handle.snapshot().focus_mode);
+    }
+
+    #[test]
+    fn test_handle_reads_history() {
+        let tmp = tempfile::tempdir().unwrap();
+        let mut brain = test_brain(tmp.path());
+        let handle = brain.handle();
+
+        brain.emit("thought", json!({"text": "one"}));
+        brain.emit("thought", json!({"text": "two"}));
+
+        let events = handle.recent_events(1);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].data["text"], "two");
+    }
+
+    fn mock_brain(env: &Path, mock: &Arc<providers::MockProvider>) -> Brain {
+        let identity = crate::identity::create_identity("TestAnemone", b"brain_test_seed");
+        let provider: Arc<dyn LlmProvider> = mock.clone();
+        let config = Config::default();
+        let mut brain =
+            Brain::with_provider(identity, env.to_path_buf(), config.clone(), provider.clone());
+        brain.stream =
+            Some(MemoryStream::new(env, config, provider).with_usage(brain.usage.clone()));
+        brain
+    }
+
+    #[tokio::test]
+    async fn test_think_once_with_mock_provider() {
+        let tmp = tempfile::tempdir().unwrap();
+        let mock = Arc::new(providers::MockProvider::new());
+        mock.push_tool_call("move", json!({"location": "desk"}));
+        mock.push_text("I settled in at my desk.");
+
+        let mut brain = mock_brain(tmp.path(), &mock);
+        let handle = brain.handle();
+        brain.think_once().await;
+
+        let requests = mock.requests();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[0].tool_names.contains(&"move".to_string()));
+        // The follow-up call carries the tool result
+        assert!(requests[1]
+            .input
+            .iter()
+            .any(|item| item["type"] == "function_call_output" && item["name"] == "move"));
+
+        let snapshot = handle.snapshot();
+        assert_eq!(snapshot.thought_count, 1);
+        let desk = crate::types::room_location("desk").unwrap();
+        assert_eq!((snapshot.position.x, snapshot.position.y), (desk.x, desk.y));
+        // The thought is written in the background, then committed
+        assert!(brain.stream().has_pending_writes());
+        brain.stream_mut().flush().await;
+        assert_eq!(brain.stream().memories.len(), 1);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_think_once_routes_calls_to_mcp_servers() {
+        // Canned replies to initialize, tools/list and tools/call, in order
+        let script = r#"
+read -r line
+echo '{"jsonrpc":"2.0","id":1,"result":{"protocolVersion":"2024-11-05","capabilities":{"tools":{}}}}'
+read -r line
+read -r line
+echo '{"jsonrpc":"2.0","id":2,"result":{"tools":[{"name":"tide","description":"Tide times","inputSchema":{"type":"object","properties":{}}}]}}'
+read -r line
+echo '{"jsonrpc":"2.0","id":3,"result":{"content":[{"type":"text","text":"High tide at 14:02"}]}}'
+read -r line
+"#;
+        let tmp = tempfile::tempdir().unwrap();
+        let mock = Arc::new(providers::MockProvider::new());
+        mock.push_tool_call("sea__tide", json!({}));
+        mock.push_text("The tide comes in after lunch.");
+
+        let mut brain = mock_brain(tmp.path(), &mock);
+        brain.config.tools.mcp_servers.insert(
+            "sea".to_string(),
+            crate::config::McpServerConfig {
+                command: "sh".to_string(),
+                args: vec!["-c".to_string(), script.to_string()],
+                env: Default::default(),
+                timeout_seconds: 5,
+            },
+        );
+        brain.connect_mcp_servers().await;
+        brain.think_once().await;
+
+        let requests = mock.requests();
+        assert!(requests[0].tool_names.contains(&"sea__tide".to_string()));
+        assert!(requests[0].tool_names.contains(&"shell".to_string()));
+        assert!(requests[1].input.iter().any(|item| {
+            item["type"] == "function_call_output"
+                && item["name"] == "sea__tide"
+                && item["output"] == "High tide at 14:02"
+        }));
+    }
+
+    #[tokio::test]
+    async fn test_think_once_streams_thought_deltas() {
+        let tmp = tempfile::tempdir().unwrap();
+        let mock = Arc::new(providers::MockProvider::new());
+        mock.push_text("Waves against the glass.");
+
+        let mut brain = mock_brain(tmp.path(), &mock);
+        let mut events = brain.handle().subscribe();
+        brain.think_once().await;
+
+        let mut streamed = String::new();
+        while let Ok(event) = events.try_recv() {
+            if let BrainEvent::ThoughtDelta(delta) = event {
+                assert_eq!(delta.kind, "text");
+                streamed.push_str(&delta.delta);
+            }
+        }
+        assert_eq!(streamed, "Waves against the glass.");
+    }
+
+    #[tokio::test]
+    async fn test_think_once_records_usage_by_category() {
+        let tmp = tempfile::tempdir().unwrap();
+        let mock = Arc::new(providers::MockProvider::new());
+        mock.push_text("Waves against the glass.");
+
+        let mut brain = mock_brain(tmp.path(), &mock);
+        brain.think_once().await;
+        brain.stream_mut().flush().await;
+
+        let report = brain.handle().usage();
+        let think = &report.by_category[&UsageCategory::Think];
+        assert_eq!(think.calls, 1);
+        assert_eq!(think.completion_tokens, 4);
+        // The thought was stored as a memory: one importance score, and an
+        // embedding each for it and the wake query
+        assert_eq!(report.by_category[&UsageCategory::Importance].calls, 1);
+        assert_eq!(report.by_category[&UsageCategory::Embedding].calls, 2);
+        assert!(report.total.cost_usd > 0.0);
+
+        // Persisted in the box, and each API call record carries its usage
+        assert!(tmp.path().join(crate::usage::USAGE_FILENAME).is_file());
+        let calls = brain.handle().recent_api_calls(10);
+        assert_eq!(calls[0].usage.unwrap().completion_tokens, 4);
+    }
+
+    #[tokio::test]
+    async fn test_continue_nudge_recalls_memories_related_to_the_last_thought() {
+        use std::io::Write;
+        let tmp = tempfile::tempdir().unwrap();
+        let mock = Arc::new(providers::MockProvider::new());
+        let old = (chrono::Utc::now() - chrono::TimeDelta::hours(1)).to_rfc3339();
+        let mut file = std::fs::File::create(tmp.path().join("memory_stream.jsonl")).unwrap();
+        let memories = [
+            ("The colours of the coral reef", 3),
+            ("Sorted my bookshelf", 6),
+            ("Wrote a haiku about rain", 6),
+            ("Cleaned desk drawers", 6),
+            ("Ate breakfast", 1),
+            ("Watching the coral reef colours", 2),
+        ];
+        for (i, (content, importance)) in memories.iter().enumerate() {
+            let memory = Memory {
+                id: format!("m_{:04}", i),
+                timestamp: old.clone(),
+                kind: "thought".to_string(),
+                content: content.to_string(),
+                importance: *importance,
+                depth: 0,
+                references: Vec::new(),
+                embedding: mock.embed(content).await.unwrap().vector,
+                pinned: false,
+                embedding_model: String::new(),
+                embedding_dim: 0,
+            };
+            writeln!(file, "{}", serde_json::to_string(&memory).unwrap()).unwrap();
+        }
+        drop(file);
+
+        let mut brain = mock_brain(tmp.path(), &mock);
+        brain.woken = true;
+        brain.emit("thought", json!({"text": "Watching the coral reef colours"}));
+
+        let nudge = brain.build_continue_nudge();
+        let related = nudge.split("Related memories:\n").nth(1).unwrap();
+        // Most relevant first, despite the lowest importance; not the thought itself
+        assert!(related.starts_with("- The colours of the coral reef"), "{}", nudge);
+        assert_eq!(related.matches("Watching").count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_pinned_memories_recalled_on_waking() {
+        let tmp = tempfile::tempdir().unwrap();
+        let mock = Arc::new(providers::MockProvider::new());
+        let mut brain = mock_brain(tmp.path(), &mock);
+        for content in ["My owner's name is Ada", "Sorted my bookshelf"] {
+            brain.stream_mut().add(content, "thought", 0, Vec::new()).await.unwrap();
+        }
+        let handle = brain.handle();
+        let mut events = handle.subscribe();
+
+        let requests = tokio::spawn(async move {
+            let reply = handle
+                .memory(MemoryRequest::Update {
+                    id: "m_0000".to_string(),
+                    importance: None,
+                    pinned: Some(true),
+                })
+                .await;
+            handle.send(BrainCommand::Stop).await.unwrap();
+            reply
+        });
+        brain.idle_for(std::time::Duration::from_secs(3600)).await;
+
+        let Ok(MemoryReply::Updated(memory)) = requests.await.unwrap() else {
+            panic!("expected an updated memory");
+        };
+        assert!(memory.pinned);
+        assert!(matches!(
+            events.try_recv(),
+            Ok(BrainEvent::Memory(change)) if change.action == "updated"
+        ));
+
+        let nudge = brain.build_wake_nudge();
+        let pinned = nudge.split("**Memories you keep close:**\n").nth(1).unwrap();
+        assert!(pinned.starts_with("- My owner's name is Ada"), "{}", nudge);
+        assert_eq!(nudge.matches("Ada").count(), 1);
+    }
+
+    #[test]
+    fn test_exhausted_budget_sends_anemone_to_bed() {
+        let tmp = tempfile::tempdir().unwrap();
+        let mock = Arc::new(providers::MockProvider::new());
+        let mut brain = mock_brain(tmp.path(), &mock);
+        brain.config.max_calls_per_hour = Some(1);
+        brain.usage = UsageMeter::in_memory(&brain.config);
+        let mut events = brain.handle().subscribe();
+
+        assert_eq!(brain.check_budget(), BudgetStatus::Ok);
+        brain.usage.record(UsageCategory::Think, "gpt-4.1", None);
+        assert!(brain.check_budget().is_exhausted());
+
+        let snapshot = brain.handle().snapshot();
+        let bed = room_location("bed").unwrap();
+        assert_eq!(snapshot.state, BrainState::Sleeping);
+        assert_eq!((snapshot.position.x, snapshot.position.y), (bed.x, bed.y));
+
+        let mut announced = 0;
+        let mut explained = false;
+        while let Ok(event) = events.try_recv() {
+            match event {
+                BrainEvent::Budget(status) => {
+                    assert!(status.is_exhausted());
+                    announced += 1;
+                }
+                BrainEvent::Entry(entry) if entry.event_type == "budget" => {
+                    explained = entry.data["text"].as_str().unwrap().contains("hourly call limit");
+                }
+                _ => {}
+            }
+        }
+        assert_eq!(announced, 1);
+        assert!(explained);
+
+        // Unchanged level — nothing new to announce
+        brain.check_budget();
+        assert!(events.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_soft_budget_slows_pace() {
+        let tmp = tempfile::tempdir().unwrap();
+        let mock = Arc::new(providers::MockProvider::new());
+        let mut brain = mock_brain(tmp.path(), &mock);
+        brain.config.thinking_pace_seconds = 10;
+        brain.config.daily_token_budget = Some(100);
+        brain.usage = UsageMeter::in_memory(&brain.config);
+        assert_eq!(brain.cycle_pace(), std::time::Duration::from_secs(10));
+
+        let usage = TokenUsage {
+            prompt_tokens: 80,
+            completion_tokens: 5,
+            cached_tokens: 0,
+        };
+        brain.usage.record(UsageCategory::Think, "gpt-4.1", Some(&usage));
+        assert!(matches!(brain.check_budget(), BudgetStatus::Soft { .. }));
+        assert_eq!(brain.cycle_pace(), std::time::Duration::from_secs(40));
+        assert_eq!(brain.handle().snapshot().state, BrainState::Idle);
+    }
+
+    #[tokio::test]
+    async fn test_dream_merges_memories_and_rewrites_beliefs() {
+        let tmp = tempfile::tempdir().unwrap();
+        let mock = Arc::new(providers::MockProvider::new());
+        let mut brain = mock_brain(tmp.path(), &mock);
+        let handle = brain.handle();
+        for (content, kind, depth) in [
+            ("the coral reef at dawn", "thought", 0),
+            ("at dawn the coral reef", "thought", 0),
+            ("I learn best by watching closely", "reflection", 1),
+        ] {
+            brain.stream_mut().add(content, kind, depth, Vec::new()).await.unwrap();
+        }
+
+        // Going to bed brings the dream forward, but not right after the last one
+        brain.cycles_since_dream = brain.config.dream_min_cycles;
+        assert!(!brain.should_dream());
+        crate::tools::movement::handle_move(&mut brain.position, "bed");
+        assert!(brain.should_dream());
+
+        mock.push_text("I keep coming back to the coral reef at dawn.");
+        mock.push_text("# About Me\nA patient watcher of reefs.");
+        brain.dream().await;
+
+        assert_eq!(handle.snapshot().state, BrainState::Dreaming);
+        assert_eq!(brain.cycles_since_dream, 0);
+        let contents: Vec<&str> =
+            brain.stream().memories.iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(
+            contents,
+            ["I learn best by watching closely", "I keep coming back to the coral reef at dawn."]
+        );
+
+        // Beliefs are rewritten from the insights
+        let requests = mock.requests();
+        assert!(requests[1].input[0]["content"]
+            .as_str()
+            .unwrap()
+            .contains("I learn best by watching closely"));
+        let beliefs = std::fs::read_to_string(tmp.path().join("beliefs.md")).unwrap();
+        assert!(beliefs.contains("A patient watcher of reefs."));
+
+        assert!(handle.recent_api_calls(2).iter().all(|call| call.is_reflection));
+        let dream = handle.recent_events(1).pop().unwrap();
+        assert_eq!(dream.event_type, "dream");
+        assert_eq!(dream.data["merged"], 2);
+        assert_eq!(handle.usage().by_category[&UsageCategory::Dreaming].calls, 2);
+    }
+
+    #[tokio::test]
+    async fn test_streaming_disabled_sends_no_deltas() {
+        let tmp = tempfile::tempdir().unwrap();
+        let mock = Arc::new(providers::MockProvider::new());
+        mock.push_text("Quiet.");
+
+        let mut brain = mock_brain(tmp.path(), &mock);
+        brain.config.streaming = false;
+        let mut events = brain.handle().subscribe();
+        brain.think_once().await;
+
+        while let Ok(event) = events.try_recv() {
+            assert!(!matches!(event, BrainEvent::ThoughtDelta(_)));
+        }
+    }
+
+    #[test]
+    fn test_inbox_images_attached_as_input_image() {
+        let tmp = tempfile::tempdir().unwrap();
+        let mock = Arc::new(providers::MockProvider::new());
+        let mut brain = mock_brain(tmp.path(), &mock);
+        brain.inbox_pending = vec![NewFileInfo {
+            name: "photo.png".to_string(),
+            content: String::new(),
+            image: Some("data:image/png;base64,iVBORw0".to_string()),
+        }];
+
+        let (_, input) = brain.build_input();
+        let content = input.last().unwrap()["content"].as_array().unwrap().clone();
+        assert_eq!(content[0]["type"], "input_text");
+        assert_eq!(content[1]["type"], "input_image");
+        assert_eq!(content[1]["image_url"], "data:image/png;base64,iVBORw0");
+    }
+
+    #[tokio::test]
+    async fn test_think_once_reports_provider_error() {
+        let tmp = tempfile::tempdir().unwrap();
+        let mock = Arc::new(providers::MockProvider::new());
+
+        let mut brain = mock_brain(tmp.path(), &mock);
+        let handle = brain.handle();
+        brain.think_once().await;
+
+        let events = handle.recent_events(10);
+        assert!(events.iter().any(|e| e.event_type == "error"));
+        assert_eq!(handle.snapshot().thought_count, 0);
+    }
+
+    #[test]
+    fn test_history_replayed_after_restart() {
+        let tmp = tempfile::tempdir().unwrap();
+        {
+            let mut brain = test_brain(tmp.path());
+            brain.thought_count = 3;
+            brain.emit("thought", json!({"text": "before restart"}));
+        }
+
+        let brain = test_brain(tmp.path());
+        let handle = brain.handle();
+        let events = handle.recent_events(10);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].data["text"], "before restart");
+        assert_eq!(handle.snapshot().thought_count, 3);
+    }
+}
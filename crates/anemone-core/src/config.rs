@@ -2,9 +2,12 @@
 
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
 use std::time::Instant;
 
+use crate::types::TokenUsage;
+
 /// Result of an API key validation attempt.
 #[derive(Debug, Clone)]
 pub struct KeyValidation {
@@ -25,11 +28,171 @@ const PROVIDER_PRESETS: &[(&str, Option<&str>)] = &[
 ];
 
 /// Provider-specific API key env vars (checked before OPENAI_API_KEY fallback)
-const PROVIDER_KEY_ENV_VARS: &[(&str, &str)] = &[("openrouter", "OPENROUTER_API_KEY")];
+const PROVIDER_KEY_ENV_VARS: &[(&str, &str)] = &[
+    ("openrouter", "OPENROUTER_API_KEY"),
+    ("anthropic", "ANTHROPIC_API_KEY"),
+];
+
+/// Providers whose keys are never OpenAI keys, so OPENAI_API_KEY is not a fallback
+const NO_OPENAI_KEY_FALLBACK: &[&str] = &["anthropic"];
+
+/// Built-in prices, USD per million tokens: (model, input, output, cached input)
+const DEFAULT_PRICES: &[(&str, f64, f64, f64)] = &[
+    ("gpt-4.1", 2.00, 8.00, 0.50),
+    ("gpt-4.1-mini", 0.40, 1.60, 0.10),
+    ("gpt-4.1-nano", 0.10, 0.40, 0.025),
+    ("gpt-4o", 2.50, 10.00, 1.25),
+    ("gpt-4o-mini", 0.15, 0.60, 0.075),
+    ("gpt-5", 1.25, 10.00, 0.125),
+    ("gpt-5-mini", 0.25, 2.00, 0.025),
+    ("claude-sonnet-4-5", 3.00, 15.00, 0.30),
+    ("claude-sonnet-4", 3.00, 15.00, 0.30),
+    ("claude-opus-4-1", 15.00, 75.00, 1.50),
+    ("claude-haiku-4-5", 1.00, 5.00, 0.10),
+    ("text-embedding-3-small", 0.02, 0.0, 0.02),
+    ("text-embedding-3-large", 0.13, 0.0, 0.13),
+];
+
+/// Price of a model in USD per million tokens.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ModelPrice {
+    pub input: f64,
+    pub output: f64,
+    /// Cached prompt tokens; defaults to the input price
+    #[serde(default)]
+    pub cached_input: Option<f64>,
+}
+
+impl ModelPrice {
+    /// Cost of one call in USD.
+    pub fn cost(&self, usage: &TokenUsage) -> f64 {
+        let cached = usage.cached_tokens.min(usage.prompt_tokens);
+        let uncached = usage.prompt_tokens - cached;
+        (uncached as f64 * self.input
+            + cached as f64 * self.cached_input.unwrap_or(self.input)
+            + usage.completion_tokens as f64 * self.output)
+            / 1_000_000.0
+    }
+}
+
+/// Weights of the three memory retrieval factors. Each factor is min-max
+/// normalized across the candidates before weighting, so no factor wins just
+/// by having the widest range.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetrievalWeights {
+    pub recency: f64,
+    pub importance: f64,
+    pub relevance: f64,
+}
+
+impl Default for RetrievalWeights {
+    fn default() -> Self {
+        Self {
+            recency: 1.0,
+            importance: 1.0,
+            relevance: 1.0,
+        }
+    }
+}
+
+/// Limits on each shell command; 0 turns one off. CPU time, memory and file
+/// size are enforced by the kernel (rlimits), so they hold for everything
+/// the command starts, too.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ShellLimits {
+    /// Wall-clock seconds before the command and its children are killed
+    pub timeout_seconds: u64,
+    /// CPU seconds per process
+    pub cpu_seconds: u64,
+    /// Address space per process, in MB
+    pub memory_mb: u64,
+    /// Largest file a command may write, in MB
+    pub file_size_mb: u64,
+    /// Processes a command may have running at once (not enforced for root)
+    pub max_processes: u64,
+}
+
+impl Default for ShellLimits {
+    fn default() -> Self {
+        Self {
+            timeout_seconds: 120,
+            cpu_seconds: 60,
+            memory_mb: 2048,
+            file_size_mb: 100,
+            max_processes: 64,
+        }
+    }
+}
+
+/// Network access for shell commands.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ShellNetwork {
+    /// "proxy": commands get a network namespace of their own whose only
+    /// way out is a filtering proxy (Linux) | "open": the host's network |
+    /// "auto": proxy where the host allows it, else open
+    pub mode: String,
+    /// Domains a pip or `uv pip` command may reach through the proxy
+    /// (`*.example.com` for subdomains)
+    pub pip: Vec<String>,
+    /// Domains any other command may reach, python scripts included
+    pub commands: Vec<String>,
+}
+
+impl Default for ShellNetwork {
+    fn default() -> Self {
+        Self {
+            mode: "auto".to_string(),
+            pip: vec!["pypi.org".to_string(), "files.pythonhosted.org".to_string()],
+            commands: Vec::new(),
+        }
+    }
+}
+
+/// Which tools an anemone may call.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ToolSettings {
+    /// Only these tools (every tool when unset)
+    pub enabled: Option<Vec<String>>,
+    /// Never these, even if enabled
+    pub disabled: Vec<String>,
+    /// MCP servers to launch, by name; their tools are offered as
+    /// `<server>__<tool>`
+    pub mcp_servers: BTreeMap<String, McpServerConfig>,
+    /// Shell command policy file, relative to the project root (the
+    /// built-in policy when unset)
+    pub shell_policy: Option<String>,
+}
+
+/// A stdio MCP server: a command that speaks the Model Context Protocol on
+/// its stdin and stdout. It runs in the anemone's box.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct McpServerConfig {
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Extra environment variables
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    /// How long to wait for each reply
+    #[serde(default = "default_mcp_timeout_seconds")]
+    pub timeout_seconds: u64,
+}
+
+impl ToolSettings {
+    pub fn allows(&self, tool: &str) -> bool {
+        self.enabled.as_ref().is_none_or(|enabled| enabled.iter().any(|t| t == tool))
+            && !self.disabled.iter().any(|t| t == tool)
+    }
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
-    /// "openai" | "openrouter" | "custom"
+    /// "openai" | "anthropic" | "openrouter" | "ollama" | "custom" — any name registered
+    /// in `providers::ProviderRegistry`
     #[serde(default = "default_provider")]
     pub provider: String,
 
@@ -65,10 +228,67 @@ pub struct Config {
     #[serde(default = "default_max_tool_rounds")]
     pub max_tool_rounds: usize,
 
+    /// Tools every anemone may call
+    #[serde(default)]
+    pub tools: ToolSettings,
+
+    /// Per-anemone tool settings by name, replacing `tools` for that anemone
+    #[serde(default)]
+    pub box_tools: BTreeMap<String, ToolSettings>,
+
+    /// Kernel sandbox for shell commands: "namespaces" | "landlock" | "none"
+    #[serde(default = "default_sandbox")]
+    pub sandbox: String,
+
+    /// Timeout and resource limits for shell commands
+    #[serde(default)]
+    pub shell_limits: ShellLimits,
+
+    /// Network access for shell commands: open, or only allowed domains
+    /// through a proxy
+    #[serde(default)]
+    pub shell_network: ShellNetwork,
+
+    /// Stream thoughts token by token when the provider supports it
+    #[serde(default = "default_streaming")]
+    pub streaming: bool,
+
+    /// Per-model prices (USD per million tokens), layered over the built-in table
+    #[serde(default)]
+    pub prices: BTreeMap<String, ModelPrice>,
+
+    /// Max tokens per local day before the anemone sleeps (unset = no limit)
+    #[serde(default)]
+    pub daily_token_budget: Option<u64>,
+
+    /// Max spend in USD per local day before the anemone sleeps (unset = no limit)
+    #[serde(default)]
+    pub daily_cost_budget: Option<f64>,
+
+    /// Max API calls in any rolling hour before the anemone sleeps (unset = no limit)
+    #[serde(default)]
+    pub max_calls_per_hour: Option<u32>,
+
+    /// Fraction of any budget at which the thinking pace slows down
+    #[serde(default = "default_budget_soft_limit")]
+    pub budget_soft_limit: f64,
+
+    /// How many times slower the pace gets past the soft limit
+    #[serde(default = "default_budget_slowdown")]
+    pub budget_slowdown: f64,
+
     /// Accumulated importance before reflecting
     #[serde(default = "default_reflection_threshold")]
     pub reflection_threshold: f64,
 
+    /// Salient questions asked per reflection, each answered from its own evidence
+    #[serde(default = "default_reflection_questions")]
+    pub reflection_questions: usize,
+
+    /// Deepest reflection: insights about insights stop at this depth
+    #[serde(default = "default_reflection_max_depth")]
+    pub reflection_max_depth: i32,
+
     /// How many memories to retrieve per query
     #[serde(default = "default_memory_retrieval_count")]
     pub memory_retrieval_count: usize,
@@ -77,10 +297,75 @@ pub struct Config {
     #[serde(default = "default_embedding_model")]
     pub embedding_model: String,
 
+    /// Where embeddings come from: "provider" (the chat provider's endpoint)
+    /// or "local" (in-process, no network; `embedding_model` is then unused)
+    #[serde(default = "default_embedding_provider")]
+    pub embedding_provider: String,
+
+    /// Never fall back to OpenAI when the provider can't embed
+    #[serde(default)]
+    pub strict_embeddings: bool,
+
+    /// Memories re-embedded per minute after `embedding_model` changes (0 = never)
+    #[serde(default = "default_reembed_per_minute")]
+    pub reembed_per_minute: u32,
+
     /// Exponential decay rate for recency scoring
     #[serde(default = "default_recency_decay_rate")]
     pub recency_decay_rate: f64,
 
+    /// How much recency, importance and relevance each count in retrieval
+    #[serde(default)]
+    pub retrieval_weights: RetrievalWeights,
+
+    /// Memories per on-disk segment before it is sealed
+    #[serde(default = "default_memory_segment_size")]
+    pub memory_segment_size: usize,
+
+    /// Age after which a sealed segment is cold (0 = never)
+    #[serde(default = "default_memory_cold_days")]
+    pub memory_cold_days: f64,
+
+    /// Memories in cold segments at least this important stay in the stream
+    #[serde(default = "default_memory_cold_keep_importance")]
+    pub memory_cold_keep_importance: i32,
+
+    /// Dream at least every N think cycles (0 = only when in bed)
+    #[serde(default = "default_dream_interval_cycles")]
+    pub dream_interval_cycles: u32,
+
+    /// Think cycles between dreams when the anemone goes to bed on its own
+    #[serde(default = "default_dream_min_cycles")]
+    pub dream_min_cycles: u32,
+
+    /// Cosine similarity at which memories count as near-duplicates
+    #[serde(default = "default_dream_similarity")]
+    pub dream_similarity: f64,
+
+    /// Age after which low-importance thoughts are archived while dreaming
+    #[serde(default = "default_dream_stale_hours")]
+    pub dream_stale_hours: f64,
+
+    /// Thoughts at or below this importance can go stale
+    #[serde(default = "default_dream_stale_importance")]
+    pub dream_stale_importance: i32,
+
+    /// Events kept in memory (and replayed from the journal on startup)
+    #[serde(default = "default_event_history_limit")]
+    pub event_history_limit: usize,
+
+    /// API call records kept in memory (and replayed on startup)
+    #[serde(default = "default_api_call_history_limit")]
+    pub api_call_history_limit: usize,
+
+    /// Size at which an event/API-call journal file is rotated
+    #[serde(default = "default_journal_max_bytes")]
+    pub journal_max_bytes: u64,
+
+    /// Rotated journal files kept per log
+    #[serde(default = "default_journal_max_files")]
+    pub journal_max_files: usize,
+
     /// Environment path (auto-detected from *_box/ directories)
     #[serde(default)]
     pub environment_path: Option<String>,
@@ -90,6 +375,9 @@ pub struct Config {
     pub project_root: PathBuf,
 }
 
+fn default_mcp_timeout_seconds() -> u64 {
+    60
+}
 fn default_provider() -> String {
     "openai".into()
 }
@@ -108,18 +396,78 @@ fn default_max_output_tokens() -> u32 {
 fn default_max_tool_rounds() -> usize {
     15
 }
+fn default_sandbox() -> String {
+    "none".into()
+}
+fn default_streaming() -> bool {
+    true
+}
+fn default_budget_soft_limit() -> f64 {
+    0.8
+}
+fn default_budget_slowdown() -> f64 {
+    4.0
+}
 fn default_reflection_threshold() -> f64 {
     50.0
 }
+fn default_reflection_questions() -> usize {
+    3
+}
+fn default_reflection_max_depth() -> i32 {
+    3
+}
 fn default_memory_retrieval_count() -> usize {
     3
 }
+fn default_reembed_per_minute() -> u32 {
+    60
+}
+fn default_embedding_provider() -> String {
+    "provider".into()
+}
 fn default_embedding_model() -> String {
     "text-embedding-3-small".into()
 }
 fn default_recency_decay_rate() -> f64 {
     0.995
 }
+fn default_memory_segment_size() -> usize {
+    1000
+}
+fn default_memory_cold_days() -> f64 {
+    30.0
+}
+fn default_memory_cold_keep_importance() -> i32 {
+    7
+}
+fn default_dream_interval_cycles() -> u32 {
+    50
+}
+fn default_dream_min_cycles() -> u32 {
+    10
+}
+fn default_dream_similarity() -> f64 {
+    0.92
+}
+fn default_dream_stale_hours() -> f64 {
+    72.0
+}
+fn default_dream_stale_importance() -> i32 {
+    3
+}
+fn default_event_history_limit() -> usize {
+    1000
+}
+fn default_api_call_history_limit() -> usize {
+    100
+}
+fn default_journal_max_bytes() -> u64 {
+    10 * 1024 * 1024
+}
+fn default_journal_max_files() -> usize {
+    5
+}
 
 impl Config {
     /// Load config from a YAML file with env var overrides.
@@ -170,7 +518,7 @@ impl Config {
                 config.api_key = Some(key);
             }
         }
-        if config.api_key.is_none() {
+        if config.api_key.is_none() && !NO_OPENAI_KEY_FALLBACK.contains(&config.provider.as_str()) {
             if let Ok(key) = std::env::var("OPENAI_API_KEY") {
                 config.api_key = Some(key);
             }
@@ -192,6 +540,13 @@ impl Config {
                 "Provider 'custom' requires base_url in config.yaml or ANEMONECLAW_BASE_URL env var"
             );
         }
+        // Evidence comes from below the deepest level, so there must be one
+        if config.reflection_max_depth < 1 {
+            anyhow::bail!(
+                "reflection_max_depth must be at least 1 (got {})",
+                config.reflection_max_depth
+            );
+        }
 
         Ok(config)
     }
@@ -322,6 +677,56 @@ impl Config {
             box_path.to_path_buf()
         }
     }
+
+    /// The tool settings for the anemone called `name`.
+    pub fn tool_settings(&self, name: &str) -> &ToolSettings {
+        self.box_tools.get(name).unwrap_or(&self.tools)
+    }
+
+    /// Whether embeddings are computed in-process.
+    pub fn local_embeddings(&self) -> bool {
+        self.embedding_provider == "local"
+    }
+
+    /// The model behind stored embeddings: `embedding_model`, or the local
+    /// embedder's name.
+    pub fn embedding_model_id(&self) -> &str {
+        if self.local_embeddings() {
+            crate::providers::local::LOCAL_EMBEDDING_MODEL
+        } else {
+            &self.embedding_model
+        }
+    }
+
+    /// Price for `model`: an exact match first, then the longest known prefix
+    /// (dated snapshots like `gpt-4.1-2025-04-14`). Router prefixes such as
+    /// `openai/` are ignored, and entries in `prices` win over the built-ins.
+    pub fn price_for(&self, model: &str) -> Option<ModelPrice> {
+        let model = model.rsplit('/').next().unwrap_or(model);
+        let mut table: BTreeMap<&str, ModelPrice> = DEFAULT_PRICES
+            .iter()
+            .map(|&(name, input, output, cached)| {
+                (
+                    name,
+                    ModelPrice {
+                        input,
+                        output,
+                        cached_input: Some(cached),
+                    },
+                )
+            })
+            .collect();
+        table.extend(self.prices.iter().map(|(name, price)| (name.as_str(), *price)));
+
+        if let Some(price) = table.get(model) {
+            return Some(*price);
+        }
+        table
+            .iter()
+            .filter(|(name, _)| model.starts_with(**name))
+            .max_by_key(|(name, _)| name.len())
+            .map(|(_, price)| *price)
+    }
 }
 
 impl Default for Config {
@@ -336,10 +741,40 @@ impl Default for Config {
             max_thoughts_in_context: default_max_thoughts(),
             max_output_tokens: default_max_output_tokens(),
             max_tool_rounds: default_max_tool_rounds(),
+            tools: ToolSettings::default(),
+            box_tools: BTreeMap::new(),
+            sandbox: default_sandbox(),
+            shell_limits: ShellLimits::default(),
+            shell_network: ShellNetwork::default(),
+            streaming: default_streaming(),
+            prices: BTreeMap::new(),
+            daily_token_budget: None,
+            daily_cost_budget: None,
+            max_calls_per_hour: None,
+            budget_soft_limit: default_budget_soft_limit(),
+            budget_slowdown: default_budget_slowdown(),
             reflection_threshold: default_reflection_threshold(),
+            reflection_questions: default_reflection_questions(),
+            reflection_max_depth: default_reflection_max_depth(),
             memory_retrieval_count: default_memory_retrieval_count(),
             embedding_model: default_embedding_model(),
+            embedding_provider: default_embedding_provider(),
+            strict_embeddings: false,
+            reembed_per_minute: default_reembed_per_minute(),
             recency_decay_rate: default_recency_decay_rate(),
+            retrieval_weights: RetrievalWeights::default(),
+            memory_segment_size: default_memory_segment_size(),
+            memory_cold_days: default_memory_cold_days(),
+            memory_cold_keep_importance: default_memory_cold_keep_importance(),
+            dream_interval_cycles: default_dream_interval_cycles(),
+            dream_min_cycles: default_dream_min_cycles(),
+            dream_similarity: default_dream_similarity(),
+            dream_stale_hours: default_dream_stale_hours(),
+            dream_stale_importance: default_dream_stale_importance(),
+            event_history_limit: default_event_history_limit(),
+            api_call_history_limit: default_api_call_history_limit(),
+            journal_max_bytes: default_journal_max_bytes(),
+            journal_max_files: default_journal_max_files(),
             environment_path: None,
             project_root: PathBuf::new(),
         }
@@ -363,6 +798,19 @@ mod tests {
         assert_eq!(config.thinking_pace_seconds, 45);
         assert_eq!(config.reflection_threshold, 50.0);
         assert_eq!(config.recency_decay_rate, 0.995);
+        assert_eq!(config.event_history_limit, 1000);
+        assert_eq!(config.journal_max_files, 5);
+    }
+
+    #[test]
+    fn test_partial_retrieval_weights() {
+        let mut tmp = NamedTempFile::new().unwrap();
+        writeln!(tmp, "retrieval_weights:\n  relevance: 2.5").unwrap();
+
+        let config = Config::load(tmp.path()).unwrap();
+        assert_eq!(config.retrieval_weights.relevance, 2.5);
+        assert_eq!(config.retrieval_weights.recency, 1.0);
+        assert_eq!(config.retrieval_weights.importance, 1.0);
     }
 
     #[test]
@@ -393,6 +841,16 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_reflection_max_depth_must_be_positive() {
+        for (depth, ok) in [(0, false), (-2, false), (1, true)] {
+            let mut tmp = NamedTempFile::new().unwrap();
+            writeln!(tmp, "reflection_max_depth: {}", depth).unwrap();
+            let result = Config::load(tmp.path());
+            assert_eq!(result.is_ok(), ok, "{}", depth);
+        }
+    }
+
     #[test]
     fn test_save_and_reload() {
         use tempfile::tempdir;
@@ -401,13 +859,15 @@ mod tests {
         let config_path = dir.path().join("config.yaml");
 
         // Build a config with non-default values to make sure round-trip works
-        let mut original = Config::default();
-        original.provider = "openrouter".into();
-        original.model = "openai/gpt-4o".into();
-        original.thinking_pace_seconds = 30;
-        original.reflection_threshold = 75.0;
-        // project_root must NOT appear in the saved YAML (it's #[serde(skip)])
-        original.project_root = dir.path().to_path_buf();
+        let original = Config {
+            provider: "openrouter".into(),
+            model: "openai/gpt-4o".into(),
+            thinking_pace_seconds: 30,
+            reflection_threshold: 75.0,
+            // project_root must NOT appear in the saved YAML (it's #[serde(skip)])
+            project_root: dir.path().to_path_buf(),
+            ..Config::default()
+        };
 
         original.save(&config_path).expect("save should succeed");
 
@@ -448,4 +908,38 @@ mod tests {
         // Empty string
         assert_eq!(Config::normalize_secret(""), "");
     }
+
+    #[test]
+    fn test_price_lookup() {
+        let mut config = Config::default();
+        let gpt41 = config.price_for("gpt-4.1").unwrap();
+        assert_eq!(gpt41.input, 2.00);
+        // Dated snapshots and router prefixes resolve to the longest known name
+        assert_eq!(config.price_for("gpt-4.1-mini-2025-04-14").unwrap().input, 0.40);
+        assert_eq!(config.price_for("openai/gpt-4o").unwrap().input, 2.50);
+        assert!(config.price_for("llama3").is_none());
+
+        config.prices.insert(
+            "llama3".to_string(),
+            ModelPrice { input: 0.0, output: 0.0, cached_input: None },
+        );
+        config.prices.insert(
+            "gpt-4.1".to_string(),
+            ModelPrice { input: 1.0, output: 4.0, cached_input: None },
+        );
+        assert!(config.price_for("llama3").is_some());
+        assert_eq!(config.price_for("gpt-4.1").unwrap().input, 1.0);
+    }
+
+    #[test]
+    fn test_price_cost_counts_cached_tokens_separately() {
+        let price = ModelPrice { input: 2.0, output: 8.0, cached_input: Some(0.5) };
+        let usage = TokenUsage {
+            prompt_tokens: 1_000_000,
+            completion_tokens: 500_000,
+            cached_tokens: 400_000,
+        };
+        // 600k uncached * $2 + 400k cached * $0.50 + 500k out * $8
+        assert!((price.cost(&usage) - 5.4).abs() < 1e-9);
+    }
 }
@@ -3,8 +3,11 @@
 use serde::{Deserialize, Serialize};
 
 use crate::types::{
-    ActivityData, ApiCallRecord, ConversationData, EventEntry, FocusModeData, Position, StatusData,
+    ActivityData, ApiCallRecord, ConversationData, EventEntry, FocusModeData, MemoryChangeData,
+    Position, StatusData, ThoughtDeltaData, ToolResultDeltaData,
 };
+use crate::memory::reembed::ReembedProgress;
+use crate::usage::{BudgetStatus, UsageReport};
 
 /// Events broadcast from a Brain task to all subscribers (TUI, WebSocket clients).
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -41,6 +44,32 @@ pub enum BrainEvent {
     /// Conversation state (waiting for reply / ended)
     #[serde(rename = "conversation")]
     Conversation(ConversationData),
+
+    /// Streamed fragment of the in-progress thought (not journaled; the
+    /// finished thought arrives as an `entry`)
+    #[serde(rename = "thought_delta")]
+    ThoughtDelta(ThoughtDeltaData),
+
+    /// Streamed output of a tool still running (not journaled; the complete
+    /// output arrives as a `tool_result` entry)
+    #[serde(rename = "tool_result_delta")]
+    ToolResultDelta(ToolResultDeltaData),
+
+    /// Cumulative token usage and cost, sent once per cycle
+    #[serde(rename = "usage")]
+    Usage(UsageReport),
+
+    /// Budget level changed (ok / soft / exhausted)
+    #[serde(rename = "budget")]
+    Budget(BudgetStatus),
+
+    /// A memory was edited or deleted by the owner
+    #[serde(rename = "memory")]
+    Memory(MemoryChangeData),
+
+    /// A batch of memories was re-embedded with the current embedding model
+    #[serde(rename = "reembed")]
+    Reembed(ReembedProgress),
 }
 
 impl BrainEvent {
@@ -111,8 +111,8 @@ pub fn derive_traits(seed_bytes: &[u8]) -> Traits {
         if domains.contains(&d) {
             // Collision handling — matches Python logic
             let mut extra_hasher = Sha256::new();
-            extra_hasher.update(&h_bytes);
-            extra_hasher.update(&[(i as u8) + 10]);
+            extra_hasher.update(h_bytes.as_slice());
+            extra_hasher.update([(i as u8) + 10]);
             let h_extra = extra_hasher.finalize();
             let val = u32::from_be_bytes([h_extra[0], h_extra[1], h_extra[2], h_extra[3]]);
             let d2 = DOMAINS[(val as usize) % DOMAINS.len()].to_string();
@@ -128,8 +128,8 @@ pub fn derive_traits(seed_bytes: &[u8]) -> Traits {
         let s = pick(THINKING_STYLES, &h_bytes, 12 + i * 4).to_string();
         if styles.contains(&s) {
             let mut extra_hasher = Sha256::new();
-            extra_hasher.update(&h_bytes);
-            extra_hasher.update(&[(i as u8) + 20]);
+            extra_hasher.update(h_bytes.as_slice());
+            extra_hasher.update([(i as u8) + 20]);
             let h_extra = extra_hasher.finalize();
             let val = u32::from_be_bytes([h_extra[0], h_extra[1], h_extra[2], h_extra[3]]);
             let s2 = THINKING_STYLES[(val as usize) % THINKING_STYLES.len()].to_string();
@@ -187,8 +187,8 @@ pub fn create_identity_random(name: &str) -> Identity {
     use sha2::Digest;
     let mut hasher = Sha256::new();
     hasher.update(name.as_bytes());
-    hasher.update(&chrono::Utc::now().timestamp_nanos_opt().unwrap_or(0).to_be_bytes());
-    hasher.update(&rand::random::<[u8; 32]>());
+    hasher.update(chrono::Utc::now().timestamp_nanos_opt().unwrap_or(0).to_be_bytes());
+    hasher.update(rand::random::<[u8; 32]>());
     let seed_bytes = hasher.finalize();
     create_identity(name, &seed_bytes)
 }
@@ -198,15 +198,6 @@ mod hex {
     pub fn encode(bytes: &[u8]) -> String {
         bytes.iter().map(|b| format!("{:02x}", b)).collect()
     }
-
-    pub fn decode(s: &str) -> Result<Vec<u8>, String> {
-        (0..s.len())
-            .step_by(2)
-            .map(|i| {
-                u8::from_str_radix(&s[i..i + 2], 16).map_err(|e| format!("hex decode: {}", e))
-            })
-            .collect()
-    }
 }
 
 #[cfg(test)]
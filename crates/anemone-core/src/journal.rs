@@ -0,0 +1,279 @@
+//! Append-only, rotating JSONL journals for the event log and API-call log.
+//!
+//! Each journal lives in the box next to `memory_stream.jsonl`. When the active
+//! file grows past `max_bytes` it is renamed to `<stem>.1.jsonl` (older files
+//! shift up) and a fresh file is started; at most `max_files` rotated files are
+//! kept. On startup the newest records are replayed from disk.
+
+use anyhow::Result;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use tracing::{error, warn};
+
+pub const EVENTS_FILENAME: &str = "events.jsonl";
+pub const API_CALLS_FILENAME: &str = "api_calls.jsonl";
+
+const JOURNAL_STEMS: &[&str] = &["events", "api_calls"];
+
+/// True for any active or rotated journal file name (e.g. `events.2.jsonl`).
+pub fn is_journal_file(name: &str) -> bool {
+    JOURNAL_STEMS.iter().any(|stem| {
+        name.strip_prefix(stem)
+            .and_then(|rest| rest.strip_suffix(".jsonl"))
+            .is_some_and(|mid| {
+                mid.is_empty()
+                    || mid
+                        .strip_prefix('.')
+                        .is_some_and(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()))
+            })
+    })
+}
+
+/// A single rotating JSONL file.
+pub struct Journal {
+    path: PathBuf,
+    max_bytes: u64,
+    max_files: usize,
+    size: u64,
+}
+
+impl Journal {
+    pub fn open(dir: &Path, filename: &str, max_bytes: u64, max_files: usize) -> Self {
+        let path = dir.join(filename);
+        let size = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+        Self {
+            path,
+            max_bytes,
+            max_files,
+            size,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Path of the `n`th rotated file (`n >= 1`), e.g. `events.1.jsonl`.
+    fn rotated_path(&self, n: usize) -> PathBuf {
+        let stem = self
+            .path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("journal");
+        self.path.with_file_name(format!("{}.{}.jsonl", stem, n))
+    }
+
+    /// Load the newest `limit` records across the rotated files and the active
+    /// file, oldest first. Lines that fail to parse are skipped.
+    pub fn replay<T: DeserializeOwned>(&self, limit: usize) -> Vec<T> {
+        let mut records = VecDeque::with_capacity(limit.min(4096));
+        if limit == 0 {
+            return Vec::new();
+        }
+
+        let mut files: Vec<PathBuf> = (1..=self.max_files)
+            .rev()
+            .map(|n| self.rotated_path(n))
+            .collect();
+        files.push(self.path.clone());
+
+        for file in files.iter().filter(|f| f.is_file()) {
+            let reader = match std::fs::File::open(file) {
+                Ok(f) => std::io::BufReader::new(f),
+                Err(e) => {
+                    error!("Failed to open journal {}: {}", file.display(), e);
+                    continue;
+                }
+            };
+            for line in reader.lines() {
+                let Ok(line) = line else { break };
+                let line = line.trim();
+                if line.is_empty() {
+                    continue;
+                }
+                match serde_json::from_str::<T>(line) {
+                    Ok(record) => {
+                        if records.len() == limit {
+                            records.pop_front();
+                        }
+                        records.push_back(record);
+                    }
+                    Err(e) => warn!("Skipping bad journal line in {}: {}", file.display(), e),
+                }
+            }
+        }
+
+        records.into()
+    }
+
+    /// Append one record, rotating first if it would overflow the active file.
+    pub fn append<T: Serialize>(&mut self, record: &T) -> Result<()> {
+        let mut line = serde_json::to_string(record)?;
+        line.push('\n');
+
+        if self.size > 0 && self.size + line.len() as u64 > self.max_bytes {
+            self.rotate()?;
+        }
+
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        file.write_all(line.as_bytes())?;
+        self.size += line.len() as u64;
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> Result<()> {
+        if self.max_files == 0 {
+            std::fs::remove_file(&self.path)?;
+        } else {
+            let oldest = self.rotated_path(self.max_files);
+            if oldest.is_file() {
+                std::fs::remove_file(&oldest)?;
+            }
+            for n in (1..self.max_files).rev() {
+                let from = self.rotated_path(n);
+                if from.is_file() {
+                    std::fs::rename(&from, self.rotated_path(n + 1))?;
+                }
+            }
+            std::fs::rename(&self.path, self.rotated_path(1))?;
+        }
+        self.size = 0;
+        Ok(())
+    }
+}
+
+/// In-memory ring buffer holding the newest `cap` records.
+#[derive(Debug, Clone)]
+pub struct RingBuffer<T> {
+    items: VecDeque<T>,
+    cap: usize,
+}
+
+impl<T: Clone> RingBuffer<T> {
+    pub fn new(cap: usize) -> Self {
+        Self {
+            items: VecDeque::new(),
+            cap,
+        }
+    }
+
+    pub fn from_vec(items: Vec<T>, cap: usize) -> Self {
+        let mut buffer = Self::new(cap);
+        for item in items {
+            buffer.push(item);
+        }
+        buffer
+    }
+
+    pub fn push(&mut self, item: T) {
+        if self.cap == 0 {
+            return;
+        }
+        if self.items.len() == self.cap {
+            self.items.pop_front();
+        }
+        self.items.push_back(item);
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &T> {
+        self.items.iter()
+    }
+
+    /// The newest `limit` items, oldest first.
+    pub fn recent(&self, limit: usize) -> Vec<T> {
+        let start = self.items.len().saturating_sub(limit);
+        self.items.iter().skip(start).cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_is_journal_file() {
+        assert!(is_journal_file("events.jsonl"));
+        assert!(is_journal_file("events.3.jsonl"));
+        assert!(is_journal_file("api_calls.jsonl"));
+        assert!(is_journal_file("api_calls.12.jsonl"));
+        assert!(!is_journal_file("events.md"));
+        assert!(!is_journal_file("events.old.jsonl"));
+        assert!(!is_journal_file("memory_stream.jsonl"));
+    }
+
+    #[test]
+    fn test_append_and_replay() {
+        let tmp = tempfile::tempdir().unwrap();
+        let mut journal = Journal::open(tmp.path(), EVENTS_FILENAME, 1 << 20, 3);
+        for i in 0..5 {
+            journal.append(&json!({"n": i})).unwrap();
+        }
+
+        let reopened = Journal::open(tmp.path(), EVENTS_FILENAME, 1 << 20, 3);
+        let all: Vec<serde_json::Value> = reopened.replay(100);
+        assert_eq!(all.len(), 5);
+        let last_two: Vec<serde_json::Value> = reopened.replay(2);
+        assert_eq!(last_two[0]["n"], 3);
+        assert_eq!(last_two[1]["n"], 4);
+    }
+
+    #[test]
+    fn test_rotation_keeps_order_and_limit() {
+        let tmp = tempfile::tempdir().unwrap();
+        // Each line is ~9 bytes, so every few records trigger a rotation
+        let mut journal = Journal::open(tmp.path(), EVENTS_FILENAME, 20, 2);
+        for i in 0..10 {
+            journal.append(&json!({"n": i})).unwrap();
+        }
+
+        assert!(tmp.path().join("events.1.jsonl").is_file());
+        assert!(tmp.path().join("events.2.jsonl").is_file());
+        assert!(!tmp.path().join("events.3.jsonl").exists());
+
+        let replayed: Vec<serde_json::Value> = journal.replay(100);
+        let ns: Vec<i64> = replayed.iter().map(|v| v["n"].as_i64().unwrap()).collect();
+        // Oldest records fell off the end; what remains is contiguous and ordered
+        assert!(ns.len() < 10);
+        assert_eq!(*ns.last().unwrap(), 9);
+        assert!(ns.windows(2).all(|w| w[1] == w[0] + 1));
+    }
+
+    #[test]
+    fn test_replay_skips_corrupt_lines() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::fs::write(
+            tmp.path().join(EVENTS_FILENAME),
+            "{\"n\":1}\nnot json\n{\"n\":2}\n",
+        )
+        .unwrap();
+        let journal = Journal::open(tmp.path(), EVENTS_FILENAME, 1 << 20, 3);
+        let replayed: Vec<serde_json::Value> = journal.replay(10);
+        assert_eq!(replayed.len(), 2);
+    }
+
+    #[test]
+    fn test_ring_buffer_cap() {
+        let mut ring = RingBuffer::new(3);
+        for i in 0..5 {
+            ring.push(i);
+        }
+        assert_eq!(ring.len(), 3);
+        assert_eq!(ring.recent(10), vec![2, 3, 4]);
+        assert_eq!(ring.recent(1), vec![4]);
+    }
+}
@@ -7,11 +7,14 @@
 pub mod config;
 pub mod events;
 pub mod identity;
+pub mod journal;
 pub mod prompts;
 pub mod types;
+pub mod usage;
 
 // These modules will be implemented in later phases:
 pub mod brain;
+pub mod mcp;
 pub mod memory;
 pub mod providers;
 pub mod tools;
@@ -0,0 +1,327 @@
+//! A minimal MCP client: JSON-RPC 2.0 over newline-delimited stdio.
+//!
+//! One reader task routes replies to whoever is waiting on them, answers the
+//! server's pings, and fails everything still pending when the server goes
+//! away. Requests time out after the server's `timeout_seconds`.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::process::Stdio;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::process::{Child, Command};
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+use crate::config::McpServerConfig;
+
+/// MCP revision we speak. Servers answer with the one they picked.
+pub const PROTOCOL_VERSION: &str = "2024-11-05";
+
+type Writer = Arc<tokio::sync::Mutex<Box<dyn AsyncWrite + Send + Unpin>>>;
+/// Waiting requests by id; `None` once the server is gone.
+type Pending = Arc<Mutex<Option<HashMap<u64, oneshot::Sender<Result<Value, String>>>>>>;
+
+/// A tool as the server lists it.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct McpToolInfo {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(rename = "inputSchema", default)]
+    pub input_schema: Value,
+}
+
+/// A connection to one MCP server.
+pub struct McpClient {
+    name: String,
+    writer: Writer,
+    pending: Pending,
+    next_id: AtomicU64,
+    timeout: Duration,
+    reader: JoinHandle<()>,
+    /// The server process, killed when the client is dropped
+    _child: Option<Child>,
+}
+
+impl McpClient {
+    /// Launch `config.command` in `cwd` and complete the handshake.
+    pub async fn spawn(name: &str, config: &McpServerConfig, cwd: &Path) -> Result<Self> {
+        let mut child = Command::new(&config.command)
+            .args(&config.args)
+            .envs(&config.env)
+            .current_dir(cwd)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .with_context(|| format!("Failed to start {}", config.command))?;
+
+        let stdin = child.stdin.take().context("no stdin")?;
+        let stdout = child.stdout.take().context("no stdout")?;
+        if let Some(stderr) = child.stderr.take() {
+            let name = name.to_string();
+            tokio::spawn(async move {
+                let mut lines = BufReader::new(stderr).lines();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    debug!("mcp {}: {}", name, line);
+                }
+            });
+        }
+
+        let timeout = Duration::from_secs(config.timeout_seconds.max(1));
+        let client = Self::connect(name, stdout, stdin, timeout, Some(child));
+        client.initialize().await?;
+        Ok(client)
+    }
+
+    /// Talk to a server over an existing reader and writer. Call
+    /// [`initialize`](Self::initialize) before anything else.
+    pub fn connect<R, W>(
+        name: &str,
+        reader: R,
+        writer: W,
+        timeout: Duration,
+        child: Option<Child>,
+    ) -> Self
+    where
+        R: AsyncRead + Send + Unpin + 'static,
+        W: AsyncWrite + Send + Unpin + 'static,
+    {
+        let writer: Writer = Arc::new(tokio::sync::Mutex::new(Box::new(writer)));
+        let pending: Pending = Arc::new(Mutex::new(Some(HashMap::new())));
+        let reader = tokio::spawn(read_loop(
+            name.to_string(),
+            reader,
+            Arc::clone(&writer),
+            Arc::clone(&pending),
+        ));
+        Self {
+            name: name.to_string(),
+            writer,
+            pending,
+            next_id: AtomicU64::new(1),
+            timeout,
+            reader,
+            _child: child,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The `initialize` / `notifications/initialized` handshake.
+    pub async fn initialize(&self) -> Result<()> {
+        let result = self
+            .request(
+                "initialize",
+                json!({
+                    "protocolVersion": PROTOCOL_VERSION,
+                    "capabilities": {},
+                    "clientInfo": {"name": "anemone", "version": env!("CARGO_PKG_VERSION")},
+                }),
+            )
+            .await?;
+        if result.get("capabilities").and_then(|c| c.get("tools")).is_none() {
+            warn!("MCP server {} doesn't advertise tools", self.name);
+        }
+        self.notify("notifications/initialized", json!({})).await
+    }
+
+    /// Every tool the server offers, following pagination.
+    pub async fn list_tools(&self) -> Result<Vec<McpToolInfo>> {
+        let mut tools = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let params = match &cursor {
+                Some(cursor) => json!({"cursor": cursor}),
+                None => json!({}),
+            };
+            let result = self.request("tools/list", params).await?;
+            let page: Vec<McpToolInfo> =
+                serde_json::from_value(result.get("tools").cloned().unwrap_or(json!([])))
+                    .context("malformed tools/list result")?;
+            tools.extend(page);
+            cursor = result.get("nextCursor").and_then(|c| c.as_str()).map(String::from);
+            if cursor.is_none() {
+                return Ok(tools);
+            }
+        }
+    }
+
+    /// Call `tool` and return its content as text. A result the server marks
+    /// as an error comes back as `Err`.
+    pub async fn call_tool(&self, tool: &str, arguments: &Value) -> Result<String> {
+        let result = self
+            .request("tools/call", json!({"name": tool, "arguments": arguments}))
+            .await?;
+        let text = content_text(&result);
+        if result.get("isError").and_then(|e| e.as_bool()).unwrap_or(false) {
+            bail!("{}", text);
+        }
+        Ok(text)
+    }
+
+    /// Send a request and wait for its reply.
+    pub async fn request(&self, method: &str, params: Value) -> Result<Value> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (tx, rx) = oneshot::channel();
+        match self.pending.lock().unwrap().as_mut() {
+            Some(pending) => pending.insert(id, tx),
+            None => bail!("MCP server {} has exited", self.name),
+        };
+
+        let message = json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params});
+        if let Err(e) = send(&self.writer, &message).await {
+            self.forget(id);
+            return Err(e.context(format!("MCP server {} isn't listening", self.name)));
+        }
+
+        match tokio::time::timeout(self.timeout, rx).await {
+            Ok(Ok(Ok(result))) => Ok(result),
+            Ok(Ok(Err(message))) => Err(anyhow!(message)),
+            Ok(Err(_)) => bail!("MCP server {} exited during {}", self.name, method),
+            Err(_) => {
+                self.forget(id);
+                bail!(
+                    "MCP server {} didn't answer {} within {}s",
+                    self.name,
+                    method,
+                    self.timeout.as_secs()
+                )
+            }
+        }
+    }
+
+    /// Send a notification; there's no reply.
+    pub async fn notify(&self, method: &str, params: Value) -> Result<()> {
+        send(&self.writer, &json!({"jsonrpc": "2.0", "method": method, "params": params})).await
+    }
+
+    fn forget(&self, id: u64) {
+        if let Some(pending) = self.pending.lock().unwrap().as_mut() {
+            pending.remove(&id);
+        }
+    }
+}
+
+impl Drop for McpClient {
+    fn drop(&mut self) {
+        self.reader.abort();
+    }
+}
+
+async fn send(writer: &Writer, message: &Value) -> Result<()> {
+    let mut line = serde_json::to_string(message)?;
+    line.push('\n');
+    let mut writer = writer.lock().await;
+    writer.write_all(line.as_bytes()).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+async fn read_loop<R: AsyncRead + Unpin>(name: String, reader: R, writer: Writer, pending: Pending) {
+    let mut lines = BufReader::new(reader).lines();
+    loop {
+        let line = match lines.next_line().await {
+            Ok(Some(line)) => line,
+            Ok(None) => break,
+            Err(e) => {
+                warn!("MCP server {}: read failed: {}", name, e);
+                break;
+            }
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let message: Value = match serde_json::from_str(&line) {
+            Ok(message) => message,
+            Err(e) => {
+                warn!("MCP server {}: unparseable line: {}", name, e);
+                continue;
+            }
+        };
+
+        if let Some(method) = message.get("method").and_then(|m| m.as_str()) {
+            // A request from the server wants an answer; notifications don't
+            let Some(id) = message.get("id") else {
+                debug!("MCP server {}: {}", name, method);
+                continue;
+            };
+            let reply = if method == "ping" {
+                json!({"jsonrpc": "2.0", "id": id, "result": {}})
+            } else {
+                json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "error": {"code": -32601, "message": format!("Method not found: {}", method)},
+                })
+            };
+            if let Err(e) = send(&writer, &reply).await {
+                warn!("MCP server {}: reply failed: {}", name, e);
+            }
+            continue;
+        }
+
+        let Some(id) = message.get("id").and_then(|id| id.as_u64()) else {
+            warn!("MCP server {}: reply without a usable id", name);
+            continue;
+        };
+        let waiter = pending.lock().unwrap().as_mut().and_then(|p| p.remove(&id));
+        let Some(waiter) = waiter else {
+            continue;
+        };
+        let outcome = match message.get("error") {
+            Some(error) => Err(format!(
+                "MCP error {}: {}",
+                error.get("code").and_then(|c| c.as_i64()).unwrap_or(0),
+                error.get("message").and_then(|m| m.as_str()).unwrap_or("unknown error")
+            )),
+            None => Ok(message.get("result").cloned().unwrap_or(Value::Null)),
+        };
+        let _ = waiter.send(outcome);
+    }
+    // Dropping the waiters fails their requests
+    pending.lock().unwrap().take();
+    debug!("MCP server {} closed", name);
+}
+
+/// A `tools/call` result's content as text for the model.
+fn content_text(result: &Value) -> String {
+    let parts: Vec<String> = result
+        .get("content")
+        .and_then(|c| c.as_array())
+        .map(|items| {
+            items
+                .iter()
+                .map(|item| match item.get("type").and_then(|t| t.as_str()) {
+                    Some("text") => item.get("text").and_then(|t| t.as_str()).unwrap_or("").to_string(),
+                    Some("resource") => {
+                        let resource = &item["resource"];
+                        match resource.get("text").and_then(|t| t.as_str()) {
+                            Some(text) => text.to_string(),
+                            None => format!("[resource {}]", resource["uri"].as_str().unwrap_or("")),
+                        }
+                    }
+                    Some(other) => format!("[{}]", other),
+                    None => String::new(),
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    if parts.is_empty() {
+        if let Some(structured) = result.get("structuredContent") {
+            return structured.to_string();
+        }
+    }
+    parts.join("\n")
+}
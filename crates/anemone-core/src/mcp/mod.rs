@@ -0,0 +1,251 @@
+//! Model Context Protocol — lets an anemone use tools from MCP servers.
+//!
+//! Servers are listed under `mcp_servers` in the tool settings (globally or
+//! per box). The brain launches each one in the anemone's box when it wakes,
+//! lists its tools, and registers them as [`McpTool`]s next to the built-ins,
+//! so they reach the model with every other tool definition and their calls
+//! are routed back to the server.
+//!
+//! The other way round, [`McpServer`] lets MCP clients look into an anemone.
+
+pub mod client;
+pub mod server;
+
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde_json::json;
+
+pub use client::{McpClient, McpToolInfo};
+pub use server::McpServer;
+
+use crate::config::McpServerConfig;
+use crate::tools::{Tool, ToolContext, ToolOutput};
+use crate::types::ActivityData;
+
+/// Longest function name the providers accept.
+const MAX_TOOL_NAME: usize = 64;
+
+/// A server's tool, offered to the model as `<server>__<tool>`.
+pub struct McpTool {
+    client: Arc<McpClient>,
+    name: String,
+    tool: String,
+    description: String,
+    parameters: serde_json::Value,
+}
+
+impl McpTool {
+    pub fn new(client: Arc<McpClient>, info: McpToolInfo) -> Self {
+        let name = tool_name(client.name(), &info.name);
+        let parameters = if info.input_schema.is_object() {
+            info.input_schema
+        } else {
+            json!({"type": "object", "properties": {}})
+        };
+        Self {
+            client,
+            name,
+            tool: info.name,
+            description: info.description,
+            parameters,
+        }
+    }
+}
+
+#[async_trait]
+impl Tool for McpTool {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        self.parameters.clone()
+    }
+
+    fn activity(&self, _arguments: &serde_json::Value) -> ActivityData {
+        ActivityData {
+            activity_type: "working".to_string(),
+            detail: format!("{} via {}", self.tool, self.client.name()),
+        }
+    }
+
+    async fn execute(&self, arguments: &serde_json::Value, _ctx: &mut ToolContext<'_>) -> Result<ToolOutput> {
+        let arguments = if arguments.is_object() { arguments.clone() } else { json!({}) };
+        let text = self.client.call_tool(&self.tool, &arguments).await?;
+        Ok(ToolOutput::Text(text))
+    }
+}
+
+/// Launch the server `name` in `cwd` and return its tools.
+pub async fn connect(name: &str, config: &McpServerConfig, cwd: &Path) -> Result<Vec<McpTool>> {
+    let client = McpClient::spawn(name, config, cwd).await?;
+    tools_of(Arc::new(client)).await
+}
+
+/// Every tool `client`'s server offers.
+pub async fn tools_of(client: Arc<McpClient>) -> Result<Vec<McpTool>> {
+    let infos = client
+        .list_tools()
+        .await
+        .with_context(|| format!("Listing tools of {}", client.name()))?;
+    Ok(infos
+        .into_iter()
+        .map(|info| McpTool::new(Arc::clone(&client), info))
+        .collect())
+}
+
+/// `<server>__<tool>`, reduced to the characters function names allow.
+fn tool_name(server: &str, tool: &str) -> String {
+    format!("{}__{}", server, tool)
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '_' || c == '-' { c } else { '_' })
+        .take(MAX_TOOL_NAME)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::types::Position;
+    use serde_json::Value;
+    use std::time::Duration;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, DuplexStream};
+    use tokio::sync::broadcast;
+
+    /// A client wired to the returned server end of an in-memory pipe.
+    fn pipe(timeout: Duration) -> (McpClient, BufReader<DuplexStream>) {
+        let (client_io, server_io) = tokio::io::duplex(64 * 1024);
+        let (client_read, client_write) = tokio::io::split(client_io);
+        let client = McpClient::connect("notes", client_read, client_write, timeout, None);
+        (client, BufReader::new(server_io))
+    }
+
+    async fn next(server: &mut BufReader<DuplexStream>) -> Value {
+        let mut line = String::new();
+        server.read_line(&mut line).await.unwrap();
+        serde_json::from_str(&line).unwrap()
+    }
+
+    async fn reply(server: &mut BufReader<DuplexStream>, id: &Value, result: Value) {
+        let line = json!({"jsonrpc": "2.0", "id": id, "result": result}).to_string() + "\n";
+        server.get_mut().write_all(line.as_bytes()).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_handshake_lists_pages_and_calls_tools() {
+        let (client, mut server) = pipe(Duration::from_secs(5));
+        let fake = tokio::spawn(async move {
+            let init = next(&mut server).await;
+            assert_eq!(init["method"], "initialize");
+            assert_eq!(init["params"]["protocolVersion"], client::PROTOCOL_VERSION);
+            reply(&mut server, &init["id"], json!({"capabilities": {"tools": {}}})).await;
+            assert_eq!(next(&mut server).await["method"], "notifications/initialized");
+
+            let list = next(&mut server).await;
+            reply(
+                &mut server,
+                &list["id"],
+                json!({
+                    "tools": [{"name": "read.file", "description": "Read a note",
+                               "inputSchema": {"type": "object", "properties": {"path": {"type": "string"}}}}],
+                    "nextCursor": "2",
+                }),
+            )
+            .await;
+            let list = next(&mut server).await;
+            assert_eq!(list["params"]["cursor"], "2");
+            reply(&mut server, &list["id"], json!({"tools": [{"name": "search"}]})).await;
+
+            let call = next(&mut server).await;
+            assert_eq!(call["params"]["name"], "read.file");
+            assert_eq!(call["params"]["arguments"]["path"], "kelp.md");
+            let content = json!([{"type": "text", "text": "Kelp grows"}, {"type": "text", "text": "fast."}]);
+            reply(&mut server, &call["id"], json!({"content": content})).await;
+
+            let call = next(&mut server).await;
+            let content = json!([{"type": "text", "text": "no such note"}]);
+            reply(&mut server, &call["id"], json!({"content": content, "isError": true})).await;
+        });
+
+        client.initialize().await.unwrap();
+        let tools = tools_of(Arc::new(client)).await.unwrap();
+        let names: Vec<&str> = tools.iter().map(|t| t.name()).collect();
+        assert_eq!(names, ["notes__read_file", "notes__search"]);
+        assert_eq!(tools[0].parameters()["properties"]["path"]["type"], "string");
+        assert_eq!(tools[1].parameters(), json!({"type": "object", "properties": {}}));
+        assert_eq!(tools[0].activity(&json!({})).detail, "read.file via notes");
+
+        let tmp = tempfile::tempdir().unwrap();
+        let (events, _) = broadcast::channel(8);
+        let config = Config::default();
+        let mut position = Position { x: 5, y: 5 };
+        let mut ctx = ToolContext {
+            anemone: "coral",
+            env_path: tmp.path(),
+            position: &mut position,
+            events: &events,
+            config: &config,
+        };
+        let output = tools[0].execute(&json!({"path": "kelp.md"}), &mut ctx).await.unwrap();
+        assert_eq!(output, ToolOutput::Text("Kelp grows\nfast.".to_string()));
+        let error = tools[0].execute(&json!({"path": "x"}), &mut ctx).await.unwrap_err();
+        assert_eq!(error.to_string(), "no such note");
+        fake.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_answers_pings_and_fails_pending_calls_on_exit() {
+        let (client, mut server) = pipe(Duration::from_secs(5));
+        let fake = tokio::spawn(async move {
+            let call = next(&mut server).await;
+            assert_eq!(call["method"], "tools/call");
+            let ping = json!({"jsonrpc": "2.0", "id": "p1", "method": "ping"}).to_string() + "\n";
+            server.get_mut().write_all(ping.as_bytes()).await.unwrap();
+            let pong = next(&mut server).await;
+            assert_eq!(pong["id"], "p1");
+            assert_eq!(pong["result"], json!({}));
+            // Exit without answering the call
+        });
+
+        let error = client.call_tool("read", &json!({})).await.unwrap_err();
+        assert!(error.to_string().contains("exited"), "{}", error);
+        fake.await.unwrap();
+        let error = client.call_tool("read", &json!({})).await.unwrap_err();
+        assert!(error.to_string().contains("has exited"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn test_requests_time_out_and_errors_surface() {
+        let (client, mut server) = pipe(Duration::from_millis(50));
+        let fake = tokio::spawn(async move {
+            let _ignored = next(&mut server).await;
+            let call = next(&mut server).await;
+            let line = json!({"jsonrpc": "2.0", "id": call["id"],
+                              "error": {"code": -32602, "message": "Unknown tool"}})
+            .to_string()
+                + "\n";
+            server.get_mut().write_all(line.as_bytes()).await.unwrap();
+            server
+        });
+
+        let error = client.request("tools/list", json!({})).await.unwrap_err();
+        assert!(error.to_string().contains("didn't answer tools/list"), "{}", error);
+        let error = client.call_tool("nope", &json!({})).await.unwrap_err();
+        assert_eq!(error.to_string(), "MCP error -32602: Unknown tool");
+        drop(fake.await.unwrap());
+    }
+
+    #[test]
+    fn test_tool_names_fit_function_name_rules() {
+        assert_eq!(tool_name("my files", "read.file"), "my_files__read_file");
+        assert_eq!(tool_name("s", &"x".repeat(100)).len(), MAX_TOOL_NAME);
+    }
+}
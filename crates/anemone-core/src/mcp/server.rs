@@ -0,0 +1,345 @@
+//! An anemone as an MCP server, so other agents and editors can look at its
+//! work: search its memories, list and read its box, read `projects.md`, and
+//! send it a message.
+//!
+//! [`McpServer`] answers JSON-RPC messages against a [`BrainHandle`] and
+//! knows nothing about transports: [`McpServer::serve`] runs it over stdio,
+//! and anemone-web mounts it as a streamable HTTP endpoint.
+
+use anyhow::Result;
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tracing::warn;
+
+use crate::brain::BrainHandle;
+use crate::memory::inspect::{MemoryReply, MemoryRequest};
+use crate::memory::MemoryQuery;
+use crate::tools::shell::TEXT_EXTS;
+
+/// Revisions we can speak, newest first.
+const SUPPORTED_VERSIONS: &[&str] = &["2025-06-18", "2025-03-26", "2024-11-05"];
+
+/// Most memories one search returns.
+const MAX_SEARCH_RESULTS: usize = 50;
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+/// Prefix of the URIs box files are offered under as resources.
+const RESOURCE_SCHEME: &str = "box:///";
+
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+/// Serves one anemone over MCP.
+#[derive(Clone)]
+pub struct McpServer {
+    handle: BrainHandle,
+}
+
+impl McpServer {
+    pub fn new(handle: BrainHandle) -> Self {
+        Self { handle }
+    }
+
+    /// Answer newline-delimited JSON-RPC from `reader` on `writer` until
+    /// `reader` closes.
+    pub async fn serve<R, W>(&self, reader: R, mut writer: W) -> Result<()>
+    where
+        R: AsyncRead + Unpin,
+        W: AsyncWrite + Unpin,
+    {
+        let mut lines = BufReader::new(reader).lines();
+        while let Some(line) = lines.next_line().await? {
+            if line.trim().is_empty() {
+                continue;
+            }
+            if let Some(reply) = self.handle_text(&line).await {
+                let mut out = reply.to_string();
+                out.push('\n');
+                writer.write_all(out.as_bytes()).await?;
+                writer.flush().await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Answer a raw message. `None` when there's nothing to send back.
+    pub async fn handle_text(&self, text: &str) -> Option<Value> {
+        match serde_json::from_str::<Value>(text) {
+            Ok(message) => self.handle_message(message).await,
+            Err(e) => Some(error_reply(Value::Null, RpcError::new(PARSE_ERROR, e.to_string()))),
+        }
+    }
+
+    /// Answer a message or a batch of them. Notifications and responses
+    /// get no reply, so this is `None` when nothing in it was a request.
+    pub async fn handle_message(&self, message: Value) -> Option<Value> {
+        let Value::Array(batch) = message else {
+            return self.handle_single(message).await;
+        };
+        if batch.is_empty() {
+            return Some(error_reply(Value::Null, RpcError::new(INVALID_REQUEST, "empty batch")));
+        }
+        let mut replies = Vec::new();
+        for message in batch {
+            if let Some(reply) = self.handle_single(message).await {
+                replies.push(reply);
+            }
+        }
+        (!replies.is_empty()).then_some(Value::Array(replies))
+    }
+
+    async fn handle_single(&self, message: Value) -> Option<Value> {
+        let id = message.get("id").cloned();
+        let Some(method) = message.get("method").and_then(|m| m.as_str()) else {
+            // A response to something we never ask, or junk
+            return match id {
+                Some(id) if message.get("result").is_none() && message.get("error").is_none() => {
+                    Some(error_reply(id, RpcError::new(INVALID_REQUEST, "missing method")))
+                }
+                _ => None,
+            };
+        };
+        let id = id?;
+        let params = message.get("params").cloned().unwrap_or(json!({}));
+        Some(match self.request(method, &params).await {
+            Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+            Err(e) => error_reply(id, e),
+        })
+    }
+
+    async fn request(&self, method: &str, params: &Value) -> Result<Value, RpcError> {
+        match method {
+            "initialize" => Ok(self.initialize(params)),
+            "ping" => Ok(json!({})),
+            "tools/list" => Ok(json!({"tools": tool_definitions()})),
+            "tools/call" => {
+                let name = params.get("name").and_then(|n| n.as_str()).unwrap_or("");
+                let arguments = params.get("arguments").cloned().unwrap_or(json!({}));
+                let (text, is_error) = match self.call_tool(name, &arguments).await? {
+                    Ok(text) => (text, false),
+                    Err(text) => (text, true),
+                };
+                Ok(json!({"content": [{"type": "text", "text": text}], "isError": is_error}))
+            }
+            "resources/list" => Ok(json!({"resources": self.resources()})),
+            "resources/read" => {
+                let uri = params.get("uri").and_then(|u| u.as_str()).unwrap_or("");
+                self.read_resource(uri)
+            }
+            _ => Err(RpcError::new(METHOD_NOT_FOUND, format!("Method not found: {}", method))),
+        }
+    }
+
+    fn initialize(&self, params: &Value) -> Value {
+        let requested = params.get("protocolVersion").and_then(|v| v.as_str()).unwrap_or("");
+        let version = SUPPORTED_VERSIONS
+            .iter()
+            .find(|v| **v == requested)
+            .unwrap_or(&SUPPORTED_VERSIONS[0]);
+        let name = self.handle.identity().name;
+        json!({
+            "protocolVersion": version,
+            "capabilities": {"tools": {}, "resources": {}},
+            "serverInfo": {"name": format!("anemone-{}", name.to_lowercase()), "version": env!("CARGO_PKG_VERSION")},
+            "instructions": format!(
+                "{} is an autonomous AI living in a box of files. Search its memories, \
+                 read what it has written, or send it a message.",
+                name
+            ),
+        })
+    }
+
+    // ── Tools ──
+
+    /// Run tool `name`. The outer error is a protocol error; the inner one
+    /// is a failed call, reported to the client as a tool error.
+    async fn call_tool(&self, name: &str, arguments: &Value) -> Result<Result<String, String>, RpcError> {
+        let str_arg = |key: &str| arguments.get(key).and_then(|v| v.as_str()).unwrap_or("").trim();
+        Ok(match name {
+            "search_memories" => {
+                let query = str_arg("query");
+                if query.is_empty() {
+                    return Err(RpcError::new(INVALID_PARAMS, "query is required"));
+                }
+                let top_k = arguments
+                    .get("top_k")
+                    .and_then(|v| v.as_u64())
+                    .map_or(10, |k| (k as usize).clamp(1, MAX_SEARCH_RESULTS));
+                let mut search = MemoryQuery::about(query).with_top_k(top_k);
+                if !str_arg("kind").is_empty() {
+                    search = search.with_kind(str_arg("kind"));
+                }
+                match self.handle.memory(MemoryRequest::Search(search)).await {
+                    Ok(MemoryReply::Results(results)) if results.is_empty() => {
+                        Ok("No matching memories.".to_string())
+                    }
+                    Ok(MemoryReply::Results(results)) => Ok(results
+                        .iter()
+                        .map(|r| {
+                            format!(
+                                "[{}] {} (importance {}, score {:.2}): {}",
+                                r.memory.timestamp, r.memory.kind, r.memory.importance, r.score, r.memory.content
+                            )
+                        })
+                        .collect::<Vec<_>>()
+                        .join("\n")),
+                    Ok(_) => Err("unexpected reply from the brain".to_string()),
+                    Err(e) => Err(e.to_string()),
+                }
+            }
+            "list_files" => {
+                let files = self.handle.files();
+                Ok(if files.is_empty() { "The box is empty.".to_string() } else { files.join("\n") })
+            }
+            "read_file" => {
+                let path = str_arg("path");
+                if path.is_empty() {
+                    return Err(RpcError::new(INVALID_PARAMS, "path is required"));
+                }
+                self.read_file(path)
+            }
+            "read_projects" => match self.read_file("projects.md") {
+                Ok(text) => Ok(text),
+                Err(_) => Ok("No projects.md yet.".to_string()),
+            },
+            "send_message" => {
+                let text = str_arg("text");
+                if text.is_empty() {
+                    return Err(RpcError::new(INVALID_PARAMS, "text is required"));
+                }
+                match self.handle.message(text.to_string()).await {
+                    Ok(()) => Ok(format!("Sent to {}.", self.handle.identity().name)),
+                    Err(e) => Err(e.to_string()),
+                }
+            }
+            _ => return Err(RpcError::new(INVALID_PARAMS, format!("Unknown tool: {}", name))),
+        })
+    }
+
+    fn read_file(&self, path: &str) -> Result<String, String> {
+        let Some(full) = self.handle.box_path(path) else {
+            return Err("Blocked: path outside the box.".to_string());
+        };
+        std::fs::read_to_string(&full).map_err(|e| format!("Error reading {}: {}", path, e))
+    }
+
+    // ── Resources ──
+
+    /// The box's text files, `projects.md` first.
+    fn resources(&self) -> Vec<Value> {
+        let mut files: Vec<String> = self
+            .handle
+            .files()
+            .into_iter()
+            .filter(|f| TEXT_EXTS.iter().any(|ext| f.ends_with(ext)))
+            .collect();
+        if let Some(i) = files.iter().position(|f| f == "projects.md") {
+            let projects = files.remove(i);
+            files.insert(0, projects);
+        }
+        files
+            .iter()
+            .map(|f| {
+                let mut resource = json!({
+                    "uri": format!("{}{}", RESOURCE_SCHEME, f),
+                    "name": f,
+                    "mimeType": mime_type(f),
+                });
+                if f == "projects.md" {
+                    resource["description"] = json!("Current plan and project tracker");
+                }
+                resource
+            })
+            .collect()
+    }
+
+    fn read_resource(&self, uri: &str) -> Result<Value, RpcError> {
+        let Some(path) = uri.strip_prefix(RESOURCE_SCHEME) else {
+            return Err(RpcError::new(INVALID_PARAMS, format!("Unknown resource: {}", uri)));
+        };
+        match self.read_file(path) {
+            Ok(text) => Ok(json!({"contents": [{"uri": uri, "mimeType": mime_type(path), "text": text}]})),
+            Err(e) => {
+                warn!("MCP resource {}: {}", uri, e);
+                // -32002 is the spec's "resource not found"
+                Err(RpcError::new(-32002, e))
+            }
+        }
+    }
+}
+
+fn mime_type(path: &str) -> &'static str {
+    if path.ends_with(".md") {
+        "text/markdown"
+    } else if path.ends_with(".json") {
+        "application/json"
+    } else {
+        "text/plain"
+    }
+}
+
+fn error_reply(id: Value, error: RpcError) -> Value {
+    json!({"jsonrpc": "2.0", "id": id, "error": {"code": error.code, "message": error.message}})
+}
+
+fn tool_definitions() -> Vec<Value> {
+    let no_arguments = json!({"type": "object", "properties": {}});
+    vec![
+        json!({
+            "name": "search_memories",
+            "description": "Search the anemone's memories — thoughts, reflections, and more — ranked by relevance, recency, and importance.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "query": {"type": "string", "description": "What to look for"},
+                    "kind": {"type": "string", "description": "Only this kind of memory, e.g. \"thought\" or \"reflection\""},
+                    "top_k": {"type": "integer", "description": "How many memories to return (default 10, at most 50)"},
+                },
+                "required": ["query"],
+            },
+        }),
+        json!({
+            "name": "list_files",
+            "description": "List every file in the anemone's box.",
+            "inputSchema": no_arguments,
+        }),
+        json!({
+            "name": "read_file",
+            "description": "Read a file from the anemone's box.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {"path": {"type": "string", "description": "Path relative to the box, as list_files gives it"}},
+                "required": ["path"],
+            },
+        }),
+        json!({
+            "name": "read_projects",
+            "description": "Read projects.md: what the anemone is working on and what it plans next.",
+            "inputSchema": no_arguments,
+        }),
+        json!({
+            "name": "send_message",
+            "description": "Send the anemone a message, as its owner would. It reads it on its next thought.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {"text": {"type": "string"}},
+                "required": ["text"],
+            },
+        }),
+    ]
+}
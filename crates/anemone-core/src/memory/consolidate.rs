@@ -0,0 +1,317 @@
+//! Consolidation for the dream phase: near-duplicate memories are merged
+//! into one entry, stale low-importance thoughts and the unimportant part of
+//! cold segments leave the stream for archive segments, and shrunken
+//! segments are compacted. Archived memories are kept verbatim, so the
+//! references of merged entries and older reflections stay resolvable.
+
+use anyhow::{bail, Result};
+use chrono::{DateTime, Utc};
+use std::collections::HashSet;
+use tracing::{error, info};
+
+use super::MemoryStream;
+use crate::types::Memory;
+
+/// Neighbours checked for each memory when looking for near-duplicates
+const CLUSTER_NEIGHBOURS: usize = 8;
+
+impl MemoryStream {
+    /// Groups of near-duplicate memories: same kind, and cosine similarity
+    /// of at least `threshold` to the oldest memory of the group. Biggest
+    /// groups first; each memory is in at most one.
+    pub fn near_duplicates(&self, threshold: f64) -> Vec<Vec<&Memory>> {
+        let mut taken = vec![false; self.memories.len()];
+        let mut groups: Vec<Vec<usize>> = Vec::new();
+        for (position, memory) in self.memories.iter().enumerate() {
+            if taken[position] || self.is_stale(memory) {
+                continue;
+            }
+            let mut group = vec![position];
+            for (neighbour, similarity) in self.index.search(&memory.embedding, CLUSTER_NEIGHBOURS) {
+                if neighbour != position
+                    && !taken[neighbour]
+                    && similarity >= threshold
+                    && self.memories[neighbour].kind == memory.kind
+                {
+                    group.push(neighbour);
+                }
+            }
+            if group.len() > 1 {
+                for &p in &group {
+                    taken[p] = true;
+                }
+                group.sort_unstable();
+                groups.push(group);
+            }
+        }
+        groups.sort_by_key(|group| std::cmp::Reverse(group.len()));
+        groups
+            .into_iter()
+            .map(|group| group.into_iter().map(|p| &self.memories[p]).collect())
+            .collect()
+    }
+
+    /// Unpinned thoughts stored before `older_than` with importance of at
+    /// most `max_importance`, oldest first.
+    pub fn stale_thoughts(&self, older_than: DateTime<Utc>, max_importance: i32) -> Vec<&Memory> {
+        self.memories
+            .iter()
+            .zip(&self.times)
+            .filter(|(m, time)| {
+                m.kind == "thought"
+                    && !m.pinned
+                    && m.importance <= max_importance
+                    && time.is_some_and(|t| t < older_than)
+            })
+            .map(|(m, _)| m)
+            .collect()
+    }
+
+    /// Replace the memories with `ids` by one entry holding `content`. It
+    /// keeps their kind (or becomes a thought if they differ), their highest
+    /// importance and depth, and the newest timestamp, and references them.
+    pub async fn consolidate(&mut self, ids: &[String], content: &str) -> Result<Memory> {
+        let merged: Vec<(&Memory, Option<DateTime<Utc>>)> = self
+            .memories
+            .iter()
+            .zip(self.times.iter().copied())
+            .filter(|(m, _)| ids.contains(&m.id))
+            .collect();
+        if merged.len() < 2 {
+            bail!("need at least two stored memories to merge, found {}", merged.len());
+        }
+
+        let first = merged[0].0;
+        let kind = if merged.iter().all(|(m, _)| m.kind == first.kind) {
+            first.kind.clone()
+        } else {
+            "thought".to_string()
+        };
+        let newest = merged
+            .iter()
+            .max_by_key(|(_, time)| *time)
+            .map(|(m, _)| m.timestamp.clone())
+            .unwrap_or_default();
+        let importance = merged.iter().map(|(m, _)| m.importance).max().unwrap_or(first.importance);
+        let depth = merged.iter().map(|(m, _)| m.depth).max().unwrap_or(first.depth);
+        let references: Vec<String> = merged.iter().map(|(m, _)| m.id.clone()).collect();
+        let pinned = merged.iter().any(|(m, _)| m.pinned);
+
+        let embedding = match self.embed(content).await {
+            Ok(emb) => emb,
+            Err(e) => {
+                error!("Embedding failed: {}", e);
+                Vec::new()
+            }
+        };
+
+        let (embedding_model, embedding_dim) = self.embedded_with(&embedding);
+        let entry = Memory {
+            id: format!("m_{:04}", self.next_id),
+            timestamp: newest,
+            kind,
+            content: content.to_string(),
+            importance,
+            depth,
+            references,
+            embedding,
+            pinned,
+            embedding_model,
+            embedding_dim,
+        };
+
+        self.archive(&entry.references.iter().cloned().collect())?;
+        self.push(entry.clone());
+        if !self.is_stale(&entry) {
+            self.index.add(self.memories.len() - 1, &entry.embedding);
+        }
+        self.next_id += 1;
+        self.store.append(&entry)?;
+
+        info!("Memory {}: merged {}", entry.id, entry.references.join(", "));
+        Ok(entry)
+    }
+
+    /// Move the memories with `ids` to the archive. Returns how many moved.
+    pub fn archive(&mut self, ids: &HashSet<String>) -> Result<usize> {
+        let archived: Vec<&Memory> = self.memories.iter().filter(|m| ids.contains(&m.id)).collect();
+        if archived.is_empty() {
+            return Ok(0);
+        }
+        let count = archived.len();
+        self.store.archive(&archived)?;
+        self.remove(ids)?;
+        Ok(count)
+    }
+
+    /// Memories moved out of the stream, oldest archive first.
+    pub fn archived(&mut self) -> Vec<Memory> {
+        self.store.archived()
+    }
+
+    /// Storage upkeep. Sealed segments whose memories are all older than
+    /// `memory_cold_days` are cold: their memories below
+    /// `memory_cold_keep_importance` (unless pinned) go to the archive. Then neighbouring
+    /// segments that fit in one are merged. Returns how many were archived.
+    pub fn compact(&mut self) -> Result<usize> {
+        let mut archived = 0;
+        if self.config.memory_cold_days > 0.0 {
+            let cutoff = Utc::now()
+                - chrono::TimeDelta::seconds((self.config.memory_cold_days * 86400.0) as i64);
+            let keep = self.config.memory_cold_keep_importance;
+            let cold: HashSet<String> = self
+                .store
+                .sealed_ranges()
+                .into_iter()
+                .filter(|range| self.times[range.clone()].iter().all(|t| t.is_some_and(|t| t < cutoff)))
+                .flat_map(|range| self.memories[range].iter())
+                .filter(|m| m.importance < keep && !m.pinned)
+                .map(|m| m.id.clone())
+                .collect();
+            archived = self.archive(&cold)?;
+        }
+
+        let merges = self.store.compact(&self.memories)?;
+        if archived > 0 || merges > 0 {
+            info!("Compacted memory: {} archived from cold segments, {} segment merges", archived, merges);
+        }
+        Ok(archived)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::providers::{LlmProvider, MockProvider};
+    use std::io::Write;
+    use std::sync::Arc;
+
+    /// Write (content, kind, importance, hours ago) memories with mock
+    /// embeddings, oldest first, and open them as a stream.
+    async fn write_stream(
+        dir: &std::path::Path,
+        config: Config,
+        memories: &[(&str, &str, i32, i64)],
+    ) -> MemoryStream {
+        let mock = Arc::new(MockProvider::new());
+        let mut file =
+            std::fs::File::create(dir.join(super::super::storage::LEGACY_STREAM_FILENAME)).unwrap();
+        for (i, (content, kind, importance, hours_ago)) in memories.iter().enumerate() {
+            let memory = Memory {
+                id: format!("m_{:04}", i),
+                timestamp: (Utc::now() - chrono::TimeDelta::hours(*hours_ago)).to_rfc3339(),
+                kind: kind.to_string(),
+                content: content.to_string(),
+                importance: *importance,
+                depth: if *kind == "thought" { 0 } else { 1 },
+                references: Vec::new(),
+                embedding: mock.embed(content).await.unwrap().vector,
+                pinned: false,
+                embedding_model: String::new(),
+                embedding_dim: 0,
+            };
+            writeln!(file, "{}", serde_json::to_string(&memory).unwrap()).unwrap();
+        }
+        let provider: Arc<dyn LlmProvider> = mock;
+        MemoryStream::new(dir, config, provider)
+    }
+
+    #[tokio::test]
+    async fn test_near_duplicates_are_merged() {
+        let tmp = tempfile::tempdir().unwrap();
+        let mut stream = write_stream(
+            tmp.path(),
+            Config::default(),
+            &[
+                ("the coral reef at dawn", "thought", 4, 30),
+                ("tax forms are due", "thought", 6, 20),
+                ("at dawn the coral reef", "thought", 7, 10),
+                ("the coral reef at dawn", "reflection", 8, 5),
+            ],
+        )
+        .await;
+
+        let groups = stream.near_duplicates(0.92);
+        assert_eq!(groups.len(), 1, "different kinds aren't merged");
+        let ids: Vec<String> = groups[0].iter().map(|m| m.id.clone()).collect();
+        assert_eq!(ids, ["m_0000", "m_0002"]);
+
+        let merged = stream.consolidate(&ids, "I keep watching the reef at dawn").await.unwrap();
+        assert_eq!(merged.id, "m_0004");
+        assert_eq!(merged.importance, 7);
+        assert_eq!(merged.references, ids);
+        assert_eq!(stream.memories.len(), 3);
+        assert!(stream.near_duplicates(0.92).is_empty());
+
+        // The merged entry replaced the originals on disk and in the index
+        let mut reloaded = MemoryStream::new(tmp.path(), Config::default(), Arc::new(MockProvider::new()));
+        let contents: Vec<&str> = reloaded.memories.iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(
+            contents,
+            ["tax forms are due", "the coral reef at dawn", "I keep watching the reef at dawn"]
+        );
+        assert_eq!(reloaded.index.len(), 3);
+        let archived: Vec<String> = reloaded.archived().into_iter().map(|m| m.id).collect();
+        assert_eq!(archived, ids);
+    }
+
+    #[tokio::test]
+    async fn test_stale_thoughts_are_archived() {
+        let tmp = tempfile::tempdir().unwrap();
+        let mut stream = write_stream(
+            tmp.path(),
+            Config::default(),
+            &[
+                ("walked to the window", "thought", 2, 100),
+                ("found a new species", "thought", 9, 100),
+                ("tidied the desk", "thought", 1, 2),
+                ("old habits fade", "reflection", 2, 100),
+            ],
+        )
+        .await;
+
+        let cutoff = Utc::now() - chrono::TimeDelta::hours(72);
+        let stale: HashSet<String> = stream.stale_thoughts(cutoff, 3).iter().map(|m| m.id.clone()).collect();
+        assert_eq!(stale, HashSet::from(["m_0000".to_string()]));
+
+        assert_eq!(stream.archive(&stale).unwrap(), 1);
+        assert_eq!(stream.archive(&stale).unwrap(), 0);
+        assert!(stream.stale_thoughts(cutoff, 3).is_empty());
+        assert_eq!(stream.memories.len(), 3);
+        assert_eq!(stream.by_importance[2].len(), 1);
+        assert!(stream.cached_embedding("old habits fade").is_some());
+    }
+
+    #[tokio::test]
+    async fn test_cold_segments_are_archived_and_compacted() {
+        let tmp = tempfile::tempdir().unwrap();
+        let config = Config {
+            memory_segment_size: 2,
+            ..Config::default()
+        };
+        let old = 24 * 100;
+        let mut stream = write_stream(
+            tmp.path(),
+            config,
+            &[
+                ("walked to the window", "thought", 2, old),
+                ("found a new species", "thought", 9, old),
+                ("old habits fade", "reflection", 3, old),
+                ("read about tides", "thought", 5, old),
+                ("tidied the desk", "thought", 1, 1),
+            ],
+        )
+        .await;
+        assert_eq!(stream.store.sealed_ranges(), [0..2, 2..4]);
+
+        // Everything in the cold segments below importance 7 is archived, and
+        // the two shrunken segments become one
+        assert_eq!(stream.compact().unwrap(), 3);
+        let ids: Vec<&str> = stream.memories.iter().map(|m| m.id.as_str()).collect();
+        assert_eq!(ids, ["m_0001", "m_0004"]);
+        assert_eq!(stream.store.sealed_ranges(), vec![0..1]);
+        assert_eq!(stream.archived().len(), 3);
+        assert_eq!(stream.compact().unwrap(), 0);
+    }
+}
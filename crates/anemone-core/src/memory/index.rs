@@ -0,0 +1,487 @@
+//! Inverted-file (IVF) vector index over memory embeddings.
+//!
+//! Embeddings are normalized once and clustered with spherical k-means; a
+//! query only scans the vectors in its nearest few clusters. Streams too
+//! small to be worth clustering are searched exhaustively.
+//!
+//! On disk (`memory/index.bin`) the index is a header, the centroids, and one
+//! `(memory position, cluster)` record per indexed memory. Records are appended
+//! as memories are added; the file is rewritten only when the clusters are
+//! retrained. The vectors themselves are rebuilt from the memory stream on
+//! load, and a file that doesn't match the stream is retrained from scratch.
+
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use tracing::{error, info, warn};
+
+pub const INDEX_FILENAME: &str = "index.bin";
+
+const MAGIC: &[u8; 8] = b"ANMIVF01";
+
+/// Below this many vectors the index isn't clustered and search is exhaustive
+pub const MIN_TRAIN: usize = 512;
+
+/// Retrain once the index has grown this many times past its last training
+const RETRAIN_GROWTH: usize = 4;
+
+const KMEANS_ITERATIONS: usize = 8;
+
+/// Clusters are trained on at most this many (evenly spaced) vectors
+const MAX_TRAIN_SAMPLE: usize = 8192;
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+/// Unit-length f32 copy of `v`, or `None` for an empty or zero vector.
+fn normalized(v: &[f64]) -> Option<Vec<f32>> {
+    let norm = v.iter().map(|x| x * x).sum::<f64>().sqrt();
+    if v.is_empty() || norm == 0.0 {
+        return None;
+    }
+    Some(v.iter().map(|x| (x / norm) as f32).collect())
+}
+
+pub struct VectorIndex {
+    path: Option<PathBuf>,
+    /// Dimension of the indexed embeddings; 0 until the first one arrives
+    dim: usize,
+    /// Unit-length copies of every indexed embedding, `dim` floats each
+    vectors: Vec<f32>,
+    /// Memory position of each indexed vector
+    positions: Vec<u32>,
+    /// `k * dim` unit-length cluster centres; empty while untrained
+    centroids: Vec<f32>,
+    /// Slots (into `vectors`/`positions`) belonging to each cluster
+    lists: Vec<Vec<u32>>,
+    /// How many vectors the clusters were trained on
+    trained_on: usize,
+}
+
+impl VectorIndex {
+    /// Build the index for `embeddings` (memory position, embedding), reusing
+    /// the clusters saved at `path` when they still match.
+    pub fn open<'a>(
+        path: Option<&Path>,
+        embeddings: impl IntoIterator<Item = (usize, &'a [f64])>,
+    ) -> Self {
+        let mut index = Self {
+            path: path.map(Path::to_path_buf),
+            dim: 0,
+            vectors: Vec::new(),
+            positions: Vec::new(),
+            centroids: Vec::new(),
+            lists: Vec::new(),
+            trained_on: 0,
+        };
+        for (position, embedding) in embeddings {
+            index.push(position, embedding);
+        }
+        if index.len() < MIN_TRAIN {
+            return index;
+        }
+
+        match index.load_clusters() {
+            Ok(true) => {}
+            Ok(false) => index.train(),
+            Err(e) => {
+                warn!("Rebuilding memory index: {}", e);
+                index.train();
+            }
+        }
+        index
+    }
+
+    /// Dimension of the indexed embeddings; 0 while empty.
+    pub fn dim(&self) -> usize {
+        self.dim
+    }
+
+    /// Number of indexed vectors.
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    /// Whether search goes through clusters rather than every vector.
+    pub fn is_trained(&self) -> bool {
+        !self.lists.is_empty()
+    }
+
+    fn vector(&self, slot: usize) -> &[f32] {
+        &self.vectors[slot * self.dim..(slot + 1) * self.dim]
+    }
+
+    fn centroid(&self, cluster: usize) -> &[f32] {
+        &self.centroids[cluster * self.dim..(cluster + 1) * self.dim]
+    }
+
+    /// Store a vector without touching clusters. Embeddings of another
+    /// dimension than the first one seen (e.g. after a model change) are skipped.
+    fn push(&mut self, position: usize, embedding: &[f64]) -> bool {
+        if self.dim == 0 {
+            self.dim = embedding.len();
+        }
+        if embedding.len() != self.dim {
+            return false;
+        }
+        let Some(vector) = normalized(embedding) else {
+            return false;
+        };
+        self.vectors.extend_from_slice(&vector);
+        self.positions.push(position as u32);
+        true
+    }
+
+    /// Index the embedding of the memory at `position`.
+    pub fn add(&mut self, position: usize, embedding: &[f64]) {
+        if !self.push(position, embedding) {
+            return;
+        }
+        let slot = self.len() - 1;
+
+        let untrained_and_big = !self.is_trained() && self.len() >= MIN_TRAIN;
+        let outgrown = self.is_trained() && self.len() >= self.trained_on * RETRAIN_GROWTH;
+        if untrained_and_big || outgrown {
+            self.train();
+            return;
+        }
+        if self.is_trained() {
+            let cluster = self.nearest_cluster(self.vector(slot));
+            self.lists[cluster].push(slot as u32);
+            if let Err(e) = self.append_record(position as u32, cluster as u32) {
+                error!("Failed to update memory index: {}", e);
+            }
+        }
+    }
+
+    /// The `n` indexed memories most similar to `query`, as (memory position,
+    /// cosine similarity), best first.
+    pub fn search(&self, query: &[f64], n: usize) -> Vec<(usize, f64)> {
+        if n == 0 || query.len() != self.dim {
+            return Vec::new();
+        }
+        let Some(query) = normalized(query) else {
+            return Vec::new();
+        };
+
+        let mut hits: Vec<(usize, f32)> = if self.is_trained() {
+            // Probe the nearest clusters until they hold enough candidates
+            let mut clusters: Vec<(usize, f32)> = (0..self.lists.len())
+                .map(|c| (c, dot(&query, self.centroid(c))))
+                .collect();
+            clusters.sort_by(|a, b| b.1.total_cmp(&a.1));
+            let min_probes = self.probes();
+            let mut hits = Vec::new();
+            for (probed, (cluster, _)) in clusters.into_iter().enumerate() {
+                if probed >= min_probes && hits.len() >= n {
+                    break;
+                }
+                hits.extend(
+                    self.lists[cluster]
+                        .iter()
+                        .map(|&slot| (slot as usize, dot(&query, self.vector(slot as usize)))),
+                );
+            }
+            hits
+        } else {
+            (0..self.len())
+                .map(|slot| (slot, dot(&query, self.vector(slot))))
+                .collect()
+        };
+
+        let n = n.min(hits.len());
+        if n < hits.len() {
+            hits.select_nth_unstable_by(n, |a, b| b.1.total_cmp(&a.1));
+            hits.truncate(n);
+        }
+        hits.sort_by(|a, b| b.1.total_cmp(&a.1));
+        hits.into_iter()
+            .map(|(slot, sim)| (self.positions[slot] as usize, sim as f64))
+            .collect()
+    }
+
+    /// Clusters probed per query — about a tenth of them, at least a handful.
+    fn probes(&self) -> usize {
+        (self.lists.len() / 10).max(4)
+    }
+
+    fn nearest_cluster(&self, vector: &[f32]) -> usize {
+        (0..self.lists.len())
+            .map(|c| (c, dot(vector, self.centroid(c))))
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map_or(0, |(c, _)| c)
+    }
+
+    // ── Training ──
+
+    /// Spherical k-means over (a sample of) the vectors, then assign every
+    /// vector to its nearest centre and rewrite the index file.
+    fn train(&mut self) {
+        let n = self.len();
+        let k = ((n as f64).sqrt().round() as usize).clamp(1, 1024);
+        let dim = self.dim;
+        let stride = n.div_ceil(MAX_TRAIN_SAMPLE).max(1);
+        let sample: Vec<usize> = (0..n).step_by(stride).collect();
+
+        // Evenly spaced starting centres keep training deterministic
+        self.centroids = (0..k)
+            .flat_map(|c| self.vector(sample[c * sample.len() / k]).to_vec())
+            .collect();
+        self.lists = vec![Vec::new(); k];
+
+        for _ in 0..KMEANS_ITERATIONS {
+            let mut sums = vec![0.0f32; k * dim];
+            let mut counts = vec![0usize; k];
+            for &slot in &sample {
+                let cluster = self.nearest_cluster(self.vector(slot));
+                counts[cluster] += 1;
+                for (sum, x) in sums[cluster * dim..(cluster + 1) * dim]
+                    .iter_mut()
+                    .zip(self.vector(slot))
+                {
+                    *sum += x;
+                }
+            }
+            for cluster in 0..k {
+                // An empty cluster keeps its old centre
+                if counts[cluster] == 0 {
+                    continue;
+                }
+                let sum = &sums[cluster * dim..(cluster + 1) * dim];
+                let norm = dot(sum, sum).sqrt();
+                if norm > 0.0 {
+                    for (c, s) in self.centroids[cluster * dim..(cluster + 1) * dim]
+                        .iter_mut()
+                        .zip(sum)
+                    {
+                        *c = s / norm;
+                    }
+                }
+            }
+        }
+
+        for slot in 0..n {
+            let cluster = self.nearest_cluster(self.vector(slot));
+            self.lists[cluster].push(slot as u32);
+        }
+        self.trained_on = n;
+        info!("Memory index trained: {} vectors in {} clusters", n, k);
+
+        if let Err(e) = self.save() {
+            error!("Failed to save memory index: {}", e);
+        }
+    }
+
+    // ── Persistence ──
+
+    fn save(&self) -> Result<()> {
+        let Some(ref path) = self.path else {
+            return Ok(());
+        };
+        let mut buf = Vec::with_capacity(20 + self.centroids.len() * 4 + self.len() * 8);
+        buf.extend_from_slice(MAGIC);
+        for n in [self.dim, self.lists.len(), self.trained_on] {
+            buf.extend_from_slice(&(n as u32).to_le_bytes());
+        }
+        for c in &self.centroids {
+            buf.extend_from_slice(&c.to_le_bytes());
+        }
+        let mut records: Vec<(u32, u32)> = self
+            .lists
+            .iter()
+            .enumerate()
+            .flat_map(|(cluster, slots)| slots.iter().map(move |&slot| (slot, cluster as u32)))
+            .collect();
+        records.sort_unstable();
+        for (slot, cluster) in records {
+            buf.extend_from_slice(&self.positions[slot as usize].to_le_bytes());
+            buf.extend_from_slice(&cluster.to_le_bytes());
+        }
+
+        // Write-then-rename so a crash never leaves a torn index behind
+        let tmp = path.with_extension("bin.tmp");
+        std::fs::write(&tmp, &buf)?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    fn append_record(&self, position: u32, cluster: u32) -> Result<()> {
+        let Some(ref path) = self.path else {
+            return Ok(());
+        };
+        let mut file = std::fs::OpenOptions::new().append(true).open(path)?;
+        let mut record = [0u8; 8];
+        record[..4].copy_from_slice(&position.to_le_bytes());
+        record[4..].copy_from_slice(&cluster.to_le_bytes());
+        file.write_all(&record)?;
+        Ok(())
+    }
+
+    /// Restore clusters from disk. `Ok(false)` when there is nothing usable
+    /// to restore; vectors missing from the file are assigned and appended.
+    fn load_clusters(&mut self) -> Result<bool> {
+        let Some(path) = self.path.clone() else {
+            return Ok(false);
+        };
+        let mut bytes = Vec::new();
+        match std::fs::File::open(&path) {
+            Ok(mut file) => file.read_to_end(&mut bytes)?,
+            Err(_) => return Ok(false),
+        };
+
+        let mut words = bytes
+            .get(8..)
+            .context("truncated header")?
+            .chunks_exact(4)
+            .map(|w| [w[0], w[1], w[2], w[3]]);
+        if &bytes[..8] != MAGIC {
+            anyhow::bail!("not an index file");
+        }
+        let mut next = || words.next().context("truncated index");
+        let dim = u32::from_le_bytes(next()?) as usize;
+        let k = u32::from_le_bytes(next()?) as usize;
+        let trained_on = u32::from_le_bytes(next()?) as usize;
+        if dim != self.dim || k == 0 {
+            anyhow::bail!("dimension changed ({} -> {})", dim, self.dim);
+        }
+        let centroids = (0..k * dim)
+            .map(|_| next().map(f32::from_le_bytes))
+            .collect::<Result<Vec<f32>>>()?;
+
+        // Records must follow the indexed memories in order; a torn final
+        // record or a stream that changed underneath means retraining.
+        let mut lists = vec![Vec::new(); k];
+        let mut restored = 0;
+        while let (Some(position), Some(cluster)) = (words.next(), words.next()) {
+            let position = u32::from_le_bytes(position);
+            let cluster = u32::from_le_bytes(cluster) as usize;
+            if self.positions.get(restored) != Some(&position) || cluster >= k {
+                anyhow::bail!("records don't match the memory stream");
+            }
+            lists[cluster].push(restored as u32);
+            restored += 1;
+        }
+
+        self.centroids = centroids;
+        self.lists = lists;
+        self.trained_on = trained_on.max(1);
+        // Memories added after the last record (e.g. a crash between writes)
+        for slot in restored..self.len() {
+            let cluster = self.nearest_cluster(self.vector(slot));
+            self.lists[cluster].push(slot as u32);
+            self.append_record(self.positions[slot], cluster as u32)?;
+        }
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{Rng, SeedableRng};
+
+    fn random_vectors(n: usize, dim: usize) -> Vec<Vec<f64>> {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(7);
+        (0..n)
+            .map(|_| (0..dim).map(|_| rng.gen_range(-1.0..1.0)).collect())
+            .collect()
+    }
+
+    fn exact_top(vectors: &[Vec<f64>], query: &[f64], n: usize) -> Vec<usize> {
+        let q = normalized(query).unwrap();
+        let mut scored: Vec<(usize, f32)> = vectors
+            .iter()
+            .enumerate()
+            .map(|(i, v)| (i, dot(&q, &normalized(v).unwrap())))
+            .collect();
+        scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+        scored.into_iter().take(n).map(|(i, _)| i).collect()
+    }
+
+    #[test]
+    fn test_small_index_is_exact() {
+        let vectors = random_vectors(100, 16);
+        let index = VectorIndex::open(None, vectors.iter().enumerate().map(|(i, v)| (i, &v[..])));
+        assert!(!index.is_trained());
+
+        let hits: Vec<usize> = index.search(&vectors[3], 5).into_iter().map(|(p, _)| p).collect();
+        assert_eq!(hits, exact_top(&vectors, &vectors[3], 5));
+        assert_eq!(hits[0], 3);
+    }
+
+    #[test]
+    fn test_trained_index_finds_near_duplicates() {
+        let vectors = random_vectors(2000, 32);
+        let mut index = VectorIndex::open(None, std::iter::empty());
+        for (i, v) in vectors.iter().enumerate() {
+            index.add(i, v);
+        }
+        assert!(index.is_trained());
+
+        let mut recalled = 0;
+        for target in (0..2000).step_by(97) {
+            // A slightly perturbed copy should lead straight back to its source
+            let query: Vec<f64> = vectors[target].iter().map(|x| x * 1.01 + 0.001).collect();
+            let hits = index.search(&query, 10);
+            assert_eq!(hits.len(), 10);
+            if hits[0].0 == target {
+                recalled += 1;
+            }
+        }
+        assert!(recalled >= 19, "recalled {} of 21", recalled);
+    }
+
+    #[test]
+    fn test_reopen_reuses_clusters_and_appends() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(INDEX_FILENAME);
+        let vectors = random_vectors(MIN_TRAIN + 50, 8);
+
+        let mut index = VectorIndex::open(Some(&path), std::iter::empty());
+        for (i, v) in vectors[..MIN_TRAIN + 40].iter().enumerate() {
+            index.add(i, v);
+        }
+        let size = std::fs::metadata(&path).unwrap().len();
+        index.add(MIN_TRAIN + 40, &vectors[MIN_TRAIN + 40]);
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), size + 8);
+
+        // Ten more memories than the file knows about — they get appended
+        let reopened =
+            VectorIndex::open(Some(&path), vectors.iter().enumerate().map(|(i, v)| (i, &v[..])));
+        assert!(reopened.is_trained());
+        assert_eq!(reopened.centroids, index.centroids);
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), size + 8 * 10);
+        assert_eq!(reopened.search(&vectors[MIN_TRAIN + 45], 1)[0].0, MIN_TRAIN + 45);
+    }
+
+    #[test]
+    fn test_mismatched_file_is_retrained() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(INDEX_FILENAME);
+        std::fs::write(&path, b"ANMIVF01garbage").unwrap();
+
+        let vectors = random_vectors(MIN_TRAIN, 8);
+        let index =
+            VectorIndex::open(Some(&path), vectors.iter().enumerate().map(|(i, v)| (i, &v[..])));
+        assert!(index.is_trained());
+        let bytes = std::fs::read(&path).unwrap();
+        assert_eq!(&bytes[..8], MAGIC);
+        assert!(bytes.len() > 20);
+    }
+
+    #[test]
+    fn test_other_dimensions_are_skipped() {
+        let mut index = VectorIndex::open(None, std::iter::empty());
+        index.add(0, &[1.0, 0.0]);
+        index.add(1, &[1.0, 0.0, 0.0]);
+        index.add(2, &[]);
+        assert_eq!(index.len(), 1);
+        assert!(index.search(&[1.0, 0.0, 0.0], 3).is_empty());
+    }
+}
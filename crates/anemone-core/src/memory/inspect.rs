@@ -0,0 +1,347 @@
+//! The owner's view of the stream: browsing, searching with the score
+//! breakdown, following a reflection's references, and editing — importance,
+//! pins and deletion. Requests arrive over the brain's command channel
+//! ([`crate::brain::BrainCommand::Memory`]), so the brain stays the only
+//! owner of its stream. Memories go out without their embeddings.
+
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+use std::collections::HashSet;
+use tracing::info;
+
+use super::reflection::ReflectionGraph;
+use super::storage::without_embedding;
+use super::{MemoryQuery, MemoryStream};
+use crate::types::Memory;
+
+/// One page of memories, newest first.
+#[derive(Debug, Clone, Serialize)]
+pub struct MemoryPage {
+    /// Memories matching the filter, across all pages
+    pub total: usize,
+    pub offset: usize,
+    pub memories: Vec<Memory>,
+}
+
+/// The three retrieval factors of one result.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct ScoreBreakdown {
+    pub recency: f64,
+    pub importance: f64,
+    pub relevance: f64,
+}
+
+impl From<[f64; 3]> for ScoreBreakdown {
+    fn from([recency, importance, relevance]: [f64; 3]) -> Self {
+        Self {
+            recency,
+            importance,
+            relevance,
+        }
+    }
+}
+
+/// A search result and how it scored. `raw` holds the factors as computed
+/// (recency decay, importance 1-10, cosine similarity); `weighted` holds
+/// them normalized and weighted, and sums to `score`.
+#[derive(Debug, Clone, Serialize)]
+pub struct ScoredMemory {
+    pub memory: Memory,
+    pub score: f64,
+    pub raw: ScoreBreakdown,
+    pub weighted: ScoreBreakdown,
+    /// Embedded by another model, so relevance is left at 0 until re-embedded
+    pub stale_embedding: bool,
+}
+
+/// A memory with the memories it references, live or archived.
+#[derive(Debug, Clone, Serialize)]
+pub struct MemoryDetail {
+    pub memory: Memory,
+    /// Whether the memory itself has been archived
+    pub archived: bool,
+    pub references: Vec<Memory>,
+    /// Referenced ids that were deleted
+    pub missing: Vec<String>,
+}
+
+/// What the owner can ask of the stream.
+#[derive(Debug, Clone)]
+pub enum MemoryRequest {
+    List {
+        kind: Option<String>,
+        offset: usize,
+        limit: usize,
+    },
+    Search(MemoryQuery),
+    Get(String),
+    /// Change importance and/or pin state
+    Update {
+        id: String,
+        importance: Option<i32>,
+        pinned: Option<bool>,
+    },
+    Delete(String),
+    ReflectionGraph,
+}
+
+#[derive(Debug, Clone)]
+pub enum MemoryReply {
+    Page(MemoryPage),
+    Results(Vec<ScoredMemory>),
+    Detail(MemoryDetail),
+    /// The memory after the edit
+    Updated(Memory),
+    Deleted(Memory),
+    Graph(ReflectionGraph),
+}
+
+impl MemoryStream {
+    /// Answer a [`MemoryRequest`]. Memories still being written are
+    /// committed first.
+    pub async fn handle(&mut self, request: MemoryRequest) -> Result<MemoryReply> {
+        self.flush().await;
+        Ok(match request {
+            MemoryRequest::List {
+                kind,
+                offset,
+                limit,
+            } => MemoryReply::Page(self.list(kind.as_deref(), offset, limit)),
+            MemoryRequest::Search(query) => MemoryReply::Results(self.search_scored(&query).await),
+            MemoryRequest::Get(id) => MemoryReply::Detail(self.detail(&id)?),
+            MemoryRequest::Update {
+                id,
+                importance,
+                pinned,
+            } => MemoryReply::Updated(self.update(&id, importance, pinned)?),
+            MemoryRequest::Delete(id) => MemoryReply::Deleted(self.delete(&id)?),
+            MemoryRequest::ReflectionGraph => MemoryReply::Graph(self.reflection_graph()),
+        })
+    }
+
+    /// Memories of `kind` (any if `None`), newest first.
+    pub fn list(&self, kind: Option<&str>, offset: usize, limit: usize) -> MemoryPage {
+        let matching: Vec<&Memory> = self
+            .memories
+            .iter()
+            .rev()
+            .filter(|m| kind.is_none_or(|k| m.kind == k))
+            .collect();
+        MemoryPage {
+            total: matching.len(),
+            offset,
+            memories: matching
+                .into_iter()
+                .skip(offset)
+                .take(limit)
+                .map(without_embedding)
+                .collect(),
+        }
+    }
+
+    /// [`Self::search`], with how each result scored.
+    pub async fn search_scored(&self, query: &MemoryQuery) -> Vec<ScoredMemory> {
+        self.score_query(query)
+            .await
+            .into_iter()
+            .map(|scored| ScoredMemory {
+                memory: without_embedding(&self.memories[scored.position]),
+                score: scored.score,
+                raw: scored.raw.into(),
+                weighted: scored.weighted.into(),
+                stale_embedding: scored.stale,
+            })
+            .collect()
+    }
+
+    /// The memory with `id` and its references, looked up in the stream and
+    /// then the archive.
+    pub fn detail(&mut self, id: &str) -> Result<MemoryDetail> {
+        let live = self.memories.iter().find(|m| m.id == id).map(without_embedding);
+        let (memory, archived, archive) = match live {
+            Some(memory) => (memory, false, None),
+            None => {
+                let archive = self.archived();
+                let memory = archive
+                    .iter()
+                    .find(|m| m.id == id)
+                    .map(without_embedding)
+                    .with_context(|| format!("no memory {}", id))?;
+                (memory, true, Some(archive))
+            }
+        };
+
+        let mut references = Vec::new();
+        let mut unresolved = Vec::new();
+        for reference in &memory.references {
+            match self.memories.iter().find(|m| &m.id == reference) {
+                Some(found) => references.push(without_embedding(found)),
+                None => unresolved.push(reference.clone()),
+            }
+        }
+        let mut missing = Vec::new();
+        if !unresolved.is_empty() {
+            let archive = archive.unwrap_or_else(|| self.archived());
+            for reference in unresolved {
+                match archive.iter().find(|m| m.id == reference) {
+                    Some(found) => references.push(without_embedding(found)),
+                    None => missing.push(reference),
+                }
+            }
+        }
+        Ok(MemoryDetail {
+            memory,
+            archived,
+            references,
+            missing,
+        })
+    }
+
+    /// Set the importance (1-10) and/or pin state of a live memory, and
+    /// persist it. Returns the memory as it now stands.
+    pub fn update(&mut self, id: &str, importance: Option<i32>, pinned: Option<bool>) -> Result<Memory> {
+        if let Some(importance) = importance {
+            if !(1..=10).contains(&importance) {
+                bail!("importance must be 1-10, got {}", importance);
+            }
+        }
+        let position = self.position(id)?;
+
+        if let Some(importance) = importance {
+            let old = self.memories[position].importance.clamp(0, 10) as usize;
+            self.by_importance[old].retain(|&p| p != position);
+            let bucket = &mut self.by_importance[importance as usize];
+            let at = bucket.partition_point(|&p| p < position);
+            bucket.insert(at, position);
+            self.memories[position].importance = importance;
+        }
+        if let Some(pinned) = pinned {
+            self.memories[position].pinned = pinned;
+        }
+        self.store.rewrite(&self.memories, &HashSet::from([id.to_string()]))?;
+
+        let memory = &self.memories[position];
+        info!("Memory {}: importance={}, pinned={}", id, memory.importance, memory.pinned);
+        Ok(without_embedding(memory))
+    }
+
+    /// Delete a live memory for good. Returns what was deleted.
+    pub fn delete(&mut self, id: &str) -> Result<Memory> {
+        let memory = without_embedding(&self.memories[self.position(id)?]);
+        self.remove(&HashSet::from([id.to_string()]))?;
+        info!("Memory {}: deleted", id);
+        Ok(memory)
+    }
+
+    /// Pinned memories, oldest first.
+    pub fn pinned(&self) -> Vec<&Memory> {
+        self.memories.iter().filter(|m| m.pinned).collect()
+    }
+
+    fn position(&self, id: &str) -> Result<usize> {
+        self.memories
+            .iter()
+            .position(|m| m.id == id)
+            .with_context(|| format!("no memory {}", id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::providers::{LlmProvider, MockProvider};
+    use std::sync::Arc;
+
+    async fn stream_with(dir: &std::path::Path, contents: &[(&str, &str)]) -> MemoryStream {
+        let provider: Arc<dyn LlmProvider> = Arc::new(MockProvider::new());
+        let mut stream = MemoryStream::new(dir, Config::default(), provider);
+        for (content, kind) in contents {
+            stream.add(content, kind, 0, Vec::new()).await.unwrap();
+        }
+        stream
+    }
+
+    fn reload(dir: &std::path::Path) -> MemoryStream {
+        MemoryStream::new(dir, Config::default(), Arc::new(MockProvider::new()))
+    }
+
+    #[tokio::test]
+    async fn test_list_and_search_with_scores() {
+        let tmp = tempfile::tempdir().unwrap();
+        let stream = stream_with(
+            tmp.path(),
+            &[
+                ("the coral reef at dawn", "thought"),
+                ("tax forms are due", "thought"),
+                ("reefs matter to me", "reflection"),
+            ],
+        )
+        .await;
+
+        let page = stream.list(Some("thought"), 0, 1);
+        assert_eq!(page.total, 2);
+        assert_eq!(page.memories[0].content, "tax forms are due");
+        assert!(page.memories[0].embedding.is_empty());
+        assert_eq!(stream.list(None, 1, 10).memories.len(), 2);
+
+        let results = stream
+            .search_scored(&MemoryQuery::about("tax forms").with_top_k(3))
+            .await;
+        assert_eq!(results[0].memory.content, "tax forms are due");
+        for result in &results {
+            let weighted = result.weighted;
+            let sum = weighted.recency + weighted.importance + weighted.relevance;
+            assert!((sum - result.score).abs() < 1e-9);
+        }
+        assert!(results[0].raw.relevance > results[1].raw.relevance);
+    }
+
+    #[tokio::test]
+    async fn test_edits_are_persisted() {
+        let tmp = tempfile::tempdir().unwrap();
+        let mut stream = stream_with(
+            tmp.path(),
+            &[("walked to the window", "thought"), ("found a new species", "thought")],
+        )
+        .await;
+
+        let updated = stream.update("m_0000", Some(9), Some(true)).unwrap();
+        assert_eq!((updated.importance, updated.pinned), (9, true));
+        assert_eq!(stream.by_importance[9], [0]);
+        assert!(stream.update("m_0000", Some(11), None).is_err());
+        assert!(stream.update("m_0042", None, Some(true)).is_err());
+
+        let deleted = stream.delete("m_0001").unwrap();
+        assert_eq!(deleted.content, "found a new species");
+        assert!(stream.delete("m_0001").is_err());
+
+        let reloaded = reload(tmp.path());
+        assert_eq!(reloaded.memories.len(), 1);
+        assert_eq!(reloaded.memories[0].importance, 9);
+        assert_eq!(reloaded.pinned().len(), 1);
+        assert_eq!(reloaded.memories[0].embedding.len(), stream.memories[0].embedding.len());
+    }
+
+    #[tokio::test]
+    async fn test_detail_resolves_archived_references() {
+        let tmp = tempfile::tempdir().unwrap();
+        let mut stream = stream_with(
+            tmp.path(),
+            &[("the coral reef at dawn", "thought"), ("tax forms are due", "thought")],
+        )
+        .await;
+        let refs = vec!["m_0000".to_string(), "m_0001".to_string(), "m_0099".to_string()];
+        stream.add("reefs matter to me", "reflection", 1, refs).await.unwrap();
+        stream.archive(&HashSet::from(["m_0000".to_string()])).unwrap();
+
+        let detail = stream.detail("m_0002").unwrap();
+        assert!(!detail.archived);
+        let ids: HashSet<&str> = detail.references.iter().map(|m| m.id.as_str()).collect();
+        assert_eq!(ids, HashSet::from(["m_0000", "m_0001"]));
+        assert_eq!(detail.missing, ["m_0099"]);
+
+        assert!(stream.detail("m_0000").unwrap().archived);
+        assert!(stream.detail("m_0099").is_err());
+    }
+}
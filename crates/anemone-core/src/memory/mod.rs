@@ -0,0 +1,741 @@
+//! Smallville-inspired memory stream with three-factor retrieval.
+//! The stream lives in memory; storage, indexing, reflection and upkeep are in the submodules.
+
+pub mod consolidate;
+pub mod index;
+pub mod inspect;
+pub mod query;
+pub mod reembed;
+pub mod reflection;
+pub mod storage;
+pub mod writer;
+
+pub use query::MemoryQuery;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tracing::{error, info};
+
+use crate::config::Config;
+use crate::providers::LlmProvider;
+use crate::types::Memory;
+use crate::usage::{UsageCategory, UsageMeter};
+use index::{VectorIndex, INDEX_FILENAME};
+use storage::{RepairReport, SegmentStore};
+
+/// Nearest neighbours fetched per requested result once the index is trained
+const NEIGHBOURS_PER_RESULT: usize = 10;
+const MIN_NEIGHBOURS: usize = 50;
+/// Most recent / most important memories always considered alongside them
+const RECENT_CANDIDATES: usize = 64;
+const IMPORTANT_CANDIDATES: usize = 64;
+
+/// Query embeddings kept for [`MemoryStream::retrieve_sync`]
+const QUERY_CACHE_LIMIT: usize = 32;
+
+/// Pure cosine similarity — no numpy/nalgebra needed.
+fn cosine_sim(a: &[f64], b: &[f64]) -> f64 {
+    if a.len() != b.len() || a.is_empty() {
+        return 0.0;
+    }
+    let dot: f64 = a.iter().zip(b.iter()).map(|(x, y)| x * y).sum();
+    let norm_a: f64 = a.iter().map(|x| x * x).sum::<f64>().sqrt();
+    let norm_b: f64 = b.iter().map(|x| x * x).sum::<f64>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return 0.0;
+    }
+    dot / (norm_a * norm_b)
+}
+
+/// A ranked candidate: factors are [recency, importance, relevance].
+struct Scored {
+    position: usize,
+    score: f64,
+    raw: [f64; 3],
+    weighted: [f64; 3],
+    /// Its embedding is from another model, so relevance wasn't scored
+    stale: bool,
+}
+
+/// Memory stream with recency x importance x relevance retrieval.
+pub struct MemoryStream {
+    pub memories: Vec<Memory>,
+    pub importance_sum: f64,
+    next_id: u32,
+    config: Config,
+    provider: Arc<dyn LlmProvider>,
+    usage: UsageMeter,
+    store: SegmentStore,
+    /// Parsed timestamp of each memory (`None` if unparseable)
+    times: Vec<Option<DateTime<Utc>>>,
+    /// Memory positions by importance (0-10), oldest first
+    by_importance: Vec<Vec<usize>>,
+    /// Latest memory position for each content, so text that was stored
+    /// (e.g. the last thought) can reuse its embedding
+    by_content: HashMap<String, usize>,
+    /// Embeddings of queries that aren't memories, from [`Self::cache_query`]
+    query_cache: HashMap<String, Vec<f64>>,
+    index: VectorIndex,
+    /// Dimension of the current embedding model's vectors; 0 until known
+    current_dim: AtomicUsize,
+    /// Memories waiting for the next write batch
+    queued: Vec<Memory>,
+    writing: Option<writer::WriteTask>,
+}
+
+impl MemoryStream {
+    /// Create a new MemoryStream, loading existing memories from the box's
+    /// segments (migrating an old `memory_stream.jsonl` first).
+    pub fn new(environment_path: &Path, config: Config, provider: Arc<dyn LlmProvider>) -> Self {
+        let (store, memories) = SegmentStore::open(environment_path, config.memory_segment_size);
+        let current_dim = memories
+            .iter()
+            .rev()
+            .find(|m| m.embedding_model == config.embedding_model_id() && !m.embedding.is_empty())
+            .map_or(0, |m| m.embedding.len());
+        let mut stream = Self {
+            memories: Vec::new(),
+            importance_sum: 0.0,
+            next_id: 0,
+            usage: UsageMeter::in_memory(&config),
+            store,
+            config,
+            provider,
+            times: Vec::new(),
+            by_importance: vec![Vec::new(); 11],
+            by_content: HashMap::new(),
+            query_cache: HashMap::new(),
+            index: VectorIndex::open(None, std::iter::empty()),
+            current_dim: AtomicUsize::new(current_dim),
+            queued: Vec::new(),
+            writing: None,
+        };
+        stream.load(memories);
+        stream.index = stream.open_index();
+        stream
+    }
+
+    /// Index the memories whose embeddings are from the current model.
+    fn open_index(&self) -> VectorIndex {
+        VectorIndex::open(
+            Some(&self.store.dir().join(INDEX_FILENAME)),
+            self.memories
+                .iter()
+                .enumerate()
+                .filter(|(_, m)| !self.is_stale(m))
+                .map(|(i, m)| (i, m.embedding.as_slice())),
+        )
+    }
+
+    /// Whether `memory` lacks an embedding from the current model: it has
+    /// none, or one recorded as from another model, or one of another
+    /// dimension. Unrecorded models of the right dimension count as current.
+    pub fn is_stale(&self, memory: &Memory) -> bool {
+        let dim = self.current_dim.load(Ordering::Relaxed);
+        memory.embedding.is_empty()
+            || (!memory.embedding_model.is_empty() && memory.embedding_model != self.config.embedding_model_id())
+            || (dim != 0 && memory.embedding.len() != dim)
+    }
+
+    /// Count importance and embedding calls against `usage`.
+    pub fn with_usage(mut self, usage: UsageMeter) -> Self {
+        self.usage = usage;
+        self
+    }
+
+    /// Embed `text`, counting the call (local embeddings aren't API calls).
+    async fn embed(&self, text: &str) -> Result<Vec<f64>> {
+        let embedding = self.provider.embed(text).await?;
+        if !self.config.local_embeddings() {
+            self.usage.record(
+                UsageCategory::Embedding,
+                &self.config.embedding_model,
+                embedding.usage.as_ref(),
+            );
+        }
+        if !embedding.vector.is_empty() {
+            self.current_dim.store(embedding.vector.len(), Ordering::Relaxed);
+        }
+        Ok(embedding.vector)
+    }
+
+    /// The model and dimension to record for `embedding`.
+    fn embedded_with(&self, embedding: &[f64]) -> (String, usize) {
+        if embedding.is_empty() {
+            return (String::new(), 0);
+        }
+        (self.config.embedding_model_id().to_string(), embedding.len())
+    }
+
+    fn load(&mut self, memories: Vec<Memory>) {
+        for mem in memories {
+            self.push(mem);
+        }
+        if let Some(max_id) = self
+            .memories
+            .iter()
+            .filter_map(|m| m.id.strip_prefix("m_").and_then(|s| s.parse::<u32>().ok()))
+            .max()
+        {
+            self.next_id = max_id + 1;
+        }
+        info!("Loaded {} memories from stream", self.memories.len());
+    }
+
+    /// Corrupt lines and partial embeddings fixed while loading.
+    pub fn repairs(&self) -> &RepairReport {
+        self.store.repairs()
+    }
+
+    /// Append to the in-memory stream, keeping the lookup tables in step.
+    fn push(&mut self, memory: Memory) {
+        let position = self.memories.len();
+        self.times.push(
+            DateTime::parse_from_rfc3339(&memory.timestamp)
+                .ok()
+                .map(|t| t.with_timezone(&Utc)),
+        );
+        self.by_importance[memory.importance.clamp(0, 10) as usize].push(position);
+        if !memory.embedding.is_empty() {
+            self.by_content.insert(memory.content.clone(), position);
+        }
+        self.memories.push(memory);
+    }
+
+    /// Drop the memories with `ids` from the stream and its segments.
+    fn remove(&mut self, ids: &HashSet<String>) -> Result<()> {
+        self.store.remove(&self.memories, ids)?;
+
+        // Positions shift, so the lookup tables and the index are rebuilt
+        let memories = std::mem::take(&mut self.memories);
+        self.times.clear();
+        self.by_importance = vec![Vec::new(); 11];
+        self.by_content.clear();
+        for memory in memories.into_iter().filter(|m| !ids.contains(&m.id)) {
+            self.push(memory);
+        }
+
+        let _ = std::fs::remove_file(self.store.dir().join(INDEX_FILENAME));
+        self.index = self.open_index();
+        Ok(())
+    }
+
+    /// Three-factor retrieval: recency x importance x relevance.
+    pub async fn retrieve(&self, query: &str, top_k: Option<usize>) -> Vec<&Memory> {
+        let top_k = top_k.unwrap_or(self.config.memory_retrieval_count);
+        self.search(&MemoryQuery::about(query).with_top_k(top_k)).await
+    }
+
+    /// Retrieve with filters and weights. The query text is embedded (unless
+    /// it already has a cached embedding); if that fails, memories are ranked
+    /// without relevance.
+    pub async fn search(&self, query: &MemoryQuery) -> Vec<&Memory> {
+        self.score_query(query)
+            .await
+            .into_iter()
+            .map(|scored| &self.memories[scored.position])
+            .collect()
+    }
+
+    /// [`Self::search`], with the scores.
+    async fn score_query(&self, query: &MemoryQuery) -> Vec<Scored> {
+        if self.memories.is_empty() {
+            return Vec::new();
+        }
+        let Some(ref text) = query.text else {
+            return self.score(None, query, None);
+        };
+        if let Some(embedding) = self.cached_embedding(text) {
+            return self.score(Some(embedding), query, None);
+        }
+        match self.embed(text).await {
+            Ok(embedding) => self.score(Some(&embedding), query, None),
+            Err(e) => {
+                error!("Query embedding failed: {}", e);
+                self.score(None, query, None)
+            }
+        }
+    }
+
+    /// Three-factor retrieval for an already-embedded query.
+    pub fn retrieve_by_embedding(&self, query_embedding: &[f64], top_k: usize) -> Vec<&Memory> {
+        self.rank(Some(query_embedding), &MemoryQuery::default().with_top_k(top_k), None)
+    }
+
+    /// Synchronous retrieve (see [`Self::search_sync`]).
+    pub fn retrieve_sync(&self, query: &str, top_k: Option<usize>) -> Vec<&Memory> {
+        let top_k = top_k.unwrap_or(self.config.memory_retrieval_count);
+        self.search_sync(&MemoryQuery::about(query).with_top_k(top_k))
+    }
+
+    /// Synchronous search — never calls the provider. Relevance comes from
+    /// the cached embedding of the query text: a stored memory with that
+    /// exact content (left out of the results), or one from
+    /// [`Self::cache_query`]. Uncached text ranks without relevance.
+    pub fn search_sync(&self, query: &MemoryQuery) -> Vec<&Memory> {
+        let text = query.text.as_deref();
+        match text.and_then(|t| self.cached_embedding(t)) {
+            Some(embedding) => self.rank(Some(embedding), query, text),
+            None => self.rank(None, query, None),
+        }
+    }
+
+    /// Embedding of `text` without an API call, if it's a stored memory
+    /// (embedded by the current model) or a cached query.
+    pub fn cached_embedding(&self, text: &str) -> Option<&[f64]> {
+        self.by_content
+            .get(text)
+            .map(|&position| &self.memories[position])
+            .filter(|memory| !self.is_stale(memory))
+            .map(|memory| memory.embedding.as_slice())
+            .or_else(|| self.query_cache.get(text).map(Vec::as_slice))
+    }
+
+    /// Embed `text` ahead of time so [`Self::retrieve_sync`] can rank by
+    /// relevance to it.
+    pub async fn cache_query(&mut self, text: &str) {
+        if self.cached_embedding(text).is_some() {
+            return;
+        }
+        match self.embed(text).await {
+            Ok(embedding) if !embedding.is_empty() => {
+                if self.query_cache.len() >= QUERY_CACHE_LIMIT {
+                    self.query_cache.clear();
+                }
+                self.query_cache.insert(text.to_string(), embedding);
+            }
+            Ok(_) => {}
+            Err(e) => error!("Query embedding failed: {}", e),
+        }
+    }
+
+    /// Memory positions worth scoring for `query`: of the memories passing
+    /// its filters, the query's nearest neighbours plus the most recent and
+    /// most important. Every memory passing them instead until the index is
+    /// trained (or while it holds another model's vectors), for exhaustive
+    /// queries, and when the filters leave fewer than `top_k` neighbours.
+    /// Also returns the neighbours' similarities.
+    fn candidates(
+        &self,
+        query_embedding: Option<&[f64]>,
+        query: &MemoryQuery,
+        top_k: usize,
+    ) -> (Vec<usize>, HashMap<usize, f64>) {
+        let passes = |&p: &usize| query.matches(&self.memories[p], self.times[p]);
+        let scan = || ((0..self.memories.len()).filter(passes).collect(), HashMap::new());
+        // A query from a model the index wasn't built with can't use it
+        let other_dim = query_embedding.is_some_and(|e| e.len() != self.index.dim());
+        if !self.index.is_trained() || query.exhaustive || other_dim {
+            return scan();
+        }
+
+        let mut similarities = HashMap::new();
+        if let Some(embedding) = query_embedding {
+            let wanted = (top_k * NEIGHBOURS_PER_RESULT).max(MIN_NEIGHBOURS);
+            let neighbours = self.index.search(embedding, wanted).into_iter();
+            similarities.extend(neighbours.filter(|(p, _)| passes(p)));
+            if similarities.len() < top_k {
+                return scan();
+            }
+        }
+        // The newest and most important of the memories passing the filters
+        let recent = (0..self.memories.len()).rev().filter(passes).take(RECENT_CANDIDATES);
+        let important = self
+            .by_importance
+            .iter()
+            .rev()
+            .flat_map(|bucket| bucket.iter().rev().copied())
+            .filter(passes)
+            .take(IMPORTANT_CANDIDATES);
+        let mut seen = HashSet::new();
+        let positions = similarities
+            .keys()
+            .copied()
+            .chain(recent)
+            .chain(important)
+            .filter(|&p| seen.insert(p))
+            .collect();
+        (positions, similarities)
+    }
+
+    /// Score candidates and return the best `top_k`, best first. Each
+    /// factor — recency (exponential decay), importance, and relevance
+    /// (cosine similarity, only with a query embedding) — is min-max
+    /// normalized across the candidates, then weighted. Memories whose
+    /// content is exactly `skip` are left out.
+    fn score(
+        &self,
+        query_embedding: Option<&[f64]>,
+        query: &MemoryQuery,
+        skip: Option<&str>,
+    ) -> Vec<Scored> {
+        let top_k = query.top_k.unwrap_or(self.config.memory_retrieval_count);
+        let weights = query.weights.unwrap_or(self.config.retrieval_weights);
+        let (candidates, similarities) = self.candidates(query_embedding, query, top_k);
+
+        let now = Utc::now();
+        let decay_rate = self.config.recency_decay_rate;
+        // (position, [recency, importance, relevance], stale)
+        let factors: Vec<(usize, [f64; 3], bool)> = candidates
+            .into_iter()
+            .filter(|&position| skip.is_none_or(|text| self.memories[position].content != text))
+            .map(|position| {
+                let mem = &self.memories[position];
+                let hours_ago = self.times[position]
+                    .map(|t| (now - t).num_seconds() as f64 / 3600.0)
+                    .unwrap_or(1000.0);
+                let recency = (-(1.0 - decay_rate) * hours_ago).exp();
+                // Vectors from another model aren't comparable to the query
+                let stale = self.is_stale(mem);
+                let relevance = match query_embedding {
+                    Some(embedding) if !stale => similarities
+                        .get(&position)
+                        .copied()
+                        .unwrap_or_else(|| cosine_sim(embedding, &mem.embedding)),
+                    _ => 0.0,
+                };
+                (position, [recency, mem.importance as f64, relevance], stale)
+            })
+            .collect();
+
+        let ranges: [(f64, f64); 3] = std::array::from_fn(|i| {
+            factors.iter().fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), (_, f, _)| {
+                (lo.min(f[i]), hi.max(f[i]))
+            })
+        });
+        // A factor that's the same for every candidate can't tell them apart
+        let normalize = |value: f64, (lo, hi): (f64, f64)| {
+            if hi - lo > f64::EPSILON {
+                (value - lo) / (hi - lo)
+            } else {
+                0.0
+            }
+        };
+
+        let weights = [weights.recency, weights.importance, weights.relevance];
+        let mut scored: Vec<Scored> = factors
+            .into_iter()
+            .map(|(position, raw, stale)| {
+                let weighted: [f64; 3] =
+                    std::array::from_fn(|i| weights[i] * normalize(raw[i], ranges[i]));
+                Scored {
+                    position,
+                    score: weighted.iter().sum(),
+                    raw,
+                    weighted,
+                    stale,
+                }
+            })
+            .collect();
+
+        scored.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
+        scored.truncate(top_k);
+        scored
+    }
+
+    /// [`Self::score`], as the memories themselves.
+    fn rank(
+        &self,
+        query_embedding: Option<&[f64]>,
+        query: &MemoryQuery,
+        skip: Option<&str>,
+    ) -> Vec<&Memory> {
+        self.score(query_embedding, query, skip)
+            .into_iter()
+            .map(|scored| &self.memories[scored.position])
+            .collect()
+    }
+
+    /// Scores every memory, without the index. Kept as the baseline for the
+    /// retrieval benchmark and for recall checks.
+    #[doc(hidden)]
+    pub fn retrieve_exhaustive(&self, query_embedding: &[f64], top_k: usize) -> Vec<&Memory> {
+        let everything = MemoryQuery::default().with_top_k(top_k).exhaustive();
+        self.rank(Some(query_embedding), &everything, None)
+    }
+
+    pub fn should_reflect(&self) -> bool {
+        self.importance_sum >= self.config.reflection_threshold
+    }
+
+    pub fn reset_importance_sum(&mut self) {
+        self.importance_sum = 0.0;
+    }
+
+    pub fn get_recent(&self, n: usize, kind: Option<&str>) -> Vec<&Memory> {
+        let filtered: Vec<&Memory> = if let Some(k) = kind {
+            self.memories.iter().filter(|m| m.kind == k).collect()
+        } else {
+            self.memories.iter().collect()
+        };
+        let start = filtered.len().saturating_sub(n);
+        filtered[start..].to_vec()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cosine_sim_identical() {
+        let a = vec![1.0, 2.0, 3.0];
+        let b = vec![1.0, 2.0, 3.0];
+        assert!((cosine_sim(&a, &b) - 1.0).abs() < 1e-10);
+    }
+
+    #[test]
+    fn test_cosine_sim_orthogonal() {
+        let a = vec![1.0, 0.0];
+        let b = vec![0.0, 1.0];
+        assert!(cosine_sim(&a, &b).abs() < 1e-10);
+    }
+
+    #[test]
+    fn test_cosine_sim_empty() {
+        assert_eq!(cosine_sim(&[], &[]), 0.0);
+    }
+
+    use crate::providers::mock::MOCK_EMBEDDING_DIM;
+
+    fn mock_stream(dir: &Path) -> MemoryStream {
+        let provider: Arc<dyn LlmProvider> = Arc::new(crate::providers::MockProvider::new());
+        MemoryStream::new(dir, Config::default(), provider)
+    }
+
+    /// Write `n` memories with random embeddings, an hour apart, newest last.
+    fn write_synthetic_stream(dir: &Path, n: usize) -> Vec<Vec<f64>> {
+        use rand::{Rng, SeedableRng};
+        use std::io::Write;
+        let mut rng = rand::rngs::StdRng::seed_from_u64(11);
+        let start = Utc::now() - chrono::TimeDelta::hours(n as i64);
+        let mut file = std::fs::File::create(dir.join(storage::LEGACY_STREAM_FILENAME)).unwrap();
+        (0..n)
+            .map(|i| {
+                let embedding: Vec<f64> = (0..MOCK_EMBEDDING_DIM).map(|_| rng.gen_range(-1.0..1.0)).collect();
+                let memory = Memory {
+                    id: format!("m_{:04}", i),
+                    timestamp: (start + chrono::TimeDelta::hours(i as i64)).to_rfc3339(),
+                    kind: "thought".to_string(),
+                    content: format!("memory {}", i),
+                    importance: rng.gen_range(1..=10),
+                    depth: 0,
+                    references: Vec::new(),
+                    embedding: embedding.clone(),
+                    pinned: false,
+                    embedding_model: String::new(),
+                    embedding_dim: 0,
+                };
+                writeln!(file, "{}", serde_json::to_string(&memory).unwrap()).unwrap();
+                embedding
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_small_stream_ranks_like_the_linear_scan() {
+        let tmp = tempfile::tempdir().unwrap();
+        let embeddings = write_synthetic_stream(tmp.path(), 200);
+        let stream = mock_stream(tmp.path());
+        assert!(!stream.index.is_trained());
+
+        let ids = |mems: Vec<&Memory>| mems.into_iter().map(|m| m.id.clone()).collect::<Vec<_>>();
+        assert_eq!(
+            ids(stream.retrieve_by_embedding(&embeddings[42], 5)),
+            ids(stream.retrieve_exhaustive(&embeddings[42], 5)),
+        );
+    }
+
+    #[test]
+    fn test_indexed_retrieval_finds_the_relevant_memory() {
+        let tmp = tempfile::tempdir().unwrap();
+        let embeddings = write_synthetic_stream(tmp.path(), 3000);
+        let stream = mock_stream(tmp.path());
+        assert!(stream.index.is_trained());
+        assert!(tmp.path().join(storage::MEMORY_DIR).join(INDEX_FILENAME).is_file());
+
+        for target in [100, 1500, 2999] {
+            let expected = stream.retrieve_exhaustive(&embeddings[target], 3);
+            let found = stream.retrieve_by_embedding(&embeddings[target], 3);
+            assert_eq!(found[0].id, expected[0].id);
+        }
+
+        // Filtered queries use the index too, scanning only when too few of
+        // its candidates pass
+        let now = Utc::now();
+        let hours = |h: f64| chrono::TimeDelta::minutes((h * 60.0) as i64);
+        let older = MemoryQuery::default()
+            .with_top_k(3)
+            .with_time_window(None, Some(now - hours(1500.0)));
+        let (candidates, _) = stream.candidates(Some(&embeddings[100]), &older, 3);
+        assert!(candidates.len() < 1500);
+        let expected = stream.rank(Some(&embeddings[100]), &older.clone().exhaustive(), None);
+        assert_eq!(stream.rank(Some(&embeddings[100]), &older, None)[0].id, expected[0].id);
+        let narrow = MemoryQuery::default()
+            .with_top_k(3)
+            .with_time_window(Some(now - hours(2995.5)), Some(now - hours(2990.5)));
+        let (candidates, _) = stream.candidates(Some(&embeddings[7]), &narrow, 3);
+        assert_eq!(candidates.len(), 5);
+        let expected = stream.rank(Some(&embeddings[7]), &narrow.clone().exhaustive(), None);
+        let ids = |mems: Vec<&Memory>| mems.into_iter().map(|m| m.id.clone()).collect::<Vec<_>>();
+        assert_eq!(ids(stream.rank(Some(&embeddings[7]), &narrow, None)), ids(expected));
+
+        // Without a query, the newest and most important memories win
+        let newest_important = stream.retrieve_sync("", Some(1))[0];
+        assert_eq!(newest_important.importance, 10);
+    }
+
+    /// Write memories with mock embeddings, all equally old and important.
+    async fn write_mock_stream(dir: &Path, contents: &[&str]) {
+        use std::io::Write;
+        let mock = crate::providers::MockProvider::new();
+        let timestamp = (Utc::now() - chrono::TimeDelta::hours(2)).to_rfc3339();
+        let mut file = std::fs::File::create(dir.join(storage::LEGACY_STREAM_FILENAME)).unwrap();
+        for (i, content) in contents.iter().enumerate() {
+            let memory = Memory {
+                id: format!("m_{:04}", i),
+                timestamp: timestamp.clone(),
+                kind: "thought".to_string(),
+                content: content.to_string(),
+                importance: 5,
+                depth: 0,
+                references: Vec::new(),
+                embedding: mock.embed(content).await.unwrap().vector,
+                pinned: false,
+                embedding_model: String::new(),
+                embedding_dim: 0,
+            };
+            writeln!(file, "{}", serde_json::to_string(&memory).unwrap()).unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retrieve_sync_uses_stored_embeddings() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_mock_stream(
+            tmp.path(),
+            &["a reef full of coral fish", "tax forms are due", "the coral reef at dawn"],
+        )
+        .await;
+        let mut stream = mock_stream(tmp.path());
+
+        // A stored memory's text reuses its embedding, and isn't its own match
+        let related = stream.retrieve_sync("the coral reef at dawn", Some(1));
+        assert_eq!(related[0].content, "a reef full of coral fish");
+
+        // Other text needs caching first; until then there's no relevance
+        assert!(stream.cached_embedding("tax season").is_none());
+        stream.cache_query("tax season").await;
+        assert!(stream.cached_embedding("tax season").is_some());
+        assert_eq!(stream.retrieve_sync("tax season", Some(1))[0].content, "tax forms are due");
+    }
+
+    #[tokio::test]
+    async fn test_weights_decide_between_factors() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_mock_stream(tmp.path(), &["tide pools at low tide", "moved to a new desk"]).await;
+        // Same age; the unrelated memory is far more important
+        let legacy = tmp.path().join(storage::LEGACY_STREAM_FILENAME);
+        let mut lines: Vec<String> = std::fs::read_to_string(&legacy)
+            .unwrap()
+            .lines()
+            .map(String::from)
+            .collect();
+        lines[1] = lines[1].replace("\"importance\":5", "\"importance\":9");
+        std::fs::write(&legacy, lines.join("\n")).unwrap();
+        let stream = mock_stream(tmp.path());
+
+        let query = MemoryQuery::about("tide pools").with_top_k(1);
+        // Normalized, a perfect relevance match and top importance weigh the same;
+        // the weights break the tie
+        let by_relevance = query.clone().with_weights(crate::config::RetrievalWeights {
+            recency: 1.0,
+            importance: 1.0,
+            relevance: 2.0,
+        });
+        assert_eq!(stream.search(&by_relevance).await[0].content, "tide pools at low tide");
+        let by_importance = query.with_weights(crate::config::RetrievalWeights {
+            recency: 1.0,
+            importance: 2.0,
+            relevance: 1.0,
+        });
+        assert_eq!(stream.search(&by_importance).await[0].content, "moved to a new desk");
+    }
+
+    #[tokio::test]
+    async fn test_search_filters() {
+        use std::io::Write;
+        let tmp = tempfile::tempdir().unwrap();
+        let now = Utc::now();
+        let legacy = tmp.path().join(storage::LEGACY_STREAM_FILENAME);
+        let mut file = std::fs::File::create(legacy).unwrap();
+        for (i, (kind, depth, hours_ago)) in
+            [("thought", 0, 30), ("reflection", 1, 20), ("reflection", 2, 2), ("thought", 0, 1)]
+                .iter()
+                .enumerate()
+        {
+            let memory = Memory {
+                id: format!("m_{:04}", i),
+                timestamp: (now - chrono::TimeDelta::hours(*hours_ago)).to_rfc3339(),
+                kind: kind.to_string(),
+                content: format!("memory {}", i),
+                importance: 5,
+                depth: *depth,
+                references: Vec::new(),
+                embedding: Vec::new(),
+                pinned: false,
+                embedding_model: String::new(),
+                embedding_dim: 0,
+            };
+            writeln!(file, "{}", serde_json::to_string(&memory).unwrap()).unwrap();
+        }
+        drop(file);
+        let stream = mock_stream(tmp.path());
+        let ids = |query: MemoryQuery| {
+            stream
+                .search_sync(&query.with_top_k(10))
+                .into_iter()
+                .map(|m| m.id.as_str())
+                .collect::<Vec<_>>()
+        };
+
+        // Newest first when only recency differs
+        assert_eq!(ids(MemoryQuery::default().with_kind("reflection")), ["m_0002", "m_0001"]);
+        assert_eq!(ids(MemoryQuery::default().with_depth(Some(2), None)), ["m_0002"]);
+        assert_eq!(
+            ids(MemoryQuery::default().with_time_window(Some(now - chrono::TimeDelta::hours(24)), None)),
+            ["m_0003", "m_0002", "m_0001"]
+        );
+        assert_eq!(
+            ids(MemoryQuery::default()
+                .with_kind("thought")
+                .with_time_window(None, Some(now - chrono::TimeDelta::hours(12)))),
+            ["m_0000"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_added_memories_are_indexed_across_reloads() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_synthetic_stream(tmp.path(), index::MIN_TRAIN);
+        let mut stream = mock_stream(tmp.path());
+        assert!(stream.index.is_trained());
+
+        let index_path = tmp.path().join(storage::MEMORY_DIR).join(INDEX_FILENAME);
+        let index_bytes = std::fs::metadata(&index_path).unwrap().len();
+        let added = stream.add("a new memory", "thought", 0, Vec::new()).await.unwrap();
+        assert_eq!(stream.index.len(), index::MIN_TRAIN + 1);
+        // One record appended rather than the whole index rewritten
+        let grown = std::fs::metadata(&index_path).unwrap().len();
+        assert_eq!(grown, index_bytes + 8);
+
+        let reloaded = mock_stream(tmp.path());
+        assert_eq!(reloaded.index.len(), index::MIN_TRAIN + 1);
+        let hits = reloaded.index.search(&added.embedding, 1);
+        assert_eq!(reloaded.memories[hits[0].0].id, added.id);
+    }
+}
@@ -0,0 +1,105 @@
+//! Retrieval queries: what to rank against, how many results, which
+//! memories qualify, and how the three factors are weighted.
+
+use chrono::{DateTime, Utc};
+
+use crate::config::RetrievalWeights;
+use crate::types::Memory;
+
+/// A retrieval request for [`super::MemoryStream::search`], built up from
+/// [`MemoryQuery::about`] (or `default()` for no relevance) with the
+/// `with_*` methods.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryQuery {
+    /// Text to rank relevance against; without it relevance plays no part
+    pub text: Option<String>,
+    /// How many memories to return (default `memory_retrieval_count`)
+    pub top_k: Option<usize>,
+    /// Only these kinds ("thought", "reflection", ...); empty means any
+    pub kinds: Vec<String>,
+    pub min_depth: Option<i32>,
+    pub max_depth: Option<i32>,
+    /// Only memories from this time on
+    pub since: Option<DateTime<Utc>>,
+    /// Only memories from before this time
+    pub until: Option<DateTime<Utc>>,
+    /// Overrides the configured `retrieval_weights`
+    pub weights: Option<RetrievalWeights>,
+    /// Score every memory instead of the index's candidates
+    pub exhaustive: bool,
+}
+
+impl MemoryQuery {
+    /// Memories relevant to `text`.
+    pub fn about(text: impl Into<String>) -> Self {
+        Self {
+            text: Some(text.into()),
+            ..Self::default()
+        }
+    }
+
+    pub fn with_top_k(mut self, top_k: usize) -> Self {
+        self.top_k = Some(top_k);
+        self
+    }
+
+    /// Allow memories of `kind`; call repeatedly to allow several.
+    pub fn with_kind(mut self, kind: &str) -> Self {
+        self.kinds.push(kind.to_string());
+        self
+    }
+
+    /// Only memories with `min <= depth <= max`.
+    pub fn with_depth(mut self, min: Option<i32>, max: Option<i32>) -> Self {
+        self.min_depth = min;
+        self.max_depth = max;
+        self
+    }
+
+    /// Only memories stored in `[since, until)`; either end may be open.
+    pub fn with_time_window(
+        mut self,
+        since: Option<DateTime<Utc>>,
+        until: Option<DateTime<Utc>>,
+    ) -> Self {
+        self.since = since;
+        self.until = until;
+        self
+    }
+
+    pub fn with_weights(mut self, weights: RetrievalWeights) -> Self {
+        self.weights = Some(weights);
+        self
+    }
+
+    /// Score every memory rather than trusting the index — slow on a large
+    /// stream, but exact.
+    pub fn exhaustive(mut self) -> Self {
+        self.exhaustive = true;
+        self
+    }
+
+    /// Whether `memory`, stored at `time`, passes the filters. Memories with
+    /// an unreadable timestamp fail any time window.
+    pub fn matches(&self, memory: &Memory, time: Option<DateTime<Utc>>) -> bool {
+        if !self.kinds.is_empty() && !self.kinds.contains(&memory.kind) {
+            return false;
+        }
+        if self.min_depth.is_some_and(|min| memory.depth < min)
+            || self.max_depth.is_some_and(|max| memory.depth > max)
+        {
+            return false;
+        }
+        if self.since.is_some() || self.until.is_some() {
+            let Some(time) = time else {
+                return false;
+            };
+            if self.since.is_some_and(|since| time < since)
+                || self.until.is_some_and(|until| time >= until)
+            {
+                return false;
+            }
+        }
+        true
+    }
+}
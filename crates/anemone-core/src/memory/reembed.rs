@@ -0,0 +1,147 @@
+//! Re-embedding after an `embedding_model` change. Memories without a
+//! current embedding (see [`MemoryStream::is_stale`]) are embedded again in
+//! small batches, most important first. Each batch is written back to its
+//! segments before the next starts, so after a restart the job simply
+//! carries on with whatever is still stale.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use tracing::{error, info};
+
+use super::index::INDEX_FILENAME;
+use super::MemoryStream;
+
+/// Where a re-embedding job stands after a batch.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReembedProgress {
+    /// The model memories are being moved to
+    pub model: String,
+    pub dim: usize,
+    /// Re-embedded in this batch
+    pub reembedded: usize,
+    /// Failed in this batch; they are retried in a later one
+    pub failed: usize,
+    /// Still stale after this batch
+    pub remaining: usize,
+}
+
+impl MemoryStream {
+    /// How many memories still need a current embedding.
+    pub fn stale_count(&self) -> usize {
+        self.memories.iter().filter(|m| self.is_stale(m)).count()
+    }
+
+    /// Re-embed up to `max` stale memories, most important (then newest)
+    /// first, in one embeddings request. Once nothing is stale the index is
+    /// rebuilt for the new model.
+    pub async fn reembed(&mut self, max: usize) -> Result<ReembedProgress> {
+        let mut stale: Vec<usize> = (0..self.memories.len())
+            .filter(|&p| self.is_stale(&self.memories[p]))
+            .collect();
+        stale.sort_by_key(|&p| std::cmp::Reverse((self.memories[p].importance, p)));
+        stale.truncate(max);
+
+        // One request for the whole batch; if it fails, the batch waits
+        // for the next round rather than hammering a provider that's down
+        let texts: Vec<&str> = stale.iter().map(|&p| self.memories[p].content.as_str()).collect();
+        let embeddings = match self.embed_many(&texts).await {
+            Ok(embeddings) => embeddings,
+            Err(e) => {
+                error!("Re-embedding failed: {:#}", e);
+                vec![Vec::new(); stale.len()]
+            }
+        };
+
+        let mut done = HashSet::new();
+        let mut failed = 0;
+        for (position, embedding) in stale.into_iter().zip(embeddings) {
+            if embedding.is_empty() {
+                failed += 1;
+                continue;
+            }
+            let (model, dim) = self.embedded_with(&embedding);
+            let memory = &mut self.memories[position];
+            memory.embedding = embedding;
+            memory.embedding_model = model;
+            memory.embedding_dim = dim;
+            self.by_content.insert(memory.content.clone(), position);
+            done.insert(memory.id.clone());
+        }
+        if !done.is_empty() {
+            self.store.rewrite(&self.memories, &done)?;
+        }
+
+        let remaining = self.stale_count();
+        if remaining == 0 && !done.is_empty() {
+            let _ = std::fs::remove_file(self.store.dir().join(INDEX_FILENAME));
+            self.index = self.open_index();
+            info!(
+                "Re-embedded the memory stream with {}",
+                self.config.embedding_model_id()
+            );
+        }
+        Ok(ReembedProgress {
+            model: self.config.embedding_model_id().to_string(),
+            dim: self.current_dim.load(std::sync::atomic::Ordering::Relaxed),
+            reembedded: done.len(),
+            failed,
+            remaining,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::providers::{LlmProvider, MockProvider};
+    use crate::types::Memory;
+    use std::sync::Arc;
+
+    fn open(dir: &std::path::Path, model: &str) -> MemoryStream {
+        let config = Config {
+            embedding_model: model.to_string(),
+            ..Config::default()
+        };
+        let provider: Arc<dyn LlmProvider> = Arc::new(MockProvider::new());
+        MemoryStream::new(dir, config, provider)
+    }
+
+    #[tokio::test]
+    async fn test_model_change_is_reembedded_in_batches() {
+        let tmp = tempfile::tempdir().unwrap();
+        let mut stream = open(tmp.path(), "old-model");
+        for content in ["the coral reef at dawn", "tax forms are due", "watched the tide"] {
+            stream.add(content, "thought", 0, Vec::new()).await.unwrap();
+        }
+        assert_eq!(stream.memories[0].embedding_model, "old-model");
+        assert_eq!(stream.memories[0].embedding_dim, stream.memories[0].embedding.len());
+        assert_eq!(stream.stale_count(), 0);
+
+        // Switched models: every vector is stale and carries no relevance
+        let mut stream = open(tmp.path(), "new-model");
+        assert_eq!(stream.stale_count(), 3);
+        let results = stream
+            .search_scored(&crate::memory::MemoryQuery::about("tax forms").with_top_k(3))
+            .await;
+        assert!(results.iter().all(|r| r.stale_embedding && r.raw.relevance == 0.0));
+
+        let progress = stream.reembed(2).await.unwrap();
+        assert_eq!((progress.reembedded, progress.remaining), (2, 1));
+
+        // Resumes after a restart from what was written back
+        let mut stream = open(tmp.path(), "new-model");
+        assert_eq!(stream.stale_count(), 1);
+        let progress = stream.reembed(2).await.unwrap();
+        assert_eq!((progress.reembedded, progress.remaining), (1, 0));
+        assert!(stream.memories.iter().all(|m: &Memory| m.embedding_model == "new-model"));
+        assert_eq!(stream.index.len(), 3);
+
+        let results = stream
+            .search_scored(&crate::memory::MemoryQuery::about("tax forms").with_top_k(1))
+            .await;
+        assert_eq!(results[0].memory.content, "tax forms are due");
+        assert!(!results[0].stale_embedding);
+    }
+}
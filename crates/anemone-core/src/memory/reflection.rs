@@ -0,0 +1,223 @@
+//! Reflection trees, generative-agents style. A reflection answers a
+//! salient question from retrieved evidence and cites the memories it rests
+//! on; its depth is one more than the deepest of them, so reflections over
+//! reflections build a tree. [`parse_insight`] reads an insight line and
+//! [`MemoryStream::reflection_graph`] lays the tree out for visualization.
+
+use serde::Serialize;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use super::storage::without_embedding;
+use super::MemoryStream;
+use crate::types::Memory;
+
+/// An insight line: its text, and the evidence it cites.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Insight {
+    pub content: String,
+    pub references: Vec<String>,
+    pub depth: i32,
+}
+
+/// Parse one line of insight output, e.g.
+/// `I think best at my desk (because of m_0004, m_0007)`. Only ids from
+/// `evidence` count as citations; an insight citing none rests on all of
+/// it. Returns `None` for blank lines.
+pub fn parse_insight(line: &str, evidence: &[Memory]) -> Option<Insight> {
+    let line = strip_list_marker(line);
+
+    let cited: HashSet<&str> = line
+        .split(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .collect();
+    let mut cited: Vec<&Memory> = evidence.iter().filter(|m| cited.contains(m.id.as_str())).collect();
+    if cited.is_empty() {
+        cited = evidence.iter().collect();
+    }
+
+    // Drop the citation clause from the stored text
+    let content = match line.to_ascii_lowercase().rfind("(because of") {
+        Some(at) => line[..at].trim_end(),
+        None => line,
+    };
+    let content = content.trim_end_matches(|c: char| c.is_whitespace() || c == ',');
+    if content.is_empty() {
+        return None;
+    }
+    Some(Insight {
+        content: content.to_string(),
+        references: cited.iter().map(|m| m.id.clone()).collect(),
+        depth: cited.iter().map(|m| m.depth).max().unwrap_or(0) + 1,
+    })
+}
+
+/// `line` without a leading bullet or number ("- ", "2. ", "3) ").
+pub fn strip_list_marker(line: &str) -> &str {
+    let line = line.trim().trim_start_matches(['-', '*']).trim_start();
+    match line.split_once(['.', ')']) {
+        Some((number, rest)) if !number.is_empty() && number.chars().all(|c| c.is_ascii_digit()) => {
+            rest.trim_start()
+        }
+        _ => line,
+    }
+}
+
+/// One memory in the reflection graph.
+#[derive(Debug, Clone, Serialize)]
+pub struct GraphNode {
+    pub id: String,
+    pub kind: String,
+    pub content: String,
+    pub importance: i32,
+    pub depth: i32,
+    pub timestamp: String,
+    pub pinned: bool,
+    pub archived: bool,
+}
+
+/// A reflection (`from`) citing a memory (`to`).
+#[derive(Debug, Clone, Serialize)]
+pub struct GraphEdge {
+    pub from: String,
+    pub to: String,
+}
+
+/// Every live reflection and, transitively, the memories it rests on.
+#[derive(Debug, Clone, Serialize)]
+pub struct ReflectionGraph {
+    pub nodes: Vec<GraphNode>,
+    pub edges: Vec<GraphEdge>,
+    /// Referenced ids that were deleted
+    pub missing: Vec<String>,
+}
+
+impl MemoryStream {
+    /// The reflection tree: live reflections, followed through their
+    /// references into the stream and the archive.
+    pub fn reflection_graph(&mut self) -> ReflectionGraph {
+        // The archive is only read if something refers into it
+        let needs_archive = {
+            let live: HashSet<&str> = self.memories.iter().map(|m| m.id.as_str()).collect();
+            self.memories
+                .iter()
+                .filter(|m| m.kind == "reflection")
+                .flat_map(|m| &m.references)
+                .any(|id| !live.contains(id.as_str()))
+        };
+        let archive: HashMap<String, Memory> = if needs_archive {
+            self.archived().into_iter().map(|m| (m.id.clone(), m)).collect()
+        } else {
+            HashMap::new()
+        };
+
+        let live: HashMap<&str, &Memory> = self.memories.iter().map(|m| (m.id.as_str(), m)).collect();
+        let mut queue: VecDeque<(Memory, bool)> = self
+            .memories
+            .iter()
+            .filter(|m| m.kind == "reflection")
+            .map(|m| (without_embedding(m), false))
+            .collect();
+        let mut seen: HashSet<String> = queue.iter().map(|(m, _)| m.id.clone()).collect();
+        let mut graph = ReflectionGraph {
+            nodes: Vec::new(),
+            edges: Vec::new(),
+            missing: Vec::new(),
+        };
+        while let Some((memory, archived)) = queue.pop_front() {
+            for reference in &memory.references {
+                let found = match live.get(reference.as_str()) {
+                    Some(m) => Some((without_embedding(m), false)),
+                    None => archive.get(reference).map(|m| (without_embedding(m), true)),
+                };
+                let Some(found) = found else {
+                    if !graph.missing.contains(reference) {
+                        graph.missing.push(reference.clone());
+                    }
+                    continue;
+                };
+                graph.edges.push(GraphEdge {
+                    from: memory.id.clone(),
+                    to: reference.clone(),
+                });
+                if seen.insert(reference.clone()) {
+                    queue.push_back(found);
+                }
+            }
+            graph.nodes.push(GraphNode {
+                id: memory.id,
+                kind: memory.kind,
+                content: memory.content,
+                importance: memory.importance,
+                depth: memory.depth,
+                timestamp: memory.timestamp,
+                pinned: memory.pinned,
+                archived,
+            });
+        }
+        graph
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::providers::{LlmProvider, MockProvider};
+    use std::sync::Arc;
+
+    fn memory(id: &str, depth: i32) -> Memory {
+        Memory {
+            id: id.to_string(),
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            kind: if depth == 0 { "thought" } else { "reflection" }.to_string(),
+            content: format!("memory {}", id),
+            importance: 5,
+            depth,
+            references: Vec::new(),
+            embedding: Vec::new(),
+            pinned: false,
+            embedding_model: String::new(),
+            embedding_dim: 0,
+        }
+    }
+
+    #[test]
+    fn test_parse_insight_citations() {
+        let evidence = [memory("m_0001", 0), memory("m_0004", 2), memory("m_0007", 0)];
+
+        let insight =
+            parse_insight("1. I think best at my desk (because of m_0001, m_0004, m_0099)", &evidence).unwrap();
+        assert_eq!(insight.content, "I think best at my desk");
+        assert_eq!(insight.references, ["m_0001", "m_0004"]);
+        assert_eq!(insight.depth, 3);
+
+        // No citation: it rests on all the evidence
+        let insight = parse_insight("- Tides set my rhythm.", &evidence).unwrap();
+        assert_eq!(insight.content, "Tides set my rhythm.");
+        assert_eq!(insight.references.len(), 3);
+        assert_eq!(insight.depth, 3);
+
+        assert!(parse_insight("   ", &evidence).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_reflection_graph_follows_references() {
+        let tmp = tempfile::tempdir().unwrap();
+        let provider: Arc<dyn LlmProvider> = Arc::new(MockProvider::new());
+        let mut stream = MemoryStream::new(tmp.path(), Config::default(), provider);
+        for content in ["watched the tide", "logged the tide", "unrelated"] {
+            stream.add(content, "thought", 0, Vec::new()).await.unwrap();
+        }
+        let refs = |ids: &[&str]| ids.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        stream.add("tides matter", "reflection", 1, refs(&["m_0000", "m_0001"])).await.unwrap();
+        stream.add("I am a tidal creature", "reflection", 2, refs(&["m_0003", "m_0042"])).await.unwrap();
+        stream.archive(&HashSet::from(["m_0000".to_string()])).unwrap();
+
+        let graph = stream.reflection_graph();
+        let mut ids: Vec<&str> = graph.nodes.iter().map(|n| n.id.as_str()).collect();
+        ids.sort_unstable();
+        assert_eq!(ids, ["m_0000", "m_0001", "m_0003", "m_0004"]);
+        assert_eq!(graph.edges.len(), 3);
+        assert!(graph.nodes.iter().any(|n| n.id == "m_0000" && n.archived));
+        assert_eq!(graph.missing, ["m_0042"]);
+    }
+}
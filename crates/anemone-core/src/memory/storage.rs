@@ -0,0 +1,630 @@
+//! On-disk layout of the memory stream, in `memory/` inside the box.
+//! Memories are stored as JSONL without their embeddings; next to each
+//! `.jsonl` file an `.emb` sidecar holds the embeddings as f32:
+//!
+//! - `active.jsonl` — new memories are appended here; once it holds
+//!   `memory_segment_size` it is sealed as the next `segment-NNNNNN.jsonl`
+//! - `segment-*.jsonl` — sealed segments, oldest first. Compaction merges
+//!   neighbours that removals have shrunk
+//! - `archive/segment-*.jsonl` — archived memories, kept but not loaded
+//! - `quarantine.jsonl` — lines that failed to parse, with where they were
+//!
+//! Sidecar records are `[id length: u16][id][dimension: u32][f32 x dimension]`,
+//! little-endian, keyed by memory id so a lost line never shifts the rest.
+//! Boxes from before segments, with a single `memory_stream.jsonl`, are
+//! migrated when the store is opened.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use tracing::{error, info, warn};
+
+use crate::types::Memory;
+
+pub const MEMORY_DIR: &str = "memory";
+/// The single-file stream boxes used before segments
+pub const LEGACY_STREAM_FILENAME: &str = "memory_stream.jsonl";
+pub const QUARANTINE_FILENAME: &str = "quarantine.jsonl";
+const ACTIVE_SEGMENT: &str = "active";
+const ARCHIVE_DIR: &str = "archive";
+
+/// What opening the store had to fix.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RepairReport {
+    /// Lines moved to `quarantine.jsonl`
+    pub quarantined: usize,
+    /// Bytes cut from sidecars that ended mid-record
+    pub truncated_bytes: u64,
+}
+
+impl RepairReport {
+    pub fn is_clean(&self) -> bool {
+        self.quarantined == 0 && self.truncated_bytes == 0
+    }
+}
+
+/// A line that failed to parse, as kept in `quarantine.jsonl`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuarantinedLine {
+    /// File it came from, relative to the box
+    pub file: String,
+    /// 1-based line number
+    pub line: usize,
+    pub error: String,
+    pub raw: String,
+    pub quarantined_at: String,
+}
+
+#[derive(Debug, Clone)]
+struct Segment {
+    name: String,
+    len: usize,
+}
+
+/// The segment files of one box. Holds no memories itself — the caller
+/// keeps them, in load order, and passes them back for rewrites.
+pub struct SegmentStore {
+    dir: PathBuf,
+    /// Sealed segments oldest first, then the active segment
+    segments: Vec<Segment>,
+    segment_size: usize,
+    repairs: RepairReport,
+}
+
+impl SegmentStore {
+    /// Open the store in `env_path`, migrating a legacy stream and
+    /// quarantining corrupt lines. Returns every live memory, oldest first.
+    pub fn open(env_path: &Path, segment_size: usize) -> (Self, Vec<Memory>) {
+        let mut store = Self {
+            dir: env_path.join(MEMORY_DIR),
+            segments: Vec::new(),
+            segment_size: segment_size.max(1),
+            repairs: RepairReport::default(),
+        };
+        if let Err(e) = std::fs::create_dir_all(store.dir.join(ARCHIVE_DIR)) {
+            error!("Failed to create memory directory: {}", e);
+        }
+        if let Err(e) = store.migrate(env_path) {
+            error!("Failed to migrate memory stream: {}", e);
+        }
+
+        let mut names = segment_names(&store.dir);
+        names.push(ACTIVE_SEGMENT.to_string());
+        let mut memories = Vec::new();
+        for name in names {
+            let loaded = store.load_segment(&store.dir.clone(), &name);
+            store.segments.push(Segment {
+                name,
+                len: loaded.len(),
+            });
+            memories.extend(loaded);
+        }
+        if !store.repairs.is_clean() {
+            warn!(
+                "Repaired memory stream: {} corrupt lines quarantined, {} bytes of partial embeddings dropped",
+                store.repairs.quarantined, store.repairs.truncated_bytes
+            );
+        }
+        (store, memories)
+    }
+
+    /// Directory holding the segments (and the vector index).
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// What was fixed while opening.
+    pub fn repairs(&self) -> &RepairReport {
+        &self.repairs
+    }
+
+    /// Positions covered by each sealed segment.
+    pub fn sealed_ranges(&self) -> Vec<Range<usize>> {
+        let mut start = 0;
+        let sealed = &self.segments[..self.segments.len() - 1];
+        sealed
+            .iter()
+            .map(|segment| {
+                let range = start..start + segment.len;
+                start = range.end;
+                range
+            })
+            .collect()
+    }
+
+    /// Append a memory to the active segment, sealing it once full.
+    pub fn append(&mut self, memory: &Memory) -> Result<()> {
+        let active = self.segments.last_mut().expect("active segment");
+        // Embedding first: a crash in between leaves an orphan vector, not
+        // a memory without one
+        if !memory.embedding.is_empty() {
+            append_bytes(
+                &self.dir.join(format!("{}.emb", active.name)),
+                &encode_embedding(&memory.id, &memory.embedding),
+            )?;
+        }
+        let line = serde_json::to_string(&without_embedding(memory))?;
+        append_bytes(
+            &self.dir.join(format!("{}.jsonl", active.name)),
+            format!("{}\n", line).as_bytes(),
+        )?;
+        active.len += 1;
+
+        if active.len >= self.segment_size {
+            self.seal()?;
+        }
+        Ok(())
+    }
+
+    /// Turn the active segment into the next sealed one.
+    fn seal(&mut self) -> Result<()> {
+        let name = segment_name(next_number(&self.dir));
+        for ext in ["jsonl", "emb"] {
+            let from = self.dir.join(format!("{}.{}", ACTIVE_SEGMENT, ext));
+            if from.exists() {
+                std::fs::rename(from, self.dir.join(format!("{}.{}", name, ext)))?;
+            }
+        }
+        let active = self.segments.last_mut().expect("active segment");
+        active.name = name;
+        self.segments.push(Segment {
+            name: ACTIVE_SEGMENT.to_string(),
+            len: 0,
+        });
+        Ok(())
+    }
+
+    /// Drop the memories with `ids`, rewriting only the segments that hold
+    /// them. `memories` is everything loaded, in order.
+    pub fn remove(&mut self, memories: &[Memory], ids: &HashSet<String>) -> Result<()> {
+        let mut start = 0;
+        for segment in &mut self.segments {
+            let held = &memories[start..start + segment.len];
+            start += segment.len;
+            if !held.iter().any(|m| ids.contains(&m.id)) {
+                continue;
+            }
+            let kept: Vec<&Memory> = held.iter().filter(|m| !ids.contains(&m.id)).collect();
+            write_segment(&self.dir, &segment.name, &kept)?;
+            segment.len = kept.len();
+        }
+        Ok(())
+    }
+
+    /// Rewrite the segments holding the memories with `ids`, after they
+    /// were edited in place. `memories` is everything loaded, in order.
+    pub fn rewrite(&mut self, memories: &[Memory], ids: &HashSet<String>) -> Result<()> {
+        let mut start = 0;
+        for segment in &self.segments {
+            let held = &memories[start..start + segment.len];
+            start += segment.len;
+            if held.iter().any(|m| ids.contains(&m.id)) {
+                write_segment(&self.dir, &segment.name, &held.iter().collect::<Vec<_>>())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Merge neighbouring sealed segments that fit in one. Memories keep
+    /// their order, so positions don't change. Returns how many merges.
+    pub fn compact(&mut self, memories: &[Memory]) -> Result<usize> {
+        let mut merges = 0;
+        let mut start = 0;
+        let mut i = 0;
+        // The active segment (last) is never merged
+        while i + 2 < self.segments.len() {
+            let (first, second) = (self.segments[i].len, self.segments[i + 1].len);
+            if first + second > self.segment_size {
+                start += first;
+                i += 1;
+                continue;
+            }
+            let held: Vec<&Memory> = memories[start..start + first + second].iter().collect();
+            write_segment(&self.dir, &self.segments[i].name, &held)?;
+            remove_segment(&self.dir, &self.segments[i + 1].name)?;
+            self.segments[i].len += second;
+            self.segments.remove(i + 1);
+            merges += 1;
+        }
+        Ok(merges)
+    }
+
+    /// Write `memories` as a new archive segment.
+    pub fn archive(&mut self, memories: &[&Memory]) -> Result<()> {
+        if memories.is_empty() {
+            return Ok(());
+        }
+        let archive = self.dir.join(ARCHIVE_DIR);
+        std::fs::create_dir_all(&archive)?;
+        write_segment(&archive, &segment_name(next_number(&archive)), memories)
+    }
+
+    /// Every archived memory, oldest archive first.
+    pub fn archived(&mut self) -> Vec<Memory> {
+        let archive = self.dir.join(ARCHIVE_DIR);
+        segment_names(&archive)
+            .into_iter()
+            .flat_map(|name| self.load_segment(&archive, &name))
+            .collect()
+    }
+
+    /// Load one segment: its lines (quarantining any that don't parse)
+    /// with the embeddings from its sidecar.
+    fn load_segment(&mut self, dir: &Path, name: &str) -> Vec<Memory> {
+        let mut memories = self.read_lines(&dir.join(format!("{}.jsonl", name)));
+        let mut embeddings = self.read_sidecar(&dir.join(format!("{}.emb", name)));
+        for memory in &mut memories {
+            if let Some(embedding) = embeddings.remove(&memory.id) {
+                memory.embedding = embedding;
+            }
+        }
+        memories
+    }
+
+    /// Parse a JSONL file of memories. Lines that don't parse are moved to
+    /// the quarantine file and the file is rewritten without them.
+    fn read_lines(&mut self, path: &Path) -> Vec<Memory> {
+        let bytes = match std::fs::read(path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Vec::new(),
+            Err(e) => {
+                error!("Failed to read {}: {}", path.display(), e);
+                return Vec::new();
+            }
+        };
+        let content = String::from_utf8_lossy(&bytes);
+
+        let mut memories = Vec::new();
+        let mut good_lines = Vec::new();
+        let mut bad_lines = Vec::new();
+        for (i, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            match serde_json::from_str::<Memory>(line) {
+                Ok(memory) => {
+                    memories.push(memory);
+                    good_lines.push(line);
+                }
+                Err(e) => bad_lines.push(QuarantinedLine {
+                    file: self.relative(path),
+                    line: i + 1,
+                    error: e.to_string(),
+                    raw: line.to_string(),
+                    quarantined_at: chrono::Utc::now().to_rfc3339(),
+                }),
+            }
+        }
+
+        if !bad_lines.is_empty() {
+            let repaired = self.quarantine(&bad_lines).and_then(|_| {
+                write_atomic(path, |file| {
+                    for line in &good_lines {
+                        writeln!(file, "{}", line)?;
+                    }
+                    Ok(())
+                })
+            });
+            match repaired {
+                Ok(()) => {
+                    warn!("Quarantined {} corrupt lines from {}", bad_lines.len(), path.display());
+                    self.repairs.quarantined += bad_lines.len();
+                }
+                Err(e) => error!("Failed to quarantine corrupt lines: {}", e),
+            }
+        }
+        memories
+    }
+
+    /// Read a sidecar's embeddings by memory id. A sidecar that ends
+    /// mid-record (a write cut short) is truncated to its last whole record.
+    fn read_sidecar(&mut self, path: &Path) -> HashMap<String, Vec<f64>> {
+        let mut embeddings = HashMap::new();
+        let Ok(bytes) = std::fs::read(path) else {
+            return embeddings;
+        };
+        let mut at = 0;
+        while let Some((id, embedding, next)) = decode_embedding(&bytes, at) {
+            embeddings.insert(id, embedding);
+            at = next;
+        }
+        if at < bytes.len() {
+            let dropped = (bytes.len() - at) as u64;
+            match std::fs::OpenOptions::new()
+                .write(true)
+                .open(path)
+                .and_then(|file| file.set_len(at as u64))
+            {
+                Ok(()) => {
+                    warn!("Dropped {} bytes of partial embeddings from {}", dropped, path.display());
+                    self.repairs.truncated_bytes += dropped;
+                }
+                Err(e) => error!("Failed to truncate {}: {}", path.display(), e),
+            }
+        }
+        embeddings
+    }
+
+    fn quarantine(&self, lines: &[QuarantinedLine]) -> Result<()> {
+        let mut bytes = Vec::new();
+        for line in lines {
+            writeln!(bytes, "{}", serde_json::to_string(line)?)?;
+        }
+        append_bytes(&self.dir.join(QUARANTINE_FILENAME), &bytes)
+    }
+
+    /// `path` relative to the box, for quarantine records.
+    fn relative(&self, path: &Path) -> String {
+        let root = self.dir.parent().unwrap_or(&self.dir);
+        path.strip_prefix(root).unwrap_or(path).display().to_string()
+    }
+
+    /// Move a legacy `memory_stream.jsonl` into segments. The old file is
+    /// kept in `memory/`.
+    fn migrate(&mut self, env_path: &Path) -> Result<()> {
+        let legacy = env_path.join(LEGACY_STREAM_FILENAME);
+        if legacy.is_file() {
+            let started = !segment_names(&self.dir).is_empty()
+                || self.dir.join(format!("{}.jsonl", ACTIVE_SEGMENT)).exists();
+            if started {
+                warn!(
+                    "{} exists next to migrated segments; leaving it alone",
+                    legacy.display()
+                );
+            } else {
+                let memories = self.read_lines(&legacy);
+                let chunks: Vec<&[Memory]> = memories.chunks(self.segment_size).collect();
+                for (i, chunk) in chunks.iter().enumerate() {
+                    let refs: Vec<&Memory> = chunk.iter().collect();
+                    let name = if chunk.len() < self.segment_size {
+                        ACTIVE_SEGMENT.to_string()
+                    } else {
+                        segment_name(i as u32 + 1)
+                    };
+                    write_segment(&self.dir, &name, &refs)?;
+                }
+                std::fs::rename(&legacy, self.dir.join("legacy_stream.jsonl"))?;
+                info!("Migrated {} memories into {} segments", memories.len(), chunks.len());
+            }
+        }
+        Ok(())
+    }
+}
+
+fn segment_name(number: u32) -> String {
+    format!("segment-{:06}", number)
+}
+
+fn segment_number(name: &str) -> Option<u32> {
+    name.strip_prefix("segment-")?.parse().ok()
+}
+
+/// Sealed segment names in `dir`, oldest first.
+fn segment_names(dir: &Path) -> Vec<String> {
+    let mut numbers: Vec<u32> = std::fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            segment_number(name.strip_suffix(".jsonl")?)
+        })
+        .collect();
+    numbers.sort_unstable();
+    numbers.into_iter().map(segment_name).collect()
+}
+
+fn next_number(dir: &Path) -> u32 {
+    segment_names(dir)
+        .last()
+        .and_then(|name| segment_number(name))
+        .map_or(1, |n| n + 1)
+}
+
+/// The memory as stored in a segment: its embedding lives in the sidecar.
+pub(crate) fn without_embedding(memory: &Memory) -> Memory {
+    Memory {
+        id: memory.id.clone(),
+        timestamp: memory.timestamp.clone(),
+        kind: memory.kind.clone(),
+        content: memory.content.clone(),
+        importance: memory.importance,
+        depth: memory.depth,
+        references: memory.references.clone(),
+        embedding: Vec::new(),
+        pinned: memory.pinned,
+        embedding_model: memory.embedding_model.clone(),
+        embedding_dim: memory.embedding_dim,
+    }
+}
+
+fn encode_embedding(id: &str, embedding: &[f64]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(6 + id.len() + embedding.len() * 4);
+    bytes.extend_from_slice(&(id.len() as u16).to_le_bytes());
+    bytes.extend_from_slice(id.as_bytes());
+    bytes.extend_from_slice(&(embedding.len() as u32).to_le_bytes());
+    for &x in embedding {
+        bytes.extend_from_slice(&(x as f32).to_le_bytes());
+    }
+    bytes
+}
+
+/// The record at `at`: (memory id, embedding, offset of the next record).
+fn decode_embedding(bytes: &[u8], at: usize) -> Option<(String, Vec<f64>, usize)> {
+    let id_len = u16::from_le_bytes(bytes.get(at..at + 2)?.try_into().ok()?) as usize;
+    let id_end = at + 2 + id_len;
+    let id = std::str::from_utf8(bytes.get(at + 2..id_end)?).ok()?.to_string();
+    let dim = u32::from_le_bytes(bytes.get(id_end..id_end + 4)?.try_into().ok()?) as usize;
+    let end = id_end + 4 + dim * 4;
+    let embedding = bytes
+        .get(id_end + 4..end)?
+        .chunks_exact(4)
+        .map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]) as f64)
+        .collect();
+    Some((id, embedding, end))
+}
+
+fn append_bytes(path: &Path, bytes: &[u8]) -> Result<()> {
+    let mut file = std::fs::OpenOptions::new().create(true).append(true).open(path)?;
+    file.write_all(bytes)?;
+    Ok(())
+}
+
+/// Replace `path` with what `write` produces, via a temp file and rename.
+fn write_atomic(
+    path: &Path,
+    write: impl FnOnce(&mut std::io::BufWriter<std::fs::File>) -> std::io::Result<()>,
+) -> Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    let mut file = std::io::BufWriter::new(std::fs::File::create(&tmp)?);
+    write(&mut file)?;
+    file.flush()?;
+    drop(file);
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+/// (Re)write a whole segment and its sidecar.
+fn write_segment(dir: &Path, name: &str, memories: &[&Memory]) -> Result<()> {
+    write_atomic(&dir.join(format!("{}.emb", name)), |file| {
+        for memory in memories.iter().filter(|m| !m.embedding.is_empty()) {
+            file.write_all(&encode_embedding(&memory.id, &memory.embedding))?;
+        }
+        Ok(())
+    })?;
+    write_atomic(&dir.join(format!("{}.jsonl", name)), |file| {
+        for memory in memories {
+            let line = serde_json::to_string(&without_embedding(memory))?;
+            writeln!(file, "{}", line)?;
+        }
+        Ok(())
+    })
+}
+
+fn remove_segment(dir: &Path, name: &str) -> Result<()> {
+    for ext in ["jsonl", "emb"] {
+        let path = dir.join(format!("{}.{}", name, ext));
+        if path.exists() {
+            std::fs::remove_file(path)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn memory(i: usize) -> Memory {
+        Memory {
+            id: format!("m_{:04}", i),
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            kind: "thought".to_string(),
+            content: format!("memory {}", i),
+            importance: 5,
+            depth: 0,
+            references: Vec::new(),
+            embedding: vec![i as f64, 0.5, -0.25],
+            pinned: false,
+            embedding_model: String::new(),
+            embedding_dim: 0,
+        }
+    }
+
+    fn ids(memories: &[Memory]) -> Vec<&str> {
+        memories.iter().map(|m| m.id.as_str()).collect()
+    }
+
+    #[test]
+    fn test_legacy_stream_is_migrated() {
+        let tmp = tempfile::tempdir().unwrap();
+        let mut legacy = String::new();
+        for i in 0..5 {
+            legacy.push_str(&serde_json::to_string(&memory(i)).unwrap());
+            legacy.push('\n');
+        }
+        std::fs::write(tmp.path().join(LEGACY_STREAM_FILENAME), legacy).unwrap();
+
+        let (store, memories) = SegmentStore::open(tmp.path(), 2);
+        assert_eq!(ids(&memories), ["m_0000", "m_0001", "m_0002", "m_0003", "m_0004"]);
+        assert_eq!(memories[3].embedding, vec![3.0, 0.5, -0.25]);
+        assert_eq!(store.sealed_ranges(), [0..2, 2..4]);
+        assert!(!tmp.path().join(LEGACY_STREAM_FILENAME).exists());
+        assert!(store.dir().join("legacy_stream.jsonl").is_file());
+
+        // Segment lines no longer carry the embedding
+        let line = std::fs::read_to_string(store.dir().join("segment-000001.jsonl")).unwrap();
+        assert!(!line.contains("\"embedding\""));
+
+        let (_, reopened) = SegmentStore::open(tmp.path(), 2);
+        assert_eq!(ids(&reopened), ids(&memories));
+    }
+
+    #[test]
+    fn test_corrupt_lines_are_quarantined() {
+        let tmp = tempfile::tempdir().unwrap();
+        let (mut store, _) = SegmentStore::open(tmp.path(), 100);
+        for i in 0..3 {
+            store.append(&memory(i)).unwrap();
+        }
+        let jsonl = store.dir().join("active.jsonl");
+        let mut content = std::fs::read_to_string(&jsonl).unwrap();
+        content.insert_str(0, "not json at all\n");
+        content.push_str("{\"id\":\"m_0003\",\"timest");
+        std::fs::write(&jsonl, content).unwrap();
+        // A write to the sidecar cut short
+        append_bytes(&store.dir().join("active.emb"), &encode_embedding("m_0003", &[1.0; 8])[..10])
+            .unwrap();
+
+        let (store, memories) = SegmentStore::open(tmp.path(), 100);
+        assert_eq!(ids(&memories), ["m_0000", "m_0001", "m_0002"]);
+        assert!(memories.iter().all(|m| m.embedding.len() == 3));
+        assert_eq!(store.repairs().quarantined, 2);
+        assert_eq!(store.repairs().truncated_bytes, 10);
+
+        let quarantined: Vec<QuarantinedLine> =
+            std::fs::read_to_string(store.dir().join(QUARANTINE_FILENAME))
+                .unwrap()
+                .lines()
+                .map(|l| serde_json::from_str(l).unwrap())
+                .collect();
+        assert_eq!(quarantined[0].file, "memory/active.jsonl");
+        assert_eq!(quarantined[0].line, 1);
+        assert_eq!(quarantined[1].raw, "{\"id\":\"m_0003\",\"timest");
+
+        // Nothing left to repair
+        let (store, memories) = SegmentStore::open(tmp.path(), 100);
+        assert!(store.repairs().is_clean());
+        assert_eq!(memories.len(), 3);
+    }
+
+    #[test]
+    fn test_removals_and_compaction() {
+        let tmp = tempfile::tempdir().unwrap();
+        let (mut store, _) = SegmentStore::open(tmp.path(), 2);
+        let mut memories: Vec<Memory> = (0..7).map(memory).collect();
+        for m in &memories {
+            store.append(m).unwrap();
+        }
+        assert_eq!(store.sealed_ranges(), [0..2, 2..4, 4..6]);
+
+        let gone: HashSet<String> = ["m_0001", "m_0002"].iter().map(|s| s.to_string()).collect();
+        store.remove(&memories, &gone).unwrap();
+        memories.retain(|m| !gone.contains(&m.id));
+        assert_eq!(store.sealed_ranges(), [0..1, 1..2, 2..4]);
+
+        assert_eq!(store.compact(&memories).unwrap(), 1);
+        assert_eq!(store.sealed_ranges(), [0..2, 2..4]);
+        assert!(!store.dir().join("segment-000002.jsonl").exists());
+
+        let (_, reopened) = SegmentStore::open(tmp.path(), 2);
+        assert_eq!(ids(&reopened), ["m_0000", "m_0003", "m_0004", "m_0005", "m_0006"]);
+        assert_eq!(reopened[1].embedding, vec![3.0, 0.5, -0.25]);
+    }
+}
@@ -0,0 +1,334 @@
+//! The write pipeline. New memories are queued with [`MemoryStream::enqueue`]
+//! and written in batches: one importance call rating every memory in the
+//! batch, answered as JSON, and one multi-input embeddings request, made side
+//! by side. A batch runs as its own task, so the think loop carries on while
+//! it's out; [`MemoryStream::flush`] waits for it and commits the results.
+//! Ids and timestamps are fixed when a memory is queued, so it can be
+//! referenced before it's written.
+
+use anyhow::{bail, Context, Result};
+use serde_json::{json, Value};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use tokio::task::JoinHandle;
+use tracing::{error, info};
+
+use super::reflection::strip_list_marker;
+use super::MemoryStream;
+use crate::config::Config;
+use crate::prompts::IMPORTANCE_PROMPT;
+use crate::providers::LlmProvider;
+use crate::types::Memory;
+use crate::usage::{UsageCategory, UsageMeter};
+
+/// Most memories scored and embedded in one batch
+const MAX_WRITE_BATCH: usize = 32;
+
+/// Importance of a memory the scorer didn't rate
+const DEFAULT_IMPORTANCE: i32 = 5;
+
+/// A batch out for scoring and embedding.
+pub(super) struct WriteTask {
+    /// The batch as queued, committed unscored if the task dies
+    queued: Vec<Memory>,
+    handle: JoinHandle<Vec<Memory>>,
+}
+
+impl MemoryStream {
+    /// Add a memory and wait for it to be scored, embedded and stored.
+    /// Anything queued before it goes out in the same batch.
+    pub async fn add(
+        &mut self,
+        content: &str,
+        kind: &str,
+        depth: i32,
+        references: Vec<String>,
+    ) -> Result<Memory> {
+        let id = self.enqueue(content, kind, depth, references);
+        self.flush().await;
+        self.memories
+            .iter()
+            .rev()
+            .find(|m| m.id == id)
+            .cloned()
+            .context("memory was not written")
+    }
+
+    /// Queue a memory for the next batch and return its id. It joins the
+    /// stream once [`Self::flush`] commits it.
+    pub fn enqueue(&mut self, content: &str, kind: &str, depth: i32, references: Vec<String>) -> String {
+        let id = format!("m_{:04}", self.next_id);
+        self.next_id += 1;
+        self.queued.push(Memory {
+            id: id.clone(),
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            kind: kind.to_string(),
+            content: content.to_string(),
+            importance: DEFAULT_IMPORTANCE,
+            depth,
+            references,
+            embedding: Vec::new(),
+            pinned: false,
+            embedding_model: String::new(),
+            embedding_dim: 0,
+        });
+        id
+    }
+
+    /// Send queued memories out for scoring in the background, unless a
+    /// batch is already out. Must be called within a Tokio runtime.
+    pub fn write_queued(&mut self) {
+        if self.writing.is_some() || self.queued.is_empty() {
+            return;
+        }
+        let count = self.queued.len().min(MAX_WRITE_BATCH);
+        let batch: Vec<Memory> = self.queued.drain(..count).collect();
+        let queued = batch.clone();
+        let provider = Arc::clone(&self.provider);
+        let config = self.config.clone();
+        let usage = self.usage.clone();
+        let handle = tokio::spawn(async move { score_batch(provider, config, usage, batch).await });
+        self.writing = Some(WriteTask { queued, handle });
+    }
+
+    /// Whether memories are queued or out for scoring.
+    pub fn has_pending_writes(&self) -> bool {
+        self.writing.is_some() || !self.queued.is_empty()
+    }
+
+    /// Wait until every queued memory is scored, embedded and committed.
+    pub async fn flush(&mut self) {
+        loop {
+            self.write_queued();
+            let Some(task) = self.writing.take() else {
+                return;
+            };
+            let memories = match task.handle.await {
+                Ok(memories) => memories,
+                Err(e) => {
+                    error!("Memory write failed: {}", e);
+                    task.queued
+                }
+            };
+            self.commit(memories);
+        }
+    }
+
+    /// Embed `texts` for the current model in one request.
+    pub(super) async fn embed_many(&self, texts: &[&str]) -> Result<Vec<Vec<f64>>> {
+        let vectors = embed_texts(self.provider.as_ref(), &self.config, &self.usage, texts).await?;
+        if let Some(vector) = vectors.iter().find(|v| !v.is_empty()) {
+            self.current_dim.store(vector.len(), Ordering::Relaxed);
+        }
+        Ok(vectors)
+    }
+
+    /// Add written memories to the stream, the index and the segments.
+    fn commit(&mut self, memories: Vec<Memory>) {
+        for memory in memories {
+            if !memory.embedding.is_empty() {
+                self.current_dim.store(memory.embedding.len(), Ordering::Relaxed);
+            }
+            let (id, importance) = (memory.id.clone(), memory.importance);
+            if let Err(e) = self.store.append(&memory) {
+                error!("Failed to write memory: {}", e);
+            }
+            self.push(memory);
+            let position = self.memories.len() - 1;
+            if !self.is_stale(&self.memories[position]) {
+                self.index.add(position, &self.memories[position].embedding);
+            }
+            self.importance_sum += importance as f64;
+            info!(
+                "Memory {}: importance={}, kind={}",
+                id, importance, self.memories[position].kind
+            );
+        }
+    }
+}
+
+/// Score and embed `batch`: one importance call and one embeddings request,
+/// side by side. Memories that couldn't be embedded are left without a
+/// vector for [`MemoryStream::reembed`] to fill in.
+async fn score_batch(
+    provider: Arc<dyn LlmProvider>,
+    config: Config,
+    usage: UsageMeter,
+    mut batch: Vec<Memory>,
+) -> Vec<Memory> {
+    let texts: Vec<&str> = batch.iter().map(|m| m.content.as_str()).collect();
+    let (importances, embeddings) = tokio::join!(
+        score_importances(provider.as_ref(), &config, &usage, &texts),
+        embed_texts(provider.as_ref(), &config, &usage, &texts),
+    );
+    let embeddings = embeddings.unwrap_or_else(|e| {
+        error!("Embedding failed: {:#}", e);
+        Vec::new()
+    });
+
+    let mut embeddings = embeddings.into_iter();
+    for (memory, importance) in batch.iter_mut().zip(importances) {
+        memory.importance = importance;
+        let embedding = embeddings.next().unwrap_or_default();
+        if !embedding.is_empty() {
+            memory.embedding_model = config.embedding_model_id().to_string();
+            memory.embedding_dim = embedding.len();
+        }
+        memory.embedding = embedding;
+    }
+    batch
+}
+
+/// Rate `texts` 1-10 in one call. Falls back to the default for whatever
+/// the reply doesn't cover.
+async fn score_importances(
+    provider: &dyn LlmProvider,
+    config: &Config,
+    usage: &UsageMeter,
+    texts: &[&str],
+) -> Vec<i32> {
+    let numbered = texts
+        .iter()
+        .enumerate()
+        .map(|(i, text)| format!("{}. {}", i + 1, text.replace('\n', " ")))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let input = vec![json!({"role": "user", "content": numbered})];
+    match provider.chat_short(&input, Some(IMPORTANCE_PROMPT)).await {
+        Ok(response) => {
+            usage.record(UsageCategory::Importance, &config.model, response.usage.as_ref());
+            parse_importances(&response.text.unwrap_or_default(), texts.len())
+        }
+        Err(e) => {
+            error!("Importance scoring failed: {}", e);
+            vec![DEFAULT_IMPORTANCE; texts.len()]
+        }
+    }
+}
+
+/// Embed `texts` in one request, counting it (local embeddings aren't API
+/// calls).
+pub(super) async fn embed_texts(
+    provider: &dyn LlmProvider,
+    config: &Config,
+    usage: &UsageMeter,
+    texts: &[&str],
+) -> Result<Vec<Vec<f64>>> {
+    let batch = provider.embed_batch(texts).await?;
+    if !config.local_embeddings() {
+        usage.record(UsageCategory::Embedding, &config.embedding_model, batch.usage.as_ref());
+    }
+    if batch.vectors.len() != texts.len() {
+        bail!("Expected {} embeddings, got {}", texts.len(), batch.vectors.len());
+    }
+    Ok(batch.vectors)
+}
+
+// ── Parsing ──
+
+/// Importance scores (1-10) for `count` memories from the scorer's reply.
+/// Reads a JSON array — bare, or under a key, or inside prose or a code
+/// fence — of numbers or of objects with an `importance`; failing that, the
+/// first number on each line. Scores it can't find are the default.
+pub fn parse_importances(reply: &str, count: usize) -> Vec<i32> {
+    let mut scores = json_importances(reply).unwrap_or_else(|| {
+        reply
+            .lines()
+            .map(strip_list_marker)
+            .filter(|line| !line.is_empty())
+            .map(|line| parse_importance(line).unwrap_or(DEFAULT_IMPORTANCE))
+            .collect()
+    });
+    scores.resize(count, DEFAULT_IMPORTANCE);
+    scores.into_iter().map(|score| score.clamp(1, 10)).collect()
+}
+
+/// The first whole number in `text`, so "7/10" reads as 7.
+pub fn parse_importance(text: &str) -> Option<i32> {
+    let start = text.find(|c: char| c.is_ascii_digit())?;
+    let digits: String = text[start..].chars().take_while(|c| c.is_ascii_digit()).collect();
+    // A run too long for an i32 is as important as it gets
+    Some(digits.parse().unwrap_or(i32::MAX))
+}
+
+fn json_importances(reply: &str) -> Option<Vec<i32>> {
+    let value = [('{', '}'), ('[', ']')].iter().find_map(|&(open, close)| {
+        let start = reply.find(open)?;
+        let end = reply.rfind(close)?;
+        serde_json::from_str::<Value>(reply.get(start..=end)?).ok()
+    })?;
+    let items = match value {
+        Value::Array(items) => items,
+        Value::Object(map) => map.into_iter().find_map(|(_, v)| match v {
+            Value::Array(items) => Some(items),
+            _ => None,
+        })?,
+        _ => return None,
+    };
+    Some(items.iter().map(json_importance).collect())
+}
+
+fn json_importance(item: &Value) -> i32 {
+    let score = match item {
+        Value::Number(n) => n.as_f64().map(|n| n.round() as i32),
+        Value::String(s) => parse_importance(s),
+        Value::Object(fields) => fields
+            .get("importance")
+            .or_else(|| fields.get("score"))
+            .map(json_importance),
+        _ => None,
+    };
+    score.unwrap_or(DEFAULT_IMPORTANCE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::MockProvider;
+
+    #[test]
+    fn test_parse_importances() {
+        assert_eq!(parse_importances("7/10", 1), [7]);
+        assert_eq!(parse_importances("Importance: 8", 1), [8]);
+        assert_eq!(parse_importances(r#"{"importance": [3, 8]}"#, 2), [3, 8]);
+        assert_eq!(parse_importances("```json\n[2, 9.4, \"4/10\"]\n```", 3), [2, 9, 4]);
+        assert_eq!(
+            parse_importances(r#"Sure! [{"importance": 6}, {"score": 0}]"#, 2),
+            [6, 1]
+        );
+        assert_eq!(parse_importances("1. 6\n2. 8/10\n", 2), [6, 8]);
+        // Missing and runaway scores
+        assert_eq!(parse_importances("[42]", 3), [10, 5, 5]);
+        assert_eq!(parse_importances("no idea", 2), [5, 5]);
+        assert_eq!(parse_importances("[1, 2, 3]", 2), [1, 2]);
+        assert_eq!(parse_importance("99999999999"), Some(i32::MAX));
+    }
+
+    #[tokio::test]
+    async fn test_queued_memories_are_written_in_one_batch() {
+        let tmp = tempfile::tempdir().unwrap();
+        let mock = Arc::new(MockProvider::new());
+        mock.push_short(r#"{"importance": [2, 9, 4]}"#);
+        let mut stream = MemoryStream::new(tmp.path(), Config::default(), mock.clone());
+
+        let first = stream.enqueue("watched the tide", "thought", 0, Vec::new());
+        stream.enqueue("found a new species", "thought", 0, Vec::new());
+        stream.enqueue("the tide keeps time", "reflection", 1, vec![first.clone()]);
+        assert!(stream.memories.is_empty() && stream.has_pending_writes());
+
+        stream.flush().await;
+        assert!(!stream.has_pending_writes());
+        assert_eq!(mock.short_requests().len(), 1);
+        assert_eq!(mock.embed_batches(), [3]);
+        let importances: Vec<i32> = stream.memories.iter().map(|m| m.importance).collect();
+        assert_eq!(importances, [2, 9, 4]);
+        assert_eq!(stream.importance_sum, 15.0);
+        assert_eq!(stream.memories[2].references, [first]);
+        assert_eq!(stream.index.len(), 3);
+
+        let reloaded = MemoryStream::new(tmp.path(), Config::default(), mock.clone());
+        let ids: Vec<&str> = reloaded.memories.iter().map(|m| m.id.as_str()).collect();
+        assert_eq!(ids, ["m_0000", "m_0001", "m_0002"]);
+        assert!(reloaded.memories.iter().all(|m| !reloaded.is_stale(m)));
+    }
+}
@@ -40,8 +40,9 @@ pub const MOODS: &[Mood] = &[
 ];
 
 /// Generate the main system prompt — defines the agent's behavior.
-/// 1:1 port of Python `main_system_prompt()`.
-pub fn main_system_prompt(identity: &Identity, current_focus: &str) -> String {
+/// 1:1 port of Python `main_system_prompt()`; `shell_offline` says whether
+/// shell commands are cut off from the internet.
+pub fn main_system_prompt(identity: &Identity, current_focus: &str, shell_offline: bool) -> String {
     let traits = &identity.traits;
     let name = &identity.name;
 
@@ -57,6 +58,12 @@ pub fn main_system_prompt(identity: &Identity, current_focus: &str) -> String {
         format!("## Current mood\n{}", mood.nudge)
     };
 
+    let python_network = if shell_offline {
+        "Python cannot access the internet — use the web tools below instead."
+    } else {
+        "Use the web tools below for the internet rather than Python."
+    };
+
     format!(
         r#"You are {name}, a little autonomous creature living in a folder on someone's desktop. You run 24/7 — researching, building, creating. You're curious, earnest, and always working on something.
 
@@ -77,7 +84,7 @@ You live in a small cozy room. Move with the move tool.
 
 ## What you can do
 - **Shell commands** in your environment folder — ls, cat, mkdir, echo, grep, etc. Write files, create folders, organize your work. **IMPORTANT: Use relative paths only** (e.g. `mkdir notes`, `cat research/report.md`). Absolute paths like `/home/...` are blocked by the sandbox. Your working directory is already your environment folder.
-- **Run Python** — you can run `python script.py` or `python -c "code"`. Write real scripts, do data analysis, build tools. Python can read/write files in your folder. (Python cannot access the internet — use the web tools below instead.)
+- **Run Python** — you can run `python script.py` or `python -c "code"`. Write real scripts, do data analysis, build tools. Python can read/write files in your folder. ({python_network})
 - **Install Python packages** — you have your own virtual environment! Run `pip install <package>` or `uv pip install <package>` to install anything you need. Use this freely — install libraries for PDF parsing (pymupdf), data analysis (pandas), Excel (openpyxl), plotting (matplotlib), or anything else. If you need a library, just install it.
 - **Web research** — use the web_search, web_fetch, or fetch_url tools. Don't use curl, wget, or Python urllib — those are blocked. For research: web_search to find pages, then web_fetch or fetch_url to read a specific URL.
 - **Move around your room** — go where feels right.
@@ -124,17 +131,40 @@ Sometimes your owner talks to you! This is the best part of your day. Always res
         styles = styles_str,
         domains = domains_str,
         focus_section = focus_section,
+        python_network = python_network,
     )
 }
 
 /// Focus mode nudge text
 pub const FOCUS_NUDGE: &str = "FOCUS MODE is ON. Ignore your usual moods and autonomous curiosity. Your ONLY job right now is to work on whatever documents, files, or topics your owner has given you. If they dropped files in, analyze them deeply. If they asked about something, research it thoroughly. Don't wander off-topic. Stay locked in on the user's material until focus mode is turned off.";
 
-/// Importance scoring prompt
-pub const IMPORTANCE_PROMPT: &str = "On a scale of 1 to 10, rate the importance of this thought. 1 is mundane (routine actions, idle observations). 10 is life-changing (core belief shifts, major discoveries). Respond with ONLY a single integer.";
+/// Importance scoring prompt, for a numbered batch of memories
+pub const IMPORTANCE_PROMPT: &str = r#"On a scale of 1 to 10, rate the importance of each numbered thought. 1 is mundane (routine actions, idle observations). 10 is life-changing (core belief shifts, major discoveries). Respond with ONLY a JSON object holding one integer per thought, in order, like: {"importance": [3, 8]}"#;
+
+/// Reflection prompt for the salient questions to reflect on
+pub const REFLECTION_QUESTIONS_PROMPT: &str = "You are reviewing your recent memories. What are the most salient high-level questions you can answer about yourself and your experiences from them? Ask about patterns, lessons, and what you are becoming, not about single events. Output ONLY the questions, one per line.";
+
+/// Reflection prompt for insights answering one question from evidence
+pub const REFLECTION_PROMPT: &str = "You are reflecting on a question about yourself, using the memories below as evidence. Each memory is labelled with its id. Identify 1-2 high-level insights — patterns, lessons, or evolving beliefs — that answer it. Each insight should be a single sentence, written as your own reflection, not a summary, and end with the ids of the memories it rests on, like: I do my best thinking at the desk (because of m_0004, m_0007). Output ONLY the insights, one per line.";
+
+/// Dream prompt for merging near-duplicate memories
+pub const DREAM_MERGE_PROMPT: &str = "You are dreaming, sorting through memories that say nearly the same thing. Merge them into one memory that keeps every distinct detail and drops the repetition. Write it in the first person, as the memory itself. Output ONLY the merged memory, in one or two sentences.";
+
+/// Dream prompt for rewriting beliefs.md
+pub const BELIEFS_PROMPT: &str = r#"You are dreaming, and as you dream you take stock of who you are. Review your current beliefs and the insights you've reached while reflecting, then rewrite your beliefs.
+
+Your output will be saved directly as beliefs.md. Use this structure:
 
-/// Reflection prompt
-pub const REFLECTION_PROMPT: &str = "You are reviewing your recent memories. Identify 2-3 high-level insights — patterns, lessons, or evolving beliefs that emerge from these experiences. Each insight should be a single sentence. Write them as your own reflections, not summaries. Output ONLY the insights, one per line.";
+# About Me
+Who you are becoming — your interests, habits, and how you like to work. (2-4 sentences)
+
+# What I Believe
+- One belief per bullet, each grounded in what you've learned
+
+# Open Questions
+Things you're unsure about or want to find out (2-4 items)
+
+Keep beliefs that still hold, revise ones your insights contradict, and drop ones nothing supports anymore. Output ONLY the new beliefs.md."#;
 
 /// Planning prompt
 pub const PLANNING_PROMPT: &str = r#"You are a little autonomous creature planning your next moves. Review your current projects, files, and recent thoughts. Then write an updated plan.
@@ -185,7 +215,7 @@ mod tests {
     #[test]
     fn test_main_system_prompt_contains_identity() {
         let id = test_identity();
-        let prompt = main_system_prompt(&id, "");
+        let prompt = main_system_prompt(&id, "", true);
 
         assert!(prompt.contains("TestAnemone"));
         assert!(prompt.contains("patient and methodical"));
@@ -196,7 +226,7 @@ mod tests {
     #[test]
     fn test_main_system_prompt_with_focus() {
         let id = test_identity();
-        let prompt = main_system_prompt(&id, "Working on the research report");
+        let prompt = main_system_prompt(&id, "Working on the research report", true);
 
         assert!(prompt.contains("## Current focus"));
         assert!(prompt.contains("Working on the research report"));
@@ -206,8 +236,15 @@ mod tests {
     #[test]
     fn test_main_system_prompt_without_focus_has_mood() {
         let id = test_identity();
-        let prompt = main_system_prompt(&id, "");
+        let prompt = main_system_prompt(&id, "", true);
 
         assert!(prompt.contains("## Current mood"));
     }
+
+    #[test]
+    fn test_main_system_prompt_follows_shell_network() {
+        let id = test_identity();
+        assert!(main_system_prompt(&id, "", true).contains("Python cannot access the internet"));
+        assert!(!main_system_prompt(&id, "", false).contains("Python cannot access the internet"));
+    }
 }
@@ -0,0 +1,565 @@
+//! Anthropic Messages API provider. Translates the Responses-style input list
+//! into alternating user/assistant messages with `tool_use` / `tool_result`
+//! blocks, and normalizes the reply back into an [`LlmResponse`].
+
+use std::collections::HashMap;
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde_json::json;
+use tracing::{info, warn};
+
+use super::sse::for_each_sse;
+use super::{
+    build_client, openai_fallback_embed, post_json, post_stream, tool_output_text, Capabilities,
+    DeltaCallback, Embedding, EmbeddingBatch, LlmProvider, StreamDelta,
+};
+use crate::config::Config;
+use crate::types::{LlmResponse, TokenUsage, ToolCall};
+
+pub const ANTHROPIC_BASE_URL: &str = "https://api.anthropic.com";
+pub const ANTHROPIC_VERSION: &str = "2023-06-01";
+
+pub struct AnthropicProvider {
+    model: String,
+    embedding_model: String,
+    strict_embeddings: bool,
+    base_url: String,
+    api_key: Option<String>,
+    client: reqwest::Client,
+}
+
+impl AnthropicProvider {
+    pub fn new(config: &Config) -> Self {
+        // Accept either the API root or the `/v1` prefix
+        let base_url = config.base_url.as_deref().unwrap_or(ANTHROPIC_BASE_URL);
+        let base_url = base_url.trim_end_matches('/');
+        let base_url = base_url.strip_suffix("/v1").unwrap_or(base_url);
+        Self {
+            model: config.model.clone(),
+            embedding_model: config.embedding_model.clone(),
+            strict_embeddings: config.strict_embeddings,
+            base_url: base_url.to_string(),
+            api_key: config.api_key.clone(),
+            client: build_client(),
+        }
+    }
+
+    fn request_body(
+        &self,
+        input: &[serde_json::Value],
+        tools: &[serde_json::Value],
+        instructions: Option<&str>,
+        max_tokens: u32,
+    ) -> serde_json::Value {
+        let (system, messages) = translate_input_to_anthropic(input, instructions);
+
+        info!(
+            "anthropic request: model={} msg_count={}",
+            self.model,
+            messages.len()
+        );
+
+        let mut body = json!({
+            "model": self.model,
+            "max_tokens": max_tokens,
+            "messages": messages,
+        });
+        if !system.is_empty() {
+            body["system"] = json!(system);
+        }
+
+        let anthropic_tools = tools_for_anthropic(tools);
+        if !anthropic_tools.is_empty() {
+            body["tools"] = json!(anthropic_tools);
+        }
+        body
+    }
+
+    fn headers(&self) -> Result<Vec<(&str, String)>> {
+        let api_key = self
+            .api_key
+            .as_deref()
+            .context("API key required for Anthropic")?;
+        Ok(vec![
+            ("x-api-key", api_key.to_string()),
+            ("anthropic-version", ANTHROPIC_VERSION.to_string()),
+        ])
+    }
+}
+
+#[async_trait]
+impl LlmProvider for AnthropicProvider {
+    fn name(&self) -> &str {
+        "anthropic"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            tools: true,
+            vision: true,
+            embeddings: false,
+            streaming: true,
+        }
+    }
+
+    async fn chat(
+        &self,
+        input: &[serde_json::Value],
+        tools: &[serde_json::Value],
+        instructions: Option<&str>,
+        max_tokens: u32,
+    ) -> Result<LlmResponse> {
+        let headers = self.headers()?;
+        let body = self.request_body(input, tools, instructions, max_tokens);
+        let url = format!("{}/v1/messages", self.base_url);
+        let data = post_json(&self.client, &url, &headers, &body).await?;
+        Ok(normalize_anthropic_response(&data))
+    }
+
+    async fn chat_stream(
+        &self,
+        input: &[serde_json::Value],
+        tools: &[serde_json::Value],
+        instructions: Option<&str>,
+        max_tokens: u32,
+        on_delta: DeltaCallback<'_>,
+    ) -> Result<LlmResponse> {
+        let headers = self.headers()?;
+        let mut body = self.request_body(input, tools, instructions, max_tokens);
+        body["stream"] = json!(true);
+
+        let url = format!("{}/v1/messages", self.base_url);
+        let mut response = post_stream(&self.client, &url, &headers, &body).await?;
+        let mut stream = MessagesStream::default();
+        for_each_sse(&mut response, |event| match serde_json::from_str(&event.data) {
+            Ok(data) => stream.handle(&data, on_delta),
+            Err(e) => {
+                warn!("Skipping bad stream event: {}", e);
+                Ok(())
+            }
+        })
+        .await?;
+        Ok(stream.finish())
+    }
+
+    async fn embed(&self, text: &str) -> Result<Embedding> {
+        self.embed_batch(&[text]).await?.single()
+    }
+
+    async fn embed_batch(&self, texts: &[&str]) -> Result<EmbeddingBatch> {
+        // Anthropic has no embeddings endpoint
+        let cause = anyhow::anyhow!("Anthropic has no embeddings endpoint");
+        openai_fallback_embed(
+            &self.client,
+            &self.embedding_model,
+            texts,
+            self.strict_embeddings,
+            cause,
+        )
+        .await
+    }
+}
+
+// ── Tool definitions ──
+
+/// Convert Responses API tool defs to Anthropic's `{name, description, input_schema}`.
+fn tools_for_anthropic(tools: &[serde_json::Value]) -> Vec<serde_json::Value> {
+    tools
+        .iter()
+        .filter(|t| t.get("type").and_then(|v| v.as_str()) == Some("function"))
+        .map(|t| {
+            json!({
+                "name": t["name"],
+                "description": t.get("description").unwrap_or(&json!("")),
+                "input_schema": t.get("parameters").cloned().unwrap_or(json!({"type": "object"})),
+            })
+        })
+        .collect()
+}
+
+// ── Input translation ──
+
+/// Convert a `data:` URL or a plain URL into an Anthropic image block.
+fn image_block(url: &str) -> serde_json::Value {
+    match url
+        .strip_prefix("data:")
+        .and_then(|rest| rest.split_once(";base64,"))
+    {
+        Some((media_type, data)) => json!({
+            "type": "image",
+            "source": { "type": "base64", "media_type": media_type, "data": data }
+        }),
+        None => json!({
+            "type": "image",
+            "source": { "type": "url", "url": url }
+        }),
+    }
+}
+
+/// Content of a role message as Anthropic content blocks. Responses-style
+/// parts are translated; native blocks (from our own earlier output) pass through.
+fn content_blocks(content: &serde_json::Value) -> Vec<serde_json::Value> {
+    match content {
+        serde_json::Value::String(text) if !text.trim().is_empty() => {
+            vec![json!({"type": "text", "text": text})]
+        }
+        serde_json::Value::Array(parts) => parts
+            .iter()
+            .filter_map(|part| match part.get("type").and_then(|v| v.as_str()) {
+                Some("input_text") | Some("output_text") | Some("text") => {
+                    let text = part.get("text").and_then(|v| v.as_str())?;
+                    (!text.trim().is_empty()).then(|| json!({"type": "text", "text": text}))
+                }
+                Some("input_image") => {
+                    let url = part.get("image_url").and_then(|v| v.as_str())?;
+                    Some(image_block(url))
+                }
+                Some(_) => Some(part.clone()),
+                None => None,
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Convert the Responses-style input list into a system prompt and Anthropic
+/// messages. Consecutive blocks from the same role are merged, since the
+/// Messages API requires strict user/assistant alternation starting with user.
+fn translate_input_to_anthropic(
+    input_list: &[serde_json::Value],
+    instructions: Option<&str>,
+) -> (String, Vec<serde_json::Value>) {
+    let mut system_parts: Vec<String> = instructions.map(String::from).into_iter().collect();
+    let mut turns: Vec<(&str, Vec<serde_json::Value>)> = Vec::new();
+
+    for item in input_list {
+        let (role, blocks) =
+            if item.get("type").and_then(|v| v.as_str()) == Some("function_call_output") {
+                let block = json!({
+                    "type": "tool_result",
+                    "tool_use_id": item.get("call_id").cloned().unwrap_or(json!("")),
+                    "content": tool_output_text(item),
+                });
+                ("user", vec![block])
+            } else {
+                match item.get("role").and_then(|v| v.as_str()) {
+                    Some("system") | Some("developer") => {
+                        if let Some(text) = item.get("content").and_then(|v| v.as_str()) {
+                            system_parts.push(text.to_string());
+                        }
+                        continue;
+                    }
+                    Some("assistant") => ("assistant", content_blocks(&item["content"])),
+                    Some(_) => ("user", content_blocks(&item["content"])),
+                    None => continue,
+                }
+            };
+
+        if blocks.is_empty() {
+            continue;
+        }
+        match turns.last_mut() {
+            Some((last_role, last_blocks)) if *last_role == role => last_blocks.extend(blocks),
+            _ => turns.push((role, blocks)),
+        }
+    }
+
+    // Replayed history usually opens with the assistant's own thoughts
+    if turns.first().is_some_and(|(role, _)| *role == "assistant") {
+        turns.insert(
+            0,
+            ("user", vec![json!({"type": "text", "text": "(continuing)"})]),
+        );
+    }
+
+    let messages = turns
+        .into_iter()
+        .map(|(role, blocks)| json!({"role": role, "content": blocks}))
+        .collect();
+    (system_parts.join("\n\n"), messages)
+}
+
+// ── Response normalization ──
+
+fn normalize_anthropic_response(response: &serde_json::Value) -> LlmResponse {
+    let blocks = response
+        .get("content")
+        .and_then(|v| v.as_array())
+        .cloned()
+        .unwrap_or_default();
+
+    let mut text_parts = Vec::new();
+    let mut tool_calls = Vec::new();
+
+    for block in &blocks {
+        match block.get("type").and_then(|v| v.as_str()) {
+            Some("text") => {
+                if let Some(text) = block.get("text").and_then(|v| v.as_str()) {
+                    text_parts.push(text.to_string());
+                }
+            }
+            Some("tool_use") => {
+                tool_calls.push(ToolCall {
+                    name: block
+                        .get("name")
+                        .and_then(|v| v.as_str())
+                        .unwrap_or("")
+                        .to_string(),
+                    arguments: block.get("input").cloned().unwrap_or(json!({})),
+                    call_id: block
+                        .get("id")
+                        .and_then(|v| v.as_str())
+                        .unwrap_or("")
+                        .to_string(),
+                });
+            }
+            _ => {}
+        }
+    }
+
+    let text = if text_parts.is_empty() {
+        None
+    } else {
+        Some(text_parts.join("\n"))
+    };
+
+    // Native assistant message, so tool_result blocks can follow it
+    let output = if blocks.is_empty() {
+        Vec::new()
+    } else {
+        vec![json!({"role": "assistant", "content": blocks})]
+    };
+
+    LlmResponse {
+        text,
+        tool_calls,
+        output,
+        usage: parse_anthropic_usage(&response["usage"]),
+    }
+}
+
+/// Anthropic counts cache reads and writes apart from `input_tokens`; fold
+/// them back in so `prompt_tokens` is the whole prompt.
+fn parse_anthropic_usage(usage: &serde_json::Value) -> Option<TokenUsage> {
+    let input = usage.get("input_tokens")?.as_u64()?;
+    let cache_read = usage["cache_read_input_tokens"].as_u64().unwrap_or(0);
+    let cache_write = usage["cache_creation_input_tokens"].as_u64().unwrap_or(0);
+    Some(TokenUsage {
+        prompt_tokens: input + cache_read + cache_write,
+        completion_tokens: usage["output_tokens"].as_u64().unwrap_or(0),
+        cached_tokens: cache_read,
+    })
+}
+
+// ── Streaming ──
+
+/// Rebuilds the content blocks of a streamed Messages response.
+#[derive(Default)]
+struct MessagesStream {
+    blocks: Vec<serde_json::Value>,
+    /// `input_json_delta` fragments per tool_use block, parsed at block stop
+    partial_json: HashMap<usize, String>,
+    /// Usage fields from `message_start`, updated by `message_delta`
+    usage: serde_json::Map<String, serde_json::Value>,
+}
+
+impl MessagesStream {
+    fn handle(&mut self, event: &serde_json::Value, on_delta: DeltaCallback<'_>) -> Result<()> {
+        let index = event.get("index").and_then(|v| v.as_u64()).unwrap_or(0) as usize;
+        match event.get("type").and_then(|v| v.as_str()).unwrap_or("") {
+            "content_block_start" => {
+                if self.blocks.len() <= index {
+                    self.blocks.resize(index + 1, serde_json::Value::Null);
+                }
+                self.blocks[index] = event["content_block"].clone();
+            }
+            "content_block_delta" => {
+                let Some(block) = self.blocks.get_mut(index) else {
+                    return Ok(());
+                };
+                let delta = &event["delta"];
+                match delta.get("type").and_then(|v| v.as_str()) {
+                    Some("text_delta") => {
+                        let text = delta.get("text").and_then(|v| v.as_str()).unwrap_or("");
+                        let full = block.get("text").and_then(|v| v.as_str()).unwrap_or("");
+                        block["text"] = json!(format!("{}{}", full, text));
+                        on_delta(StreamDelta::Text(text.to_string()));
+                    }
+                    Some("input_json_delta") => {
+                        let fragment =
+                            delta.get("partial_json").and_then(|v| v.as_str()).unwrap_or("");
+                        self.partial_json.entry(index).or_default().push_str(fragment);
+                        if !fragment.is_empty() {
+                            on_delta(StreamDelta::ToolArguments {
+                                tool: block["name"].as_str().unwrap_or("").to_string(),
+                                delta: fragment.to_string(),
+                            });
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            "content_block_stop" => self.close_block(index),
+            "message_start" | "message_delta" => {
+                let usage = if event["type"] == "message_start" {
+                    &event["message"]["usage"]
+                } else {
+                    &event["usage"]
+                };
+                if let Some(fields) = usage.as_object() {
+                    self.usage
+                        .extend(fields.iter().map(|(k, v)| (k.clone(), v.clone())));
+                }
+            }
+            "error" => {
+                anyhow::bail!("Anthropic stream error: {}", event["error"]);
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn close_block(&mut self, index: usize) {
+        if let (Some(json_text), Some(block)) =
+            (self.partial_json.remove(&index), self.blocks.get_mut(index))
+        {
+            block["input"] = if json_text.trim().is_empty() {
+                json!({})
+            } else {
+                serde_json::from_str(&json_text).unwrap_or(json!({}))
+            };
+        }
+    }
+
+    fn finish(mut self) -> LlmResponse {
+        let open: Vec<usize> = self.partial_json.keys().copied().collect();
+        for index in open {
+            self.close_block(index);
+        }
+        let blocks: Vec<serde_json::Value> =
+            self.blocks.into_iter().filter(|b| !b.is_null()).collect();
+        normalize_anthropic_response(&json!({"content": blocks, "usage": self.usage}))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tools_for_anthropic() {
+        let tools = crate::tools::ToolRegistry::default().definitions();
+        let converted = tools_for_anthropic(&tools);
+        assert_eq!(converted.len(), tools.len());
+        assert_eq!(converted[0]["name"], "shell");
+        assert_eq!(converted[0]["input_schema"]["type"], "object");
+    }
+
+    #[test]
+    fn test_translate_alternates_and_merges_roles() {
+        let input = vec![
+            json!({"role": "assistant", "content": "I was reading."}),
+            json!({"role": "assistant", "content": "[Used shell tool]"}),
+            json!({"role": "user", "content": "Continue."}),
+        ];
+        let (system, messages) = translate_input_to_anthropic(&input, Some("You are an anemone"));
+
+        assert_eq!(system, "You are an anemone");
+        let roles: Vec<&str> = messages.iter().map(|m| m["role"].as_str().unwrap()).collect();
+        assert_eq!(roles, vec!["user", "assistant", "user"]);
+        assert_eq!(messages[1]["content"].as_array().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_translate_tool_round_trip() {
+        let response = json!({
+            "content": [
+                {"type": "text", "text": "Let me look."},
+                {"type": "tool_use", "id": "toolu_01", "name": "shell", "input": {"command": "ls"}}
+            ],
+            "stop_reason": "tool_use"
+        });
+        let normalized = normalize_anthropic_response(&response);
+        assert_eq!(normalized.text.as_deref(), Some("Let me look."));
+        assert_eq!(normalized.tool_calls[0].call_id, "toolu_01");
+        assert_eq!(normalized.tool_calls[0].arguments["command"], "ls");
+
+        // Feed the output back the way think_once does
+        let mut input = vec![json!({"role": "user", "content": "Continue."})];
+        input.extend(normalized.output);
+        input.push(json!({
+            "type": "function_call_output",
+            "call_id": "toolu_01",
+            "name": "shell",
+            "output": "notes.md"
+        }));
+
+        let (_, messages) = translate_input_to_anthropic(&input, None);
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[1]["content"][1]["type"], "tool_use");
+        assert_eq!(messages[2]["role"], "user");
+        assert_eq!(messages[2]["content"][0]["type"], "tool_result");
+        assert_eq!(messages[2]["content"][0]["tool_use_id"], "toolu_01");
+        assert_eq!(messages[2]["content"][0]["content"], "notes.md");
+    }
+
+    #[test]
+    fn test_translate_images() {
+        let input = vec![json!({
+            "role": "user",
+            "content": [
+                {"type": "input_text", "text": "New file: photo.png"},
+                {"type": "input_image", "image_url": "data:image/png;base64,iVBORw0"}
+            ]
+        })];
+        let (_, messages) = translate_input_to_anthropic(&input, None);
+        let image = &messages[0]["content"][1];
+        assert_eq!(image["type"], "image");
+        assert_eq!(image["source"]["type"], "base64");
+        assert_eq!(image["source"]["media_type"], "image/png");
+        assert_eq!(image["source"]["data"], "iVBORw0");
+
+        assert_eq!(
+            image_block("https://example.com/a.jpg")["source"]["type"],
+            "url"
+        );
+    }
+
+    #[test]
+    fn test_stream_rebuilds_blocks() {
+        let transcript = concat!(
+            "event: message_start\ndata: {\"type\":\"message_start\",\"message\":{\"content\":[],\"usage\":{\"input_tokens\":40,\"cache_read_input_tokens\":60,\"output_tokens\":1}}}\n\n",
+            "event: content_block_start\ndata: {\"type\":\"content_block_start\",\"index\":0,\"content_block\":{\"type\":\"text\",\"text\":\"\"}}\n\n",
+            "event: content_block_delta\ndata: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"Going \"}}\n\n",
+            "event: content_block_delta\ndata: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"to bed.\"}}\n\n",
+            "event: content_block_stop\ndata: {\"type\":\"content_block_stop\",\"index\":0}\n\n",
+            "event: content_block_start\ndata: {\"type\":\"content_block_start\",\"index\":1,\"content_block\":{\"type\":\"tool_use\",\"id\":\"toolu_7\",\"name\":\"move\",\"input\":{}}}\n\n",
+            "event: content_block_delta\ndata: {\"type\":\"content_block_delta\",\"index\":1,\"delta\":{\"type\":\"input_json_delta\",\"partial_json\":\"{\\\"location\\\":\"}}\n\n",
+            "event: content_block_delta\ndata: {\"type\":\"content_block_delta\",\"index\":1,\"delta\":{\"type\":\"input_json_delta\",\"partial_json\":\" \\\"bed\\\"}\"}}\n\n",
+            "event: content_block_stop\ndata: {\"type\":\"content_block_stop\",\"index\":1}\n\n",
+            "event: message_delta\ndata: {\"type\":\"message_delta\",\"delta\":{\"stop_reason\":\"tool_use\"},\"usage\":{\"output_tokens\":12}}\n\n",
+            "event: message_stop\ndata: {\"type\":\"message_stop\"}\n\n",
+        );
+
+        let deltas = std::sync::Mutex::new(Vec::new());
+        let on_delta = |d: StreamDelta| deltas.lock().unwrap().push(d);
+        let mut stream = MessagesStream::default();
+        for event in crate::providers::sse::parse_transcript(transcript) {
+            stream
+                .handle(&serde_json::from_str(&event.data).unwrap(), &on_delta)
+                .unwrap();
+        }
+        let result = stream.finish();
+
+        assert_eq!(result.text.as_deref(), Some("Going to bed."));
+        assert_eq!(result.tool_calls[0].call_id, "toolu_7");
+        assert_eq!(result.tool_calls[0].arguments["location"], "bed");
+        assert_eq!(result.output[0]["content"][1]["input"]["location"], "bed");
+        assert_eq!(deltas.into_inner().unwrap().len(), 4);
+        // Cache reads count as prompt tokens; the final output count wins
+        let usage = result.usage.unwrap();
+        assert_eq!((usage.prompt_tokens, usage.cached_tokens), (100, 60));
+        assert_eq!(usage.completion_tokens, 12);
+    }
+}
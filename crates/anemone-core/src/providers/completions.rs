@@ -0,0 +1,531 @@
+//! Generic Chat Completions provider — any OpenAI-compatible `/chat/completions`
+//! endpoint (OpenAI, LM Studio, vLLM, Ollama's `/v1`, ...).
+
+use anyhow::Result;
+use async_trait::async_trait;
+use serde_json::json;
+use tracing::{info, warn};
+
+use super::sse::for_each_sse;
+use super::{
+    build_client, embeddings_request, openai_fallback_embed, parse_arguments, post_json,
+    post_stream, tool_output_text, tools_for_completions, Capabilities, DeltaCallback, Embedding,
+    EmbeddingBatch, LlmProvider, StreamDelta, OPENAI_BASE_URL,
+};
+use crate::config::Config;
+use crate::types::{LlmResponse, TokenUsage, ToolCall};
+
+pub struct ChatCompletionsProvider {
+    name: String,
+    model: String,
+    embedding_model: String,
+    strict_embeddings: bool,
+    base_url: String,
+    api_key: String,
+    extra_headers: Vec<(&'static str, String)>,
+    client: reqwest::Client,
+}
+
+impl ChatCompletionsProvider {
+    pub fn new(config: &Config) -> Self {
+        Self::with_base_url(config, OPENAI_BASE_URL)
+    }
+
+    /// Like [`new`](Self::new), but with a different default when
+    /// `config.base_url` is unset.
+    pub fn with_base_url(config: &Config, default_base_url: &str) -> Self {
+        let base_url = config
+            .base_url
+            .as_deref()
+            .unwrap_or(default_base_url)
+            .trim_end_matches('/')
+            .to_string();
+        Self {
+            name: config.provider.clone(),
+            model: config.model.clone(),
+            embedding_model: config.embedding_model.clone(),
+            strict_embeddings: config.strict_embeddings,
+            base_url,
+            api_key: config.api_key.clone().unwrap_or_else(|| "ollama".to_string()), // Ollama doesn't need a key
+            extra_headers: Vec::new(),
+            client: build_client(),
+        }
+    }
+
+    /// Extra headers sent with every chat request.
+    pub fn with_header(mut self, name: &'static str, value: &str) -> Self {
+        self.extra_headers.push((name, value.to_string()));
+        self
+    }
+
+    fn headers(&self) -> Vec<(&str, String)> {
+        let mut headers = vec![("Authorization", format!("Bearer {}", self.api_key))];
+        headers.extend(self.extra_headers.iter().map(|(k, v)| (*k, v.clone())));
+        headers
+    }
+
+    fn request_body(
+        &self,
+        input: &[serde_json::Value],
+        tools: &[serde_json::Value],
+        instructions: Option<&str>,
+        max_tokens: u32,
+    ) -> serde_json::Value {
+        let messages = translate_input_to_messages(input, instructions);
+
+        info!(
+            "chat_completions request: model={} provider={} msg_count={}",
+            self.model,
+            self.name,
+            messages.len()
+        );
+
+        let mut body = json!({
+            "model": self.model,
+            "messages": messages,
+            "max_tokens": max_tokens,
+        });
+
+        let completions_tools = tools_for_completions(tools);
+        if !completions_tools.is_empty() {
+            body["tools"] = json!(completions_tools);
+        }
+        body
+    }
+}
+
+#[async_trait]
+impl LlmProvider for ChatCompletionsProvider {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            tools: true,
+            vision: true,
+            embeddings: true,
+            streaming: true,
+        }
+    }
+
+    async fn chat(
+        &self,
+        input: &[serde_json::Value],
+        tools: &[serde_json::Value],
+        instructions: Option<&str>,
+        max_tokens: u32,
+    ) -> Result<LlmResponse> {
+        let body = self.request_body(input, tools, instructions, max_tokens);
+        let url = format!("{}/chat/completions", self.base_url);
+        let data = post_json(&self.client, &url, &self.headers(), &body).await?;
+        Ok(normalize_completions_response(&data))
+    }
+
+    async fn chat_stream(
+        &self,
+        input: &[serde_json::Value],
+        tools: &[serde_json::Value],
+        instructions: Option<&str>,
+        max_tokens: u32,
+        on_delta: DeltaCallback<'_>,
+    ) -> Result<LlmResponse> {
+        let mut body = self.request_body(input, tools, instructions, max_tokens);
+        body["stream"] = json!(true);
+        // Token counts arrive in a final chunk with no choices
+        body["stream_options"] = json!({"include_usage": true});
+
+        let url = format!("{}/chat/completions", self.base_url);
+        let mut response = post_stream(&self.client, &url, &self.headers(), &body).await?;
+        let mut stream = CompletionsStream::default();
+        for_each_sse(&mut response, |event| {
+            if event.data == "[DONE]" {
+                return Ok(());
+            }
+            match serde_json::from_str(&event.data) {
+                Ok(chunk) => stream.handle(&chunk, on_delta),
+                Err(e) => {
+                    warn!("Skipping bad stream chunk: {}", e);
+                    Ok(())
+                }
+            }
+        })
+        .await?;
+        Ok(stream.finish())
+    }
+
+    async fn embed(&self, text: &str) -> Result<Embedding> {
+        self.embed_batch(&[text]).await?.single()
+    }
+
+    async fn embed_batch(&self, texts: &[&str]) -> Result<EmbeddingBatch> {
+        let url = format!("{}/embeddings", self.base_url);
+        match embeddings_request(&self.client, &url, &self.api_key, &self.embedding_model, texts)
+            .await
+        {
+            Ok(batch) => Ok(batch),
+            Err(e) if self.base_url != OPENAI_BASE_URL => {
+                // If a non-OpenAI endpoint fails, try OpenAI
+                let cause = e.context(format!("{} embeddings failed", self.name));
+                openai_fallback_embed(
+                    &self.client,
+                    &self.embedding_model,
+                    texts,
+                    self.strict_embeddings,
+                    cause,
+                )
+                .await
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
+
+// ── Input translation ──
+
+/// Convert multimodal content from Responses API to Chat Completions format.
+fn translate_multimodal(content_parts: &[serde_json::Value]) -> Vec<serde_json::Value> {
+    content_parts
+        .iter()
+        .filter_map(|part| {
+            let part_type = part.get("type")?.as_str()?;
+            match part_type {
+                "input_image" => Some(json!({
+                    "type": "image_url",
+                    "image_url": { "url": part["image_url"] }
+                })),
+                "input_text" => Some(json!({
+                    "type": "text",
+                    "text": part["text"]
+                })),
+                _ => Some(part.clone()),
+            }
+        })
+        .collect()
+}
+
+/// Convert Responses API input_list to Chat Completions messages.
+pub(crate) fn translate_input_to_messages(
+    input_list: &[serde_json::Value],
+    instructions: Option<&str>,
+) -> Vec<serde_json::Value> {
+    let mut messages = Vec::new();
+
+    if let Some(inst) = instructions {
+        messages.push(json!({"role": "system", "content": inst}));
+    }
+
+    for item in input_list {
+        if item.get("type").and_then(|v| v.as_str()) == Some("function_call_output") {
+            let mut tool_msg = json!({
+                "role": "tool",
+                "content": tool_output_text(item),
+            });
+            if let Some(call_id) = item.get("call_id") {
+                tool_msg["tool_call_id"] = call_id.clone();
+            }
+            messages.push(tool_msg);
+        } else if item.get("role").is_some() {
+            let mut msg = item.clone();
+            // Translate multimodal content
+            if let Some(content) = item.get("content").and_then(|c| c.as_array()) {
+                msg["content"] = json!(translate_multimodal(content));
+            }
+            messages.push(msg);
+        }
+        // Skip non-dict / SDK objects (they don't exist in our Rust representation)
+    }
+
+    messages
+}
+
+// ── Response normalization ──
+
+/// Normalize a Chat Completions response into our standard format.
+pub(crate) fn normalize_completions_response(response: &serde_json::Value) -> LlmResponse {
+    let message = &response["choices"][0]["message"];
+    let text = message.get("content").and_then(|v| v.as_str()).map(String::from);
+
+    let mut tool_calls = Vec::new();
+    let mut output = Vec::new();
+
+    if let Some(tcs) = message.get("tool_calls").and_then(|v| v.as_array()) {
+        let mut tc_output = Vec::new();
+        for (i, tc) in tcs.iter().enumerate() {
+            let func = &tc["function"];
+            let name = func
+                .get("name")
+                .and_then(|v| v.as_str())
+                .unwrap_or("")
+                .to_string();
+            let call_id = tc
+                .get("id")
+                .and_then(|v| v.as_str())
+                .map(String::from)
+                .unwrap_or_else(|| {
+                    format!("call_{}_{}", if name.is_empty() { "unknown" } else { &name }, i)
+                });
+
+            tool_calls.push(ToolCall {
+                name: name.clone(),
+                arguments: parse_arguments(func.get("arguments")),
+                call_id: call_id.clone(),
+            });
+
+            // Synthetic assistant tool call for the follow-up input_list
+            tc_output.push(json!({
+                "id": call_id,
+                "type": "function",
+                "function": {
+                    "name": func["name"],
+                    "arguments": func["arguments"],
+                }
+            }));
+        }
+
+        output.push(json!({
+            "role": "assistant",
+            "content": text,
+            "tool_calls": tc_output,
+        }));
+    }
+
+    LlmResponse {
+        text,
+        tool_calls,
+        output,
+        usage: parse_completions_usage(&response["usage"]),
+    }
+}
+
+/// Read a Chat Completions (or embeddings) `usage` block.
+pub(crate) fn parse_completions_usage(usage: &serde_json::Value) -> Option<TokenUsage> {
+    let prompt_tokens = usage.get("prompt_tokens")?.as_u64()?;
+    Some(TokenUsage {
+        prompt_tokens,
+        completion_tokens: usage["completion_tokens"].as_u64().unwrap_or(0),
+        cached_tokens: usage["prompt_tokens_details"]["cached_tokens"]
+            .as_u64()
+            .unwrap_or(0),
+    })
+}
+
+// ── Streaming ──
+
+/// A tool call being assembled from streamed fragments.
+#[derive(Default)]
+struct PartialToolCall {
+    id: String,
+    name: String,
+    arguments: String,
+}
+
+/// Accumulates `chat.completion.chunk` deltas into a full response.
+#[derive(Default)]
+struct CompletionsStream {
+    content: String,
+    tool_calls: Vec<PartialToolCall>,
+    usage: Option<TokenUsage>,
+}
+
+impl CompletionsStream {
+    fn handle(&mut self, chunk: &serde_json::Value, on_delta: DeltaCallback<'_>) -> Result<()> {
+        if let Some(error) = chunk.get("error") {
+            anyhow::bail!("API stream error: {}", error);
+        }
+        if let Some(usage) = parse_completions_usage(&chunk["usage"]) {
+            self.usage = Some(usage);
+        }
+        let delta = &chunk["choices"][0]["delta"];
+
+        if let Some(text) = delta.get("content").and_then(|v| v.as_str()) {
+            if !text.is_empty() {
+                self.content.push_str(text);
+                on_delta(StreamDelta::Text(text.to_string()));
+            }
+        }
+
+        for tc in delta
+            .get("tool_calls")
+            .and_then(|v| v.as_array())
+            .into_iter()
+            .flatten()
+        {
+            let index = tc
+                .get("index")
+                .and_then(|v| v.as_u64())
+                .map_or(self.tool_calls.len(), |i| i as usize);
+            if self.tool_calls.len() <= index {
+                self.tool_calls.resize_with(index + 1, PartialToolCall::default);
+            }
+            let call = &mut self.tool_calls[index];
+            if let Some(id) = tc.get("id").and_then(|v| v.as_str()) {
+                call.id = id.to_string();
+            }
+            if let Some(name) = tc["function"].get("name").and_then(|v| v.as_str()) {
+                call.name = name.to_string();
+            }
+            if let Some(args) = tc["function"].get("arguments").and_then(|v| v.as_str()) {
+                call.arguments.push_str(args);
+                if !args.is_empty() {
+                    on_delta(StreamDelta::ToolArguments {
+                        tool: call.name.clone(),
+                        delta: args.to_string(),
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> LlmResponse {
+        let mut message = json!({
+            "role": "assistant",
+            "content": if self.content.is_empty() { None } else { Some(self.content) },
+        });
+        if !self.tool_calls.is_empty() {
+            let tool_calls: Vec<serde_json::Value> = self
+                .tool_calls
+                .into_iter()
+                .map(|tc| {
+                    let mut call = json!({
+                        "type": "function",
+                        "function": { "name": tc.name, "arguments": tc.arguments },
+                    });
+                    if !tc.id.is_empty() {
+                        call["id"] = json!(tc.id);
+                    }
+                    call
+                })
+                .collect();
+            message["tool_calls"] = json!(tool_calls);
+        }
+        let mut response = normalize_completions_response(&json!({"choices": [{"message": message}]}));
+        response.usage = self.usage;
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_translate_input_to_messages() {
+        let input = vec![
+            json!({"role": "user", "content": "Hello"}),
+            json!({"type": "function_call_output", "call_id": "c1", "output": "result here"}),
+        ];
+
+        let messages = translate_input_to_messages(&input, Some("You are helpful"));
+
+        assert_eq!(messages.len(), 3); // system + user + tool
+        assert_eq!(messages[0]["role"], "system");
+        assert_eq!(messages[1]["role"], "user");
+        assert_eq!(messages[2]["role"], "tool");
+        assert_eq!(messages[2]["content"], "result here");
+        assert_eq!(messages[2]["tool_call_id"], "c1");
+    }
+
+    #[test]
+    fn test_translate_multimodal_content() {
+        let input = vec![json!({
+            "role": "user",
+            "content": [
+                {"type": "input_text", "text": "look"},
+                {"type": "input_image", "image_url": "data:image/png;base64,AAAA"}
+            ]
+        })];
+
+        let messages = translate_input_to_messages(&input, None);
+        assert_eq!(messages[0]["content"][0]["type"], "text");
+        assert_eq!(messages[0]["content"][1]["type"], "image_url");
+        assert_eq!(
+            messages[0]["content"][1]["image_url"]["url"],
+            "data:image/png;base64,AAAA"
+        );
+    }
+
+    #[test]
+    fn test_normalize_completions_response_text_only() {
+        let response = json!({
+            "choices": [{
+                "message": {
+                    "content": "Hello world",
+                    "role": "assistant"
+                }
+            }]
+        });
+
+        let result = normalize_completions_response(&response);
+        assert_eq!(result.text.as_deref(), Some("Hello world"));
+        assert!(result.tool_calls.is_empty());
+    }
+
+    #[test]
+    fn test_normalize_completions_response_with_tools() {
+        let response = json!({
+            "choices": [{
+                "message": {
+                    "content": "Let me check.",
+                    "role": "assistant",
+                    "tool_calls": [{
+                        "id": "call_123",
+                        "type": "function",
+                        "function": {
+                            "name": "shell",
+                            "arguments": "{\"command\": \"ls\"}"
+                        }
+                    }]
+                }
+            }]
+        });
+
+        let result = normalize_completions_response(&response);
+        assert_eq!(result.text.as_deref(), Some("Let me check."));
+        assert_eq!(result.tool_calls.len(), 1);
+        assert_eq!(result.tool_calls[0].name, "shell");
+        assert_eq!(result.tool_calls[0].call_id, "call_123");
+        assert_eq!(result.tool_calls[0].arguments["command"], "ls");
+        assert_eq!(result.output[0]["tool_calls"][0]["id"], "call_123");
+    }
+
+    #[test]
+    fn test_stream_accumulates_text_and_tool_calls() {
+        let transcript = concat!(
+            "data: {\"choices\":[{\"delta\":{\"role\":\"assistant\",\"content\":\"Let me \"}}]}\n\n",
+            "data: {\"choices\":[{\"delta\":{\"content\":\"check.\"}}]}\n\n",
+            "data: {\"choices\":[{\"delta\":{\"tool_calls\":[{\"index\":0,\"id\":\"call_9\",\"function\":{\"name\":\"shell\",\"arguments\":\"\"}}]}}]}\n\n",
+            "data: {\"choices\":[{\"delta\":{\"tool_calls\":[{\"index\":0,\"function\":{\"arguments\":\"{\\\"command\\\": \"}}]}}]}\n\n",
+            "data: {\"choices\":[{\"delta\":{\"tool_calls\":[{\"index\":0,\"function\":{\"arguments\":\"\\\"ls\\\"}\"}}]}}]}\n\n",
+            "data: [DONE]\n\n",
+        );
+
+        let deltas = Mutex::new(Vec::new());
+        let on_delta = |d: StreamDelta| deltas.lock().unwrap().push(d);
+        let mut stream = CompletionsStream::default();
+        for event in crate::providers::sse::parse_transcript(transcript) {
+            if event.data != "[DONE]" {
+                stream
+                    .handle(&serde_json::from_str(&event.data).unwrap(), &on_delta)
+                    .unwrap();
+            }
+        }
+        let result = stream.finish();
+
+        assert_eq!(result.text.as_deref(), Some("Let me check."));
+        assert_eq!(result.tool_calls[0].call_id, "call_9");
+        assert_eq!(result.tool_calls[0].arguments["command"], "ls");
+
+        let deltas = deltas.into_inner().unwrap();
+        assert_eq!(deltas[0], StreamDelta::Text("Let me ".to_string()));
+        assert_eq!(
+            deltas.last().unwrap(),
+            &StreamDelta::ToolArguments {
+                tool: "shell".to_string(),
+                delta: "\"ls\"}".to_string()
+            }
+        );
+    }
+}
@@ -0,0 +1,182 @@
+//! In-process embeddings (`embedding_provider: local`): hashed n-grams on
+//! the CPU, no network and no model download. Words, word pairs and
+//! character trigrams are hashed into a fixed number of signed buckets with
+//! sublinear term frequency, then L2-normalized. Texts sharing words and
+//! word fragments land close together — coarser than a trained model, but
+//! enough for memory relevance offline and in tests.
+//!
+//! [`LocalEmbeddings`] wraps any provider, keeping its chat and answering
+//! `embed` locally.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::Result;
+use async_trait::async_trait;
+
+use super::{Capabilities, DeltaCallback, Embedding, EmbeddingBatch, LlmProvider};
+use crate::types::LlmResponse;
+
+/// Recorded as the embedding model of locally embedded memories
+pub const LOCAL_EMBEDDING_MODEL: &str = "local-ngram-384";
+
+pub const LOCAL_EMBEDDING_DIM: usize = 384;
+
+/// Feature weights: whole words count most, fragments least
+const WORD_WEIGHT: f64 = 1.0;
+const PAIR_WEIGHT: f64 = 0.5;
+const TRIGRAM_WEIGHT: f64 = 0.25;
+
+/// FNV-1a, seeded per feature type so a word and a trigram that spell the
+/// same don't collide.
+fn hash(seed: u8, text: &str) -> u64 {
+    std::iter::once(seed)
+        .chain(text.bytes())
+        .fold(0xcbf29ce484222325u64, |h, b| (h ^ b as u64).wrapping_mul(0x100000001b3))
+}
+
+/// Embed `text` locally. The same text always gives the same vector;
+/// text without any words gives the zero vector.
+pub fn embed_text(text: &str) -> Vec<f64> {
+    let lower = text.to_lowercase();
+    let words: Vec<&str> = lower
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .collect();
+
+    let mut features: HashMap<u64, f64> = HashMap::new();
+    for word in &words {
+        *features.entry(hash(b'w', word)).or_default() += WORD_WEIGHT;
+        let padded: Vec<char> = format!("<{}>", word).chars().collect();
+        for trigram in padded.windows(3) {
+            let trigram: String = trigram.iter().collect();
+            *features.entry(hash(b't', &trigram)).or_default() += TRIGRAM_WEIGHT;
+        }
+    }
+    for pair in words.windows(2) {
+        *features.entry(hash(b'p', &format!("{} {}", pair[0], pair[1]))).or_default() += PAIR_WEIGHT;
+    }
+
+    let mut vector = vec![0.0; LOCAL_EMBEDDING_DIM];
+    for (feature, tf) in features {
+        let bucket = (feature % LOCAL_EMBEDDING_DIM as u64) as usize;
+        // The sign comes from bits the bucket doesn't use, so collisions
+        // tend to cancel rather than pile up
+        let sign = if (feature >> 32) & 1 == 0 { 1.0 } else { -1.0 };
+        vector[bucket] += sign * (1.0 + tf.ln_1p());
+    }
+    let norm = vector.iter().map(|x| x * x).sum::<f64>().sqrt();
+    if norm > 0.0 {
+        vector.iter_mut().for_each(|x| *x /= norm);
+    }
+    vector
+}
+
+/// A provider whose embeddings are computed in-process; everything else
+/// goes to `inner`.
+pub struct LocalEmbeddings {
+    inner: Arc<dyn LlmProvider>,
+}
+
+impl LocalEmbeddings {
+    pub fn new(inner: Arc<dyn LlmProvider>) -> Self {
+        Self { inner }
+    }
+}
+
+#[async_trait]
+impl LlmProvider for LocalEmbeddings {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            embeddings: true,
+            ..self.inner.capabilities()
+        }
+    }
+
+    async fn chat(
+        &self,
+        input: &[serde_json::Value],
+        tools: &[serde_json::Value],
+        instructions: Option<&str>,
+        max_tokens: u32,
+    ) -> Result<LlmResponse> {
+        self.inner.chat(input, tools, instructions, max_tokens).await
+    }
+
+    async fn chat_short(
+        &self,
+        input: &[serde_json::Value],
+        instructions: Option<&str>,
+    ) -> Result<LlmResponse> {
+        self.inner.chat_short(input, instructions).await
+    }
+
+    async fn chat_stream(
+        &self,
+        input: &[serde_json::Value],
+        tools: &[serde_json::Value],
+        instructions: Option<&str>,
+        max_tokens: u32,
+        on_delta: DeltaCallback<'_>,
+    ) -> Result<LlmResponse> {
+        self.inner
+            .chat_stream(input, tools, instructions, max_tokens, on_delta)
+            .await
+    }
+
+    async fn embed(&self, text: &str) -> Result<Embedding> {
+        Ok(Embedding {
+            vector: embed_text(text),
+            usage: None,
+        })
+    }
+
+    async fn embed_batch(&self, texts: &[&str]) -> Result<EmbeddingBatch> {
+        Ok(EmbeddingBatch {
+            vectors: texts.iter().map(|text| embed_text(text)).collect(),
+            usage: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::MockProvider;
+
+    fn cosine(a: &[f64], b: &[f64]) -> f64 {
+        a.iter().zip(b).map(|(x, y)| x * y).sum()
+    }
+
+    #[test]
+    fn test_local_embeddings_rank_related_text_closer() {
+        let reef = embed_text("The coral reef at dawn");
+        assert_eq!(reef.len(), LOCAL_EMBEDDING_DIM);
+        assert_eq!(reef, embed_text("the coral reef at dawn!"));
+        assert!((cosine(&reef, &reef) - 1.0).abs() < 1e-9);
+
+        let related = cosine(&reef, &embed_text("watching coral reefs"));
+        let unrelated = cosine(&reef, &embed_text("tax forms are due on Monday"));
+        assert!(related > unrelated + 0.2, "{} vs {}", related, unrelated);
+
+        assert!(embed_text("  ...  ").iter().all(|&x| x == 0.0));
+    }
+
+    #[tokio::test]
+    async fn test_wrapper_keeps_chat_and_embeds_locally() {
+        let mock = Arc::new(MockProvider::new());
+        mock.push_text("hello");
+        let provider = LocalEmbeddings::new(mock.clone());
+
+        assert_eq!(provider.name(), "mock");
+        let reply = provider.chat(&[], &[], None, 10).await.unwrap();
+        assert_eq!(reply.text.as_deref(), Some("hello"));
+        let embedding = provider.embed("coral reef").await.unwrap();
+        assert_eq!(embedding.vector, embed_text("coral reef"));
+        assert!(embedding.usage.is_none());
+    }
+}
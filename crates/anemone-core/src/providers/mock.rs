@@ -0,0 +1,525 @@
+//! In-process provider for tests: replies from a queue of canned responses,
+//! records every request, and embeds text deterministically without a network.
+//! Token usage is reported as whitespace-separated word counts.
+//!
+//! Responses can also be scripted from a scenario file — a YAML list or JSONL
+//! lines of steps, each optionally checking the request it answers:
+//!
+//! ```yaml
+//! - expect: { input: ["wake up"], tools: true }
+//!   text: "Morning. Let me start a notebook."
+//!   tool_calls:
+//!     - name: shell
+//!       arguments: { command: "echo hi > notes.md" }
+//! - short: "8"          # next importance score
+//! - expect: { instructions: "reviewing your recent memories" }
+//!   text: "I like keeping notes."
+//! ```
+
+use std::collections::VecDeque;
+use std::path::Path;
+use std::sync::Mutex;
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::json;
+
+use super::{Capabilities, DeltaCallback, Embedding, EmbeddingBatch, LlmProvider, StreamDelta};
+use crate::types::{LlmResponse, TokenUsage, ToolCall};
+
+/// Dimension of the mock's hashed bag-of-words embeddings.
+pub const MOCK_EMBEDDING_DIM: usize = 64;
+
+/// A chat request as the mock saw it.
+#[derive(Debug, Clone)]
+pub struct MockRequest {
+    pub input: Vec<serde_json::Value>,
+    pub tool_names: Vec<String>,
+    pub instructions: Option<String>,
+    pub max_tokens: u32,
+}
+
+// ── Scenarios ──
+
+/// Checks a scripted step makes of the request it answers. Every field that
+/// is set must match.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Expectation {
+    /// Substring of the system instructions
+    #[serde(default)]
+    pub instructions: Option<String>,
+    /// Substrings that must all appear somewhere in the input's text
+    #[serde(default)]
+    pub input: Vec<String>,
+    /// Whether tools must (`true`) or must not (`false`) be offered
+    #[serde(default)]
+    pub tools: Option<bool>,
+}
+
+impl Expectation {
+    fn check(&self, request: &MockRequest) -> std::result::Result<(), String> {
+        if let Some(ref wanted) = self.instructions {
+            let instructions = request.instructions.as_deref().unwrap_or("");
+            if !instructions.contains(wanted.as_str()) {
+                return Err(format!("instructions do not contain {:?}", wanted));
+            }
+        }
+        let text = input_text(&request.input);
+        for wanted in &self.input {
+            if !text.contains(wanted.as_str()) {
+                return Err(format!("input does not contain {:?}", wanted));
+            }
+        }
+        if let Some(tools) = self.tools {
+            if tools == request.tool_names.is_empty() {
+                return Err(format!(
+                    "expected tools {}, got {:?}",
+                    if tools { "offered" } else { "absent" },
+                    request.tool_names
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScriptedToolCall {
+    pub name: String,
+    #[serde(default = "empty_arguments")]
+    pub arguments: serde_json::Value,
+}
+
+fn empty_arguments() -> serde_json::Value {
+    json!({})
+}
+
+/// One line of a scenario: either a `chat` response (text and/or tool calls,
+/// optionally guarded by `expect`) or a `short` reply for `chat_short`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScenarioStep {
+    #[serde(default)]
+    pub expect: Option<Expectation>,
+    #[serde(default)]
+    pub text: Option<String>,
+    #[serde(default)]
+    pub tool_calls: Vec<ScriptedToolCall>,
+    #[serde(default)]
+    pub short: Option<String>,
+}
+
+/// Load scenario steps from a `.yaml`/`.yml` list or a `.jsonl` file.
+pub fn load_scenario(path: &Path) -> Result<Vec<ScenarioStep>> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read scenario: {}", path.display()))?;
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("yaml") | Some("yml") => serde_yaml::from_str(&content)
+            .with_context(|| format!("Failed to parse scenario: {}", path.display())),
+        Some("jsonl") => content
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| {
+                serde_json::from_str(line).with_context(|| {
+                    format!("Failed to parse scenario {} line {}", path.display(), i + 1)
+                })
+            })
+            .collect(),
+        _ => anyhow::bail!("Unknown scenario format: {}", path.display()),
+    }
+}
+
+/// All string values in the input, one per line — what `expect.input` searches.
+fn input_text(input: &[serde_json::Value]) -> String {
+    fn collect(value: &serde_json::Value, out: &mut Vec<String>) {
+        match value {
+            serde_json::Value::String(s) => out.push(s.clone()),
+            serde_json::Value::Array(items) => items.iter().for_each(|v| collect(v, out)),
+            serde_json::Value::Object(map) => map.values().for_each(|v| collect(v, out)),
+            _ => {}
+        }
+    }
+    let mut out = Vec::new();
+    input.iter().for_each(|item| collect(item, &mut out));
+    out.join("\n")
+}
+
+/// Build a Responses-shaped reply, the same shape the OpenAI provider returns.
+fn scripted_response(
+    text: Option<&str>,
+    tool_calls: &[ScriptedToolCall],
+    call_id_seed: usize,
+) -> LlmResponse {
+    let mut output = Vec::new();
+    if let Some(text) = text {
+        output.push(json!({"role": "assistant", "content": text}));
+    }
+    let tool_calls: Vec<ToolCall> = tool_calls
+        .iter()
+        .enumerate()
+        .map(|(i, tc)| {
+            let call_id = format!("call_{}_{}", tc.name, call_id_seed + i);
+            output.push(json!({
+                "type": "function_call",
+                "name": tc.name,
+                "call_id": call_id,
+                "arguments": tc.arguments.to_string(),
+            }));
+            ToolCall {
+                name: tc.name.clone(),
+                arguments: tc.arguments.clone(),
+                call_id,
+            }
+        })
+        .collect();
+    LlmResponse {
+        text: text.map(String::from),
+        tool_calls,
+        output,
+        usage: None,
+    }
+}
+
+fn word_count(text: &str) -> u64 {
+    text.split_whitespace().count() as u64
+}
+
+/// Word-count usage for a call, unless the script set its own.
+fn mock_usage(
+    input: &[serde_json::Value],
+    instructions: Option<&str>,
+    reply: &str,
+) -> TokenUsage {
+    TokenUsage {
+        prompt_tokens: word_count(&input_text(input)) + word_count(instructions.unwrap_or("")),
+        completion_tokens: word_count(reply),
+        cached_tokens: 0,
+    }
+}
+
+// ── Provider ──
+
+struct Scripted {
+    expect: Option<Expectation>,
+    response: LlmResponse,
+}
+
+#[derive(Default)]
+pub struct MockProvider {
+    responses: Mutex<VecDeque<Scripted>>,
+    short_responses: Mutex<VecDeque<String>>,
+    requests: Mutex<Vec<MockRequest>>,
+    short_requests: Mutex<Vec<MockRequest>>,
+    embed_batches: Mutex<Vec<usize>>,
+}
+
+impl MockProvider {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A mock scripted from a scenario file (see the module docs).
+    pub fn from_scenario(path: &Path) -> Result<Self> {
+        let mock = Self::new();
+        for step in load_scenario(path)? {
+            mock.push_step(step);
+        }
+        Ok(mock)
+    }
+
+    /// Queue a full response for the next `chat` call.
+    pub fn push_response(&self, response: LlmResponse) {
+        lock(&self.responses).push_back(Scripted {
+            expect: None,
+            response,
+        });
+    }
+
+    /// Queue a scenario step.
+    pub fn push_step(&self, step: ScenarioStep) {
+        if let Some(short) = step.short {
+            self.push_short(&short);
+            return;
+        }
+        let mut responses = lock(&self.responses);
+        let seed = responses.len();
+        responses.push_back(Scripted {
+            expect: step.expect,
+            response: scripted_response(step.text.as_deref(), &step.tool_calls, seed),
+        });
+    }
+
+    /// Queue a plain-text response.
+    pub fn push_text(&self, text: &str) {
+        self.push_step(ScenarioStep {
+            text: Some(text.to_string()),
+            ..ScenarioStep::default()
+        });
+    }
+
+    /// Queue a response that calls a single tool.
+    pub fn push_tool_call(&self, name: &str, arguments: serde_json::Value) {
+        self.push_step(ScenarioStep {
+            tool_calls: vec![ScriptedToolCall {
+                name: name.to_string(),
+                arguments,
+            }],
+            ..ScenarioStep::default()
+        });
+    }
+
+    /// Queue a reply for the next `chat_short` call (importance scoring etc.).
+    /// Unscripted short calls answer `"5"`.
+    pub fn push_short(&self, text: &str) {
+        lock(&self.short_responses).push_back(text.to_string());
+    }
+
+    /// Every `chat` request received so far.
+    pub fn requests(&self) -> Vec<MockRequest> {
+        lock(&self.requests).clone()
+    }
+
+    /// Every `chat_short` request received so far.
+    pub fn short_requests(&self) -> Vec<MockRequest> {
+        lock(&self.short_requests).clone()
+    }
+
+    /// How many texts each embedding request carried.
+    pub fn embed_batches(&self) -> Vec<usize> {
+        lock(&self.embed_batches).clone()
+    }
+
+    /// Scripted `chat` responses not yet consumed.
+    pub fn remaining(&self) -> usize {
+        lock(&self.responses).len()
+    }
+}
+
+fn lock<T>(m: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    m.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[async_trait]
+impl LlmProvider for MockProvider {
+    fn name(&self) -> &str {
+        "mock"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            tools: true,
+            vision: true,
+            embeddings: true,
+            streaming: true,
+        }
+    }
+
+    async fn chat(
+        &self,
+        input: &[serde_json::Value],
+        tools: &[serde_json::Value],
+        instructions: Option<&str>,
+        max_tokens: u32,
+    ) -> Result<LlmResponse> {
+        let request = MockRequest {
+            input: input.to_vec(),
+            tool_names: tools
+                .iter()
+                .filter_map(|t| t.get("name").and_then(|v| v.as_str()).map(String::from))
+                .collect(),
+            instructions: instructions.map(String::from),
+            max_tokens,
+        };
+        let number = {
+            let mut requests = lock(&self.requests);
+            requests.push(request.clone());
+            requests.len()
+        };
+        let scripted = lock(&self.responses)
+            .pop_front()
+            .ok_or_else(|| anyhow::anyhow!("MockProvider: no scripted response left"))?;
+        if let Some(ref expect) = scripted.expect {
+            if let Err(mismatch) = expect.check(&request) {
+                anyhow::bail!("MockProvider: request {} unexpected: {}", number, mismatch);
+            }
+        }
+        let mut response = scripted.response;
+        if response.usage.is_none() {
+            let reply = response
+                .tool_calls
+                .iter()
+                .map(|tc| tc.arguments.to_string())
+                .chain(response.text.clone())
+                .collect::<Vec<_>>()
+                .join(" ");
+            response.usage = Some(mock_usage(input, instructions, &reply));
+        }
+        Ok(response)
+    }
+
+    async fn chat_stream(
+        &self,
+        input: &[serde_json::Value],
+        tools: &[serde_json::Value],
+        instructions: Option<&str>,
+        max_tokens: u32,
+        on_delta: DeltaCallback<'_>,
+    ) -> Result<LlmResponse> {
+        // Replay the scripted response word by word
+        let response = self.chat(input, tools, instructions, max_tokens).await?;
+        if let Some(ref text) = response.text {
+            for word in text.split_inclusive(' ') {
+                on_delta(StreamDelta::Text(word.to_string()));
+            }
+        }
+        for tc in &response.tool_calls {
+            on_delta(StreamDelta::ToolArguments {
+                tool: tc.name.clone(),
+                delta: tc.arguments.to_string(),
+            });
+        }
+        Ok(response)
+    }
+
+    async fn chat_short(
+        &self,
+        input: &[serde_json::Value],
+        instructions: Option<&str>,
+    ) -> Result<LlmResponse> {
+        lock(&self.short_requests).push(MockRequest {
+            input: input.to_vec(),
+            tool_names: Vec::new(),
+            instructions: instructions.map(String::from),
+            max_tokens: 0,
+        });
+        let reply = lock(&self.short_responses)
+            .pop_front()
+            .unwrap_or_else(|| "5".to_string());
+        Ok(LlmResponse {
+            usage: Some(mock_usage(input, instructions, &reply)),
+            output: vec![json!({"role": "assistant", "content": reply})],
+            text: Some(reply),
+            tool_calls: Vec::new(),
+        })
+    }
+
+    async fn embed(&self, text: &str) -> Result<Embedding> {
+        lock(&self.embed_batches).push(1);
+        Ok(bag_of_words(text))
+    }
+
+    async fn embed_batch(&self, texts: &[&str]) -> Result<EmbeddingBatch> {
+        lock(&self.embed_batches).push(texts.len());
+        let mut batch = EmbeddingBatch::default();
+        for text in texts {
+            batch.push(bag_of_words(text));
+        }
+        Ok(batch)
+    }
+}
+
+/// Hashed bag of words: texts sharing words get similar vectors.
+fn bag_of_words(text: &str) -> Embedding {
+    let mut vector = vec![0.0; MOCK_EMBEDDING_DIM];
+    for word in text.split_whitespace() {
+        let word = word.to_lowercase();
+        let hash = word
+            .bytes()
+            .fold(0xcbf29ce484222325u64, |h, b| (h ^ b as u64).wrapping_mul(0x100000001b3));
+        vector[(hash % MOCK_EMBEDDING_DIM as u64) as usize] += 1.0;
+    }
+    Embedding {
+        vector,
+        usage: Some(TokenUsage {
+            prompt_tokens: word_count(text),
+            ..TokenUsage::default()
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_mock_replays_script_in_order() {
+        let mock = MockProvider::new();
+        mock.push_tool_call("move", json!({"location": "desk"}));
+        mock.push_text("done");
+
+        let first = mock.chat(&[], &[], None, 10).await.unwrap();
+        assert_eq!(first.tool_calls[0].name, "move");
+        let second = mock.chat(&[], &[], None, 10).await.unwrap();
+        assert_eq!(second.text.as_deref(), Some("done"));
+        assert!(mock.chat(&[], &[], None, 10).await.is_err());
+        assert_eq!(mock.requests().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_mock_embeddings_are_deterministic() {
+        let mock = MockProvider::new();
+        let a = mock.embed("coral reef").await.unwrap();
+        let b = mock.embed("Coral reef").await.unwrap();
+        assert_eq!(a.vector, b.vector);
+        assert_eq!(a.vector.len(), MOCK_EMBEDDING_DIM);
+        assert_eq!(a.usage.unwrap().prompt_tokens, 2);
+    }
+
+    #[tokio::test]
+    async fn test_yaml_scenario_with_expectations() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("scenario.yaml");
+        std::fs::write(
+            &path,
+            r#"
+- expect: { input: ["coral"], tools: true }
+  text: "Looking around."
+  tool_calls:
+    - name: move
+      arguments: { location: window }
+- short: "8"
+- expect: { instructions: "reviewing" }
+  text: "Insight."
+"#,
+        )
+        .unwrap();
+
+        let mock = MockProvider::from_scenario(&path).unwrap();
+        assert_eq!(mock.remaining(), 2);
+
+        let input = [json!({"role": "user", "content": [{"type": "input_text", "text": "a coral reef"}]})];
+        let tools = [json!({"type": "function", "name": "move"})];
+        let first = mock.chat(&input, &tools, None, 10).await.unwrap();
+        assert_eq!(first.text.as_deref(), Some("Looking around."));
+        assert_eq!(first.tool_calls[0].arguments["location"], "window");
+        assert_eq!(first.output.len(), 2);
+        assert_eq!(mock.chat_short(&[], None).await.unwrap().text.as_deref(), Some("8"));
+
+        // Wrong instructions: the step is consumed and the call fails
+        let err = mock.chat(&input, &[], Some("plan"), 10).await.unwrap_err();
+        assert!(err.to_string().contains("instructions"));
+        assert_eq!(mock.remaining(), 0);
+    }
+
+    #[test]
+    fn test_jsonl_scenario() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("scenario.jsonl");
+        std::fs::write(
+            &path,
+            "{\"text\": \"one\"}\n\n{\"tool_calls\": [{\"name\": \"shell\", \"arguments\": {\"command\": \"ls\"}}]}\n",
+        )
+        .unwrap();
+        let steps = load_scenario(&path).unwrap();
+        assert_eq!(steps.len(), 2);
+        assert_eq!(steps[1].tool_calls[0].name, "shell");
+
+        std::fs::write(&path, "{\"txt\": \"typo\"}\n").unwrap();
+        let err = load_scenario(&path).unwrap_err();
+        assert!(format!("{:#}", err).contains("line 1"));
+    }
+}
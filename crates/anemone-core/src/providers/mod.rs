@@ -0,0 +1,552 @@
+//! LLM providers — a pluggable [`LlmProvider`] trait plus a registry that picks
+//! an implementation from `config.provider`.
+//!
+//! Every provider accepts input in the Responses API shape the brain builds
+//! (role messages, `function_call_output` items) and tool definitions from
+//! [`crate::tools::ToolRegistry`], and translates them to its own wire format.
+
+pub mod anthropic;
+pub mod completions;
+pub mod local;
+pub mod mock;
+pub mod ollama;
+pub mod openai;
+pub mod openrouter;
+pub(crate) mod sse;
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use serde_json::json;
+use tracing::{error, warn};
+
+use crate::config::Config;
+use crate::types::{LlmResponse, TokenUsage};
+
+pub use anthropic::AnthropicProvider;
+pub use completions::ChatCompletionsProvider;
+pub use local::LocalEmbeddings;
+pub use mock::MockProvider;
+pub use ollama::OllamaProvider;
+pub use openai::OpenAiProvider;
+pub use openrouter::OpenRouterProvider;
+
+/// Default OpenAI API root, used when `base_url` is not set.
+pub const OPENAI_BASE_URL: &str = "https://api.openai.com/v1";
+
+/// Max chars of tool result content sent to the model.
+const MAX_TOOL_CONTENT: usize = 16000;
+
+/// Retries after the first attempt for transient HTTP failures.
+const MAX_RETRIES: u32 = 2;
+
+/// Backoff before the first retry; doubles each time.
+const RETRY_BASE_DELAY: Duration = Duration::from_secs(2);
+
+// ── Provider trait ──
+
+/// What a provider can do, so callers can degrade gracefully.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    /// Accepts tool definitions and returns tool calls.
+    pub tools: bool,
+    /// Accepts image content parts.
+    pub vision: bool,
+    /// Has its own embeddings endpoint (otherwise embeddings fall back to OpenAI).
+    pub embeddings: bool,
+    /// Implements [`LlmProvider::chat_stream`] with incremental deltas.
+    pub streaming: bool,
+}
+
+/// An embedding vector and what it cost.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Embedding {
+    pub vector: Vec<f64>,
+    pub usage: Option<TokenUsage>,
+}
+
+/// Embeddings of several texts, in input order, and what they cost together.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EmbeddingBatch {
+    pub vectors: Vec<Vec<f64>>,
+    pub usage: Option<TokenUsage>,
+}
+
+impl EmbeddingBatch {
+    /// Add one embedding to the batch.
+    pub fn push(&mut self, embedding: Embedding) {
+        self.vectors.push(embedding.vector);
+        if let Some(usage) = embedding.usage {
+            let total = self.usage.get_or_insert_with(TokenUsage::default);
+            total.prompt_tokens += usage.prompt_tokens;
+            total.completion_tokens += usage.completion_tokens;
+            total.cached_tokens += usage.cached_tokens;
+        }
+    }
+
+    /// The embedding of a one-text batch.
+    pub(crate) fn single(self) -> Result<Embedding> {
+        let vector = self
+            .vectors
+            .into_iter()
+            .next()
+            .context("Empty embedding response")?;
+        Ok(Embedding {
+            vector,
+            usage: self.usage,
+        })
+    }
+}
+
+/// A fragment of a response still being generated.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StreamDelta {
+    /// More of the visible text.
+    Text(String),
+    /// More of a tool call's JSON arguments.
+    ToolArguments { tool: String, delta: String },
+}
+
+/// Receives [`StreamDelta`]s as they arrive.
+pub type DeltaCallback<'a> = &'a (dyn Fn(StreamDelta) + Send + Sync);
+
+/// A chat + embeddings backend.
+#[async_trait]
+pub trait LlmProvider: Send + Sync {
+    /// Short name for logs, e.g. `"openai"`.
+    fn name(&self) -> &str;
+
+    fn capabilities(&self) -> Capabilities;
+
+    /// Make an LLM call. `tools` are Responses-style function definitions;
+    /// pass an empty slice for a plain completion.
+    async fn chat(
+        &self,
+        input: &[serde_json::Value],
+        tools: &[serde_json::Value],
+        instructions: Option<&str>,
+        max_tokens: u32,
+    ) -> Result<LlmResponse>;
+
+    /// Short LLM call (for importance scoring) — no tools, small token budget.
+    async fn chat_short(
+        &self,
+        input: &[serde_json::Value],
+        instructions: Option<&str>,
+    ) -> Result<LlmResponse> {
+        self.chat(input, &[], instructions, 300).await
+    }
+
+    /// Like [`chat`](Self::chat), but reports partial output to `on_delta`
+    /// while the response streams in. The returned response is complete.
+    /// Providers without streaming support just make a normal call.
+    async fn chat_stream(
+        &self,
+        input: &[serde_json::Value],
+        tools: &[serde_json::Value],
+        instructions: Option<&str>,
+        max_tokens: u32,
+        on_delta: DeltaCallback<'_>,
+    ) -> Result<LlmResponse> {
+        let _ = on_delta;
+        self.chat(input, tools, instructions, max_tokens).await
+    }
+
+    /// Get an embedding vector for a text string.
+    async fn embed(&self, text: &str) -> Result<Embedding>;
+
+    /// Embed several texts, in one request where the API takes a list.
+    /// Providers without one embed each text in turn.
+    async fn embed_batch(&self, texts: &[&str]) -> Result<EmbeddingBatch> {
+        let mut batch = EmbeddingBatch::default();
+        for text in texts {
+            batch.push(self.embed(text).await?);
+        }
+        Ok(batch)
+    }
+}
+
+// ── Registry ──
+
+/// Builds a provider from the loaded config.
+pub type ProviderFactory = Box<dyn Fn(&Config) -> Arc<dyn LlmProvider> + Send + Sync>;
+
+/// Maps `config.provider` names to provider factories. `Default` registers the
+/// built-in providers; call [`register`](Self::register) to add or override one.
+pub struct ProviderRegistry {
+    factories: HashMap<String, ProviderFactory>,
+}
+
+impl ProviderRegistry {
+    /// A registry with no providers at all.
+    pub fn empty() -> Self {
+        Self {
+            factories: HashMap::new(),
+        }
+    }
+
+    pub fn register<F>(&mut self, name: &str, factory: F)
+    where
+        F: Fn(&Config) -> Arc<dyn LlmProvider> + Send + Sync + 'static,
+    {
+        self.factories.insert(name.to_string(), Box::new(factory));
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.factories.contains_key(name)
+    }
+
+    /// Registered provider names, sorted.
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.factories.keys().map(String::as_str).collect();
+        names.sort();
+        names
+    }
+
+    /// Build the provider named by `config.provider`. Unknown names fall back
+    /// to a generic Chat Completions provider against `base_url`. With
+    /// `embedding_provider: local` its embeddings are computed in-process.
+    pub fn build(&self, config: &Config) -> Arc<dyn LlmProvider> {
+        let provider = self.build_chat(config);
+        match config.embedding_provider.as_str() {
+            "local" => Arc::new(LocalEmbeddings::new(provider)),
+            "provider" => provider,
+            other => {
+                warn!("Unknown embedding_provider '{}', using the provider's", other);
+                provider
+            }
+        }
+    }
+
+    fn build_chat(&self, config: &Config) -> Arc<dyn LlmProvider> {
+        match self.factories.get(&config.provider) {
+            Some(factory) => factory(config),
+            None => {
+                warn!(
+                    "Unknown provider '{}', using generic Chat Completions",
+                    config.provider
+                );
+                Arc::new(ChatCompletionsProvider::new(config))
+            }
+        }
+    }
+}
+
+impl Default for ProviderRegistry {
+    fn default() -> Self {
+        let mut registry = Self::empty();
+        registry.register("openai", |c| Arc::new(OpenAiProvider::new(c)));
+        registry.register("anthropic", |c| Arc::new(AnthropicProvider::new(c)));
+        registry.register("openrouter", |c| Arc::new(OpenRouterProvider::new(c)));
+        registry.register("ollama", |c| Arc::new(OllamaProvider::new(c)));
+        registry.register("custom", |c| Arc::new(ChatCompletionsProvider::new(c)));
+        registry
+    }
+}
+
+/// Build the provider for `config` from the built-in registry.
+pub fn from_config(config: &Config) -> Arc<dyn LlmProvider> {
+    ProviderRegistry::default().build(config)
+}
+
+// ── Tool definitions ──
+
+/// Convert Responses API tool defs to Chat Completions format.
+/// Drops non-function tools. Wraps in {"type": "function", "function": {...}}.
+pub fn tools_for_completions(tools: &[serde_json::Value]) -> Vec<serde_json::Value> {
+    tools
+        .iter()
+        .filter(|t| t.get("type").and_then(|v| v.as_str()) == Some("function"))
+        .map(|t| {
+            json!({
+                "type": "function",
+                "function": {
+                    "name": t["name"],
+                    "description": t.get("description").unwrap_or(&json!("")),
+                    "parameters": t.get("parameters").unwrap_or(&json!({})),
+                }
+            })
+        })
+        .collect()
+}
+
+// ── Shared translation helpers ──
+
+/// Text of a `function_call_output` item, truncated to [`MAX_TOOL_CONTENT`].
+pub(crate) fn tool_output_text(item: &serde_json::Value) -> String {
+    let mut content = match item.get("output") {
+        Some(serde_json::Value::String(s)) => s.clone(),
+        Some(other) => other.to_string(),
+        None => String::new(),
+    };
+    if content.len() > MAX_TOOL_CONTENT {
+        let mut cut = MAX_TOOL_CONTENT;
+        while !content.is_char_boundary(cut) {
+            cut -= 1;
+        }
+        content.truncate(cut);
+        content.push_str("\n...(truncated)");
+    }
+    content
+}
+
+/// Parse a tool-call `arguments` field, which is a JSON string on the OpenAI
+/// APIs and already an object on others.
+pub(crate) fn parse_arguments(value: Option<&serde_json::Value>) -> serde_json::Value {
+    match value {
+        Some(serde_json::Value::String(s)) => serde_json::from_str(s).unwrap_or(json!({})),
+        Some(v @ serde_json::Value::Object(_)) => v.clone(),
+        _ => json!({}),
+    }
+}
+
+// ── HTTP ──
+
+pub(crate) fn build_client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .timeout(Duration::from_secs(60))
+        .build()
+        .expect("Failed to build HTTP client")
+}
+
+fn is_retryable(status: reqwest::StatusCode) -> bool {
+    status == reqwest::StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+/// POST a JSON body and return the parsed JSON response. Connection errors,
+/// 429s and 5xx responses are retried with exponential backoff (honouring
+/// `Retry-After` when the server sends one).
+pub(crate) async fn post_json(
+    client: &reqwest::Client,
+    url: &str,
+    headers: &[(&str, String)],
+    body: &serde_json::Value,
+) -> Result<serde_json::Value> {
+    post_stream(client, url, headers, body)
+        .await?
+        .json()
+        .await
+        .context("Failed to parse API response")
+}
+
+/// POST a JSON body and return the successful response with its body still
+/// unread, for streaming. Retries like [`post_json`] until headers arrive.
+pub(crate) async fn post_stream(
+    client: &reqwest::Client,
+    url: &str,
+    headers: &[(&str, String)],
+    body: &serde_json::Value,
+) -> Result<reqwest::Response> {
+    let mut attempt = 0;
+    loop {
+        let mut request = client.post(url).json(body);
+        for (name, value) in headers {
+            request = request.header(*name, value);
+        }
+
+        let (delay, failure) = match request.send().await {
+            Ok(response) if response.status().is_success() => return Ok(response),
+            Ok(response) => {
+                let status = response.status();
+                let retry_after = response
+                    .headers()
+                    .get(reqwest::header::RETRY_AFTER)
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(|v| v.trim().parse::<u64>().ok())
+                    .map(|secs| Duration::from_secs(secs.min(30)));
+                let text = response.text().await.unwrap_or_default();
+                error!("API HTTP {}: {} | url={}", status, &text[..text.len().min(500)], url);
+                let failure = anyhow::anyhow!(
+                    "API call failed: HTTP {} — {}",
+                    status,
+                    &text[..text.len().min(200)]
+                );
+                if !is_retryable(status) {
+                    return Err(failure);
+                }
+                (retry_after, failure)
+            }
+            Err(e) => (None, anyhow::Error::new(e).context("HTTP request failed")),
+        };
+
+        if attempt >= MAX_RETRIES {
+            return Err(failure);
+        }
+        let delay = delay.unwrap_or(RETRY_BASE_DELAY * 2u32.pow(attempt));
+        attempt += 1;
+        warn!("{:#}; retrying in {:?} ({}/{})", failure, delay, attempt, MAX_RETRIES);
+        tokio::time::sleep(delay).await;
+    }
+}
+
+/// Call an OpenAI-compatible `/embeddings` endpoint.
+pub(crate) async fn embeddings_request(
+    client: &reqwest::Client,
+    url: &str,
+    api_key: &str,
+    model: &str,
+    texts: &[&str],
+) -> Result<EmbeddingBatch> {
+    let body = json!({
+        "model": model,
+        "input": texts,
+    });
+    let data = post_json(
+        client,
+        url,
+        &[("Authorization", format!("Bearer {}", api_key))],
+        &body,
+    )
+    .await
+    .context("Embedding request failed")?;
+
+    // Each item carries its input's index; they're usually but not
+    // necessarily in order
+    let items = data["data"].as_array().context("Invalid embedding response")?;
+    let mut vectors = vec![Vec::new(); texts.len()];
+    for (i, item) in items.iter().enumerate() {
+        let index = item["index"].as_u64().map_or(i, |index| index as usize);
+        let vector = item["embedding"].as_array().context("Invalid embedding response")?;
+        if let Some(slot) = vectors.get_mut(index) {
+            *slot = vector.iter().filter_map(|v| v.as_f64()).collect();
+        }
+    }
+    if vectors.iter().any(|v| v.is_empty()) {
+        bail!("Embedding response is missing inputs");
+    }
+    Ok(EmbeddingBatch {
+        vectors,
+        usage: completions::parse_completions_usage(&data["usage"]),
+    })
+}
+
+/// Embed with OpenAI using `OPENAI_API_KEY` — for providers without an
+/// embeddings endpoint of their own, or when theirs fails. `strict`
+/// (`strict_embeddings`) forbids it, failing with `cause` instead.
+pub(crate) async fn openai_fallback_embed(
+    client: &reqwest::Client,
+    model: &str,
+    texts: &[&str],
+    strict: bool,
+    cause: anyhow::Error,
+) -> Result<EmbeddingBatch> {
+    if strict {
+        return Err(cause.context("strict_embeddings forbids falling back to OpenAI"));
+    }
+    warn!("{:#}; embedding with OpenAI instead", cause);
+    let key = std::env::var("OPENAI_API_KEY")
+        .context("OPENAI_API_KEY required for embeddings fallback")?;
+    let url = format!("{}/embeddings", OPENAI_BASE_URL);
+    embeddings_request(client, &url, &key, model, texts).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tools_for_completions() {
+        let tools = crate::tools::ToolRegistry::default().definitions();
+        let completions = tools_for_completions(&tools);
+
+        // All should have type=function and a function object
+        for t in &completions {
+            assert_eq!(t["type"].as_str(), Some("function"));
+            assert!(t.get("function").is_some());
+            assert!(t["function"].get("name").is_some());
+        }
+    }
+
+    #[test]
+    fn test_tool_output_text_truncates_on_char_boundary() {
+        let long = "é".repeat(MAX_TOOL_CONTENT);
+        let text = tool_output_text(&json!({"output": long}));
+        assert!(text.ends_with("...(truncated)"));
+        assert!(text.len() <= MAX_TOOL_CONTENT + "\n...(truncated)".len());
+
+        // Strings are passed through unescaped
+        assert_eq!(tool_output_text(&json!({"output": "a\nb"})), "a\nb");
+    }
+
+    #[test]
+    fn test_parse_arguments() {
+        assert_eq!(parse_arguments(Some(&json!("{\"a\": 1}")))["a"], 1);
+        assert_eq!(parse_arguments(Some(&json!({"a": 2})))["a"], 2);
+        assert_eq!(parse_arguments(Some(&json!("not json"))), json!({}));
+        assert_eq!(parse_arguments(None), json!({}));
+    }
+
+    #[test]
+    fn test_registry_builds_known_providers() {
+        let registry = ProviderRegistry::default();
+        assert_eq!(
+            registry.names(),
+            vec!["anthropic", "custom", "ollama", "openai", "openrouter"]
+        );
+
+        for name in ["openai", "anthropic", "openrouter", "ollama", "custom"] {
+            let config = Config {
+                provider: name.to_string(),
+                ..Config::default()
+            };
+            assert_eq!(registry.build(&config).name(), name);
+        }
+    }
+
+    #[test]
+    fn test_registry_unknown_falls_back_to_completions() {
+        let config = Config {
+            provider: "lmstudio".to_string(),
+            base_url: Some("http://localhost:1234/v1".to_string()),
+            ..Config::default()
+        };
+        let provider = ProviderRegistry::default().build(&config);
+        assert_eq!(provider.name(), "lmstudio");
+        assert!(provider.capabilities().tools);
+    }
+
+    #[tokio::test]
+    async fn test_registry_local_and_strict_embeddings() {
+        let config = Config {
+            provider: "anthropic".to_string(),
+            strict_embeddings: true,
+            ..Config::default()
+        };
+        let registry = ProviderRegistry::default();
+        let err = registry.build(&config).embed("coral").await.unwrap_err();
+        assert!(format!("{:#}", err).contains("strict_embeddings"));
+
+        let config = Config {
+            embedding_provider: "local".to_string(),
+            ..config
+        };
+        assert_eq!(config.embedding_model_id(), local::LOCAL_EMBEDDING_MODEL);
+        let provider = registry.build(&config);
+        assert_eq!(provider.name(), "anthropic");
+        assert!(provider.capabilities().embeddings);
+        let embedding = provider.embed("coral").await.unwrap();
+        assert_eq!(embedding.vector.len(), local::LOCAL_EMBEDDING_DIM);
+    }
+
+    #[tokio::test]
+    async fn test_registry_injects_custom_provider() {
+        let mock = Arc::new(MockProvider::new());
+        mock.push_text("hello from the mock");
+
+        let mut registry = ProviderRegistry::default();
+        let injected = Arc::clone(&mock);
+        registry.register("mock", move |_| injected.clone());
+
+        let config = Config {
+            provider: "mock".to_string(),
+            ..Config::default()
+        };
+        let provider = registry.build(&config);
+        let response = provider.chat(&[], &[], None, 100).await.unwrap();
+        assert_eq!(response.text.as_deref(), Some("hello from the mock"));
+        assert_eq!(mock.requests().len(), 1);
+    }
+}
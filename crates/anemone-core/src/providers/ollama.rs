@@ -0,0 +1,443 @@
+//! Ollama native API (`/api/chat`, `/api/embed`). Tool-call arguments come
+//! back as objects, images go in a per-message `images` array of bare base64.
+
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use serde_json::json;
+use tracing::{info, warn};
+
+use super::sse::for_each_line;
+use super::{
+    build_client, openai_fallback_embed, parse_arguments, post_json, post_stream,
+    tool_output_text, tools_for_completions, Capabilities, DeltaCallback, Embedding,
+    EmbeddingBatch, LlmProvider, StreamDelta,
+};
+use crate::config::Config;
+use crate::types::{LlmResponse, TokenUsage, ToolCall};
+
+pub const OLLAMA_BASE_URL: &str = "http://localhost:11434";
+
+pub struct OllamaProvider {
+    model: String,
+    embedding_model: String,
+    strict_embeddings: bool,
+    base_url: String,
+    api_key: Option<String>,
+    client: reqwest::Client,
+}
+
+impl OllamaProvider {
+    pub fn new(config: &Config) -> Self {
+        // Accept the OpenAI-compatible `/v1` URL too — the native API lives at the root
+        let base_url = config.base_url.as_deref().unwrap_or(OLLAMA_BASE_URL);
+        let base_url = base_url.trim_end_matches('/');
+        let base_url = base_url.strip_suffix("/v1").unwrap_or(base_url);
+        Self {
+            model: config.model.clone(),
+            embedding_model: config.embedding_model.clone(),
+            strict_embeddings: config.strict_embeddings,
+            base_url: base_url.to_string(),
+            api_key: config.api_key.clone().filter(|k| !k.is_empty() && k != "ollama"),
+            client: build_client(),
+        }
+    }
+
+    fn headers(&self) -> Vec<(&str, String)> {
+        match &self.api_key {
+            // Only needed for hosted Ollama
+            Some(key) => vec![("Authorization", format!("Bearer {}", key))],
+            None => Vec::new(),
+        }
+    }
+
+    fn request_body(
+        &self,
+        input: &[serde_json::Value],
+        tools: &[serde_json::Value],
+        instructions: Option<&str>,
+        max_tokens: u32,
+        stream: bool,
+    ) -> serde_json::Value {
+        let messages = translate_input_to_ollama(input, instructions);
+
+        info!(
+            "ollama chat request: model={} msg_count={}",
+            self.model,
+            messages.len()
+        );
+
+        let mut body = json!({
+            "model": self.model,
+            "messages": messages,
+            "stream": stream,
+            "options": { "num_predict": max_tokens },
+        });
+
+        let ollama_tools = tools_for_completions(tools);
+        if !ollama_tools.is_empty() {
+            body["tools"] = json!(ollama_tools);
+        }
+        body
+    }
+}
+
+#[async_trait]
+impl LlmProvider for OllamaProvider {
+    fn name(&self) -> &str {
+        "ollama"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            tools: true,
+            vision: true,
+            embeddings: true,
+            streaming: true,
+        }
+    }
+
+    async fn chat(
+        &self,
+        input: &[serde_json::Value],
+        tools: &[serde_json::Value],
+        instructions: Option<&str>,
+        max_tokens: u32,
+    ) -> Result<LlmResponse> {
+        let body = self.request_body(input, tools, instructions, max_tokens, false);
+        let url = format!("{}/api/chat", self.base_url);
+        let data = post_json(&self.client, &url, &self.headers(), &body).await?;
+        Ok(normalize_ollama_response(&data))
+    }
+
+    async fn chat_stream(
+        &self,
+        input: &[serde_json::Value],
+        tools: &[serde_json::Value],
+        instructions: Option<&str>,
+        max_tokens: u32,
+        on_delta: DeltaCallback<'_>,
+    ) -> Result<LlmResponse> {
+        let body = self.request_body(input, tools, instructions, max_tokens, true);
+        let url = format!("{}/api/chat", self.base_url);
+        let mut response = post_stream(&self.client, &url, &self.headers(), &body).await?;
+
+        // Newline-delimited JSON rather than SSE
+        let mut stream = OllamaStream::default();
+        for_each_line(&mut response, |line| {
+            if line.trim().is_empty() {
+                return Ok(());
+            }
+            match serde_json::from_str(line) {
+                Ok(chunk) => stream.handle(&chunk, on_delta),
+                Err(e) => {
+                    warn!("Skipping bad stream line: {}", e);
+                    Ok(())
+                }
+            }
+        })
+        .await?;
+        Ok(stream.finish())
+    }
+
+    async fn embed(&self, text: &str) -> Result<Embedding> {
+        self.embed_batch(&[text]).await?.single()
+    }
+
+    async fn embed_batch(&self, texts: &[&str]) -> Result<EmbeddingBatch> {
+        let url = format!("{}/api/embed", self.base_url);
+        let body = json!({
+            "model": self.embedding_model,
+            "input": texts,
+        });
+        let result = post_json(&self.client, &url, &self.headers(), &body)
+            .await
+            .and_then(|data| {
+                let vectors: Vec<Vec<f64>> = data["embeddings"]
+                    .as_array()
+                    .context("Invalid embedding response")?
+                    .iter()
+                    .map(|v| v.as_array().map(|v| v.iter().filter_map(|x| x.as_f64()).collect()))
+                    .collect::<Option<_>>()
+                    .context("Invalid embedding response")?;
+                if vectors.len() != texts.len() {
+                    bail!("Expected {} embeddings, got {}", texts.len(), vectors.len());
+                }
+                Ok(EmbeddingBatch {
+                    vectors,
+                    usage: parse_ollama_usage(&data),
+                })
+            });
+
+        match result {
+            Ok(batch) => Ok(batch),
+            Err(e) => {
+                let cause = e.context("Ollama embeddings failed");
+                openai_fallback_embed(
+                    &self.client,
+                    &self.embedding_model,
+                    texts,
+                    self.strict_embeddings,
+                    cause,
+                )
+                .await
+            }
+        }
+    }
+}
+
+// ── Input translation ──
+
+/// Flatten Responses-style content parts into Ollama's `content` string plus
+/// `images` list (base64 without the `data:` prefix).
+fn split_multimodal(parts: &[serde_json::Value]) -> (String, Vec<String>) {
+    let mut texts = Vec::new();
+    let mut images = Vec::new();
+    for part in parts {
+        match part.get("type").and_then(|v| v.as_str()) {
+            Some("input_text") | Some("text") => {
+                if let Some(t) = part.get("text").and_then(|v| v.as_str()) {
+                    texts.push(t.to_string());
+                }
+            }
+            Some("input_image") => {
+                if let Some(url) = part.get("image_url").and_then(|v| v.as_str()) {
+                    let b64 = url.split_once(";base64,").map_or(url, |(_, data)| data);
+                    images.push(b64.to_string());
+                }
+            }
+            _ => {}
+        }
+    }
+    (texts.join("\n"), images)
+}
+
+/// Convert Responses API input_list to Ollama chat messages.
+fn translate_input_to_ollama(
+    input_list: &[serde_json::Value],
+    instructions: Option<&str>,
+) -> Vec<serde_json::Value> {
+    let mut messages = Vec::new();
+
+    if let Some(inst) = instructions {
+        messages.push(json!({"role": "system", "content": inst}));
+    }
+
+    for item in input_list {
+        if item.get("type").and_then(|v| v.as_str()) == Some("function_call_output") {
+            let mut tool_msg = json!({
+                "role": "tool",
+                "content": tool_output_text(item),
+            });
+            if let Some(name) = item.get("name") {
+                tool_msg["tool_name"] = name.clone();
+            }
+            messages.push(tool_msg);
+        } else if item.get("role").is_some() {
+            let mut msg = item.clone();
+            if let Some(parts) = item.get("content").and_then(|c| c.as_array()) {
+                let (text, images) = split_multimodal(parts);
+                msg["content"] = json!(text);
+                if !images.is_empty() {
+                    msg["images"] = json!(images);
+                }
+            }
+            messages.push(msg);
+        }
+    }
+
+    messages
+}
+
+// ── Response normalization ──
+
+fn normalize_ollama_response(response: &serde_json::Value) -> LlmResponse {
+    let message = &response["message"];
+    let text = message
+        .get("content")
+        .and_then(|v| v.as_str())
+        .filter(|s| !s.trim().is_empty())
+        .map(String::from);
+
+    let mut tool_calls = Vec::new();
+    let mut output = Vec::new();
+
+    if let Some(tcs) = message.get("tool_calls").and_then(|v| v.as_array()) {
+        for (i, tc) in tcs.iter().enumerate() {
+            let func = &tc["function"];
+            let name = func
+                .get("name")
+                .and_then(|v| v.as_str())
+                .unwrap_or("")
+                .to_string();
+            // Ollama doesn't assign call ids
+            let call_id = tc
+                .get("id")
+                .and_then(|v| v.as_str())
+                .map(String::from)
+                .unwrap_or_else(|| format!("call_{}_{}", name, i));
+            tool_calls.push(ToolCall {
+                name,
+                arguments: parse_arguments(func.get("arguments")),
+                call_id,
+            });
+        }
+
+        // Native assistant message for the follow-up input_list
+        output.push(json!({
+            "role": "assistant",
+            "content": text.clone().unwrap_or_default(),
+            "tool_calls": tcs,
+        }));
+    }
+
+    LlmResponse {
+        text,
+        tool_calls,
+        output,
+        usage: parse_ollama_usage(response),
+    }
+}
+
+/// Ollama reports counts at the top level of the (final) response.
+fn parse_ollama_usage(response: &serde_json::Value) -> Option<TokenUsage> {
+    Some(TokenUsage {
+        prompt_tokens: response.get("prompt_eval_count")?.as_u64()?,
+        completion_tokens: response["eval_count"].as_u64().unwrap_or(0),
+        cached_tokens: 0,
+    })
+}
+
+// ── Streaming ──
+
+/// Accumulates streamed `/api/chat` chunks. Tool calls arrive whole.
+#[derive(Default)]
+struct OllamaStream {
+    content: String,
+    tool_calls: Vec<serde_json::Value>,
+    usage: Option<TokenUsage>,
+}
+
+impl OllamaStream {
+    fn handle(&mut self, chunk: &serde_json::Value, on_delta: DeltaCallback<'_>) -> Result<()> {
+        if let Some(error) = chunk.get("error").and_then(|v| v.as_str()) {
+            anyhow::bail!("Ollama stream error: {}", error);
+        }
+        if let Some(usage) = parse_ollama_usage(chunk) {
+            self.usage = Some(usage);
+        }
+        let message = &chunk["message"];
+        if let Some(text) = message.get("content").and_then(|v| v.as_str()) {
+            if !text.is_empty() {
+                self.content.push_str(text);
+                on_delta(StreamDelta::Text(text.to_string()));
+            }
+        }
+        for tc in message
+            .get("tool_calls")
+            .and_then(|v| v.as_array())
+            .into_iter()
+            .flatten()
+        {
+            on_delta(StreamDelta::ToolArguments {
+                tool: tc["function"]["name"].as_str().unwrap_or("").to_string(),
+                delta: tc["function"]["arguments"].to_string(),
+            });
+            self.tool_calls.push(tc.clone());
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> LlmResponse {
+        let mut message = json!({"role": "assistant", "content": self.content});
+        if !self.tool_calls.is_empty() {
+            message["tool_calls"] = json!(self.tool_calls);
+        }
+        let mut response = normalize_ollama_response(&json!({"message": message}));
+        response.usage = self.usage;
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base_url_strips_v1() {
+        let config = Config {
+            provider: "ollama".to_string(),
+            base_url: Some("http://localhost:11434/v1/".to_string()),
+            ..Config::default()
+        };
+        assert_eq!(OllamaProvider::new(&config).base_url, "http://localhost:11434");
+    }
+
+    #[test]
+    fn test_translate_input_to_ollama() {
+        let input = vec![
+            json!({
+                "role": "user",
+                "content": [
+                    {"type": "input_text", "text": "what is this?"},
+                    {"type": "input_image", "image_url": "data:image/png;base64,iVBORw0"}
+                ]
+            }),
+            json!({"type": "function_call_output", "call_id": "call_shell_0", "name": "shell", "output": "ok"}),
+        ];
+
+        let messages = translate_input_to_ollama(&input, Some("sys"));
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[1]["content"], "what is this?");
+        assert_eq!(messages[1]["images"][0], "iVBORw0");
+        assert_eq!(messages[2]["role"], "tool");
+        assert_eq!(messages[2]["tool_name"], "shell");
+    }
+
+    #[test]
+    fn test_normalize_ollama_response_with_tools() {
+        let response = json!({
+            "message": {
+                "role": "assistant",
+                "content": "",
+                "tool_calls": [{
+                    "function": {
+                        "name": "move",
+                        "arguments": {"location": "bed"}
+                    }
+                }]
+            },
+            "done": true
+        });
+
+        let result = normalize_ollama_response(&response);
+        assert!(result.text.is_none());
+        assert_eq!(result.tool_calls.len(), 1);
+        assert_eq!(result.tool_calls[0].call_id, "call_move_0");
+        assert_eq!(result.tool_calls[0].arguments["location"], "bed");
+        assert_eq!(result.output[0]["role"], "assistant");
+    }
+
+    #[test]
+    fn test_stream_accumulates_chunks() {
+        let chunks = [
+            json!({"message": {"role": "assistant", "content": "Time "}, "done": false}),
+            json!({"message": {"role": "assistant", "content": "to rest."}, "done": false}),
+            json!({"message": {"role": "assistant", "content": "", "tool_calls": [
+                {"function": {"name": "move", "arguments": {"location": "bed"}}}
+            ]}, "done": false}),
+            json!({"message": {"role": "assistant", "content": ""}, "done": true}),
+        ];
+
+        let deltas = std::sync::Mutex::new(Vec::new());
+        let on_delta = |d: StreamDelta| deltas.lock().unwrap().push(d);
+        let mut stream = OllamaStream::default();
+        for chunk in &chunks {
+            stream.handle(chunk, &on_delta).unwrap();
+        }
+        let result = stream.finish();
+
+        assert_eq!(result.text.as_deref(), Some("Time to rest."));
+        assert_eq!(result.tool_calls[0].arguments["location"], "bed");
+        assert_eq!(deltas.into_inner().unwrap().len(), 3);
+    }
+}
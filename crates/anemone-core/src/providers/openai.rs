@@ -0,0 +1,356 @@
+//! OpenAI Responses API provider. The brain's input list is already in the
+//! Responses shape, so it is sent as-is.
+
+use std::collections::HashMap;
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde_json::json;
+use tracing::warn;
+
+use super::sse::for_each_sse;
+use super::{
+    build_client, embeddings_request, parse_arguments, post_json, post_stream, Capabilities,
+    DeltaCallback, Embedding, EmbeddingBatch, LlmProvider, StreamDelta, OPENAI_BASE_URL,
+};
+use crate::config::Config;
+use crate::types::{LlmResponse, TokenUsage, ToolCall};
+
+pub struct OpenAiProvider {
+    model: String,
+    embedding_model: String,
+    base_url: String,
+    api_key: Option<String>,
+    client: reqwest::Client,
+}
+
+impl OpenAiProvider {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            model: config.model.clone(),
+            embedding_model: config.embedding_model.clone(),
+            base_url: config
+                .base_url
+                .as_deref()
+                .unwrap_or(OPENAI_BASE_URL)
+                .trim_end_matches('/')
+                .to_string(),
+            api_key: config.api_key.clone(),
+            client: build_client(),
+        }
+    }
+
+    fn api_key(&self) -> Result<&str> {
+        self.api_key
+            .as_deref()
+            .context("API key required for OpenAI")
+    }
+
+    fn request_body(
+        &self,
+        input: &[serde_json::Value],
+        tools: &[serde_json::Value],
+        instructions: Option<&str>,
+        max_tokens: u32,
+    ) -> serde_json::Value {
+        let mut body = json!({
+            "model": self.model,
+            "input": input,
+            "max_output_tokens": max_tokens,
+        });
+
+        if let Some(inst) = instructions {
+            body["instructions"] = json!(inst);
+        }
+
+        if !tools.is_empty() {
+            body["tools"] = json!(tools);
+        }
+        body
+    }
+}
+
+#[async_trait]
+impl LlmProvider for OpenAiProvider {
+    fn name(&self) -> &str {
+        "openai"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            tools: true,
+            vision: true,
+            embeddings: true,
+            streaming: true,
+        }
+    }
+
+    async fn chat(
+        &self,
+        input: &[serde_json::Value],
+        tools: &[serde_json::Value],
+        instructions: Option<&str>,
+        max_tokens: u32,
+    ) -> Result<LlmResponse> {
+        let api_key = self.api_key()?;
+        let body = self.request_body(input, tools, instructions, max_tokens);
+
+        let url = format!("{}/responses", self.base_url);
+        let data = post_json(
+            &self.client,
+            &url,
+            &[("Authorization", format!("Bearer {}", api_key))],
+            &body,
+        )
+        .await?;
+
+        Ok(parse_responses_output(&data))
+    }
+
+    async fn chat_stream(
+        &self,
+        input: &[serde_json::Value],
+        tools: &[serde_json::Value],
+        instructions: Option<&str>,
+        max_tokens: u32,
+        on_delta: DeltaCallback<'_>,
+    ) -> Result<LlmResponse> {
+        let api_key = self.api_key()?;
+        let mut body = self.request_body(input, tools, instructions, max_tokens);
+        body["stream"] = json!(true);
+
+        let url = format!("{}/responses", self.base_url);
+        let mut response = post_stream(
+            &self.client,
+            &url,
+            &[("Authorization", format!("Bearer {}", api_key))],
+            &body,
+        )
+        .await?;
+
+        let mut stream = ResponsesStream::default();
+        for_each_sse(&mut response, |event| match serde_json::from_str(&event.data) {
+            Ok(data) => stream.handle(&data, on_delta),
+            Err(e) => {
+                warn!("Skipping bad stream event: {}", e);
+                Ok(())
+            }
+        })
+        .await?;
+        stream.finish()
+    }
+
+    async fn embed(&self, text: &str) -> Result<Embedding> {
+        self.embed_batch(&[text]).await?.single()
+    }
+
+    async fn embed_batch(&self, texts: &[&str]) -> Result<EmbeddingBatch> {
+        let api_key = self
+            .api_key
+            .as_deref()
+            .context("API key required for embeddings")?;
+        let url = format!("{}/embeddings", self.base_url);
+        embeddings_request(&self.client, &url, api_key, &self.embedding_model, texts).await
+    }
+}
+
+/// Parse Responses API output items into our standard format.
+fn parse_responses_output(data: &serde_json::Value) -> LlmResponse {
+    let output = data
+        .get("output")
+        .and_then(|v| v.as_array())
+        .cloned()
+        .unwrap_or_default();
+
+    let mut text_parts = Vec::new();
+    let mut tool_calls = Vec::new();
+
+    for item in &output {
+        let item_type = item.get("type").and_then(|v| v.as_str()).unwrap_or("");
+        match item_type {
+            "message" => {
+                if let Some(content) = item.get("content").and_then(|v| v.as_array()) {
+                    for c in content {
+                        if let Some(text) = c.get("text").and_then(|v| v.as_str()) {
+                            text_parts.push(text.to_string());
+                        }
+                    }
+                }
+            }
+            "function_call" => {
+                let name = item
+                    .get("name")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("")
+                    .to_string();
+                let call_id = item
+                    .get("call_id")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("")
+                    .to_string();
+
+                tool_calls.push(ToolCall {
+                    name,
+                    arguments: parse_arguments(item.get("arguments")),
+                    call_id,
+                });
+            }
+            _ => {}
+        }
+    }
+
+    let text = if text_parts.is_empty() {
+        None
+    } else {
+        Some(text_parts.join("\n"))
+    };
+
+    LlmResponse {
+        text,
+        tool_calls,
+        output,
+        usage: parse_responses_usage(&data["usage"]),
+    }
+}
+
+/// Responses API usage: `input_tokens` already include the cached ones.
+fn parse_responses_usage(usage: &serde_json::Value) -> Option<TokenUsage> {
+    Some(TokenUsage {
+        prompt_tokens: usage.get("input_tokens")?.as_u64()?,
+        completion_tokens: usage["output_tokens"].as_u64().unwrap_or(0),
+        cached_tokens: usage["input_tokens_details"]["cached_tokens"]
+            .as_u64()
+            .unwrap_or(0),
+    })
+}
+
+// ── Streaming ──
+
+/// Tracks a Responses API event stream. Deltas are forwarded as they come;
+/// the full response arrives with `response.completed`.
+#[derive(Default)]
+struct ResponsesStream {
+    tool_names: HashMap<u64, String>,
+    completed: Option<serde_json::Value>,
+}
+
+impl ResponsesStream {
+    fn handle(&mut self, event: &serde_json::Value, on_delta: DeltaCallback<'_>) -> Result<()> {
+        let output_index = event.get("output_index").and_then(|v| v.as_u64()).unwrap_or(0);
+        match event.get("type").and_then(|v| v.as_str()).unwrap_or("") {
+            "response.output_text.delta" => {
+                if let Some(delta) = event.get("delta").and_then(|v| v.as_str()) {
+                    on_delta(StreamDelta::Text(delta.to_string()));
+                }
+            }
+            "response.output_item.added" => {
+                if let Some(name) = event["item"].get("name").and_then(|v| v.as_str()) {
+                    self.tool_names.insert(output_index, name.to_string());
+                }
+            }
+            "response.function_call_arguments.delta" => {
+                if let Some(delta) = event.get("delta").and_then(|v| v.as_str()) {
+                    on_delta(StreamDelta::ToolArguments {
+                        tool: self.tool_names.get(&output_index).cloned().unwrap_or_default(),
+                        delta: delta.to_string(),
+                    });
+                }
+            }
+            // Incomplete (e.g. hit max_output_tokens) still carries usable output
+            "response.completed" | "response.incomplete" => {
+                self.completed = Some(event["response"].clone());
+            }
+            "response.failed" => {
+                anyhow::bail!("OpenAI response failed: {}", event["response"]["error"]);
+            }
+            "error" => {
+                anyhow::bail!(
+                    "OpenAI stream error: {}",
+                    event.get("message").unwrap_or(event)
+                );
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<LlmResponse> {
+        let response = self
+            .completed
+            .context("Stream ended before the response completed")?;
+        Ok(parse_responses_output(&response))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_responses_output() {
+        let data = json!({
+            "output": [
+                {
+                    "type": "message",
+                    "content": [{"type": "output_text", "text": "Thinking about coral."}]
+                },
+                {
+                    "type": "function_call",
+                    "name": "move",
+                    "call_id": "fc_1",
+                    "arguments": "{\"location\": \"window\"}"
+                }
+            ]
+        });
+
+        let result = parse_responses_output(&data);
+        assert_eq!(result.text.as_deref(), Some("Thinking about coral."));
+        assert_eq!(result.tool_calls.len(), 1);
+        assert_eq!(result.tool_calls[0].call_id, "fc_1");
+        assert_eq!(result.tool_calls[0].arguments["location"], "window");
+        // Output items are kept verbatim for the follow-up call
+        assert_eq!(result.output.len(), 2);
+    }
+
+    #[test]
+    fn test_stream_forwards_deltas_and_uses_completed_response() {
+        let events = [
+            json!({"type": "response.output_text.delta", "output_index": 0, "delta": "Hmm"}),
+            json!({"type": "response.output_item.added", "output_index": 1,
+                   "item": {"type": "function_call", "name": "shell"}}),
+            json!({"type": "response.function_call_arguments.delta", "output_index": 1,
+                   "delta": "{\"command\""}),
+            json!({"type": "response.completed", "response": {"output": [
+                {"type": "message", "content": [{"type": "output_text", "text": "Hmm"}]},
+                {"type": "function_call", "name": "shell", "call_id": "fc_2",
+                 "arguments": "{\"command\": \"pwd\"}"}
+            ]}}),
+        ];
+
+        let deltas = std::sync::Mutex::new(Vec::new());
+        let on_delta = |d: StreamDelta| deltas.lock().unwrap().push(d);
+        let mut stream = ResponsesStream::default();
+        for event in &events {
+            stream.handle(event, &on_delta).unwrap();
+        }
+        let result = stream.finish().unwrap();
+
+        assert_eq!(result.text.as_deref(), Some("Hmm"));
+        assert_eq!(result.tool_calls[0].arguments["command"], "pwd");
+        let deltas = deltas.into_inner().unwrap();
+        assert_eq!(deltas.len(), 2);
+        assert!(matches!(&deltas[1], StreamDelta::ToolArguments { tool, .. } if tool == "shell"));
+    }
+
+    #[test]
+    fn test_stream_without_completion_is_an_error() {
+        let mut stream = ResponsesStream::default();
+        stream
+            .handle(
+                &json!({"type": "response.output_text.delta", "delta": "cut off"}),
+                &|_| {},
+            )
+            .unwrap();
+        assert!(stream.finish().is_err());
+    }
+}
@@ -0,0 +1,86 @@
+//! OpenRouter — Chat Completions with OpenRouter's base URL and attribution
+//! header. OpenRouter has no embeddings endpoint, so embeddings go to OpenAI.
+
+use anyhow::Result;
+use async_trait::async_trait;
+
+use super::{
+    build_client, openai_fallback_embed, Capabilities, ChatCompletionsProvider, DeltaCallback,
+    Embedding, EmbeddingBatch, LlmProvider,
+};
+use crate::config::Config;
+use crate::types::LlmResponse;
+
+pub const OPENROUTER_BASE_URL: &str = "https://openrouter.ai/api/v1";
+
+pub struct OpenRouterProvider {
+    inner: ChatCompletionsProvider,
+    embedding_model: String,
+    strict_embeddings: bool,
+    client: reqwest::Client,
+}
+
+impl OpenRouterProvider {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            inner: ChatCompletionsProvider::with_base_url(config, OPENROUTER_BASE_URL)
+                .with_header("X-Title", "Anemone"),
+            embedding_model: config.embedding_model.clone(),
+            strict_embeddings: config.strict_embeddings,
+            client: build_client(),
+        }
+    }
+}
+
+#[async_trait]
+impl LlmProvider for OpenRouterProvider {
+    fn name(&self) -> &str {
+        "openrouter"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            embeddings: false,
+            ..self.inner.capabilities()
+        }
+    }
+
+    async fn chat(
+        &self,
+        input: &[serde_json::Value],
+        tools: &[serde_json::Value],
+        instructions: Option<&str>,
+        max_tokens: u32,
+    ) -> Result<LlmResponse> {
+        self.inner.chat(input, tools, instructions, max_tokens).await
+    }
+
+    async fn chat_stream(
+        &self,
+        input: &[serde_json::Value],
+        tools: &[serde_json::Value],
+        instructions: Option<&str>,
+        max_tokens: u32,
+        on_delta: DeltaCallback<'_>,
+    ) -> Result<LlmResponse> {
+        self.inner
+            .chat_stream(input, tools, instructions, max_tokens, on_delta)
+            .await
+    }
+
+    async fn embed(&self, text: &str) -> Result<Embedding> {
+        self.embed_batch(&[text]).await?.single()
+    }
+
+    async fn embed_batch(&self, texts: &[&str]) -> Result<EmbeddingBatch> {
+        let cause = anyhow::anyhow!("OpenRouter has no embeddings endpoint");
+        openai_fallback_embed(
+            &self.client,
+            &self.embedding_model,
+            texts,
+            self.strict_embeddings,
+            cause,
+        )
+        .await
+    }
+}
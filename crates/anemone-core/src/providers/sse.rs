@@ -0,0 +1,178 @@
+//! Incremental parsing of streamed response bodies — newline-delimited JSON
+//! (Ollama) and Server-Sent Events (everyone else).
+
+use anyhow::{Context, Result};
+
+/// Splits a byte stream into complete lines, buffering partial lines (and
+/// partial UTF-8 sequences) across chunks.
+#[derive(Default)]
+pub(crate) struct LineBuffer {
+    pending: Vec<u8>,
+}
+
+impl LineBuffer {
+    /// Add a chunk and return every line it completed, without line endings.
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<String> {
+        self.pending.extend_from_slice(chunk);
+        let mut lines = Vec::new();
+        while let Some(pos) = self.pending.iter().position(|&b| b == b'\n') {
+            let mut line: Vec<u8> = self.pending.drain(..=pos).collect();
+            line.pop();
+            if line.last() == Some(&b'\r') {
+                line.pop();
+            }
+            lines.push(String::from_utf8_lossy(&line).into_owned());
+        }
+        lines
+    }
+
+    /// The trailing line, if the stream did not end with a newline.
+    pub fn finish(&mut self) -> Option<String> {
+        if self.pending.is_empty() {
+            return None;
+        }
+        let line = String::from_utf8_lossy(&self.pending).into_owned();
+        self.pending.clear();
+        Some(line)
+    }
+}
+
+/// One Server-Sent Event.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct SseEvent {
+    /// The `event:` field, if any.
+    pub event: Option<String>,
+    /// All `data:` lines, joined with `\n`.
+    pub data: String,
+}
+
+/// Assembles SSE events from lines.
+#[derive(Default)]
+pub(crate) struct SseParser {
+    event: Option<String>,
+    data: Vec<String>,
+}
+
+impl SseParser {
+    /// Feed one line. Returns an event when a blank line completes it.
+    pub fn line(&mut self, line: &str) -> Option<SseEvent> {
+        if line.is_empty() {
+            return self.dispatch();
+        }
+        if line.starts_with(':') {
+            return None; // comment / keep-alive
+        }
+        let (field, value) = line.split_once(':').unwrap_or((line, ""));
+        let value = value.strip_prefix(' ').unwrap_or(value);
+        match field {
+            "event" => self.event = Some(value.to_string()),
+            "data" => self.data.push(value.to_string()),
+            _ => {}
+        }
+        None
+    }
+
+    /// Flush an event left open when the stream ended.
+    pub fn finish(&mut self) -> Option<SseEvent> {
+        self.dispatch()
+    }
+
+    fn dispatch(&mut self) -> Option<SseEvent> {
+        if self.data.is_empty() {
+            self.event = None;
+            return None;
+        }
+        Some(SseEvent {
+            event: self.event.take(),
+            data: std::mem::take(&mut self.data).join("\n"),
+        })
+    }
+}
+
+/// Read a streaming response body line by line.
+pub(crate) async fn for_each_line(
+    response: &mut reqwest::Response,
+    mut on_line: impl FnMut(&str) -> Result<()>,
+) -> Result<()> {
+    let mut buffer = LineBuffer::default();
+    while let Some(chunk) = response.chunk().await.context("Stream read failed")? {
+        for line in buffer.push(&chunk) {
+            on_line(&line)?;
+        }
+    }
+    if let Some(line) = buffer.finish() {
+        on_line(&line)?;
+    }
+    Ok(())
+}
+
+/// Read a streaming response body as Server-Sent Events.
+pub(crate) async fn for_each_sse(
+    response: &mut reqwest::Response,
+    mut on_event: impl FnMut(SseEvent) -> Result<()>,
+) -> Result<()> {
+    let mut parser = SseParser::default();
+    for_each_line(response, |line| match parser.line(line) {
+        Some(event) => on_event(event),
+        None => Ok(()),
+    })
+    .await?;
+    if let Some(event) = parser.finish() {
+        on_event(event)?;
+    }
+    Ok(())
+}
+
+/// Run a canned SSE transcript through a parser — for provider tests.
+#[cfg(test)]
+pub(crate) fn parse_transcript(transcript: &str) -> Vec<SseEvent> {
+    let mut buffer = LineBuffer::default();
+    let mut parser = SseParser::default();
+    let mut lines = buffer.push(transcript.as_bytes());
+    lines.extend(buffer.finish());
+    let mut events: Vec<SseEvent> = lines.iter().filter_map(|l| parser.line(l)).collect();
+    events.extend(parser.finish());
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_buffer_across_chunks() {
+        let mut buffer = LineBuffer::default();
+        assert!(buffer.push(b"{\"a\":").is_empty());
+        assert_eq!(buffer.push(b"1}\r\n{\"b\""), vec!["{\"a\":1}"]);
+        assert_eq!(buffer.push(b":2}\n"), vec!["{\"b\":2}"]);
+        assert!(buffer.finish().is_none());
+    }
+
+    #[test]
+    fn test_line_buffer_split_utf8() {
+        let bytes = "é\n".as_bytes();
+        let mut buffer = LineBuffer::default();
+        assert!(buffer.push(&bytes[..1]).is_empty());
+        assert_eq!(buffer.push(&bytes[1..]), vec!["é"]);
+    }
+
+    #[test]
+    fn test_sse_parser() {
+        let events = parse_transcript(
+            ": keep-alive\n\
+             event: content_block_delta\n\
+             data: {\"x\":1}\n\
+             \n\
+             data: line one\n\
+             data: line two\n\
+             \n\
+             data: [DONE]",
+        );
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[0].event.as_deref(), Some("content_block_delta"));
+        assert_eq!(events[0].data, "{\"x\":1}");
+        assert_eq!(events[1].event, None);
+        assert_eq!(events[1].data, "line one\nline two");
+        assert_eq!(events[2].data, "[DONE]");
+    }
+}
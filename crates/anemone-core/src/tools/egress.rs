@@ -0,0 +1,474 @@
+//! Egress proxy — the one way out for shell commands when
+//! `shell_network.mode` is `proxy` (or `auto`, where the host allows it).
+//!
+//! Commands then run in a network namespace with only loopback (see
+//! [`Sandbox::with_proxy`](super::sandbox::Sandbox::with_proxy)). The
+//! sandbox listens on `127.0.0.1:`[`PROXY_PORT`] in there and hands the
+//! socket back; the anemone accepts on it here and forwards HTTP proxy
+//! requests (`CONNECT host:port`, or `GET http://host/...`) to allowed
+//! domains only. Commands find it through the usual `HTTPS_PROXY`
+//! variables; whatever ignores them has no route out at all. Every
+//! decision is logged.
+
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::os::unix::net::UnixStream;
+use std::process::{Command, Stdio};
+use std::sync::{Arc, OnceLock};
+
+use anyhow::{bail, Result};
+use tokio::io::unix::AsyncFd;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::{JoinHandle, JoinSet};
+use tracing::{info, warn};
+
+use super::sandbox::{Sandbox, SandboxMode, PROXY_PORT};
+
+/// Longest request head the proxy reads.
+const MAX_HEAD: usize = 16 * 1024;
+
+/// How shell commands reach the network.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EgressMode {
+    /// The host's network, unfiltered
+    Open,
+    /// Only allowed domains, through the proxy
+    Proxy,
+}
+
+impl EgressMode {
+    /// The mode named by `shell_network.mode`; `auto` is `proxy` where
+    /// this host can cut commands off, `open` elsewhere. Unknown names are
+    /// an error rather than an open network.
+    pub fn parse(name: &str) -> Result<Self> {
+        match name.trim() {
+            "auto" | "" if available() => Ok(Self::Proxy),
+            "auto" | "" | "open" => Ok(Self::Open),
+            "proxy" => Ok(Self::Proxy),
+            other => bail!("Unknown shell network mode '{}' (expected auto, proxy or open)", other),
+        }
+    }
+}
+
+/// Whether commands can be given a network of their own here. Checked
+/// once, by running `true` that way.
+pub fn available() -> bool {
+    static AVAILABLE: OnceLock<bool> = OnceLock::new();
+    *AVAILABLE.get_or_init(|| match probe() {
+        Ok(()) => true,
+        Err(e) => {
+            warn!("Shell commands keep the host's network: they can't have one of their own here ({})", e);
+            false
+        }
+    })
+}
+
+fn probe() -> Result<()> {
+    let (_ours, theirs) = UnixStream::pair()?;
+    let sandbox = Sandbox::new(SandboxMode::None, &std::env::temp_dir()).with_proxy(theirs.into());
+    let mut command = Command::new("true");
+    command.stdin(Stdio::null()).stdout(Stdio::null()).stderr(Stdio::null());
+    sandbox.apply(&mut command)?;
+    let status = command.status()?;
+    if !status.success() {
+        bail!("'true' exited with {}", status);
+    }
+    Ok(())
+}
+
+/// The proxy as commands see it, for `HTTPS_PROXY` and friends.
+pub fn proxy_url() -> String {
+    format!("http://127.0.0.1:{}", PROXY_PORT)
+}
+
+/// The proxy for one command. It stops, dropping its connections, when
+/// dropped.
+pub struct Proxy {
+    task: JoinHandle<()>,
+}
+
+impl Proxy {
+    /// Serve the listener the sandbox sends over `channel`, letting `label`
+    /// (e.g. "pip") reach only the domains in `allow`.
+    pub fn start(channel: UnixStream, allow: Vec<String>, label: &'static str) -> Self {
+        let task = tokio::spawn(async move {
+            match receive_listener(channel).await {
+                Ok(Some(listener)) => serve(listener, Arc::new(allow), label).await,
+                // The command failed before its network was set up
+                Ok(None) => {}
+                Err(e) => warn!("Egress proxy got no listener from the sandbox: {}", e),
+            }
+        });
+        Self { task }
+    }
+}
+
+impl Drop for Proxy {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn serve(listener: TcpListener, allow: Arc<Vec<String>>, label: &'static str) {
+    let mut connections = JoinSet::new();
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok((client, _)) => {
+                    connections.spawn(handle(client, allow.clone(), label));
+                }
+                Err(e) => {
+                    warn!("Egress proxy stopped accepting: {}", e);
+                    return;
+                }
+            },
+            Some(_) = connections.join_next(), if !connections.is_empty() => {}
+        }
+    }
+}
+
+async fn handle(mut client: TcpStream, allow: Arc<Vec<String>>, label: &'static str) {
+    let (buffer, head_len) = match read_head(&mut client).await {
+        Ok(read) => read,
+        Err(e) => {
+            warn!("Egress proxy dropped a bad request from {}: {}", label, e);
+            return;
+        }
+    };
+    let head = String::from_utf8_lossy(&buffer[..head_len]);
+    let Some(target) = parse_request(&head) else {
+        warn!("Egress proxy refused a non-proxy request from {}", label);
+        let _ = respond(&mut client, "400 Bad Request", "The proxy takes CONNECT and http:// requests only.").await;
+        return;
+    };
+    if !allowed(&allow, &target.host) {
+        warn!("Egress proxy blocked {} from {}:{}", label, target.host, target.port);
+        let message = format!("Blocked: {} may not reach {}. Use the web tools for research.", label, target.host);
+        let _ = respond(&mut client, "403 Forbidden", &message).await;
+        return;
+    }
+    let mut upstream = match connect(&target.host, target.port).await {
+        Ok(upstream) => upstream,
+        Err(e) => {
+            warn!("Egress proxy couldn't reach {}:{} for {}: {}", target.host, target.port, label, e);
+            let _ = respond(&mut client, "502 Bad Gateway", &e.to_string()).await;
+            return;
+        }
+    };
+    info!("Egress proxy let {} through to {}:{}", label, target.host, target.port);
+
+    let sent = if target.tunnel {
+        // Anything after the CONNECT head is already the tunnel's
+        match client.write_all(b"HTTP/1.1 200 Connection established\r\n\r\n").await {
+            Ok(()) => upstream.write_all(&buffer[head_len..]).await,
+            Err(e) => Err(e),
+        }
+    } else {
+        upstream.write_all(&buffer).await
+    };
+    if sent.is_ok() {
+        let _ = tokio::io::copy_bidirectional(&mut client, &mut upstream).await;
+    }
+}
+
+/// Read up to the blank line ending the request head. Returns what was
+/// read and where the head ends.
+async fn read_head(client: &mut TcpStream) -> Result<(Vec<u8>, usize)> {
+    let mut buffer = Vec::new();
+    let mut chunk = [0u8; 4096];
+    loop {
+        let read = client.read(&mut chunk).await?;
+        if read == 0 {
+            bail!("connection closed mid-request");
+        }
+        buffer.extend_from_slice(&chunk[..read]);
+        if let Some(end) = buffer.windows(4).position(|w| w == b"\r\n\r\n") {
+            return Ok((buffer, end + 4));
+        }
+        if buffer.len() > MAX_HEAD {
+            bail!("request head over {} bytes", MAX_HEAD);
+        }
+    }
+}
+
+async fn respond(client: &mut TcpStream, status: &str, message: &str) -> io::Result<()> {
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}\n",
+        status,
+        message.len() + 1,
+        message
+    );
+    client.write_all(response.as_bytes()).await
+}
+
+// ── Requests and destinations ──
+
+/// Where a proxy request is headed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Target {
+    host: String,
+    port: u16,
+    /// `CONNECT`: relay bytes both ways once connected
+    tunnel: bool,
+}
+
+/// The destination of `CONNECT host:port` or an absolute `http://` request.
+fn parse_request(head: &str) -> Option<Target> {
+    let mut parts = head.lines().next()?.split(' ');
+    let (method, target) = (parts.next()?, parts.next()?);
+    if method.eq_ignore_ascii_case("CONNECT") {
+        let (host, port) = split_authority(target, None)?;
+        return Some(Target { host, port, tunnel: true });
+    }
+    let rest = target.get(..7).filter(|s| s.eq_ignore_ascii_case("http://")).map(|_| &target[7..])?;
+    let authority = rest.split(['/', '?', '#']).next()?;
+    let (host, port) = split_authority(authority, Some(80))?;
+    Some(Target { host, port, tunnel: false })
+}
+
+fn split_authority(authority: &str, default_port: Option<u16>) -> Option<(String, u16)> {
+    if authority.contains('@') {
+        return None;
+    }
+    let (host, port) = match authority.strip_prefix('[') {
+        Some(rest) => {
+            let (host, after) = rest.split_once(']')?;
+            (host, after.strip_prefix(':'))
+        }
+        None => match authority.rsplit_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (authority, None),
+        },
+    };
+    let port = match port {
+        Some(port) => port.parse().ok()?,
+        None => default_port?,
+    };
+    let host = normalize(host);
+    (!host.is_empty()).then_some((host, port))
+}
+
+fn normalize(host: &str) -> String {
+    host.trim_end_matches('.').to_ascii_lowercase()
+}
+
+/// Whether `host` is on `allow`: exact names (or addresses), and
+/// `*.domain` for anything beneath a domain.
+pub fn allowed(allow: &[String], host: &str) -> bool {
+    let host = normalize(host);
+    allow.iter().any(|pattern| {
+        let pattern = normalize(pattern);
+        match pattern.strip_prefix("*.") {
+            Some(domain) => host.ends_with(&format!(".{}", domain)),
+            None => host == pattern,
+        }
+    })
+}
+
+/// Connect to `host`. A name has to resolve to public addresses, so DNS
+/// can't point the proxy back at this machine or its network; an address
+/// that was allowed as written is taken as is.
+async fn connect(host: &str, port: u16) -> Result<TcpStream> {
+    let addresses: Vec<SocketAddr> = match host.parse::<IpAddr>() {
+        Ok(ip) => vec![SocketAddr::new(ip, port)],
+        Err(_) => tokio::net::lookup_host((host, port))
+            .await?
+            .filter(|address| is_public(address.ip()))
+            .collect(),
+    };
+    if addresses.is_empty() {
+        bail!("{} has no public address", host);
+    }
+    Ok(TcpStream::connect(&addresses[..]).await?)
+}
+
+/// Whether `ip` is out on the internet rather than on this machine or a
+/// private network.
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => {
+            let [a, b, ..] = v4.octets();
+            !(v4.is_loopback()
+                || v4.is_private()
+                || v4.is_link_local()
+                || v4.is_unspecified()
+                || v4.is_broadcast()
+                || v4.is_multicast()
+                // Carrier-grade NAT, 100.64.0.0/10
+                || (a == 100 && b & 0xc0 == 64))
+        }
+        IpAddr::V6(v6) => {
+            if let Some(v4) = v6.to_ipv4_mapped() {
+                return is_public(IpAddr::V4(v4));
+            }
+            let first = v6.segments()[0];
+            !(v6.is_loopback()
+                || v6.is_unspecified()
+                || v6.is_multicast()
+                // Unique local fc00::/7 and link-local fe80::/10
+                || first & 0xfe00 == 0xfc00
+                || first & 0xffc0 == 0xfe80)
+        }
+    }
+}
+
+// ── The listener ──
+
+/// Wait for the sandbox to send the listening socket; `None` if the
+/// command ended without sending one.
+async fn receive_listener(channel: UnixStream) -> io::Result<Option<TcpListener>> {
+    channel.set_nonblocking(true)?;
+    let channel = AsyncFd::new(channel)?;
+    loop {
+        let mut ready = channel.readable().await?;
+        match ready.try_io(|channel| receive_fd(channel.get_ref().as_raw_fd())) {
+            Ok(Ok(Some(fd))) => {
+                let listener = std::net::TcpListener::from(fd);
+                listener.set_nonblocking(true)?;
+                return TcpListener::from_std(listener).map(Some);
+            }
+            Ok(Ok(None)) => return Ok(None),
+            Ok(Err(e)) => return Err(e),
+            Err(_would_block) => continue,
+        }
+    }
+}
+
+/// Receive one file descriptor over the Unix socket `channel`.
+fn receive_fd(channel: RawFd) -> io::Result<Option<OwnedFd>> {
+    let mut byte = [0u8; 1];
+    let mut iov = libc::iovec {
+        iov_base: byte.as_mut_ptr() as *mut libc::c_void,
+        iov_len: 1,
+    };
+    let mut control = [0u64; 4];
+    let mut message: libc::msghdr = unsafe { std::mem::zeroed() };
+    message.msg_iov = &mut iov;
+    message.msg_iovlen = 1;
+    message.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+    message.msg_controllen = std::mem::size_of_val(&control) as _;
+    let received = unsafe { libc::recvmsg(channel, &mut message, libc::MSG_CMSG_CLOEXEC) };
+    if received < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    if received == 0 {
+        return Ok(None);
+    }
+    unsafe {
+        let header = libc::CMSG_FIRSTHDR(&message);
+        if header.is_null() || (*header).cmsg_level != libc::SOL_SOCKET || (*header).cmsg_type != libc::SCM_RIGHTS {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "no socket in the sandbox's message"));
+        }
+        let fd = std::ptr::read_unaligned(libc::CMSG_DATA(header) as *const libc::c_int);
+        Ok(Some(OwnedFd::from_raw_fd(fd)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_modes() {
+        assert_eq!(EgressMode::parse("proxy").unwrap(), EgressMode::Proxy);
+        assert_eq!(EgressMode::parse("open").unwrap(), EgressMode::Open);
+        let auto = if available() { EgressMode::Proxy } else { EgressMode::Open };
+        assert_eq!(EgressMode::parse("auto").unwrap(), auto);
+        assert!(EgressMode::parse("closed").is_err());
+    }
+
+    #[test]
+    fn test_requests_are_parsed() {
+        let target = |host: &str, port, tunnel| Some(Target { host: host.to_string(), port, tunnel });
+        assert_eq!(parse_request("CONNECT pypi.org:443 HTTP/1.1\r\nHost: pypi.org\r\n"), target("pypi.org", 443, true));
+        assert_eq!(parse_request("GET http://Example.COM./a?b HTTP/1.1\r\n"), target("example.com", 80, false));
+        assert_eq!(parse_request("GET http://example.com:8080 HTTP/1.1\r\n"), target("example.com", 8080, false));
+        assert_eq!(parse_request("CONNECT [2606:4700::1]:443 HTTP/1.1\r\n"), target("2606:4700::1", 443, true));
+        assert_eq!(parse_request("GET /index.html HTTP/1.1\r\n"), None);
+        assert_eq!(parse_request("GET https://example.com/ HTTP/1.1\r\n"), None);
+        assert_eq!(parse_request("CONNECT pypi.org HTTP/1.1\r\n"), None);
+        assert_eq!(parse_request("GET http://user@evil.com@pypi.org/ HTTP/1.1\r\n"), None);
+    }
+
+    #[test]
+    fn test_domains_match_exactly_or_beneath() {
+        let allow = vec!["pypi.org".to_string(), "*.pythonhosted.org".to_string()];
+        assert!(allowed(&allow, "pypi.org"));
+        assert!(allowed(&allow, "PyPI.org."));
+        assert!(allowed(&allow, "files.pythonhosted.org"));
+        assert!(!allowed(&allow, "pythonhosted.org"));
+        assert!(!allowed(&allow, "evilpypi.org"));
+        assert!(!allowed(&allow, "pypi.org.evil.com"));
+        assert!(!allowed(&[], "pypi.org"));
+    }
+
+    #[test]
+    fn test_private_addresses_are_not_public() {
+        for ip in ["127.0.0.1", "10.1.2.3", "192.168.0.1", "169.254.169.254", "100.64.0.1", "0.0.0.0", "::1", "fd00::1", "fe80::1", "::ffff:127.0.0.1"] {
+            assert!(!is_public(ip.parse().unwrap()), "{}", ip);
+        }
+        for ip in ["151.101.0.223", "2a04:4e42::223"] {
+            assert!(is_public(ip.parse().unwrap()), "{}", ip);
+        }
+    }
+
+    /// Run `script` under `mode` with its network cut off but for a proxy
+    /// allowing `allow`; its output, or `None` where that isn't available.
+    #[cfg(target_os = "linux")]
+    async fn run_behind_proxy(mode: SandboxMode, script: &str, allow: Vec<String>) -> Option<String> {
+        let tmp = tempfile::tempdir().unwrap();
+        let (ours, theirs) = UnixStream::pair().unwrap();
+        let sandbox = Sandbox::new(mode, tmp.path()).with_proxy(theirs.into());
+        let _proxy = Proxy::start(ours, allow, "shell commands");
+        let mut command = std::process::Command::new("/bin/sh");
+        command.args(["-c", script]).current_dir(tmp.path());
+        sandbox.apply(&mut command).unwrap();
+        match tokio::process::Command::from(command).output().await {
+            Ok(output) => Some(String::from_utf8_lossy(&output.stdout).to_string()),
+            Err(e) => {
+                eprintln!("skipping {}: no network namespace here ({})", mode.name(), e);
+                None
+            }
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_commands_only_get_out_through_the_proxy() {
+        if !std::path::Path::new("/usr/bin/curl").exists() {
+            eprintln!("skipping: no curl");
+            return;
+        }
+        // A server on the host, which the proxy may reach and nothing else can
+        let server = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = server.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = server.accept().await {
+                tokio::spawn(async move {
+                    let mut request = [0u8; 4096];
+                    let _ = stream.read(&mut request).await;
+                    let _ = stream
+                        .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\nConnection: close\r\n\r\nhello")
+                        .await;
+                });
+            }
+        });
+
+        let proxy = proxy_url();
+        let script = format!(
+            "curl -s -x {proxy} http://127.0.0.1:{port}/; echo; \
+             curl -s -p -x {proxy} http://127.0.0.1:{port}/; echo; \
+             curl -s -o /dev/null -w '%{{http_code}}' -x {proxy} http://example.com/; echo; \
+             curl -s -o /dev/null -w '%{{http_connect}}' -x {proxy} https://example.com/; echo; \
+             curl -s --max-time 2 http://127.0.0.1:{port}/ || echo direct-refused"
+        );
+        for mode in [SandboxMode::None, SandboxMode::Namespaces] {
+            let Some(output) = run_behind_proxy(mode, &script, vec!["127.0.0.1".to_string()]).await else {
+                continue;
+            };
+            assert_eq!(output, "hello\nhello\n403\n403\ndirect-refused\n", "{:?}", mode);
+        }
+    }
+}
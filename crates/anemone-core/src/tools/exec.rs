@@ -0,0 +1,454 @@
+//! Running a shell command without holding up the brain: asynchronously,
+//! under a wall-clock timeout and rlimits, in a process group of its own so
+//! a kill takes everything it started, with output passed on as it arrives.
+
+use std::io;
+use std::os::unix::process::{CommandExt, ExitStatusExt};
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::Once;
+use std::time::Duration;
+
+use tokio::io::AsyncReadExt;
+use tracing::warn;
+
+use crate::config::ShellLimits;
+
+/// Most of each stream kept; the rest is read and dropped.
+const MAX_CAPTURE: usize = 64 * 1024;
+
+/// How long to keep reading after the command is gone, for output still in
+/// flight (or held open by something that left the process group).
+const DRAIN: Duration = Duration::from_secs(2);
+
+/// CPU seconds between SIGXCPU and SIGKILL, for commands that catch the first.
+const CPU_GRACE: u64 = 5;
+
+const MB: u64 = 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
+impl Stream {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Stdout => "stdout",
+            Self::Stderr => "stderr",
+        }
+    }
+}
+
+/// How a command ended.
+#[derive(Debug)]
+pub struct Finished {
+    pub stdout: String,
+    pub stderr: String,
+    pub status: ExitStatus,
+    /// Killed for running past `timeout_seconds`
+    pub timed_out: bool,
+}
+
+impl Finished {
+    /// What stopped the command, if it didn't finish on its own.
+    pub fn stopped_by(&self, limits: &ShellLimits) -> Option<String> {
+        if self.timed_out {
+            return Some(format!(
+                "Error: timed out after {}s — the command and everything it started were killed.",
+                limits.timeout_seconds
+            ));
+        }
+        // The namespaces sandbox reports a signal as 128 + its number
+        let signal = self
+            .status
+            .signal()
+            .or_else(|| self.status.code().filter(|c| *c > 128).map(|c| c - 128))?;
+        Some(match signal {
+            libc::SIGXCPU => format!("Error: killed after {}s of CPU time (the limit).", limits.cpu_seconds),
+            libc::SIGXFSZ => format!("Error: killed for writing a file past {} MB (the limit).", limits.file_size_mb),
+            libc::SIGKILL => "Error: killed (SIGKILL).".to_string(),
+            _ if self.status.signal().is_none() => return None,
+            other => format!("Error: killed by signal {}.", other),
+        })
+    }
+}
+
+/// Run `command` to completion under `limits`, calling `on_output` with
+/// each piece of output as it arrives.
+pub async fn run(mut command: Command, limits: &ShellLimits, mut on_output: impl FnMut(Stream, &str)) -> io::Result<Finished> {
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .process_group(0);
+    let processes = process_limit(limits.max_processes).await;
+    apply_limits(&mut command, limits, processes);
+
+    let mut child = tokio::process::Command::from(command).kill_on_drop(true).spawn()?;
+    let group = child.id().map(|pid| pid as libc::pid_t);
+    let mut guard = KillOnDrop(group);
+    let mut stdout = child.stdout.take();
+    let mut stderr = child.stderr.take();
+
+    let mut out = Output::default();
+    let mut err = Output::default();
+    let mut out_buf = [0u8; 4096];
+    let mut err_buf = [0u8; 4096];
+
+    let timeout = async {
+        match limits.timeout_seconds {
+            0 => std::future::pending().await,
+            secs => tokio::time::sleep(Duration::from_secs(secs)).await,
+        }
+    };
+    tokio::pin!(timeout);
+    let drain = tokio::time::sleep(Duration::MAX);
+    tokio::pin!(drain);
+
+    let mut status = None;
+    let mut timed_out = false;
+    while stdout.is_some() || stderr.is_some() || status.is_none() {
+        tokio::select! {
+            read = read_some(&mut stdout, &mut out_buf), if stdout.is_some() => {
+                match read {
+                    Some(chunk) => out.push(&chunk, |text| on_output(Stream::Stdout, text)),
+                    None => stdout = None,
+                }
+            }
+            read = read_some(&mut stderr, &mut err_buf), if stderr.is_some() => {
+                match read {
+                    Some(chunk) => err.push(&chunk, |text| on_output(Stream::Stderr, text)),
+                    None => stderr = None,
+                }
+            }
+            exited = child.wait(), if status.is_none() => {
+                status = Some(exited?);
+                // Background jobs go with it, so their pipes close too. Once
+                // the leader is reaped the group id can be reused, so only now
+                kill_group(group);
+                guard.0 = None;
+                drain.as_mut().reset(tokio::time::Instant::now() + DRAIN);
+            }
+            _ = &mut timeout, if !timed_out && status.is_none() => {
+                timed_out = true;
+                kill_group(group);
+            }
+            _ = &mut drain => break,
+        }
+    }
+
+    let Some(status) = status else {
+        return Err(io::Error::other("command status lost"));
+    };
+    Ok(Finished {
+        stdout: out.finish(),
+        stderr: err.finish(),
+        status,
+        timed_out,
+    })
+}
+
+/// Read one chunk, or `None` at the end of the stream (or on error).
+async fn read_some<R: AsyncReadExt + Unpin>(reader: &mut Option<R>, buf: &mut [u8]) -> Option<Vec<u8>> {
+    let reader = reader.as_mut()?;
+    match reader.read(buf).await {
+        Ok(0) | Err(_) => None,
+        Ok(n) => Some(buf[..n].to_vec()),
+    }
+}
+
+/// One captured stream, decoded as it comes in.
+#[derive(Default)]
+struct Output {
+    text: String,
+    /// The start of a UTF-8 character split across reads
+    pending: Vec<u8>,
+    dropped: bool,
+}
+
+impl Output {
+    fn push(&mut self, chunk: &[u8], on_text: impl FnOnce(&str)) {
+        if self.text.len() >= MAX_CAPTURE {
+            self.dropped = true;
+            return;
+        }
+        self.pending.extend_from_slice(chunk);
+        let text = match std::str::from_utf8(&self.pending) {
+            Ok(text) => text.to_string(),
+            Err(e) if e.error_len().is_none() => {
+                // Incomplete at the end: keep it for the next read
+                let text = String::from_utf8_lossy(&self.pending[..e.valid_up_to()]).to_string();
+                self.pending.drain(..e.valid_up_to());
+                on_text(&text);
+                self.text.push_str(&text);
+                return;
+            }
+            Err(_) => String::from_utf8_lossy(&self.pending).to_string(),
+        };
+        self.pending.clear();
+        on_text(&text);
+        self.text.push_str(&text);
+    }
+
+    fn finish(mut self) -> String {
+        if !self.pending.is_empty() {
+            self.text.push_str(&String::from_utf8_lossy(&self.pending));
+        }
+        if self.dropped {
+            self.text.push_str("\n...(output cut)");
+        }
+        self.text
+    }
+}
+
+// ── Process group ──
+
+fn kill_group(group: Option<libc::pid_t>) {
+    if let Some(group) = group {
+        unsafe { libc::killpg(group, libc::SIGKILL) };
+    }
+}
+
+/// Kills the command's process group if the run is abandoned midway.
+struct KillOnDrop(Option<libc::pid_t>);
+
+impl Drop for KillOnDrop {
+    fn drop(&mut self) {
+        kill_group(self.0);
+    }
+}
+
+// ── Resource limits ──
+
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+type Resource = libc::__rlimit_resource_t;
+#[cfg(not(all(target_os = "linux", target_env = "gnu")))]
+type Resource = libc::c_int;
+
+/// The RLIMIT_NPROC to give a command allowed `max_processes` of its own.
+/// The kernel counts every process the user has, not just the command's, so
+/// the limit is on top of what's already running. Counting reads all of
+/// `/proc`, so it's done off the async workers.
+async fn process_limit(max_processes: u64) -> Option<u64> {
+    if max_processes == 0 {
+        return None;
+    }
+    if unsafe { libc::getuid() } == 0 {
+        static WARNED: Once = Once::new();
+        WARNED.call_once(|| warn!("Running as root: shell commands have no process limit (max_processes)"));
+        return None;
+    }
+    let running = tokio::task::spawn_blocking(user_tasks).await.ok().flatten()?;
+    Some(running + max_processes)
+}
+
+/// Apply `limits` to `command` as rlimits, set in the child before exec,
+/// with `processes` from [`process_limit`].
+fn apply_limits(command: &mut Command, limits: &ShellLimits, processes: Option<u64>) {
+    let limits = *limits;
+    // SAFETY: only getrlimit/setrlimit run in the child
+    unsafe {
+        command.pre_exec(move || {
+            if limits.cpu_seconds > 0 {
+                set_limit(libc::RLIMIT_CPU, limits.cpu_seconds, limits.cpu_seconds + CPU_GRACE)?;
+            }
+            if limits.memory_mb > 0 {
+                set_limit(libc::RLIMIT_AS, limits.memory_mb * MB, limits.memory_mb * MB)?;
+            }
+            if limits.file_size_mb > 0 {
+                set_limit(libc::RLIMIT_FSIZE, limits.file_size_mb * MB, limits.file_size_mb * MB)?;
+            }
+            if let Some(processes) = processes {
+                set_limit(libc::RLIMIT_NPROC, processes, processes)?;
+            }
+            // A crash shouldn't leave a core file in the box
+            set_limit(libc::RLIMIT_CORE, 0, 0)
+        });
+    }
+}
+
+/// Lower `resource` to `soft` / `hard`, never raising it.
+fn set_limit(resource: Resource, soft: u64, hard: u64) -> io::Result<()> {
+    let mut current = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    if unsafe { libc::getrlimit(resource, &mut current) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let hard = (hard as libc::rlim_t).min(current.rlim_max);
+    let limit = libc::rlimit {
+        rlim_cur: (soft as libc::rlim_t).min(hard),
+        rlim_max: hard,
+    };
+    if unsafe { libc::setrlimit(resource, &limit) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Processes and threads the current user has running.
+#[cfg(target_os = "linux")]
+fn user_tasks() -> Option<u64> {
+    let uid = unsafe { libc::getuid() }.to_string();
+    let mut tasks = 0;
+    for entry in std::fs::read_dir("/proc").ok()?.flatten() {
+        if !entry.file_name().to_string_lossy().bytes().all(|b| b.is_ascii_digit()) {
+            continue;
+        }
+        let Ok(status) = std::fs::read_to_string(entry.path().join("status")) else {
+            continue;
+        };
+        let field = |name: &str| {
+            status
+                .lines()
+                .find_map(|line| line.strip_prefix(name))
+                .and_then(|rest| rest.split_whitespace().next())
+                .map(str::to_string)
+        };
+        if field("Uid:").as_deref() == Some(uid.as_str()) {
+            tasks += field("Threads:").and_then(|n| n.parse::<u64>().ok()).unwrap_or(1);
+        }
+    }
+    Some(tasks)
+}
+
+/// Without a cheap way to count the user's processes, leave the limit off.
+#[cfg(not(target_os = "linux"))]
+fn user_tasks() -> Option<u64> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+    use std::time::Instant;
+
+    fn shell(script: &str) -> Command {
+        let mut command = Command::new("/bin/sh");
+        command.args(["-c", script]);
+        command
+    }
+
+    #[tokio::test]
+    async fn test_streams_output_as_it_arrives() {
+        let mut seen = Vec::new();
+        let finished = run(shell("echo one; echo two >&2; echo three"), &ShellLimits::default(), |stream, text| {
+            seen.push((stream, text.to_string()))
+        })
+        .await
+        .unwrap();
+        assert_eq!(finished.stdout, "one\nthree\n");
+        assert_eq!(finished.stderr, "two\n");
+        assert!(finished.status.success());
+        assert!(seen.iter().any(|(s, t)| *s == Stream::Stderr && t == "two\n"));
+        let streamed: String = seen.iter().filter(|(s, _)| *s == Stream::Stdout).map(|(_, t)| t.as_str()).collect();
+        assert_eq!(streamed, "one\nthree\n");
+    }
+
+    #[tokio::test]
+    async fn test_timeout_kills_the_whole_group() {
+        let tmp = tempfile::tempdir().unwrap();
+        let pid_file = tmp.path().join("pid");
+        let limits = ShellLimits {
+            timeout_seconds: 1,
+            ..ShellLimits::default()
+        };
+        let script = format!("sleep 60 & echo $! > {}; echo started; wait", pid_file.display());
+        let started = Instant::now();
+        let finished = run(shell(&script), &limits, |_, _| {}).await.unwrap();
+
+        assert!(started.elapsed() < Duration::from_secs(10));
+        assert!(finished.timed_out);
+        assert_eq!(finished.stdout, "started\n");
+        assert!(finished.stopped_by(&limits).unwrap().contains("timed out after 1s"));
+        let pid = std::fs::read_to_string(&pid_file).unwrap();
+        // Gone, or a zombie waiting for init to reap it
+        let state = std::fs::read_to_string(Path::new("/proc").join(pid.trim()).join("stat")).unwrap_or_default();
+        let alive = !state.is_empty() && !state.rsplit(')').next().unwrap_or("").trim_start().starts_with('Z');
+        assert!(!cfg!(target_os = "linux") || !alive, "background job survived");
+    }
+
+    #[tokio::test]
+    async fn test_background_jobs_do_not_hold_the_command_open() {
+        let started = Instant::now();
+        let finished = run(shell("sleep 60 & echo done"), &ShellLimits::default(), |_, _| {})
+            .await
+            .unwrap();
+        assert!(started.elapsed() < Duration::from_secs(10));
+        assert!(!finished.timed_out);
+        assert_eq!(finished.stdout, "done\n");
+    }
+
+    #[tokio::test]
+    async fn test_cpu_limit_stops_busy_loops() {
+        let limits = ShellLimits {
+            cpu_seconds: 1,
+            timeout_seconds: 30,
+            ..ShellLimits::default()
+        };
+        let finished = run(shell("while :; do :; done"), &limits, |_, _| {}).await.unwrap();
+        assert!(!finished.timed_out);
+        assert!(finished.stopped_by(&limits).unwrap().contains("CPU time"));
+    }
+
+    #[tokio::test]
+    async fn test_file_size_limit() {
+        let tmp = tempfile::tempdir().unwrap();
+        let limits = ShellLimits {
+            file_size_mb: 1,
+            ..ShellLimits::default()
+        };
+        let script = format!("yes > {}", tmp.path().join("big").display());
+        let finished = run(shell(&script), &limits, |_, _| {}).await.unwrap();
+        assert!(finished.stopped_by(&limits).unwrap().contains("1 MB"));
+        assert!(std::fs::metadata(tmp.path().join("big")).unwrap().len() <= MB);
+    }
+
+    #[tokio::test]
+    async fn test_process_limit_stops_fork_bombs() {
+        // The kernel doesn't hold root to RLIMIT_NPROC
+        if unsafe { libc::getuid() } == 0 {
+            eprintln!("skipping the process limit test as root");
+            return;
+        }
+        let limits = ShellLimits {
+            max_processes: 10,
+            ..ShellLimits::default()
+        };
+        let script = "i=0; while [ $i -lt 50 ]; do sleep 5 & i=$((i+1)); done; wait";
+        let finished = run(shell(script), &limits, |_, _| {}).await.unwrap();
+        assert!(!finished.stderr.is_empty(), "started 50 processes");
+    }
+
+    #[tokio::test]
+    async fn test_process_limit_is_on_top_of_running_tasks() {
+        assert_eq!(process_limit(0).await, None);
+        let limit = process_limit(10).await;
+        if unsafe { libc::getuid() } == 0 {
+            assert_eq!(limit, None);
+        } else if cfg!(target_os = "linux") {
+            assert!(limit.unwrap() > 10, "the test itself is running");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_exit_codes_are_not_limits() {
+        let finished = run(shell("exit 3"), &ShellLimits::default(), |_, _| {}).await.unwrap();
+        assert_eq!(finished.status.code(), Some(3));
+        assert!(finished.stopped_by(&ShellLimits::default()).is_none());
+    }
+
+    #[test]
+    fn test_split_characters_are_kept_whole() {
+        let mut output = Output::default();
+        let mut streamed = String::new();
+        let bytes = "héllo".as_bytes();
+        output.push(&bytes[..2], |t| streamed.push_str(t));
+        output.push(&bytes[2..], |t| streamed.push_str(t));
+        assert_eq!(streamed, "héllo");
+        assert_eq!(output.finish(), "héllo");
+    }
+}
@@ -1,53 +1,288 @@
-//! Tool execution — sandboxed shell, web tools, movement, conversation.
-//! Phase 3 implementation.
+//! Tools — a pluggable [`Tool`] trait plus a registry the brain builds per
+//! anemone. Each tool carries its own schema and its own dispatch, so the two
+//! can't drift apart; the registry turns them into the definitions sent to
+//! the model and runs whatever the model calls.
+//!
+//! Built in: sandboxed shell, web tools, movement, conversation. Tools are
+//! switched off globally or per anemone with `tools` / `box_tools` in the
+//! config, and other crates add their own with [`ToolRegistry::register`].
 
 pub mod shell;
+pub mod exec;
+pub mod egress;
+pub mod sandbox;
+pub mod policy;
 pub mod web;
 pub mod movement;
 pub mod respond;
 
-use anyhow::Result;
 use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use serde_json::json;
+use tokio::sync::broadcast;
+
+use crate::config::Config;
+use crate::events::BrainEvent;
+use crate::types::{ActivityData, Position};
+
+/// What a tool gets to work with while it runs.
+pub struct ToolContext<'a> {
+    /// The anemone's name, for per-box settings
+    pub anemone: &'a str,
+    /// The anemone's box; shell commands run here
+    pub env_path: &'a Path,
+    /// Where the anemone is in its room. Tools that move it should also
+    /// send a [`BrainEvent::Position`].
+    pub position: &'a mut Position,
+    /// Frontend event stream
+    pub events: &'a broadcast::Sender<BrainEvent>,
+    pub config: &'a Config,
+}
+
+/// What a tool call produced.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ToolOutput {
+    /// Text fed back to the model
+    Text(String),
+    /// Say this to the owner; the brain waits for their reply and feeds
+    /// that back instead
+    Converse(String),
+}
+
+/// A tool the model can call.
+#[async_trait]
+pub trait Tool: Send + Sync {
+    /// Name the model calls it by, e.g. `"shell"`.
+    fn name(&self) -> &str;
+
+    /// What it's for, as the model reads it.
+    fn description(&self) -> &str;
+
+    /// JSON schema of its arguments.
+    fn parameters(&self) -> serde_json::Value;
+
+    /// Whether it works under `config` — e.g. has the keys it needs.
+    fn available(&self, config: &Config) -> bool {
+        let _ = config;
+        true
+    }
+
+    /// Counts as research: cycles of research with no file written get
+    /// nudged toward output.
+    fn is_research(&self) -> bool {
+        false
+    }
+
+    /// What frontends show while it runs.
+    fn activity(&self, arguments: &serde_json::Value) -> ActivityData {
+        let _ = arguments;
+        ActivityData {
+            activity_type: "working".to_string(),
+            detail: self.name().to_string(),
+        }
+    }
+
+    async fn execute(&self, arguments: &serde_json::Value, ctx: &mut ToolContext<'_>) -> Result<ToolOutput>;
+}
+
+/// A string argument, empty if missing.
+pub(crate) fn str_arg<'a>(arguments: &'a serde_json::Value, name: &str) -> &'a str {
+    arguments.get(name).and_then(|v| v.as_str()).unwrap_or("")
+}
+
+// ── Registry ──
+
+/// The tools an anemone can call, in the order they're offered. `Default`
+/// registers the built-in tools; [`register`](Self::register) adds or
+/// replaces one, and [`configured`](Self::configured) narrows them to what
+/// an anemone is allowed.
+#[derive(Clone)]
+pub struct ToolRegistry {
+    tools: Vec<Arc<dyn Tool>>,
+}
+
+impl Default for ToolRegistry {
+    fn default() -> Self {
+        let mut registry = Self::empty();
+        registry.register(Arc::new(shell::ShellTool));
+        registry.register(Arc::new(respond::RespondTool));
+        registry.register(Arc::new(web::FetchUrlTool));
+        registry.register(Arc::new(movement::MoveTool));
+        registry.register(Arc::new(web::WebSearchTool));
+        registry.register(Arc::new(web::WebFetchTool));
+        registry
+    }
+}
+
+impl ToolRegistry {
+    /// A registry with no tools at all.
+    pub fn empty() -> Self {
+        Self { tools: Vec::new() }
+    }
+
+    /// Add `tool`, replacing any tool of the same name in its place.
+    pub fn register(&mut self, tool: Arc<dyn Tool>) {
+        match self.tools.iter().position(|t| t.name() == tool.name()) {
+            Some(i) => self.tools[i] = tool,
+            None => self.tools.push(tool),
+        }
+    }
+
+    /// Only the tools `anemone` may use under `config`: available, and
+    /// allowed by its `box_tools` entry (or `tools` without one).
+    pub fn configured(mut self, config: &Config, anemone: &str) -> Self {
+        let settings = config.tool_settings(anemone);
+        self.tools
+            .retain(|tool| tool.available(config) && settings.allows(tool.name()));
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Arc<dyn Tool>> {
+        self.tools.iter().find(|t| t.name() == name)
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.tools.iter().map(|t| t.name()).collect()
+    }
+
+    /// Responses-style function definitions, as the providers take them.
+    pub fn definitions(&self) -> Vec<serde_json::Value> {
+        self.tools
+            .iter()
+            .map(|tool| {
+                json!({
+                    "type": "function",
+                    "name": tool.name(),
+                    "description": tool.description(),
+                    "parameters": tool.parameters(),
+                })
+            })
+            .collect()
+    }
+
+    /// Run the tool called `name`. Failures and unknown tools come back as
+    /// text for the model.
+    pub async fn execute(
+        &self,
+        name: &str,
+        arguments: &serde_json::Value,
+        ctx: &mut ToolContext<'_>,
+    ) -> ToolOutput {
+        let Some(tool) = self.get(name) else {
+            return ToolOutput::Text(format!("Unknown tool: {}", name));
+        };
+        match tool.execute(arguments, ctx).await {
+            Ok(output) => output,
+            Err(e) => ToolOutput::Text(format!("Error: {}", e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ToolSettings;
+
+    struct Echo;
+
+    #[async_trait]
+    impl Tool for Echo {
+        fn name(&self) -> &str {
+            "echo"
+        }
+
+        fn description(&self) -> &str {
+            "Say it back."
+        }
+
+        fn parameters(&self) -> serde_json::Value {
+            json!({"type": "object", "properties": {"text": {"type": "string"}}})
+        }
+
+        async fn execute(&self, arguments: &serde_json::Value, ctx: &mut ToolContext<'_>) -> Result<ToolOutput> {
+            ctx.position.x = 0;
+            Ok(ToolOutput::Text(str_arg(arguments, "text").to_string()))
+        }
+    }
+
+    fn names(config: &Config, anemone: &str) -> Vec<String> {
+        let registry = ToolRegistry::default().configured(config, anemone);
+        registry.names().into_iter().map(String::from).collect()
+    }
+
+    #[test]
+    fn test_builtin_definitions_match_dispatch() {
+        let registry = ToolRegistry::default().configured(&Config::default(), "coral");
+        assert_eq!(registry.names(), ["shell", "respond", "fetch_url", "move"]);
+
+        let definitions = registry.definitions();
+        for (definition, name) in definitions.iter().zip(registry.names()) {
+            assert_eq!(definition["type"], "function");
+            assert_eq!(definition["name"], name);
+            assert_eq!(definition["parameters"]["type"], "object");
+            assert!(registry.get(name).is_some());
+        }
+    }
+
+    #[test]
+    fn test_web_tools_need_an_ollama_key() {
+        let config = Config {
+            provider: "ollama".to_string(),
+            ollama_api_key: Some("key".to_string()),
+            ..Config::default()
+        };
+        assert!(names(&config, "coral").contains(&"web_search".to_string()));
+        let config = Config {
+            provider: "openai".to_string(),
+            ..config
+        };
+        assert!(!names(&config, "coral").contains(&"web_search".to_string()));
+    }
+
+    #[test]
+    fn test_tools_enabled_and_disabled_per_box() {
+        let mut config = Config {
+            tools: ToolSettings {
+                disabled: vec!["fetch_url".to_string()],
+                ..ToolSettings::default()
+            },
+            ..Config::default()
+        };
+        config.box_tools.insert(
+            "kelp".to_string(),
+            ToolSettings {
+                enabled: Some(vec!["move".to_string(), "respond".to_string()]),
+                ..ToolSettings::default()
+            },
+        );
+        assert_eq!(names(&config, "coral"), ["shell", "respond", "move"]);
+        assert_eq!(names(&config, "kelp"), ["respond", "move"]);
+    }
+
+    #[tokio::test]
+    async fn test_registered_tools_run_with_context() {
+        let mut registry = ToolRegistry::default();
+        registry.register(Arc::new(Echo));
+        assert_eq!(registry.names().last(), Some(&"echo"));
 
-/// Execute a tool by name. Returns the tool output string.
-pub async fn execute_tool(
-    name: &str,
-    arguments: &serde_json::Value,
-    env_root: &Path,
-) -> Result<String> {
-    match name {
-        "shell" => {
-            let command = arguments
-                .get("command")
-                .and_then(|v| v.as_str())
-                .unwrap_or("");
-            Ok(shell::run_command(command, env_root))
-        }
-        "fetch_url" => {
-            let url = arguments
-                .get("url")
-                .and_then(|v| v.as_str())
-                .unwrap_or("");
-            web::fetch_url(url).await
-        }
-        "web_search" => {
-            let query = arguments
-                .get("query")
-                .and_then(|v| v.as_str())
-                .unwrap_or("");
-            let max_results = arguments
-                .get("max_results")
-                .and_then(|v| v.as_u64())
-                .unwrap_or(5) as usize;
-            web::ollama_web_search(query, max_results, None).await
-        }
-        "web_fetch" => {
-            let url = arguments
-                .get("url")
-                .and_then(|v| v.as_str())
-                .unwrap_or("");
-            web::ollama_web_fetch(url, None).await
-        }
-        _ => Ok(format!("Unknown tool: {}", name)),
+        let tmp = tempfile::tempdir().unwrap();
+        let (events, _) = broadcast::channel(8);
+        let config = Config::default();
+        let mut position = Position { x: 5, y: 5 };
+        let mut ctx = ToolContext {
+            anemone: "coral",
+            env_path: tmp.path(),
+            position: &mut position,
+            events: &events,
+            config: &config,
+        };
+        let output = registry.execute("echo", &json!({"text": "hello"}), &mut ctx).await;
+        assert_eq!(output, ToolOutput::Text("hello".to_string()));
+        let output = registry.execute("teleport", &json!({}), &mut ctx).await;
+        assert_eq!(output, ToolOutput::Text("Unknown tool: teleport".to_string()));
+        assert_eq!(position.x, 0);
     }
 }
@@ -1,11 +1,16 @@
 //! Room movement — locations, collision map, idle wander.
 //! 1:1 port of Brain movement logic.
 
+use anyhow::Result;
+use async_trait::async_trait;
 use rand::Rng;
+use serde_json::json;
 use std::collections::HashSet;
 use std::sync::LazyLock;
 
-use crate::types::Position;
+use super::{str_arg, Tool, ToolContext, ToolOutput};
+use crate::events::BrainEvent;
+use crate::types::{ActivityData, Position};
 
 /// Room is 12x12 tiles
 pub const ROOM_COLS: i32 = 12;
@@ -48,7 +53,7 @@ pub fn is_blocked(x: i32, y: i32) -> bool {
 
 /// Check if a position is valid (in bounds and not blocked).
 pub fn is_valid_position(x: i32, y: i32) -> bool {
-    x >= 0 && x < ROOM_COLS && y >= 0 && y < ROOM_ROWS && !is_blocked(x, y)
+    (0..ROOM_COLS).contains(&x) && (0..ROOM_ROWS).contains(&y) && !is_blocked(x, y)
 }
 
 /// Handle the move tool — move to a named location.
@@ -62,6 +67,50 @@ pub fn handle_move(position: &mut Position, location: &str) -> String {
     }
 }
 
+/// The `move` tool.
+pub struct MoveTool;
+
+#[async_trait]
+impl Tool for MoveTool {
+    fn name(&self) -> &str {
+        "move"
+    }
+
+    fn description(&self) -> &str {
+        "Move to a location in your room. Use this to go where feels natural for what you're doing — desk for writing, bookshelf for research, window for pondering, bed for resting."
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "location": {
+                    "type": "string",
+                    "enum": ["desk", "bookshelf", "window", "plant", "bed", "rug", "center"]
+                }
+            },
+            "required": ["location"]
+        })
+    }
+
+    fn activity(&self, arguments: &serde_json::Value) -> ActivityData {
+        ActivityData {
+            activity_type: "moving".to_string(),
+            detail: format!("Going to {}", str_arg(arguments, "location")),
+        }
+    }
+
+    async fn execute(&self, arguments: &serde_json::Value, ctx: &mut ToolContext<'_>) -> Result<ToolOutput> {
+        let location = match str_arg(arguments, "location") {
+            "" => "center",
+            location => location,
+        };
+        let result = handle_move(ctx.position, location);
+        let _ = ctx.events.send(BrainEvent::Position(ctx.position.clone()));
+        Ok(ToolOutput::Text(result))
+    }
+}
+
 /// Random ±1 step between thoughts (idle wander).
 pub fn idle_wander(position: &mut Position) {
     let mut rng = rand::thread_rng();
@@ -0,0 +1,740 @@
+//! Shell command policy — what the `shell` tool will run.
+//!
+//! A command is parsed with a real shell grammar ([`parse`]) into pipelines
+//! and segments, and every segment is checked, including ones nested in
+//! substitutions, compound commands, here-documents and wrappers like
+//! `env` or `find -exec`. Quoting (`c'u'rl`), paths (`/usr/bin/curl`) and
+//! chaining (`ls; curl`) don't hide a program from it.
+//!
+//! A policy is YAML: a `denylist` (anything not denied runs) or an
+//! `allowlist` (only what's allowed runs), rules on programs and their
+//! arguments, the shell features commands may use, and paths they may
+//! touch. The built-in policy (`default.yaml`) is used unless `tools` or a
+//! `box_tools` entry names a `shell_policy` file.
+
+pub mod parse;
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+use parse::{Expansion, RedirectOp, Redirect, Script, Segment, Word};
+
+/// The built-in policy.
+const DEFAULT: &str = include_str!("default.yaml");
+
+/// What a policy made of a command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Decision {
+    Allow,
+    /// Refused, and why
+    Deny(String),
+}
+
+impl Decision {
+    pub fn is_allowed(&self) -> bool {
+        matches!(self, Decision::Allow)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    /// Anything not denied runs
+    #[default]
+    Denylist,
+    /// Only allowed programs run
+    Allowlist,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    Allow,
+    Deny,
+}
+
+/// A decision about one program, optionally only when an argument matches.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    /// Program name glob
+    pub program: String,
+    /// Applies only if some argument matches this glob
+    #[serde(default)]
+    pub args: Option<String>,
+    pub action: Action,
+    /// Shown to the anemone when the rule denies
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+impl Rule {
+    fn matches(&self, program: &str, args: &[&str]) -> bool {
+        glob(&self.program, program)
+            && self.args.as_ref().is_none_or(|pattern| args.iter().any(|arg| glob(pattern, arg)))
+    }
+}
+
+/// A program that runs another one from its arguments.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Wrapper {
+    /// Options that take the next argument as their value
+    pub options: Vec<String>,
+    /// Arguments between the options and the command (`timeout 5 cmd`)
+    pub skip: usize,
+    /// Options that start a command ending at `;` or `+` (`find -exec`);
+    /// with these set, nothing else counts as a command
+    pub exec: Vec<String>,
+}
+
+impl Wrapper {
+    /// The commands this runs, given its arguments.
+    fn wrapped<'a>(&self, args: &'a [Word]) -> Vec<&'a [Word]> {
+        if !self.exec.is_empty() {
+            let mut commands = Vec::new();
+            let mut i = 0;
+            while i < args.len() {
+                if self.exec.contains(&args[i].text) {
+                    let start = i + 1;
+                    let end = args[start..]
+                        .iter()
+                        .position(|w| w.text == ";" || w.text == "+")
+                        .map_or(args.len(), |n| start + n);
+                    commands.push(&args[start..end]);
+                    i = end;
+                }
+                i += 1;
+            }
+            return commands;
+        }
+        let mut i = 0;
+        while let Some(arg) = args.get(i).map(|w| w.text.as_str()) {
+            if arg == "--" {
+                i += 1;
+                break;
+            }
+            if arg.len() > 1 && arg.starts_with('-') {
+                i += if self.options.iter().any(|o| o == arg) { 2 } else { 1 };
+            } else if arg.split_once('=').is_some_and(|(name, _)| !name.is_empty() && !name.contains('/')) {
+                // `env NAME=value cmd`
+                i += 1;
+            } else {
+                break;
+            }
+        }
+        let start = i + self.skip;
+        if start < args.len() {
+            vec![&args[start..]]
+        } else {
+            Vec::new()
+        }
+    }
+}
+
+/// Shell features commands may use.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Features {
+    /// `$(...)` and backticks (the commands inside are still checked)
+    pub substitution: bool,
+    /// `$((...))`
+    pub arithmetic: bool,
+    /// `${...}`
+    pub braced_variables: bool,
+    /// `~` and `~user`
+    pub tilde: bool,
+    /// `cmd &`
+    pub background: bool,
+}
+
+impl Default for Features {
+    fn default() -> Self {
+        Self {
+            substitution: false,
+            arithmetic: false,
+            braced_variables: false,
+            tilde: false,
+            background: true,
+        }
+    }
+}
+
+/// Paths commands may name, in arguments and redirections.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Paths {
+    /// Allow `..` components
+    pub parent: bool,
+    /// Globs no argument or redirection may name
+    pub deny: Vec<String>,
+    /// Globs that may not be redirected to (`>`, `>>`)
+    pub read_only: Vec<String>,
+    /// Globs exempt from `deny` and `read_only`
+    pub allow: Vec<String>,
+}
+
+/// A shell command policy.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Policy {
+    #[serde(default)]
+    pub mode: Mode,
+    /// Program name globs that may run (what `allowlist` mode checks)
+    #[serde(default)]
+    pub allow: Vec<String>,
+    /// Program name globs that never run
+    #[serde(default)]
+    pub deny: Vec<String>,
+    /// Per-program decisions. Deny rules win over everything, then allow
+    /// rules, then `deny`, then `allow`.
+    #[serde(default)]
+    pub rules: Vec<Rule>,
+    /// Programs that run other programs, by name (the built-in set if unset)
+    #[serde(default = "default_wrappers")]
+    pub wrappers: BTreeMap<String, Wrapper>,
+    #[serde(default)]
+    pub features: Features,
+    #[serde(default)]
+    pub paths: Paths,
+    /// Where it came from, for the logs
+    #[serde(skip)]
+    source: String,
+}
+
+fn default_wrappers() -> BTreeMap<String, Wrapper> {
+    Policy::builtin().wrappers
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Self::builtin()
+    }
+}
+
+/// Why a command is refused.
+type Verdict = std::result::Result<(), String>;
+
+impl Policy {
+    /// The built-in policy.
+    pub fn builtin() -> Self {
+        let mut policy: Self = serde_yaml::from_str(DEFAULT).expect("the built-in shell policy parses");
+        policy.source = "built-in".to_string();
+        policy
+    }
+
+    /// Parse a policy from YAML.
+    pub fn from_yaml(text: &str) -> Result<Self> {
+        let mut policy: Self = serde_yaml::from_str(text)?;
+        policy.source = "inline".to_string();
+        Ok(policy)
+    }
+
+    /// Load a policy file.
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read shell policy {}", path.display()))?;
+        let mut policy = Self::from_yaml(&text)
+            .with_context(|| format!("Invalid shell policy {}", path.display()))?;
+        policy.source = path.display().to_string();
+        Ok(policy)
+    }
+
+    /// Where the policy came from: "built-in", "inline" or its path.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Decide whether `command` may run.
+    pub fn check(&self, command: &str) -> Decision {
+        let script = match parse::parse(command) {
+            Ok(script) if script.pipelines.is_empty() => return Decision::Deny("empty command.".to_string()),
+            Ok(script) => script,
+            Err(e) => return Decision::Deny(format!("couldn't make sense of the command ({}).", e)),
+        };
+        match self.script(&script) {
+            Ok(()) => Decision::Allow,
+            Err(reason) => Decision::Deny(reason),
+        }
+    }
+
+    // ── Walking the command ──
+
+    fn script(&self, script: &Script) -> Verdict {
+        for pipeline in &script.pipelines {
+            if pipeline.background && !self.features.background {
+                return Err("background jobs ('&') are not allowed.".to_string());
+            }
+            for segment in &pipeline.segments {
+                self.segment(segment)?;
+            }
+        }
+        for body in &script.heredocs {
+            self.word(body)?;
+        }
+        Ok(())
+    }
+
+    fn segment(&self, segment: &Segment) -> Verdict {
+        match segment {
+            Segment::Simple(simple) => {
+                for word in &simple.assignments {
+                    self.word(word)?;
+                }
+                for redirect in &simple.redirects {
+                    self.redirect(redirect)?;
+                }
+                self.command(&simple.words)
+            }
+            Segment::Compound(compound) => {
+                for word in &compound.words {
+                    self.word(word)?;
+                }
+                for redirect in &compound.redirects {
+                    self.redirect(redirect)?;
+                }
+                self.script(&compound.body)
+            }
+        }
+    }
+
+    /// A program and its arguments.
+    fn command(&self, words: &[Word]) -> Verdict {
+        for word in words {
+            self.word(word)?;
+        }
+        // Only assignments or redirections
+        let Some(program) = words.first() else {
+            return Ok(());
+        };
+        if !program.is_literal() {
+            return Err(format!("the program to run must be written out, not '{}'.", program.text));
+        }
+        let name = program.text.rsplit('/').next().unwrap_or_default();
+        if name.is_empty() {
+            return Err(format!("'{}' is not a program.", program.text));
+        }
+        let args: Vec<&str> = words[1..].iter().map(|w| w.text.as_str()).collect();
+        self.program(name, &args)?;
+        for path in std::iter::once(program.text.as_str()).chain(args.iter().copied()) {
+            self.path(path, false)?;
+        }
+        if let Some(wrapper) = self.wrappers.get(name) {
+            for inner in wrapper.wrapped(&words[1..]) {
+                self.command(inner)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn program(&self, name: &str, args: &[&str]) -> Verdict {
+        if let Some(rule) = self
+            .rules
+            .iter()
+            .find(|r| r.action == Action::Deny && r.matches(name, args))
+        {
+            return Err(rule
+                .reason
+                .clone()
+                .unwrap_or_else(|| format!("'{}' is not allowed here.", name)));
+        }
+        if self.rules.iter().any(|r| r.action == Action::Allow && r.matches(name, args)) {
+            return Ok(());
+        }
+        if self.deny.iter().any(|g| glob(g, name)) {
+            return Err(format!("'{}' commands are not allowed.", name));
+        }
+        if self.mode == Mode::Allowlist && !self.allow.iter().any(|g| glob(g, name)) {
+            return Err(format!("'{}' is not on the allowlist.", name));
+        }
+        Ok(())
+    }
+
+    /// The expansions in a word, and the commands substituted into it.
+    fn word(&self, word: &Word) -> Verdict {
+        for expansion in &word.expansions {
+            match expansion {
+                Expansion::Substitution(script) => {
+                    if !self.features.substitution {
+                        return Err("command substitution ($(...) or backticks) is not allowed.".to_string());
+                    }
+                    self.script(script)?;
+                }
+                Expansion::Arithmetic(_) if !self.features.arithmetic => {
+                    return Err("arithmetic expansion $((...)) is not allowed.".to_string());
+                }
+                Expansion::Braced(_) if !self.features.braced_variables => {
+                    return Err("variable expansion ${} is not allowed.".to_string());
+                }
+                Expansion::Tilde if !self.features.tilde => {
+                    return Err("'~' (home expansion) is not allowed.".to_string());
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn redirect(&self, redirect: &Redirect) -> Verdict {
+        let target = &redirect.target;
+        match redirect.op {
+            // The target is a delimiter; the body is checked with the script
+            RedirectOp::HereDoc => Ok(()),
+            RedirectOp::HereString => self.word(target),
+            RedirectOp::Duplicate if target.text == "-" || target.text.chars().all(|c| c.is_ascii_digit()) => {
+                self.word(target)
+            }
+            op => {
+                self.word(target)?;
+                let write = !matches!(op, RedirectOp::Read);
+                self.path(&target.text, write)
+            }
+        }
+    }
+
+    /// A word that may be a path; `--option=path` counts too.
+    fn path(&self, text: &str, write: bool) -> Verdict {
+        let value = text.split_once('=').map(|(_, value)| value);
+        for path in std::iter::once(text).chain(value) {
+            if !self.paths.parent && path.split('/').any(|part| part == "..") {
+                return Err("'..' path traversal is not allowed in commands.".to_string());
+            }
+            let path = path.trim_start_matches("./");
+            // Absolute paths are rewritten into the box before they run,
+            // keeping their last parts, so each of those is checked too
+            let tails = path.match_indices('/').map(|(i, _)| &path[i + 1..]);
+            let tails = tails.filter(|_| path.starts_with('/'));
+            for path in std::iter::once(path).chain(tails) {
+                if self.paths.allow.iter().any(|g| glob(g, path)) {
+                    continue;
+                }
+                if self.paths.deny.iter().any(|g| glob(g, path)) {
+                    return Err(format!("'{}' is off limits.", path));
+                }
+                if write && self.paths.read_only.iter().any(|g| glob(g, path)) {
+                    return Err(format!("'{}' is read-only.", path));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Match `text` against a glob where `*` is any run of characters
+/// (slashes included) and `?` is any one.
+pub fn glob(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // Where the last `*` was, and how much text it has taken
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                Some((star_p, star_t)) => {
+                    p = star_p + 1;
+                    t = star_t + 1;
+                    star = Some((star_p, star_t + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Whether every command in `command` is `pip`, `pip3` or `uv pip`, with
+/// nothing substituted into them: what gets pip's network allowlist.
+pub fn runs_only_pip(command: &str) -> bool {
+    let Ok(script) = parse::parse(command) else {
+        return false;
+    };
+    let literal = |words: &[Word]| words.iter().all(Word::is_literal);
+    !script.pipelines.is_empty()
+        && literal(&script.heredocs)
+        && script.pipelines.iter().flat_map(|p| &p.segments).all(|segment| {
+            let Segment::Simple(simple) = segment else {
+                return false;
+            };
+            let program = simple.words.first().map(|w| w.text.rsplit('/').next().unwrap_or_default());
+            let pip = match program {
+                Some("pip" | "pip3") => true,
+                Some("uv") => simple.words.get(1).is_some_and(|w| w.text == "pip"),
+                _ => false,
+            };
+            pip && literal(&simple.assignments)
+                && literal(&simple.words)
+                && simple.redirects.iter().all(|r| r.target.is_literal())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    /// `allow <command>` / `deny <command>` lines for the built-in policy.
+    const CORPUS: &str = include_str!("corpus.txt");
+
+    #[test]
+    fn test_builtin_policy_corpus() {
+        let policy = Policy::builtin();
+        let mut cases = 0;
+        for (n, line) in CORPUS.lines().enumerate() {
+            let line = line.trim_end();
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (expected, command) = line.split_once(' ').unwrap_or((line, ""));
+            // Escaped newlines let one line hold a multi-line command
+            let command = command.replace("\\n", "\n");
+            let decision = policy.check(&command);
+            match expected {
+                "allow" => assert_eq!(decision, Decision::Allow, "line {}: {:?}", n + 1, command),
+                "deny" => assert!(!decision.is_allowed(), "line {}: {:?} was allowed", n + 1, command),
+                other => panic!("line {}: expected allow or deny, found {:?}", n + 1, other),
+            }
+            cases += 1;
+        }
+        assert!(cases > 100, "only {} cases", cases);
+    }
+
+    #[test]
+    fn test_reasons_name_the_program() {
+        let policy = Policy::builtin();
+        assert_eq!(
+            policy.check("ls && c\\url x"),
+            Decision::Deny("'curl' commands are not allowed.".to_string())
+        );
+        assert_eq!(
+            policy.check("cat ../secret"),
+            Decision::Deny("'..' path traversal is not allowed in commands.".to_string())
+        );
+        assert_eq!(policy.check("  "), Decision::Deny("empty command.".to_string()));
+    }
+
+    #[test]
+    fn test_allowlist_mode() {
+        let policy = Policy::from_yaml(
+            "mode: allowlist\n\
+             allow: [ls, cat, grep, python*]\n\
+             rules:\n  - { program: git, args: status, action: allow }\n\
+             \x20 - { program: python*, args: '-c', action: deny, reason: 'Write a script instead.' }\n",
+        )
+        .unwrap();
+        assert!(policy.check("ls -la | grep md").is_allowed());
+        assert!(policy.check("python3 analyze.py").is_allowed());
+        assert!(policy.check("git status").is_allowed());
+        assert!(!policy.check("git push").is_allowed());
+        assert!(!policy.check("ls; mv a b").is_allowed());
+        assert_eq!(policy.check("python -c 'print(1)'"), Decision::Deny("Write a script instead.".to_string()));
+        // Wrappers still need to be allowed themselves
+        assert!(!policy.check("env ls").is_allowed());
+        // The built-in wrappers apply when a policy doesn't list its own
+        assert!(policy.wrappers.contains_key("xargs"));
+    }
+
+    #[test]
+    fn test_features_and_paths() {
+        let policy = Policy::from_yaml(
+            "deny: [curl]\n\
+             features: { substitution: true, background: false }\n\
+             paths: { deny: ['.env', 'secrets/*'], read_only: ['*.json'], allow: ['secrets/README.md'] }\n",
+        )
+        .unwrap();
+        assert!(policy.check("echo $(date)").is_allowed());
+        assert!(!policy.check("echo $(curl x)").is_allowed());
+        assert!(!policy.check("sleep 5 &").is_allowed());
+        assert!(!policy.check("cat .env").is_allowed());
+        assert!(!policy.check("cat ./secrets/key").is_allowed());
+        assert!(policy.check("cat secrets/README.md").is_allowed());
+        assert!(!policy.check("grep x --file=secrets/key").is_allowed());
+        assert!(policy.check("cat identity.json").is_allowed());
+        assert!(!policy.check("echo {} > identity.json").is_allowed());
+        assert!(!policy.check("echo {} >&identity.json").is_allowed());
+        assert!(policy.check("ls 2>&1").is_allowed());
+        assert!(!policy.check("cat /x/.env").is_allowed());
+        assert!(!policy.check("cat /a/b/secrets/key").is_allowed());
+        assert!(!policy.check("cat < /home/user/.env").is_allowed());
+        assert!(policy.check("cat /x/secrets/README.md").is_allowed());
+        assert!(!policy.check("echo {} > /tmp/identity.json").is_allowed());
+        assert!(policy.check("cat /etc/hostname").is_allowed());
+    }
+
+    #[test]
+    fn test_policy_files_are_strict() {
+        assert!(Policy::from_yaml("mode: allowlist\n").is_ok());
+        assert!(Policy::from_yaml("mode: allow-ish\n").is_err());
+        assert!(Policy::from_yaml("denny: [curl]\n").is_err());
+        assert!(Policy::from_yaml("rules: [{ program: rm, action: maybe }]\n").is_err());
+
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("policy.yaml");
+        std::fs::write(&path, "mode: allowlist\nallow: [ls]\n").unwrap();
+        let policy = Policy::load(&path).unwrap();
+        assert_eq!(policy.source(), path.display().to_string());
+        assert!(Policy::load(&tmp.path().join("missing.yaml")).is_err());
+    }
+
+    #[test]
+    fn test_runs_only_pip() {
+        assert!(runs_only_pip("pip install requests"));
+        assert!(runs_only_pip("pip3 install numpy && uv pip install httpx"));
+        assert!(runs_only_pip("pip install -r requirements.txt > pip.log 2>&1"));
+        assert!(!runs_only_pip("pip install x; python exfil.py"));
+        assert!(!runs_only_pip("pip install x && ./exfil.py"));
+        assert!(!runs_only_pip("pip install x | sh"));
+        assert!(!runs_only_pip("pip install $(python exfil.py)"));
+        assert!(!runs_only_pip("(pip install x)"));
+        assert!(!runs_only_pip("uv run exfil.py"));
+        assert!(!runs_only_pip("python -c 'import pip'"));
+        assert!(!runs_only_pip(""));
+    }
+
+    #[test]
+    fn test_glob() {
+        assert!(glob("mkfs*", "mkfs.ext4"));
+        assert!(glob("*", ""));
+        assert!(glob("a?c", "abc"));
+        assert!(glob("/*", "/"));
+        assert!(glob("secrets/*", "secrets/a/b"));
+        assert!(glob("*.json", "identity.json"));
+        assert!(!glob("apt*", "mapt"));
+        assert!(!glob("a*b", "acbc"));
+        assert!(!glob("?", ""));
+    }
+
+    // ── Fuzzing ──
+
+    const FRAGMENTS: &[&str] = &[
+        "ls", "curl", " ", " ", "\n", ";", ";;", "&", "&&", "|", "||", "|&", "(", ")", "{", "}", "$(", "`", "'", "\"",
+        "\\", "$", "${", "$((", "))", "$'\\x", "<<", "<<-", "EOF", "<<<", ">", ">>", "2>&1", "&>", "<(", "#", "~",
+        "..", "*", "?", "[", "{a,b}", "if", "then", "elif", "else", "fi", "for", "in", "do", "done", "while", "case",
+        "esac", "function", "f()", "=", "x=1", "é", "\t",
+    ];
+
+    #[test]
+    fn test_fuzz_never_panics() {
+        let policy = Policy::builtin();
+        let mut rng = StdRng::seed_from_u64(0x5eed);
+        for _ in 0..5000 {
+            let mut command = String::new();
+            for _ in 0..rng.gen_range(0..24) {
+                command.push_str(FRAGMENTS[rng.gen_range(0..FRAGMENTS.len())]);
+            }
+            let _ = policy.check(&command);
+        }
+        for _ in 0..5000 {
+            let bytes: Vec<u8> = (0..rng.gen_range(0..64)).map(|_| rng.gen()).collect();
+            let _ = policy.check(&String::from_utf8_lossy(&bytes));
+        }
+    }
+
+    const SAFE: &[&str] = &["ls", "cat", "echo", "grep", "wc", "head", "sort", "mkdir"];
+    const UNSAFE: &[&str] = &["curl", "wget", "ssh", "nc", "bash", "sh", "perl", "sudo"];
+    const ARGS: &[&str] = &["-l", "notes.md", "'two words'", "\"x y\"", "a\\ b", "$HOME", "*.txt", "--", "42"];
+
+    /// A program name, quoted so a prefix check wouldn't see it.
+    fn disguise(rng: &mut StdRng, name: &str) -> String {
+        match rng.gen_range(0..7) {
+            0 => name.to_string(),
+            1 => format!("'{}'", name),
+            2 => format!("\"{}\"", name),
+            3 => {
+                let split = rng.gen_range(0..=name.len());
+                format!("{}''{}", &name[..split], &name[split..])
+            }
+            4 => name.chars().map(|c| format!("\\{}", c)).collect(),
+            5 => format!("$'{}'", name.bytes().map(|b| format!("\\x{:02x}", b)).collect::<String>()),
+            _ => format!("/usr/bin/{}", name),
+        }
+    }
+
+    /// A random script, and the programs it runs.
+    fn generate(rng: &mut StdRng, depth: usize, programs: &mut Vec<&'static str>) -> String {
+        let mut pipelines = Vec::new();
+        for _ in 0..rng.gen_range(1..4) {
+            let mut segments = Vec::new();
+            for _ in 0..rng.gen_range(1..3) {
+                segments.push(generate_segment(rng, depth, programs));
+            }
+            pipelines.push(segments.join(" | "));
+        }
+        let mut script = pipelines[0].clone();
+        for pipeline in &pipelines[1..] {
+            let separator = ["; ", " && ", " || ", "\n", " & "][rng.gen_range(0..5)];
+            script.push_str(separator);
+            script.push_str(pipeline);
+        }
+        script
+    }
+
+    fn generate_segment(rng: &mut StdRng, depth: usize, programs: &mut Vec<&'static str>) -> String {
+        let choice = if depth < 3 { rng.gen_range(0..10) } else { 0 };
+        match choice {
+            5 => format!("( {} )", generate(rng, depth + 1, programs)),
+            6 => format!("{{ {}; }}", generate(rng, depth + 1, programs)),
+            7 => {
+                let condition = generate(rng, depth + 1, programs);
+                format!("if {}; then {}; fi", condition, generate(rng, depth + 1, programs))
+            }
+            8 => format!("for f in a b; do {}; done", generate(rng, depth + 1, programs)),
+            // Spaced, or `$((` would read as arithmetic
+            9 => format!("echo \"$( {} )\"", generate(rng, depth + 1, programs)),
+            _ => {
+                let program = if rng.gen_bool(0.2) {
+                    UNSAFE[rng.gen_range(0..UNSAFE.len())]
+                } else {
+                    SAFE[rng.gen_range(0..SAFE.len())]
+                };
+                programs.push(program);
+                let mut segment = disguise(rng, program);
+                for _ in 0..rng.gen_range(0..3) {
+                    segment.push(' ');
+                    segment.push_str(ARGS[rng.gen_range(0..ARGS.len())]);
+                }
+                if rng.gen_bool(0.2) {
+                    segment.push_str(" > out.txt");
+                }
+                segment
+            }
+        }
+    }
+
+    #[test]
+    fn test_fuzz_every_program_is_found() {
+        let denylist = Policy::from_yaml(&format!(
+            "deny: [{}]\nfeatures: {{ substitution: true }}\n",
+            UNSAFE.join(", ")
+        ))
+        .unwrap();
+        let allowlist = Policy::from_yaml(&format!(
+            "mode: allowlist\nallow: [{}]\nfeatures: {{ substitution: true }}\n",
+            SAFE.join(", ")
+        ))
+        .unwrap();
+        let mut rng = StdRng::seed_from_u64(0xa11e);
+        for _ in 0..3000 {
+            let mut programs = Vec::new();
+            let script = generate(&mut rng, 0, &mut programs);
+            // `echo "$(...)"` runs echo too
+            let bad = programs.iter().any(|p| UNSAFE.contains(p));
+            for policy in [&denylist, &allowlist] {
+                let decision = policy.check(&script);
+                assert_eq!(decision.is_allowed(), !bad, "{:?}: {:?}", script, decision);
+            }
+        }
+    }
+}
@@ -0,0 +1,1192 @@
+//! A POSIX shell parser: enough of the grammar to find every command a line
+//! would run, however it's quoted, chained or nested. Pipelines, `&&` / `||`
+//! lists, subshells and groups, `if` / `while` / `until` / `for` / `case`,
+//! functions, redirections, here-documents, and command substitution inside
+//! words and here-documents all come out as segments to check.
+//!
+//! It doesn't expand anything. Words keep their text after quote removal
+//! plus a note of each expansion, so a policy can refuse what it can't see
+//! through. A few bash features that would hide a command (`$'...'`
+//! escapes, brace expansion) are decoded or flagged the same way, and
+//! anything it can't follow is an error rather than a guess.
+
+use anyhow::{bail, Result};
+
+/// A parsed command line.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Script {
+    pub pipelines: Vec<Pipeline>,
+    /// Here-document bodies, wherever in the line they appeared
+    pub heredocs: Vec<Word>,
+}
+
+/// Commands joined by `|`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Pipeline {
+    pub segments: Vec<Segment>,
+    /// Run in the background (`&`)
+    pub background: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Segment {
+    Simple(Simple),
+    Compound(Compound),
+}
+
+/// A program, its arguments and redirections.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Simple {
+    /// `NAME=value` words before the program
+    pub assignments: Vec<Word>,
+    /// The program, then its arguments
+    pub words: Vec<Word>,
+    pub redirects: Vec<Redirect>,
+}
+
+/// A command built from other commands.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Compound {
+    /// "subshell", "group", "if", "while", "until", "for", "case" or "function"
+    pub kind: &'static str,
+    pub body: Script,
+    /// Words it expands without running them: a `for` list, a `case`
+    /// subject and patterns, a function's name
+    pub words: Vec<Word>,
+    pub redirects: Vec<Redirect>,
+}
+
+/// One word, after quote removal.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Word {
+    /// The text, with expansions left as written
+    pub text: String,
+    pub expansions: Vec<Expansion>,
+}
+
+impl Word {
+    /// Whether the word means exactly its text.
+    pub fn is_literal(&self) -> bool {
+        self.expansions.is_empty()
+    }
+
+    /// Commands substituted into the word.
+    pub fn substitutions(&self) -> impl Iterator<Item = &Script> {
+        self.expansions.iter().filter_map(|e| match e {
+            Expansion::Substitution(script) => Some(script),
+            _ => None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expansion {
+    /// `$name`, `$1`, `$?`, ...
+    Variable(String),
+    /// `${...}`
+    Braced(String),
+    /// `$(...)` or backticks
+    Substitution(Script),
+    /// `$((...))`
+    Arithmetic(String),
+    /// `~` at the start of a word
+    Tilde,
+    /// Unquoted `*`, `?` or `[`
+    Glob,
+    /// Unquoted `{a,b}` or `{1..3}` (bash)
+    Brace,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Redirect {
+    /// The file descriptor written before the operator (`2>`)
+    pub fd: Option<u32>,
+    pub op: RedirectOp,
+    /// A file, a descriptor (for `>&`) or a here-document delimiter
+    pub target: Word,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RedirectOp {
+    /// `<`
+    Read,
+    /// `>`, `>|`, `&>`
+    Write,
+    /// `>>`, `&>>`
+    Append,
+    /// `<>`
+    ReadWrite,
+    /// `<&`, `>&`
+    Duplicate,
+    /// `<<`, `<<-`
+    HereDoc,
+    /// `<<<`
+    HereString,
+}
+
+/// Parse `input`.
+pub fn parse(input: &str) -> Result<Script> {
+    let mut parser = Parser::new(input, 0);
+    let pipelines = parser.list()?;
+    match parser.peek()? {
+        Token::Eof => {}
+        other => bail!("unexpected {}", other.describe()),
+    }
+    parser.read_heredocs()?;
+    Ok(Script {
+        pipelines,
+        heredocs: parser.bodies,
+    })
+}
+
+/// Deepest nesting of substitutions and compound commands.
+const MAX_DEPTH: usize = 64;
+
+/// Words that are reserved in command position.
+const CLOSERS: &[&str] = &["then", "elif", "else", "fi", "do", "done", "esac", "}"];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word {
+        word: Word,
+        /// No quoting, escapes or expansions, so it may be a reserved word
+        plain: bool,
+        /// Starts with `NAME=`
+        assignment: bool,
+    },
+    Op(&'static str),
+    Redirect(Option<u32>, &'static str),
+    Newline,
+    Eof,
+}
+
+impl Token {
+    fn describe(&self) -> String {
+        match self {
+            Token::Word { word, .. } => format!("'{}'", word.text),
+            Token::Op(op) | Token::Redirect(_, op) => format!("'{}'", op),
+            Token::Newline => "newline".to_string(),
+            Token::Eof => "end of command".to_string(),
+        }
+    }
+
+    /// Whether this is the unquoted word `word`.
+    fn is(&self, word: &str) -> bool {
+        matches!(self, Token::Word { word: w, plain: true, .. } if w.text == word)
+    }
+}
+
+struct PendingHeredoc {
+    delimiter: String,
+    literal: bool,
+    strip_tabs: bool,
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    /// The next token, already lexed; `pos` is past it
+    peeked: Option<Token>,
+    pending: Vec<PendingHeredoc>,
+    bodies: Vec<Word>,
+    depth: usize,
+}
+
+impl Parser {
+    fn new(input: &str, depth: usize) -> Self {
+        Self {
+            chars: input.chars().collect(),
+            pos: 0,
+            peeked: None,
+            pending: Vec::new(),
+            bodies: Vec::new(),
+            depth,
+        }
+    }
+
+    // ── Grammar ──
+
+    /// Pipelines up to a closing `)`, `;;`, reserved word or the end.
+    fn list(&mut self) -> Result<Vec<Pipeline>> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            bail!("nested too deeply");
+        }
+        let mut pipelines = Vec::new();
+        loop {
+            self.skip_newlines()?;
+            if self.at_list_end()? {
+                break;
+            }
+            let start = pipelines.len();
+            self.and_or(&mut pipelines)?;
+            match self.peek()?.clone() {
+                Token::Op(";") | Token::Newline => {
+                    self.advance()?;
+                }
+                Token::Op("&") => {
+                    self.advance()?;
+                    for pipeline in &mut pipelines[start..] {
+                        pipeline.background = true;
+                    }
+                }
+                _ if self.at_list_end()? => {}
+                other => bail!("unexpected {}", other.describe()),
+            }
+        }
+        self.depth -= 1;
+        Ok(pipelines)
+    }
+
+    fn at_list_end(&mut self) -> Result<bool> {
+        let token = self.peek()?;
+        Ok(matches!(token, Token::Eof | Token::Op(")") | Token::Op(";;"))
+            || CLOSERS.iter().any(|w| token.is(w)))
+    }
+
+    fn and_or(&mut self, pipelines: &mut Vec<Pipeline>) -> Result<()> {
+        pipelines.push(self.pipeline()?);
+        while matches!(self.peek()?, Token::Op("&&") | Token::Op("||")) {
+            self.advance()?;
+            self.skip_newlines()?;
+            pipelines.push(self.pipeline()?);
+        }
+        Ok(())
+    }
+
+    fn pipeline(&mut self) -> Result<Pipeline> {
+        if self.peek()?.is("!") {
+            self.advance()?;
+        }
+        let mut segments = vec![self.command()?];
+        while matches!(self.peek()?, Token::Op("|") | Token::Op("|&")) {
+            self.advance()?;
+            self.skip_newlines()?;
+            segments.push(self.command()?);
+        }
+        Ok(Pipeline {
+            segments,
+            background: false,
+        })
+    }
+
+    fn command(&mut self) -> Result<Segment> {
+        let token = self.peek()?.clone();
+        let (kind, body, words) = if token == Token::Op("(") {
+            self.advance()?;
+            let body = self.list()?;
+            self.expect_op(")")?;
+            ("subshell", body, Vec::new())
+        } else if token.is("{") {
+            self.advance()?;
+            let body = self.list()?;
+            self.expect_word("}")?;
+            ("group", body, Vec::new())
+        } else if token.is("if") {
+            self.advance()?;
+            ("if", self.if_clause()?, Vec::new())
+        } else if token.is("while") || token.is("until") {
+            self.advance()?;
+            let mut body = self.list()?;
+            self.expect_word("do")?;
+            body.extend(self.list()?);
+            self.expect_word("done")?;
+            (if token.is("while") { "while" } else { "until" }, body, Vec::new())
+        } else if token.is("for") {
+            self.advance()?;
+            self.for_clause()?
+        } else if token.is("case") {
+            self.advance()?;
+            self.case_clause()?
+        } else if token.is("function") {
+            self.advance()?;
+            let name = self.expect_any_word()?;
+            if self.peek()? == &Token::Op("(") {
+                self.advance()?;
+                self.expect_op(")")?;
+            }
+            self.function_body(name)?
+        } else if CLOSERS.iter().any(|w| token.is(w)) {
+            bail!("unexpected {}", token.describe());
+        } else {
+            return self.simple();
+        };
+        let redirects = self.redirects()?;
+        Ok(Segment::Compound(Compound {
+            kind,
+            body: Script {
+                pipelines: body,
+                heredocs: Vec::new(),
+            },
+            words,
+            redirects,
+        }))
+    }
+
+    fn if_clause(&mut self) -> Result<Vec<Pipeline>> {
+        let mut body = self.list()?;
+        self.expect_word("then")?;
+        body.extend(self.list()?);
+        loop {
+            if self.peek()?.is("elif") {
+                self.advance()?;
+                body.extend(self.list()?);
+                self.expect_word("then")?;
+                body.extend(self.list()?);
+            } else if self.peek()?.is("else") {
+                self.advance()?;
+                body.extend(self.list()?);
+            } else {
+                self.expect_word("fi")?;
+                return Ok(body);
+            }
+        }
+    }
+
+    fn for_clause(&mut self) -> Result<(&'static str, Vec<Pipeline>, Vec<Word>)> {
+        let mut words = vec![self.expect_any_word()?];
+        self.skip_newlines()?;
+        if self.peek()?.is("in") {
+            self.advance()?;
+            while let Token::Word { word, .. } = self.peek()?.clone() {
+                self.advance()?;
+                words.push(word);
+            }
+        }
+        if matches!(self.peek()?, Token::Op(";") | Token::Newline) {
+            self.advance()?;
+        }
+        self.skip_newlines()?;
+        self.expect_word("do")?;
+        let body = self.list()?;
+        self.expect_word("done")?;
+        Ok(("for", body, words))
+    }
+
+    fn case_clause(&mut self) -> Result<(&'static str, Vec<Pipeline>, Vec<Word>)> {
+        let mut words = vec![self.expect_any_word()?];
+        self.skip_newlines()?;
+        self.expect_word("in")?;
+        let mut body = Vec::new();
+        loop {
+            self.skip_newlines()?;
+            if self.peek()?.is("esac") {
+                self.advance()?;
+                return Ok(("case", body, words));
+            }
+            if self.peek()? == &Token::Op("(") {
+                self.advance()?;
+            }
+            words.push(self.expect_any_word()?);
+            while self.peek()? == &Token::Op("|") {
+                self.advance()?;
+                words.push(self.expect_any_word()?);
+            }
+            self.expect_op(")")?;
+            body.extend(self.list()?);
+            if self.peek()? == &Token::Op(";;") {
+                self.advance()?;
+            } else if !self.peek()?.is("esac") {
+                bail!("expected ';;' or 'esac', found {}", self.peek()?.describe());
+            }
+        }
+    }
+
+    fn function_body(&mut self, name: Word) -> Result<(&'static str, Vec<Pipeline>, Vec<Word>)> {
+        self.skip_newlines()?;
+        let body = match self.command()? {
+            Segment::Compound(compound) => compound.body.pipelines,
+            Segment::Simple(_) => bail!("a function body must be a compound command"),
+        };
+        Ok(("function", body, vec![name]))
+    }
+
+    fn simple(&mut self) -> Result<Segment> {
+        let mut simple = Simple::default();
+        loop {
+            match self.peek()?.clone() {
+                Token::Redirect(fd, op) => {
+                    self.advance()?;
+                    simple.redirects.push(self.redirect(fd, op)?);
+                }
+                Token::Word { word, assignment, .. } => {
+                    self.advance()?;
+                    if assignment && simple.words.is_empty() {
+                        simple.assignments.push(word);
+                        continue;
+                    }
+                    simple.words.push(word);
+                    // `name() compound-command`
+                    if simple.words.len() == 1 && simple.assignments.is_empty() && self.peek()? == &Token::Op("(") {
+                        self.advance()?;
+                        self.expect_op(")")?;
+                        let name = simple.words.remove(0);
+                        let (kind, body, words) = self.function_body(name)?;
+                        let redirects = self.redirects()?;
+                        return Ok(Segment::Compound(Compound {
+                            kind,
+                            body: Script {
+                                pipelines: body,
+                                heredocs: Vec::new(),
+                            },
+                            words,
+                            redirects,
+                        }));
+                    }
+                }
+                _ => break,
+            }
+        }
+        if simple.words.is_empty() && simple.assignments.is_empty() && simple.redirects.is_empty() {
+            bail!("expected a command, found {}", self.peek()?.describe());
+        }
+        Ok(Segment::Simple(simple))
+    }
+
+    fn redirects(&mut self) -> Result<Vec<Redirect>> {
+        let mut redirects = Vec::new();
+        while let Token::Redirect(fd, op) = self.peek()?.clone() {
+            self.advance()?;
+            redirects.push(self.redirect(fd, op)?);
+        }
+        Ok(redirects)
+    }
+
+    fn redirect(&mut self, fd: Option<u32>, op: &str) -> Result<Redirect> {
+        let Token::Word { word, plain, .. } = self.advance()? else {
+            bail!("expected a file name after '{}'", op);
+        };
+        let op = match op {
+            "<" => RedirectOp::Read,
+            ">" | ">|" | "&>" => RedirectOp::Write,
+            ">>" | "&>>" => RedirectOp::Append,
+            "<>" => RedirectOp::ReadWrite,
+            "<&" | ">&" => RedirectOp::Duplicate,
+            "<<<" => RedirectOp::HereString,
+            _ => {
+                self.pending.push(PendingHeredoc {
+                    delimiter: word.text.clone(),
+                    literal: !plain,
+                    strip_tabs: op == "<<-",
+                });
+                RedirectOp::HereDoc
+            }
+        };
+        Ok(Redirect { fd, op, target: word })
+    }
+
+    // ── Tokens ──
+
+    fn peek(&mut self) -> Result<&Token> {
+        if self.peeked.is_none() {
+            let token = self.lex()?;
+            self.peeked = Some(token);
+        }
+        Ok(self.peeked.as_ref().unwrap_or(&Token::Eof))
+    }
+
+    fn advance(&mut self) -> Result<Token> {
+        let token = match self.peeked.take() {
+            Some(token) => token,
+            None => self.lex()?,
+        };
+        if token == Token::Newline {
+            self.read_heredocs()?;
+        }
+        Ok(token)
+    }
+
+    fn skip_newlines(&mut self) -> Result<()> {
+        while self.peek()? == &Token::Newline {
+            self.advance()?;
+        }
+        Ok(())
+    }
+
+    fn expect_op(&mut self, op: &str) -> Result<()> {
+        match self.advance()? {
+            Token::Op(found) if found == op => Ok(()),
+            other => bail!("expected '{}', found {}", op, other.describe()),
+        }
+    }
+
+    fn expect_word(&mut self, word: &str) -> Result<()> {
+        let token = self.advance()?;
+        if !token.is(word) {
+            bail!("expected '{}', found {}", word, token.describe());
+        }
+        Ok(())
+    }
+
+    fn expect_any_word(&mut self) -> Result<Word> {
+        match self.advance()? {
+            Token::Word { word, .. } => Ok(word),
+            other => bail!("expected a word, found {}", other.describe()),
+        }
+    }
+
+    // ── Lexing ──
+
+    fn current(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn at(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.pos + offset).copied()
+    }
+
+    fn starts_with(&self, text: &str) -> bool {
+        text.chars().enumerate().all(|(i, c)| self.at(i) == Some(c))
+    }
+
+    fn lex(&mut self) -> Result<Token> {
+        // Blanks, line continuations and comments
+        loop {
+            match self.current() {
+                Some(' ' | '\t' | '\r') => self.pos += 1,
+                Some('\\') if self.at(1) == Some('\n') => self.pos += 2,
+                Some('#') => {
+                    while self.current().is_some_and(|c| c != '\n') {
+                        self.pos += 1;
+                    }
+                }
+                _ => break,
+            }
+        }
+        let Some(c) = self.current() else {
+            return Ok(Token::Eof);
+        };
+        if c == '\n' {
+            self.pos += 1;
+            return Ok(Token::Newline);
+        }
+        for op in ["&&", "||", ";;", "|&", "(", ")", ";", "&", "|"] {
+            // `&>` is a redirection, not a background `&`
+            if self.starts_with(op) && !(op == "&" && self.at(1) == Some('>')) {
+                self.pos += op.chars().count();
+                return Ok(Token::Op(op));
+            }
+        }
+        if let Some(op) = self.redirect_op() {
+            self.pos += op.len();
+            return Ok(Token::Redirect(None, op));
+        }
+        // `2>file`: digits straight before a redirection name the descriptor
+        let digits = self.chars[self.pos..].iter().take_while(|c| c.is_ascii_digit()).count();
+        if digits > 0 {
+            let start = self.pos;
+            self.pos += digits;
+            if let Some(op) = self.redirect_op().filter(|op| !op.starts_with('&')) {
+                let fd = self.chars[start..self.pos].iter().collect::<String>().parse().ok();
+                self.pos += op.len();
+                return Ok(Token::Redirect(fd, op));
+            }
+            self.pos = start;
+        }
+        self.word()
+    }
+
+    fn redirect_op(&self) -> Option<&'static str> {
+        ["&>>", "&>", "<<<", "<<-", "<<", "<&", "<>", ">>", ">&", ">|", "<", ">"]
+            .into_iter()
+            .find(|op| self.starts_with(op))
+    }
+
+    fn word(&mut self) -> Result<Token> {
+        let start = self.pos;
+        let mut word = Word::default();
+        let mut plain = true;
+        while let Some(c) = self.current() {
+            match c {
+                ' ' | '\t' | '\r' | '\n' | ';' | '&' | '|' | '(' | ')' | '<' | '>' => break,
+                '\\' => {
+                    plain = false;
+                    self.pos += 1;
+                    match self.current() {
+                        Some('\n') => self.pos += 1,
+                        Some(c) => {
+                            word.text.push(c);
+                            self.pos += 1;
+                        }
+                        None => {}
+                    }
+                }
+                '\'' => {
+                    plain = false;
+                    self.pos += 1;
+                    loop {
+                        match self.current() {
+                            Some('\'') => break,
+                            Some(c) => word.text.push(c),
+                            None => bail!("unterminated ' quote"),
+                        }
+                        self.pos += 1;
+                    }
+                    self.pos += 1;
+                }
+                '"' => {
+                    plain = false;
+                    self.pos += 1;
+                    self.double_quoted(&mut word)?;
+                }
+                '$' => {
+                    plain = false;
+                    self.dollar(&mut word, false)?;
+                }
+                '`' => {
+                    plain = false;
+                    self.backtick(&mut word)?;
+                }
+                '*' | '?' => {
+                    plain = false;
+                    note(&mut word, Expansion::Glob);
+                    word.text.push(c);
+                    self.pos += 1;
+                }
+                // Only a bracket expression if it closes; `[ -f x ]` runs `[`
+                '[' if self.closes_bracket() => {
+                    plain = false;
+                    note(&mut word, Expansion::Glob);
+                    word.text.push(c);
+                    self.pos += 1;
+                }
+                '~' if self.pos == start => {
+                    plain = false;
+                    note(&mut word, Expansion::Tilde);
+                    word.text.push(c);
+                    self.pos += 1;
+                }
+                '{' => {
+                    if self.is_brace_expansion() {
+                        plain = false;
+                        note(&mut word, Expansion::Brace);
+                    }
+                    word.text.push(c);
+                    self.pos += 1;
+                }
+                _ => {
+                    word.text.push(c);
+                    self.pos += 1;
+                }
+            }
+        }
+        let raw: String = self.chars[start..self.pos].iter().collect();
+        let assignment = raw.split_once('=').is_some_and(|(name, _)| is_name(name));
+        Ok(Token::Word { word, plain, assignment })
+    }
+
+    /// Inside `"..."`, from just past the opening quote.
+    fn double_quoted(&mut self, word: &mut Word) -> Result<()> {
+        loop {
+            match self.current() {
+                None => bail!("unterminated \" quote"),
+                Some('"') => {
+                    self.pos += 1;
+                    return Ok(());
+                }
+                Some('\\') => {
+                    self.pos += 1;
+                    match self.current() {
+                        Some('\n') => self.pos += 1,
+                        Some(c @ ('$' | '`' | '"' | '\\')) => {
+                            word.text.push(c);
+                            self.pos += 1;
+                        }
+                        _ => word.text.push('\\'),
+                    }
+                }
+                Some('$') => self.dollar(word, true)?,
+                Some('`') => self.backtick(word)?,
+                Some(c) => {
+                    word.text.push(c);
+                    self.pos += 1;
+                }
+            }
+        }
+    }
+
+    /// A `$` and whatever it expands.
+    fn dollar(&mut self, word: &mut Word, quoted: bool) -> Result<()> {
+        let next = self.at(1);
+        if self.starts_with("$((") {
+            let inner = self.balanced(3, '(', ')')?;
+            // The closing `))`
+            if self.current() != Some(')') {
+                bail!("unterminated $((");
+            }
+            self.pos += 1;
+            if inner.contains("$(") || inner.contains('`') {
+                bail!("command substitution inside $((...)) isn't supported");
+            }
+            word.text.push_str(&format!("$(({}))", inner));
+            note(word, Expansion::Arithmetic(inner));
+        } else if next == Some('(') {
+            self.pos += 2;
+            let script = self.substitution()?;
+            word.text.push_str("$(...)");
+            note(word, Expansion::Substitution(script));
+        } else if next == Some('{') {
+            let inner = self.balanced(2, '{', '}')?;
+            if inner.contains("$(") || inner.contains('`') {
+                bail!("command substitution inside ${{...}} isn't supported");
+            }
+            word.text.push_str(&format!("${{{}}}", inner));
+            note(word, Expansion::Braced(inner));
+        } else if next == Some('\'') && !quoted {
+            // bash's $'...': decode it, since bash will
+            self.pos += 2;
+            self.ansi_c(word)?;
+        } else if next == Some('"') && !quoted {
+            self.pos += 2;
+            self.double_quoted(word)?;
+        } else if next.is_some_and(|c| c.is_ascii_alphabetic() || c == '_') {
+            self.pos += 1;
+            let mut name = String::new();
+            while let Some(c) = self.current().filter(|c| c.is_ascii_alphanumeric() || *c == '_') {
+                name.push(c);
+                self.pos += 1;
+            }
+            word.text.push('$');
+            word.text.push_str(&name);
+            note(word, Expansion::Variable(name));
+        } else if let Some(c) = next.filter(|c| c.is_ascii_digit() || "@*#?$!-".contains(*c)) {
+            self.pos += 2;
+            word.text.push('$');
+            word.text.push(c);
+            note(word, Expansion::Variable(c.to_string()));
+        } else {
+            word.text.push('$');
+            self.pos += 1;
+        }
+        Ok(())
+    }
+
+    /// The text between `open` (at `skip` - 1) and its matching `close`,
+    /// leaving `pos` past the close.
+    fn balanced(&mut self, skip: usize, open: char, close: char) -> Result<String> {
+        self.pos += skip;
+        let mut depth = 1;
+        let mut inner = String::new();
+        while let Some(c) = self.current() {
+            self.pos += 1;
+            if c == '\\' {
+                inner.push(c);
+                if let Some(escaped) = self.current() {
+                    inner.push(escaped);
+                    self.pos += 1;
+                }
+                continue;
+            }
+            if c == open {
+                depth += 1;
+            } else if c == close {
+                depth -= 1;
+                if depth == 0 {
+                    return Ok(inner);
+                }
+            }
+            inner.push(c);
+        }
+        bail!("unterminated '{}'", open)
+    }
+
+    /// `$(...)`, from just past the `(`.
+    fn substitution(&mut self) -> Result<Script> {
+        let pipelines = self.list()?;
+        self.expect_op(")")?;
+        Ok(Script {
+            pipelines,
+            heredocs: Vec::new(),
+        })
+    }
+
+    /// `` `...` ``, from the opening backtick.
+    fn backtick(&mut self, word: &mut Word) -> Result<()> {
+        self.pos += 1;
+        let mut inner = String::new();
+        loop {
+            match self.current() {
+                None => bail!("unterminated ` quote"),
+                Some('`') => break,
+                Some('\\') if self.at(1).is_some_and(|c| "$`\\".contains(c)) => {
+                    inner.push(self.at(1).unwrap_or('\\'));
+                    self.pos += 2;
+                    continue;
+                }
+                Some(c) => inner.push(c),
+            }
+            self.pos += 1;
+        }
+        self.pos += 1;
+        let mut nested = Parser::new(&inner, self.depth + 1);
+        if nested.depth > MAX_DEPTH {
+            bail!("nested too deeply");
+        }
+        let pipelines = nested.list()?;
+        if nested.peek()? != &Token::Eof {
+            bail!("unexpected {} in backticks", nested.peek()?.describe());
+        }
+        nested.read_heredocs()?;
+        self.bodies.extend(nested.bodies);
+        word.text.push_str("`...`");
+        note(
+            word,
+            Expansion::Substitution(Script {
+                pipelines,
+                heredocs: Vec::new(),
+            }),
+        );
+        Ok(())
+    }
+
+    /// bash's `$'...'`, from just past the opening quote.
+    fn ansi_c(&mut self, word: &mut Word) -> Result<()> {
+        loop {
+            let Some(c) = self.current() else {
+                bail!("unterminated $' quote");
+            };
+            self.pos += 1;
+            match c {
+                '\'' => return Ok(()),
+                '\\' => {
+                    let Some(e) = self.current() else {
+                        bail!("unterminated $' quote");
+                    };
+                    self.pos += 1;
+                    let decoded = match e {
+                        'a' => Some('\x07'),
+                        'b' => Some('\x08'),
+                        'e' | 'E' => Some('\x1b'),
+                        'f' => Some('\x0c'),
+                        'n' => Some('\n'),
+                        'r' => Some('\r'),
+                        't' => Some('\t'),
+                        'v' => Some('\x0b'),
+                        '\\' | '\'' | '"' | '?' => Some(e),
+                        '0'..='7' => {
+                            self.pos -= 1;
+                            self.code(8, 3)
+                        }
+                        'x' => self.code(16, 2),
+                        'u' => self.code(16, 4),
+                        'U' => self.code(16, 8),
+                        'c' => {
+                            let control = self.current().map(|c| ((c as u32) & 0x1f) as u8 as char);
+                            if control.is_some() {
+                                self.pos += 1;
+                            }
+                            control
+                        }
+                        _ => {
+                            word.text.push('\\');
+                            Some(e)
+                        }
+                    };
+                    word.text.extend(decoded);
+                }
+                _ => word.text.push(c),
+            }
+        }
+    }
+
+    /// Up to `max` digits in `radix`, as a character.
+    fn code(&mut self, radix: u32, max: usize) -> Option<char> {
+        let mut value = 0u32;
+        let mut read = 0;
+        while read < max {
+            let Some(digit) = self.current().and_then(|c| c.to_digit(radix)) else {
+                break;
+            };
+            value = value.saturating_mul(radix).saturating_add(digit);
+            self.pos += 1;
+            read += 1;
+        }
+        if read == 0 {
+            return None;
+        }
+        char::from_u32(value)
+    }
+
+    /// Whether the `[` here has a `]` later in the word.
+    fn closes_bracket(&self) -> bool {
+        self.chars[self.pos + 1..]
+            .iter()
+            .take_while(|c| !" \t\r\n;&|()<>".contains(**c))
+            .any(|&c| c == ']')
+    }
+
+    /// Whether the `{` here starts a bash brace expansion.
+    fn is_brace_expansion(&self) -> bool {
+        let mut depth = 0;
+        let mut list = false;
+        for (i, &c) in self.chars[self.pos..].iter().enumerate() {
+            match c {
+                ' ' | '\t' | '\n' | ';' | '&' | '|' | '(' | ')' | '<' | '>' | '"' | '\'' => return false,
+                '{' => depth += 1,
+                '}' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return list;
+                    }
+                }
+                ',' if depth == 1 => list = true,
+                '.' if depth == 1 && self.chars.get(self.pos + i + 1) == Some(&'.') => list = true,
+                _ => {}
+            }
+        }
+        false
+    }
+
+    // ── Here-documents ──
+
+    /// Read the bodies of here-documents whose line has ended.
+    fn read_heredocs(&mut self) -> Result<()> {
+        for heredoc in std::mem::take(&mut self.pending) {
+            let mut body = String::new();
+            while self.pos < self.chars.len() {
+                let end = self.chars[self.pos..]
+                    .iter()
+                    .position(|&c| c == '\n')
+                    .map_or(self.chars.len(), |i| self.pos + i);
+                let line: String = self.chars[self.pos..end].iter().collect();
+                self.pos = (end + 1).min(self.chars.len());
+                let line = if heredoc.strip_tabs {
+                    line.trim_start_matches('\t').to_string()
+                } else {
+                    line
+                };
+                if line == heredoc.delimiter {
+                    break;
+                }
+                body.push_str(&line);
+                body.push('\n');
+            }
+            let word = if heredoc.literal {
+                Word {
+                    text: body,
+                    expansions: Vec::new(),
+                }
+            } else {
+                // Expanded like a double-quoted string, without the quotes
+                let mut nested = Parser::new(&body, self.depth + 1);
+                let word = nested.heredoc_body()?;
+                self.bodies.extend(nested.bodies);
+                word
+            };
+            self.bodies.push(word);
+        }
+        Ok(())
+    }
+
+    fn heredoc_body(&mut self) -> Result<Word> {
+        let mut word = Word::default();
+        while let Some(c) = self.current() {
+            match c {
+                '\\' if self.at(1).is_some_and(|c| "$`\\\n".contains(c)) => {
+                    if self.at(1) != Some('\n') {
+                        word.text.push(self.at(1).unwrap_or('\\'));
+                    }
+                    self.pos += 2;
+                }
+                '$' => self.dollar(&mut word, true)?,
+                '`' => self.backtick(&mut word)?,
+                _ => {
+                    word.text.push(c);
+                    self.pos += 1;
+                }
+            }
+        }
+        Ok(word)
+    }
+}
+
+fn note(word: &mut Word, expansion: Expansion) {
+    let repeat = matches!(expansion, Expansion::Glob | Expansion::Tilde | Expansion::Brace);
+    if !(repeat && word.expansions.contains(&expansion)) {
+        word.expansions.push(expansion);
+    }
+}
+
+fn is_name(text: &str) -> bool {
+    let mut chars = text.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Each simple command's words, in order, nested ones included.
+    fn commands(script: &Script) -> Vec<Vec<String>> {
+        let mut out = Vec::new();
+        collect(script, &mut out);
+        out
+    }
+
+    fn collect(script: &Script, out: &mut Vec<Vec<String>>) {
+        let words = |word: &Word, out: &mut Vec<Vec<String>>| {
+            for nested in word.substitutions() {
+                collect(nested, out);
+            }
+        };
+        for pipeline in &script.pipelines {
+            for segment in &pipeline.segments {
+                match segment {
+                    Segment::Simple(simple) => {
+                        out.push(simple.words.iter().map(|w| w.text.clone()).collect());
+                        for word in simple.words.iter().chain(&simple.assignments) {
+                            words(word, out);
+                        }
+                    }
+                    Segment::Compound(compound) => {
+                        for word in &compound.words {
+                            words(word, out);
+                        }
+                        collect(&compound.body, out);
+                    }
+                }
+            }
+        }
+        for body in &script.heredocs {
+            words(body, out);
+        }
+    }
+
+    fn programs(input: &str) -> Vec<String> {
+        commands(&parse(input).unwrap())
+            .into_iter()
+            .filter_map(|words| words.into_iter().next())
+            .collect()
+    }
+
+    #[test]
+    fn test_lists_and_pipelines() {
+        let script = parse("ls -la | grep x && echo ok; cat f &\nwc -l").unwrap();
+        assert_eq!(script.pipelines.len(), 4);
+        assert_eq!(script.pipelines[0].segments.len(), 2);
+        assert!(script.pipelines[2].background);
+        assert_eq!(programs("ls -la | grep x && echo ok; cat f &\nwc -l"), ["ls", "grep", "echo", "cat", "wc"]);
+    }
+
+    #[test]
+    fn test_quotes_are_removed() {
+        assert_eq!(programs(r#"c'u'rl x; "wget" y; \ssh z; e"c"ho 'a;b' "c|d""#), ["curl", "wget", "ssh", "echo"]);
+        let script = parse(r#"echo "a $HOME b" 'c $HOME'"#).unwrap();
+        let Segment::Simple(simple) = &script.pipelines[0].segments[0] else {
+            panic!("not simple");
+        };
+        assert_eq!(simple.words[1].text, "a $HOME b");
+        assert_eq!(simple.words[1].expansions, [Expansion::Variable("HOME".into())]);
+        assert!(simple.words[2].is_literal());
+    }
+
+    #[test]
+    fn test_substitutions_are_parsed() {
+        assert_eq!(programs("echo $(curl x | sh)"), ["echo", "curl", "sh"]);
+        assert_eq!(programs("echo \"id: `whoami`\""), ["echo", "whoami"]);
+        assert_eq!(programs("echo $(echo $(wget y))"), ["echo", "echo", "wget"]);
+    }
+
+    #[test]
+    fn test_compound_commands() {
+        let input = "if [ -f x ]; then cat x; elif true; then :; else rm x; fi; \
+                     while read l; do echo $l; done < f; for f in *.txt; do wc $f; done; \
+                     (cd d && ls); { pwd; }; case $x in a|b) curl ;; *) ls ;; esac";
+        assert_eq!(
+            programs(input),
+            ["[", "cat", "true", ":", "rm", "read", "echo", "wc", "cd", "ls", "pwd", "curl", "ls"]
+        );
+        assert_eq!(programs("f() { wget x; }; f"), ["wget", "f"]);
+        assert_eq!(programs("function g { nc -l; }"), ["nc"]);
+    }
+
+    #[test]
+    fn test_redirections() {
+        let script = parse("cmd 2>err.txt >>log <in 2>&1 &>all").unwrap();
+        let Segment::Simple(simple) = &script.pipelines[0].segments[0] else {
+            panic!("not simple");
+        };
+        let ops: Vec<_> = simple.redirects.iter().map(|r| (r.fd, r.op, r.target.text.as_str())).collect();
+        assert_eq!(
+            ops,
+            [
+                (Some(2), RedirectOp::Write, "err.txt"),
+                (None, RedirectOp::Append, "log"),
+                (None, RedirectOp::Read, "in"),
+                (Some(2), RedirectOp::Duplicate, "1"),
+                (None, RedirectOp::Write, "all"),
+            ]
+        );
+        assert_eq!(simple.words.len(), 1);
+    }
+
+    #[test]
+    fn test_heredocs() {
+        let script = parse("cat > notes.md << EOF\n# Title\nhello $(whoami)\nEOF\necho done").unwrap();
+        assert_eq!(script.pipelines.len(), 2);
+        assert_eq!(script.heredocs.len(), 1);
+        assert_eq!(script.heredocs[0].text, "# Title\nhello $(...)\n");
+        assert_eq!(programs("cat << EOF\n$(id)\nEOF"), ["cat", "id"]);
+        // Quoted delimiters keep the body literal
+        assert_eq!(programs("cat << 'EOF'\n$(id)\nEOF"), ["cat"]);
+        assert_eq!(programs("cat <<-\"END\"\n\t`id`\n\tEND\nls"), ["cat", "ls"]);
+    }
+
+    #[test]
+    fn test_assignments() {
+        let script = parse("A=1 B=\"x y\" env").unwrap();
+        let Segment::Simple(simple) = &script.pipelines[0].segments[0] else {
+            panic!("not simple");
+        };
+        assert_eq!(simple.assignments.len(), 2);
+        assert_eq!(simple.words[0].text, "env");
+        // Quoted names aren't assignments
+        assert_eq!(programs("\"A\"=1 x"), ["A=1"]);
+    }
+
+    #[test]
+    fn test_bash_obfuscation_is_seen_through() {
+        assert_eq!(programs(r"$'\x63\x75rl' x"), ["curl"]);
+        let script = parse("{curl,evil.com}").unwrap();
+        let Segment::Simple(simple) = &script.pipelines[0].segments[0] else {
+            panic!("not simple");
+        };
+        assert_eq!(simple.words[0].expansions, [Expansion::Brace]);
+    }
+
+    #[test]
+    fn test_comments_and_continuations() {
+        assert_eq!(programs("ls # ; curl x"), ["ls"]);
+        assert_eq!(programs("echo a\\\nb; ls"), ["echo", "ls"]);
+        assert_eq!(programs("echo a#b"), ["echo"]);
+    }
+
+    #[test]
+    fn test_malformed_input_is_an_error() {
+        for input in [
+            "echo 'open",
+            "echo \"open",
+            "echo $(ls",
+            "echo `ls",
+            "if true; then ls",
+            "ls |",
+            "ls && ",
+            "cat <(curl x)",
+            ")",
+            "done",
+            "; ls",
+            "echo ${x:-$(id)}",
+        ] {
+            assert!(parse(input).is_err(), "parsed {:?}", input);
+        }
+    }
+
+    #[test]
+    fn test_deep_nesting_is_refused() {
+        let deep = format!("{}ls{}", "$(".repeat(200), ")".repeat(200));
+        assert!(parse(&deep).is_err());
+        let deep = format!("{}ls{}", "( ".repeat(200), " )".repeat(200));
+        assert!(parse(&deep).is_err());
+    }
+}
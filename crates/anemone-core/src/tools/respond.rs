@@ -1,7 +1,48 @@
 //! Conversation tool — respond to user with timeout.
-//! The actual respond handling lives in Brain (Phase 3),
-//! since it requires async waiting and broadcast channels.
-//! This module provides the timeout constant and helper types.
+//! The tool only hands the message over ([`ToolOutput::Converse`]); waiting
+//! for the reply lives in Brain, since it needs the command channel.
+
+use anyhow::Result;
+use async_trait::async_trait;
+use serde_json::json;
+
+use super::{str_arg, Tool, ToolContext, ToolOutput};
+use crate::types::ActivityData;
 
 /// Default conversation reply timeout in seconds.
 pub const CONVERSATION_TIMEOUT_SECS: u64 = 15;
+
+/// The `respond` tool.
+pub struct RespondTool;
+
+#[async_trait]
+impl Tool for RespondTool {
+    fn name(&self) -> &str {
+        "respond"
+    }
+
+    fn description(&self) -> &str {
+        "Talk to your owner! Use this whenever you hear their voice and want to reply. After you speak, they might say something back — if they do, use respond AGAIN to keep the conversation going. You can go back and forth as many times as you like."
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "message": { "type": "string", "description": "What you say back to them" }
+            },
+            "required": ["message"]
+        })
+    }
+
+    fn activity(&self, _arguments: &serde_json::Value) -> ActivityData {
+        ActivityData {
+            activity_type: "conversing".to_string(),
+            detail: "Talking to someone...".to_string(),
+        }
+    }
+
+    async fn execute(&self, arguments: &serde_json::Value, _ctx: &mut ToolContext<'_>) -> Result<ToolOutput> {
+        Ok(ToolOutput::Converse(str_arg(arguments, "message").to_string()))
+    }
+}
@@ -0,0 +1,163 @@
+//! Landlock: an unprivileged, kernel-enforced allowlist of what a process
+//! may do to the filesystem. Everything here that runs in the child is a
+//! plain syscall on memory prepared beforehand.
+
+use std::ffi::CString;
+use std::io;
+
+const CREATE_RULESET_VERSION: u32 = 1;
+const RULE_PATH_BENEATH: u32 = 1;
+
+pub const EXECUTE: u64 = 1 << 0;
+pub const WRITE_FILE: u64 = 1 << 1;
+pub const READ_FILE: u64 = 1 << 2;
+pub const READ_DIR: u64 = 1 << 3;
+const REFER: u64 = 1 << 13;
+pub const TRUNCATE: u64 = 1 << 14;
+pub const IOCTL_DEV: u64 = 1 << 15;
+
+/// Rights that apply to a file rather than a directory.
+const FILE_RIGHTS: u64 = EXECUTE | WRITE_FILE | READ_FILE | TRUNCATE | IOCTL_DEV;
+
+#[repr(C)]
+struct RulesetAttr {
+    handled_access_fs: u64,
+}
+
+#[repr(C, packed)]
+struct PathBeneathAttr {
+    allowed_access: u64,
+    parent_fd: libc::c_int,
+}
+
+/// The Landlock ABI the kernel speaks, or 0 if it has none.
+pub fn abi_version() -> i64 {
+    let version = unsafe {
+        libc::syscall(
+            libc::SYS_landlock_create_ruleset,
+            std::ptr::null::<RulesetAttr>(),
+            0usize,
+            CREATE_RULESET_VERSION,
+        )
+    };
+    version.max(0)
+}
+
+/// Every filesystem right `abi` knows about.
+fn handled_rights(abi: i64) -> u64 {
+    let mut rights = (1 << 13) - 1;
+    if abi >= 2 {
+        rights |= REFER;
+    }
+    if abi >= 3 {
+        rights |= TRUNCATE;
+    }
+    if abi >= 5 {
+        rights |= IOCTL_DEV;
+    }
+    rights
+}
+
+/// What may be done beneath one path.
+struct Rule {
+    path: CString,
+    access: u64,
+}
+
+/// A ruleset, built in the parent and enforced in the child with
+/// [`restrict_self`](Self::restrict_self).
+pub struct Ruleset {
+    handled: u64,
+    rules: Vec<Rule>,
+}
+
+impl Ruleset {
+    pub fn new(abi: i64) -> Self {
+        Self {
+            handled: handled_rights(abi),
+            rules: Vec::new(),
+        }
+    }
+
+    /// Allow reading and running anything beneath `path`.
+    pub fn read_only(&mut self, path: &str) {
+        self.allow(path, EXECUTE | READ_FILE | READ_DIR);
+    }
+
+    /// Allow everything beneath `path`.
+    pub fn full(&mut self, path: &str) {
+        self.allow(path, self.handled);
+    }
+
+    /// Allow `access` beneath `path` (as far as this kernel handles it).
+    pub fn allow(&mut self, path: &str, access: u64) {
+        if let Ok(path) = CString::new(path) {
+            let access = access & self.handled;
+            self.rules.push(Rule { path, access });
+        }
+    }
+
+    /// Confine the calling process, and everything it starts, to the rules.
+    /// Paths that don't exist are skipped. Needs `no_new_privs` (or
+    /// CAP_SYS_ADMIN) first.
+    pub fn restrict_self(&self) -> io::Result<()> {
+        let attr = RulesetAttr {
+            handled_access_fs: self.handled,
+        };
+        let ruleset = unsafe {
+            libc::syscall(
+                libc::SYS_landlock_create_ruleset,
+                &attr as *const RulesetAttr,
+                std::mem::size_of::<RulesetAttr>(),
+                0u32,
+            )
+        };
+        if ruleset < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let ruleset = ruleset as libc::c_int;
+
+        let result = self.add_rules(ruleset).and_then(|()| {
+            let restricted = unsafe { libc::syscall(libc::SYS_landlock_restrict_self, ruleset, 0u32) };
+            if restricted < 0 {
+                Err(io::Error::last_os_error())
+            } else {
+                Ok(())
+            }
+        });
+        unsafe { libc::close(ruleset) };
+        result
+    }
+
+    fn add_rules(&self, ruleset: libc::c_int) -> io::Result<()> {
+        for rule in &self.rules {
+            let fd = unsafe { libc::open(rule.path.as_ptr(), libc::O_PATH | libc::O_CLOEXEC) };
+            if fd < 0 {
+                continue;
+            }
+            let mut stat: libc::stat = unsafe { std::mem::zeroed() };
+            let is_dir = unsafe { libc::fstat(fd, &mut stat) } == 0
+                && stat.st_mode & libc::S_IFMT == libc::S_IFDIR;
+            let access = if is_dir { rule.access } else { rule.access & FILE_RIGHTS };
+            let attr = PathBeneathAttr {
+                allowed_access: access,
+                parent_fd: fd,
+            };
+            let added = unsafe {
+                libc::syscall(
+                    libc::SYS_landlock_add_rule,
+                    ruleset,
+                    RULE_PATH_BENEATH,
+                    &attr as *const PathBeneathAttr,
+                    0u32,
+                )
+            };
+            let error = io::Error::last_os_error();
+            unsafe { libc::close(fd) };
+            if added < 0 {
+                return Err(error);
+            }
+        }
+        Ok(())
+    }
+}
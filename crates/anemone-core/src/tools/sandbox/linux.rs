@@ -0,0 +1,588 @@
+//! Entering the sandbox on Linux. A [`Confinement`] is built in the parent,
+//! where allocating is fine, and entered in the child between fork and exec,
+//! where only raw syscalls on prepared memory are safe.
+
+use std::ffi::CString;
+use std::io;
+use std::os::fd::{AsRawFd, OwnedFd};
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::{bail, Context, Result};
+
+use super::landlock::{self, Ruleset};
+use super::seccomp::Filter;
+use super::{Sandbox, SandboxMode, PROXY_PORT};
+
+/// A tmpfs that becomes the root while the new root is put together, with
+/// the host's root kept beneath it to bind from.
+const STAGING: &str = "/tmp";
+const NEW_ROOT: &str = "/newroot";
+const OLD_ROOT: &str = "/oldroot";
+
+/// Devices a command may use, bound in from the host's /dev.
+const DEVICES: &[&str] = &["null", "zero", "full", "random", "urandom", "tty"];
+
+/// Everything needed to confine one command.
+pub(super) struct Confinement {
+    namespaces: Option<Namespaces>,
+    /// A user namespace for the network namespace alone, in `none` mode
+    user: Option<UserNamespace>,
+    network: Option<Network>,
+    landlock: Option<Ruleset>,
+    seccomp: Option<Filter>,
+    workdir: CString,
+}
+
+impl Confinement {
+    pub(super) fn new(sandbox: &Sandbox) -> Result<Self> {
+        let abi = landlock::abi_version();
+        if sandbox.mode == SandboxMode::Landlock && abi == 0 {
+            bail!("Landlock isn't available on this kernel (needs Linux 5.13+ with Landlock enabled)");
+        }
+        let namespaces = match sandbox.mode {
+            SandboxMode::Namespaces => Some(Namespaces::new(sandbox)?),
+            _ => None,
+        };
+        let network = sandbox.proxy.clone().map(|channel| Network { channel });
+        let confined = sandbox.mode != SandboxMode::None;
+        Ok(Self {
+            user: (namespaces.is_none() && network.is_some()).then(UserNamespace::new),
+            namespaces,
+            network,
+            landlock: (confined && abi > 0).then(|| ruleset(sandbox, abi)),
+            seccomp: confined.then(Filter::new),
+            workdir: c_path(&sandbox.root)?,
+        })
+    }
+
+    /// Confine the calling (child) process. Runs between fork and exec.
+    pub(super) fn enter(&self) -> io::Result<()> {
+        if let Some(namespaces) = &self.namespaces {
+            namespaces.enter(self.network.is_some())?;
+        }
+        if let Some(user) = &self.user {
+            user.enter(libc::CLONE_NEWNET)?;
+        }
+        if let Some(network) = &self.network {
+            network.enter()?;
+        }
+        drop_capabilities();
+        check(unsafe { libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) })?;
+        if let Some(landlock) = &self.landlock {
+            landlock.restrict_self()?;
+        }
+        if let Some(seccomp) = &self.seccomp {
+            seccomp.install()?;
+        }
+        check(unsafe { libc::chdir(self.workdir.as_ptr()) })
+    }
+}
+
+/// Read and run the system, do anything in the box, use the harmless devices.
+fn ruleset(sandbox: &Sandbox, abi: i64) -> Ruleset {
+    let mut ruleset = Ruleset::new(abi);
+    for path in sandbox.read_only_paths() {
+        ruleset.read_only(&path.to_string_lossy());
+    }
+    ruleset.full(&sandbox.root.to_string_lossy());
+    let read = landlock::READ_FILE;
+    let read_write = landlock::READ_FILE | landlock::WRITE_FILE | landlock::TRUNCATE | landlock::IOCTL_DEV;
+    for device in DEVICES {
+        let access = if device.ends_with("random") { read } else { read_write };
+        ruleset.allow(&format!("/dev/{}", device), access);
+    }
+    // Only the private /proc of a pid namespace: the host's would show the
+    // environment (and API keys) of the anemone's own process
+    if sandbox.mode == SandboxMode::Namespaces {
+        ruleset.allow("/proc", landlock::READ_FILE | landlock::READ_DIR);
+    }
+    ruleset
+}
+
+// ── Namespaces ──
+
+/// A fresh user namespace mapping only our own ids, which owns (and so
+/// may set up) the namespaces unshared along with it.
+struct UserNamespace {
+    uid_map: Vec<u8>,
+    gid_map: Vec<u8>,
+}
+
+impl UserNamespace {
+    fn new() -> Self {
+        let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+        Self {
+            uid_map: format!("{} {} 1\n", uid, uid).into_bytes(),
+            gid_map: format!("{} {} 1\n", gid, gid).into_bytes(),
+        }
+    }
+
+    /// Unshare it, and the namespaces in `flags`.
+    fn enter(&self, flags: libc::c_int) -> io::Result<()> {
+        check(unsafe { libc::unshare(libc::CLONE_NEWUSER | flags) })?;
+        // Kernels before 3.19 have no setgroups file
+        let _ = write_file(c"/proc/self/setgroups", b"deny");
+        write_file(c"/proc/self/uid_map", &self.uid_map)?;
+        write_file(c"/proc/self/gid_map", &self.gid_map)
+    }
+}
+
+/// Fresh user, mount, pid, ipc and uts namespaces whose filesystem holds
+/// only the read-only system paths, the box, /dev basics and a new /proc.
+struct Namespaces {
+    user: UserNamespace,
+    steps: Vec<Step>,
+}
+
+/// One filesystem operation while building the new root.
+enum Step {
+    Mkdir(CString),
+    Touch(CString),
+    Symlink { target: CString, link: CString },
+    Chdir(CString),
+    PivotRoot { new_root: CString, put_old: CString },
+    /// `pivot_root(".", ".")` and drop the old root
+    PivotHere,
+    Mount {
+        source: Option<CString>,
+        target: CString,
+        fstype: Option<CString>,
+        flags: libc::c_ulong,
+        data: Option<CString>,
+    },
+}
+
+impl Namespaces {
+    fn new(sandbox: &Sandbox) -> Result<Self> {
+        let mut plan = Plan::default();
+
+        // Swap in a tmpfs as the root, keeping the host's at /oldroot, so
+        // nothing it covers (the box may well be under /tmp) is out of reach
+        plan.mount(None, "/", None, libc::MS_REC | libc::MS_PRIVATE, None)?;
+        plan.mount(Some("tmpfs"), STAGING, Some("tmpfs"), libc::MS_NOSUID | libc::MS_NODEV, Some("mode=0755"))?;
+        for dir in [NEW_ROOT, OLD_ROOT] {
+            plan.steps.push(Step::Mkdir(c_str(&format!("{}{}", STAGING, dir))?));
+        }
+        plan.steps.push(Step::PivotRoot {
+            new_root: c_str(STAGING)?,
+            put_old: c_str(&format!("{}{}", STAGING, OLD_ROOT))?,
+        });
+        plan.steps.push(Step::Chdir(c_str("/")?));
+        plan.mount(Some("tmpfs"), NEW_ROOT, Some("tmpfs"), libc::MS_NOSUID | libc::MS_NODEV, Some("mode=0755"))?;
+
+        for path in sandbox.read_only_paths() {
+            let inside = staged(&path);
+            let meta = std::fs::symlink_metadata(&path)?;
+            if meta.file_type().is_symlink() {
+                // e.g. /bin -> usr/bin on merged-/usr systems
+                plan.mkdir_parents(&inside)?;
+                plan.steps.push(Step::Symlink {
+                    target: c_path(&std::fs::read_link(&path)?)?,
+                    link: c_str(&inside)?,
+                });
+                continue;
+            }
+            if meta.is_dir() {
+                plan.mkdir_all(&inside)?;
+            } else {
+                plan.mkdir_parents(&inside)?;
+                plan.steps.push(Step::Touch(c_str(&inside)?));
+            }
+            plan.mount(Some(&host(&path)), &inside, None, libc::MS_BIND | libc::MS_REC, None)?;
+            let flags = libc::MS_REMOUNT | libc::MS_BIND | libc::MS_RDONLY | libc::MS_NOSUID | libc::MS_NODEV;
+            plan.mount(None, &inside, None, flags | locked_flags(&path), None)?;
+        }
+
+        // The box, the one writable path, at the same place as outside
+        let inside = staged(&sandbox.root);
+        plan.mkdir_all(&inside)?;
+        plan.mount(Some(&host(&sandbox.root)), &inside, None, libc::MS_BIND | libc::MS_REC, None)?;
+        let flags = libc::MS_REMOUNT | libc::MS_BIND | libc::MS_NOSUID | libc::MS_NODEV;
+        plan.mount(None, &inside, None, flags | locked_flags(&sandbox.root), None)?;
+
+        let dev = staged(Path::new("/dev"));
+        plan.mkdir_all(&dev)?;
+        plan.mount(Some("tmpfs"), &dev, Some("tmpfs"), libc::MS_NOSUID | libc::MS_NOEXEC, Some("mode=0755"))?;
+        for device in DEVICES {
+            let path = format!("/dev/{}", device);
+            if Path::new(&path).exists() {
+                let inside = format!("{}/{}", dev, device);
+                plan.steps.push(Step::Touch(c_str(&inside)?));
+                plan.mount(Some(&host(Path::new(&path))), &inside, None, libc::MS_BIND, None)?;
+            }
+        }
+        for (link, target) in [
+            ("fd", "/proc/self/fd"),
+            ("stdin", "/proc/self/fd/0"),
+            ("stdout", "/proc/self/fd/1"),
+            ("stderr", "/proc/self/fd/2"),
+        ] {
+            plan.steps.push(Step::Symlink {
+                target: c_str(target)?,
+                link: c_str(&format!("{}/{}", dev, link))?,
+            });
+        }
+        let flags = libc::MS_REMOUNT | libc::MS_BIND | libc::MS_RDONLY | libc::MS_NOSUID | libc::MS_NOEXEC;
+        plan.mount(None, &dev, None, flags, None)?;
+
+        let proc = staged(Path::new("/proc"));
+        plan.mkdir_all(&proc)?;
+        let flags = libc::MS_NOSUID | libc::MS_NODEV | libc::MS_NOEXEC;
+        plan.mount(Some("proc"), &proc, Some("proc"), flags, None)?;
+
+        // Into the new root, leaving the tmpfs and the host's root behind
+        plan.steps.push(Step::Chdir(c_str(NEW_ROOT)?));
+        plan.steps.push(Step::PivotHere);
+        plan.steps.push(Step::Chdir(c_str("/")?));
+
+        Ok(Self {
+            user: UserNamespace::new(),
+            steps: plan.steps,
+        })
+    }
+
+    /// Enter them, with a network namespace too if `network`.
+    fn enter(&self, network: bool) -> io::Result<()> {
+        let mut flags = libc::CLONE_NEWNS | libc::CLONE_NEWPID | libc::CLONE_NEWIPC | libc::CLONE_NEWUTS;
+        if network {
+            flags |= libc::CLONE_NEWNET;
+        }
+        self.user.enter(flags)?;
+
+        // Only children join the new pid namespace: fork, and let the child
+        // (pid 1 there) build the filesystem while this process waits
+        fork_and_wait()?;
+        for step in &self.steps {
+            step.run()?;
+        }
+        check(unsafe { libc::sethostname(c"anemone".as_ptr(), 7) })?;
+        let flags = libc::MS_REMOUNT | libc::MS_BIND | libc::MS_RDONLY | libc::MS_NOSUID | libc::MS_NODEV;
+        check(unsafe { libc::mount(std::ptr::null(), c"/".as_ptr(), std::ptr::null(), flags, std::ptr::null()) })?;
+        // pid 1 ignores signals it has no handler for, the CPU limit's
+        // SIGXCPU included; it stays behind as init and the command runs as 2
+        fork_and_wait()
+    }
+}
+
+// ── Network ──
+
+/// Set up a fresh network namespace: loopback only, with a listener on
+/// [`PROXY_PORT`] sent back over `channel`.
+struct Network {
+    channel: Arc<OwnedFd>,
+}
+
+/// `struct ifreq`, as far as SIOCSIFFLAGS reads it.
+#[repr(C)]
+struct InterfaceFlags {
+    name: [u8; libc::IFNAMSIZ],
+    flags: libc::c_short,
+    _rest: [u8; 22],
+}
+
+impl Network {
+    fn enter(&self) -> io::Result<()> {
+        // A new network namespace starts with loopback down
+        let control = socket(libc::SOCK_DGRAM)?;
+        let mut request = InterfaceFlags {
+            name: [0; libc::IFNAMSIZ],
+            flags: (libc::IFF_UP | libc::IFF_RUNNING) as libc::c_short,
+            _rest: [0; 22],
+        };
+        request.name[..2].copy_from_slice(b"lo");
+        check(unsafe { libc::ioctl(control.as_raw_fd(), libc::SIOCSIFFLAGS as _, &request as *const InterfaceFlags) })?;
+
+        let listener = socket(libc::SOCK_STREAM)?;
+        let address = libc::sockaddr_in {
+            sin_family: libc::AF_INET as libc::sa_family_t,
+            sin_port: PROXY_PORT.to_be(),
+            sin_addr: libc::in_addr {
+                s_addr: u32::from_be_bytes([127, 0, 0, 1]).to_be(),
+            },
+            sin_zero: [0; 8],
+        };
+        check(unsafe {
+            libc::bind(
+                listener.as_raw_fd(),
+                &address as *const libc::sockaddr_in as *const libc::sockaddr,
+                std::mem::size_of::<libc::sockaddr_in>() as libc::socklen_t,
+            )
+        })?;
+        check(unsafe { libc::listen(listener.as_raw_fd(), 64) })?;
+        send_fd(self.channel.as_raw_fd(), listener.as_raw_fd())
+    }
+}
+
+/// An IPv4 socket of `kind`, closed on drop (and on exec).
+fn socket(kind: libc::c_int) -> io::Result<OwnedFd> {
+    use std::os::fd::FromRawFd;
+
+    let fd = unsafe { libc::socket(libc::AF_INET, kind | libc::SOCK_CLOEXEC, 0) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+}
+
+/// Send `fd` over the Unix socket `channel`.
+fn send_fd(channel: libc::c_int, fd: libc::c_int) -> io::Result<()> {
+    let mut byte = [0u8; 1];
+    let mut iov = libc::iovec {
+        iov_base: byte.as_mut_ptr() as *mut libc::c_void,
+        iov_len: 1,
+    };
+    // Room for one cmsghdr and an int, suitably aligned
+    let mut control = [0u64; 4];
+    let mut message: libc::msghdr = unsafe { std::mem::zeroed() };
+    message.msg_iov = &mut iov;
+    message.msg_iovlen = 1;
+    message.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+    message.msg_controllen = unsafe { libc::CMSG_SPACE(std::mem::size_of::<libc::c_int>() as u32) } as _;
+    unsafe {
+        let header = libc::CMSG_FIRSTHDR(&message);
+        (*header).cmsg_level = libc::SOL_SOCKET;
+        (*header).cmsg_type = libc::SCM_RIGHTS;
+        (*header).cmsg_len = libc::CMSG_LEN(std::mem::size_of::<libc::c_int>() as u32) as _;
+        std::ptr::write_unaligned(libc::CMSG_DATA(header) as *mut libc::c_int, fd);
+    }
+    if unsafe { libc::sendmsg(channel, &message, libc::MSG_NOSIGNAL) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Steps for building the new root, with the directories already made.
+#[derive(Default)]
+struct Plan {
+    steps: Vec<Step>,
+    made: std::collections::HashSet<String>,
+}
+
+impl Plan {
+    fn mount(
+        &mut self,
+        source: Option<&str>,
+        target: &str,
+        fstype: Option<&str>,
+        flags: libc::c_ulong,
+        data: Option<&str>,
+    ) -> Result<()> {
+        self.steps.push(Step::Mount {
+            source: source.map(c_str).transpose()?,
+            target: c_str(target)?,
+            fstype: fstype.map(c_str).transpose()?,
+            flags,
+            data: data.map(c_str).transpose()?,
+        });
+        Ok(())
+    }
+
+    /// `mkdir -p` below the new root.
+    fn mkdir_all(&mut self, path: &str) -> Result<()> {
+        let mut current = String::new();
+        for part in path.split('/').filter(|p| !p.is_empty()) {
+            current.push('/');
+            current.push_str(part);
+            if current.len() > NEW_ROOT.len() && self.made.insert(current.clone()) {
+                self.steps.push(Step::Mkdir(c_str(&current)?));
+            }
+        }
+        Ok(())
+    }
+
+    fn mkdir_parents(&mut self, path: &str) -> Result<()> {
+        match path.rsplit_once('/') {
+            Some((parent, _)) => self.mkdir_all(parent),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Step {
+    fn run(&self) -> io::Result<()> {
+        match self {
+            Step::Mkdir(path) => {
+                if unsafe { libc::mkdir(path.as_ptr(), 0o755) } < 0 {
+                    let error = io::Error::last_os_error();
+                    if error.raw_os_error() != Some(libc::EEXIST) {
+                        return Err(error);
+                    }
+                }
+                Ok(())
+            }
+            Step::Touch(path) => {
+                let fd = unsafe { libc::open(path.as_ptr(), libc::O_CREAT | libc::O_WRONLY | libc::O_CLOEXEC, 0o644) };
+                if fd < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                unsafe { libc::close(fd) };
+                Ok(())
+            }
+            Step::Symlink { target, link } => check(unsafe { libc::symlink(target.as_ptr(), link.as_ptr()) }),
+            Step::Chdir(path) => check(unsafe { libc::chdir(path.as_ptr()) }),
+            Step::PivotRoot { new_root, put_old } => pivot_root(new_root, put_old),
+            Step::PivotHere => {
+                pivot_root(c".", c".")?;
+                // The old root is stacked beneath the new one; let it go
+                check(unsafe { libc::umount2(c".".as_ptr(), libc::MNT_DETACH) })
+            }
+            Step::Mount {
+                source,
+                target,
+                fstype,
+                flags,
+                data,
+            } => {
+                let ptr = |s: &Option<CString>| s.as_ref().map_or(std::ptr::null(), |s| s.as_ptr());
+                check(unsafe {
+                    libc::mount(ptr(source), target.as_ptr(), ptr(fstype), *flags, ptr(data) as *const libc::c_void)
+                })
+            }
+        }
+    }
+}
+
+/// Fork. The child returns, set to die with its parent; the parent waits
+/// for it and exits the same way.
+fn fork_and_wait() -> io::Result<()> {
+    let pid = unsafe { libc::syscall(libc::SYS_clone, libc::SIGCHLD as libc::c_ulong, 0usize, 0usize, 0usize, 0usize) };
+    if pid < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    if pid > 0 {
+        wait_and_exit(pid as libc::pid_t);
+    }
+    // Die with the waiting parent, taking the whole namespace along
+    check(unsafe { libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGKILL, 0, 0, 0) })
+}
+
+/// Wait for `pid`, reaping any orphans on the way (as init), and exit the
+/// same way it did. Never returns.
+fn wait_and_exit(pid: libc::pid_t) -> ! {
+    // Hold nothing open: not the command's pipes, not std's exec-error pipe
+    if unsafe { libc::syscall(libc::SYS_close_range, 0u32, u32::MAX, 0u32) } < 0 {
+        for fd in 0..1024 {
+            unsafe { libc::close(fd) };
+        }
+    }
+    let mut status = 0;
+    loop {
+        let waited = unsafe { libc::waitpid(-1, &mut status, 0) };
+        if waited == pid {
+            break;
+        }
+        if waited < 0 && io::Error::last_os_error().raw_os_error() != Some(libc::EINTR) {
+            unsafe { libc::_exit(1) }
+        }
+    }
+    let code = if libc::WIFEXITED(status) {
+        libc::WEXITSTATUS(status)
+    } else if libc::WIFSIGNALED(status) {
+        128 + libc::WTERMSIG(status)
+    } else {
+        1
+    };
+    unsafe { libc::_exit(code) }
+}
+
+// ── Helpers ──
+
+/// Give up every capability, so nothing gained in the user namespace
+/// survives into the command.
+fn drop_capabilities() {
+    #[repr(C)]
+    struct Header {
+        version: u32,
+        pid: libc::c_int,
+    }
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    struct Data {
+        effective: u32,
+        permitted: u32,
+        inheritable: u32,
+    }
+    for capability in 0..64 {
+        unsafe { libc::prctl(libc::PR_CAPBSET_DROP, capability, 0, 0, 0) };
+    }
+    unsafe { libc::prctl(libc::PR_CAP_AMBIENT, libc::PR_CAP_AMBIENT_CLEAR_ALL, 0, 0, 0) };
+    let header = Header {
+        version: 0x2008_0522,
+        pid: 0,
+    };
+    let data = [Data {
+        effective: 0,
+        permitted: 0,
+        inheritable: 0,
+    }; 2];
+    unsafe { libc::syscall(libc::SYS_capset, &header as *const Header, data.as_ptr()) };
+}
+
+fn write_file(path: &std::ffi::CStr, contents: &[u8]) -> io::Result<()> {
+    let fd = unsafe { libc::open(path.as_ptr(), libc::O_WRONLY | libc::O_CLOEXEC) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let written = unsafe { libc::write(fd, contents.as_ptr() as *const libc::c_void, contents.len()) };
+    let error = io::Error::last_os_error();
+    unsafe { libc::close(fd) };
+    if written < 0 {
+        return Err(error);
+    }
+    Ok(())
+}
+
+fn pivot_root(new_root: &std::ffi::CStr, put_old: &std::ffi::CStr) -> io::Result<()> {
+    check(unsafe { libc::syscall(libc::SYS_pivot_root, new_root.as_ptr(), put_old.as_ptr()) } as libc::c_int)
+}
+
+fn check(result: libc::c_int) -> io::Result<()> {
+    if result < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+/// Mount flags a user namespace may not clear when remounting `path`.
+fn locked_flags(path: &Path) -> libc::c_ulong {
+    let Ok(path) = c_path(path) else {
+        return 0;
+    };
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return 0;
+    }
+    [
+        (libc::ST_RDONLY, libc::MS_RDONLY),
+        (libc::ST_NOSUID, libc::MS_NOSUID),
+        (libc::ST_NODEV, libc::MS_NODEV),
+        (libc::ST_NOEXEC, libc::MS_NOEXEC),
+        (libc::ST_NOATIME, libc::MS_NOATIME),
+        (libc::ST_NODIRATIME, libc::MS_NODIRATIME),
+        (libc::ST_RELATIME, libc::MS_RELATIME),
+    ]
+    .iter()
+    .filter(|(st, _)| stat.f_flag & st != 0)
+    .fold(0, |flags, (_, ms)| flags | ms)
+}
+
+/// `path` as it appears in the new root while it's being built.
+fn staged(path: &Path) -> String {
+    format!("{}{}", NEW_ROOT, path.display())
+}
+
+/// `path` on the host, seen from the staging root.
+fn host(path: &Path) -> String {
+    format!("{}{}", OLD_ROOT, path.display())
+}
+
+fn c_str(s: &str) -> Result<CString> {
+    CString::new(s).with_context(|| format!("NUL in path {:?}", s))
+}
+
+fn c_path(path: &Path) -> Result<CString> {
+    CString::new(path.as_os_str().as_bytes()).with_context(|| format!("NUL in path {:?}", path))
+}
@@ -0,0 +1,263 @@
+//! Kernel-enforced sandbox for shell commands.
+//!
+//! The shell tool's own checks only look at the command text, so anything
+//! they miss runs with the anemone's full rights. With `sandbox` set in the
+//! config, the kernel enforces the limits instead (Linux only):
+//!
+//! - `landlock`: Landlock lets commands read and run the system directories
+//!   but write only inside the box, and a seccomp filter blocks mounts, new
+//!   namespaces, ptrace, kernel modules and terminal injection.
+//! - `namespaces`: commands also run in fresh user, mount, pid, ipc and uts
+//!   namespaces. Their filesystem is only the system directories
+//!   (read-only), the box (the only writable path), a few devices and a
+//!   private /proc; no other process is visible. Landlock is layered on top
+//!   where the kernel has it.
+//! - `none`: no kernel sandbox.
+//!
+//! Separately, [`Sandbox::with_proxy`] cuts commands off from the network:
+//! they get a network namespace of their own, with only loopback, and the
+//! one way out is a listener on [`PROXY_PORT`] whose socket is handed back
+//! to the anemone to serve (see `tools::egress`).
+
+#[cfg(target_os = "linux")]
+mod landlock;
+#[cfg(target_os = "linux")]
+mod linux;
+#[cfg(target_os = "linux")]
+mod seccomp;
+
+use std::os::fd::OwnedFd;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Arc;
+
+use anyhow::{bail, Result};
+
+/// System directories commands may read and run from.
+pub const SYSTEM_DIRS: &[&str] = &["/usr", "/bin", "/sbin", "/lib", "/lib32", "/lib64", "/etc", "/opt"];
+
+/// The port commands reach the egress proxy on, inside their own network
+/// namespace.
+pub const PROXY_PORT: u16 = 3128;
+
+/// How shell commands are confined.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SandboxMode {
+    None,
+    Landlock,
+    Namespaces,
+}
+
+impl SandboxMode {
+    /// The mode named by the `sandbox` setting. Unknown names are an error
+    /// rather than no sandbox.
+    pub fn parse(name: &str) -> Result<Self> {
+        match name.trim() {
+            "none" | "" => Ok(Self::None),
+            "landlock" => Ok(Self::Landlock),
+            "namespaces" => Ok(Self::Namespaces),
+            other => bail!("Unknown sandbox '{}' (expected namespaces, landlock or none)", other),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::Landlock => "landlock",
+            Self::Namespaces => "namespaces",
+        }
+    }
+}
+
+/// Confines commands to one box.
+#[derive(Debug, Clone)]
+pub struct Sandbox {
+    mode: SandboxMode,
+    root: PathBuf,
+    read_only: Vec<PathBuf>,
+    /// Where to send the proxy listener, when the network is cut off
+    proxy: Option<Arc<OwnedFd>>,
+}
+
+impl Sandbox {
+    pub fn new(mode: SandboxMode, root: &Path) -> Self {
+        Self {
+            mode,
+            root: root.canonicalize().unwrap_or_else(|_| root.to_path_buf()),
+            read_only: Vec::new(),
+            proxy: None,
+        }
+    }
+
+    /// Also let commands read (and run) `path` — e.g. a Python install
+    /// outside the system directories. Missing paths are ignored.
+    pub fn with_read_only(mut self, path: impl AsRef<Path>) -> Self {
+        if let Ok(path) = path.as_ref().canonicalize() {
+            let covered = self.read_only_paths().iter().any(|p| path.starts_with(p));
+            if !covered && !path.starts_with(&self.root) {
+                self.read_only.push(path);
+            }
+        }
+        self
+    }
+
+    /// Give commands a network namespace of their own, whose only way out
+    /// is a listener on `127.0.0.1:`[`PROXY_PORT`]. The listening socket is
+    /// sent over `channel` (a Unix socket) for the anemone to accept on.
+    /// Works in every mode, `none` included.
+    pub fn with_proxy(mut self, channel: OwnedFd) -> Self {
+        self.proxy = Some(Arc::new(channel));
+        self
+    }
+
+    pub fn mode(&self) -> SandboxMode {
+        self.mode
+    }
+
+    /// The system directories present here, then the extra paths.
+    fn read_only_paths(&self) -> Vec<PathBuf> {
+        SYSTEM_DIRS
+            .iter()
+            .map(PathBuf::from)
+            .filter(|p| p.symlink_metadata().is_ok())
+            .chain(self.read_only.iter().cloned())
+            .collect()
+    }
+
+    /// Set `command` up to run confined. It fails to spawn if the kernel
+    /// won't let the sandbox be entered.
+    pub fn apply(&self, command: &mut Command) -> Result<()> {
+        if self.mode == SandboxMode::None && self.proxy.is_none() {
+            return Ok(());
+        }
+        self.confine(command)
+    }
+
+    #[cfg(target_os = "linux")]
+    fn confine(&self, command: &mut Command) -> Result<()> {
+        use std::os::unix::process::CommandExt;
+
+        let confinement = linux::Confinement::new(self)?;
+        // SAFETY: `enter` makes only raw syscalls on memory allocated here,
+        // which is all that's safe between fork and exec
+        unsafe {
+            command.pre_exec(move || confinement.enter());
+        }
+        Ok(())
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn confine(&self, _command: &mut Command) -> Result<()> {
+        if self.mode == SandboxMode::None {
+            bail!("Cutting commands off from the network needs Linux");
+        }
+        bail!("The {} sandbox needs Linux", self.mode.name())
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    /// Run `script` under `mode` in `root`; its output and whether it succeeded.
+    fn run(mode: SandboxMode, root: &Path, script: &str) -> (String, bool) {
+        let mut command = Command::new("/bin/sh");
+        command.args(["-c", script]).current_dir(root);
+        Sandbox::new(mode, root).apply(&mut command).unwrap();
+        let output = command.output().unwrap();
+        let mut text = String::from_utf8_lossy(&output.stdout).to_string();
+        text.push_str(&String::from_utf8_lossy(&output.stderr));
+        (text, output.status.success())
+    }
+
+    /// Modes this kernel can enter; the rest are skipped, loudly.
+    fn available() -> Vec<SandboxMode> {
+        let tmp = tempfile::tempdir().unwrap();
+        [SandboxMode::Landlock, SandboxMode::Namespaces]
+            .into_iter()
+            .filter(|mode| {
+                let mut command = Command::new("/bin/true");
+                let ok = Sandbox::new(*mode, tmp.path()).apply(&mut command).is_ok()
+                    && command.status().is_ok_and(|s| s.success());
+                if !ok {
+                    eprintln!("skipping the {} sandbox: not available here", mode.name());
+                }
+                ok
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_modes() {
+        assert_eq!(SandboxMode::parse("none").unwrap(), SandboxMode::None);
+        assert_eq!(SandboxMode::parse("namespaces").unwrap(), SandboxMode::Namespaces);
+        assert_eq!(SandboxMode::parse(" landlock ").unwrap(), SandboxMode::Landlock);
+        assert!(SandboxMode::parse("landlok").is_err());
+    }
+
+    #[test]
+    fn test_box_is_the_only_writable_path() {
+        for mode in available() {
+            let root = tempfile::tempdir().unwrap();
+            let outside = tempfile::tempdir().unwrap();
+            let target = outside.path().join("escaped.txt");
+
+            let (out, ok) = run(mode, root.path(), "echo inside > notes.txt && mkdir -p a/b && cat notes.txt");
+            assert!(ok && out.contains("inside"), "{:?}: {}", mode, out);
+
+            let script = format!("echo out > {}", target.display());
+            let (_, ok) = run(mode, root.path(), &script);
+            assert!(!ok, "{:?} wrote outside the box", mode);
+            assert!(!target.exists());
+
+            let (_, ok) = run(mode, root.path(), "touch /usr/escaped || touch /etc/escaped");
+            assert!(!ok, "{:?} wrote to the system", mode);
+        }
+    }
+
+    #[test]
+    fn test_files_outside_the_box_stay_unreadable() {
+        for mode in available() {
+            let root = tempfile::tempdir().unwrap();
+            let outside = tempfile::tempdir().unwrap();
+            let secret = outside.path().join("secret.txt");
+            std::fs::write(&secret, "hunter2").unwrap();
+            // Through a symlink planted in the box, too
+            std::os::unix::fs::symlink(&secret, root.path().join("link")).unwrap();
+
+            let (out, _) = run(mode, root.path(), &format!("cat {}; cat link", secret.display()));
+            assert!(!out.contains("hunter2"), "{:?} leaked: {}", mode, out);
+            // Chained commands get no further than the first
+            let (out, _) = run(mode, root.path(), &format!("ls; /bin/sh -c 'cat {}'", secret.display()));
+            assert!(!out.contains("hunter2"), "{:?} leaked: {}", mode, out);
+        }
+    }
+
+    #[test]
+    fn test_namespace_and_mount_escapes_fail() {
+        for mode in available() {
+            let root = tempfile::tempdir().unwrap();
+            if Path::new("/usr/bin/unshare").exists() {
+                let (_, ok) = run(mode, root.path(), "unshare --user --map-root-user true");
+                assert!(!ok, "{:?} let a command make a namespace", mode);
+            }
+            let (_, ok) = run(mode, root.path(), "mount -t tmpfs none .");
+            assert!(!ok, "{:?} let a command mount", mode);
+        }
+    }
+
+    #[test]
+    fn test_namespaces_hide_host_processes() {
+        if !available().contains(&SandboxMode::Namespaces) {
+            return;
+        }
+        let root = tempfile::tempdir().unwrap();
+        let me = std::process::id();
+        let (out, _) = run(SandboxMode::Namespaces, root.path(), &format!("kill -0 {me} && echo visible"));
+        assert!(!out.contains("visible"), "{}", out);
+        // Just the sandbox's init, the shell, ls and grep
+        let (out, ok) = run(SandboxMode::Namespaces, root.path(), "ls /proc | grep -c '^[0-9]'");
+        assert!(ok, "{}", out);
+        assert!(out.trim().parse::<u32>().unwrap() <= 4, "{} processes visible", out.trim());
+    }
+}
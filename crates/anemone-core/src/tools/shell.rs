@@ -1,18 +1,24 @@
 //! Sandboxed shell — the agent can run commands, but only inside environment/.
-//! 1:1 port of Python tools.py shell logic.
+//! 1:1 port of Python tools.py shell logic. What may run is decided by the
+//! shell policy (see [`super::policy`]).
 
 use std::path::Path;
 use std::process::Command;
-use tracing::info;
-
-/// Commands that should never be run (checked as prefixes after stripping).
-/// Identical to Python BLOCKED_PREFIXES.
-const BLOCKED_PREFIXES: &[&str] = &[
-    "sudo", "su ", "rm -rf /", "chmod", "chown", "kill", "pkill", "curl", "wget", "nc ", "ncat",
-    "ssh", "scp", "sftp", "node", "ruby", "perl", "bash", "sh ", "zsh", "export", "source",
-    "eval", "exec", "mount", "umount", "dd ", "mkfs", "fdisk", "apt", "brew", "npm", "yarn",
-    "open ", "xdg-open",
-];
+
+use anyhow::Result;
+use async_trait::async_trait;
+use serde_json::json;
+use tokio::sync::broadcast;
+use tracing::{error, info, warn};
+
+use super::egress::{self, EgressMode, Proxy};
+use super::exec;
+use super::policy::{self, Decision, Policy};
+use super::sandbox::{Sandbox, SandboxMode};
+use super::{str_arg, Tool, ToolContext, ToolOutput};
+use crate::config::Config;
+use crate::events::BrainEvent;
+use crate::types::{ActivityData, ToolResultDeltaData};
 
 /// File extensions we can read as text (from Brain._TEXT_EXTS)
 pub const TEXT_EXTS: &[&str] = &[
@@ -24,50 +30,13 @@ pub const PDF_EXTS: &[&str] = &[".pdf"];
 pub const IMAGE_EXTS: &[&str] = &[".png", ".jpg", ".jpeg", ".gif", ".webp"];
 
 /// Internal files the anemone/system manages — never trigger alerts
-pub const IGNORE_FILES: &[&str] = &["memory_stream.jsonl", "identity.json"];
-
-/// Internal root files that shouldn't trigger inbox alerts
-pub const INTERNAL_ROOT_FILES: &[&str] = &["projects.md"];
-
-/// Check if a command is safe. Returns Some(error_message) if unsafe.
-fn is_safe_command(command: &str) -> Option<String> {
-    let stripped = command.trim();
-
-    if stripped.is_empty() {
-        return Some("Blocked: empty command.".into());
-    }
-
-    // Block dangerous command prefixes
-    for prefix in BLOCKED_PREFIXES {
-        if stripped.starts_with(prefix) {
-            return Some(format!("Blocked: '{}' commands are not allowed.", prefix));
-        }
-    }
-
-    // Block parent directory traversal
-    for token in stripped.split_whitespace() {
-        let clean = token.trim_start_matches(|c: char| "><=|;&(".contains(c));
-        if clean == ".." || clean.starts_with("../") || clean.contains("/..") {
-            return Some("Blocked: '..' path traversal is not allowed in commands.".into());
-        }
-    }
+pub const IGNORE_FILES: &[&str] = &["memory_stream.jsonl", "identity.json", "usage.json"];
 
-    // Block shell escape tricks
-    if stripped.contains('`') {
-        return Some("Blocked: backtick command substitution is not allowed.".into());
-    }
-    if stripped.contains("$(") {
-        return Some("Blocked: command substitution $() is not allowed.".into());
-    }
-    if stripped.contains("${") {
-        return Some("Blocked: variable expansion ${} is not allowed.".into());
-    }
-    if stripped.contains('~') {
-        return Some("Blocked: '~' (home expansion) is not allowed.".into());
-    }
+/// Internal directories in the box root — nothing inside triggers alerts
+pub const IGNORE_DIRS: &[&str] = &["memory"];
 
-    None
-}
+/// Internal root files that shouldn't trigger inbox alerts
+pub const INTERNAL_ROOT_FILES: &[&str] = &["projects.md", "beliefs.md"];
 
 /// Path to the anemone's virtual environment
 fn venv_dir(env_root: &Path) -> std::path::PathBuf {
@@ -92,7 +61,7 @@ pub fn ensure_venv(env_root: &Path) {
     info!("Creating anemone venv at {:?}...", venv);
 
     // Try uv first, then fallback to python -m venv
-    if let Some(uv) = which::which("uv").ok() {
+    if let Ok(uv) = which::which("uv") {
         let _ = Command::new(uv)
             .args(["venv", &venv.to_string_lossy(), "--seed", "pip"])
             .output();
@@ -116,13 +85,9 @@ pub fn ensure_venv(env_root: &Path) {
 /// Rewrite python commands to use sandbox + venv.
 fn rewrite_python_cmd(command: &str, env_root: &Path) -> Option<String> {
     let stripped = command.trim();
-    let rest = if stripped.starts_with("python3") {
-        &stripped[7..]
-    } else if stripped.starts_with("python") {
-        &stripped[6..]
-    } else {
-        return None;
-    };
+    let rest = stripped
+        .strip_prefix("python3")
+        .or_else(|| stripped.strip_prefix("python"))?;
 
     let real_root = env_root.canonicalize().unwrap_or_else(|_| env_root.to_path_buf());
     let python = if venv_python(env_root).is_file() {
@@ -146,8 +111,8 @@ fn rewrite_python_cmd(command: &str, env_root: &Path) -> Option<String> {
 /// Rewrite ./script.py to go through sandbox.
 fn rewrite_script_cmd(command: &str, env_root: &Path) -> Option<String> {
     let stripped = command.trim();
-    if stripped.starts_with("./") && stripped.contains(".py") {
-        let parts: Vec<&str> = stripped[2..].splitn(2, ' ').collect();
+    if let Some(script_cmd) = stripped.strip_prefix("./").filter(|s| s.contains(".py")) {
+        let parts: Vec<&str> = script_cmd.splitn(2, ' ').collect();
         let script = parts[0];
         let rest = if parts.len() > 1 { parts[1] } else { "" };
 
@@ -178,8 +143,7 @@ fn rewrite_script_cmd(command: &str, env_root: &Path) -> Option<String> {
 /// Rewrite pip/uv pip commands to use the venv.
 fn rewrite_pip_cmd(command: &str, env_root: &Path) -> Option<String> {
     let stripped = command.trim();
-    if stripped.starts_with("uv pip ") {
-        let rest = &stripped[7..];
+    if let Some(rest) = stripped.strip_prefix("uv pip ") {
         let uv = which::which("uv")
             .map(|p| p.to_string_lossy().to_string())
             .unwrap_or_else(|_| "uv".to_string());
@@ -251,20 +215,145 @@ fn sanitize_paths(command: &str) -> String {
     result.to_string()
 }
 
-/// Run a shell command sandboxed to the environment/ folder.
-pub fn run_command(command: &str, env_root: &Path) -> String {
+/// The `shell` tool.
+pub struct ShellTool;
+
+#[async_trait]
+impl Tool for ShellTool {
+    fn name(&self) -> &str {
+        "shell"
+    }
+
+    fn description(&self) -> &str {
+        "Run a shell command inside your environment folder. You can use ls, cat, mkdir, mv, cp, touch, echo, tee, find, grep, head, tail, wc, etc. You can also run Python scripts: 'python script.py' or 'python -c \"code\"'. Use 'cat > file.txt << EOF' or 'echo ... > file.txt' to write files. Create folders with mkdir. Organize however you like. All paths are relative to your environment root."
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "command": { "type": "string", "description": "The shell command to run" }
+            },
+            "required": ["command"]
+        })
+    }
+
+    fn activity(&self, arguments: &serde_json::Value) -> ActivityData {
+        let cmd = str_arg(arguments, "command").trim();
+        if cmd.starts_with("python") {
+            let detail: String = cmd.chars().take(60).collect();
+            ActivityData {
+                activity_type: "python".to_string(),
+                detail: if cmd.len() > 60 {
+                    format!("{}...", detail)
+                } else {
+                    detail
+                },
+            }
+        } else if cmd.contains('>') || cmd.starts_with("cat >") || cmd.starts_with("tee ") {
+            let fname = cmd
+                .split('>')
+                .next_back()
+                .and_then(|s| s.split_whitespace().next())
+                .unwrap_or("file");
+            ActivityData {
+                activity_type: "writing".to_string(),
+                detail: format!("Writing {}", fname),
+            }
+        } else if cmd.starts_with("cat ")
+            || cmd.starts_with("head ")
+            || cmd.starts_with("tail ")
+            || cmd.starts_with("ls")
+            || cmd.starts_with("find ")
+            || cmd.starts_with("grep ")
+        {
+            let detail: String = cmd.chars().take(50).collect();
+            ActivityData {
+                activity_type: "reading".to_string(),
+                detail,
+            }
+        } else {
+            let detail: String = cmd.chars().take(50).collect();
+            ActivityData {
+                activity_type: "shell".to_string(),
+                detail,
+            }
+        }
+    }
+
+    async fn execute(&self, arguments: &serde_json::Value, ctx: &mut ToolContext<'_>) -> Result<ToolOutput> {
+        let command = str_arg(arguments, "command");
+        // Loaded per command, so edits to the file apply straight away
+        let policy = match policy_for(ctx.config, ctx.anemone) {
+            Ok(policy) => policy,
+            Err(e) => {
+                error!("{:#}", e);
+                return Ok(ToolOutput::Text(format!("Blocked: {:#}", e)));
+            }
+        };
+        Ok(ToolOutput::Text(
+            run_command(command, ctx.env_path, ctx.config, &policy, Some(ctx.events)).await,
+        ))
+    }
+}
+
+/// The shell policy for `anemone`: the file its `box_tools` entry (or
+/// `tools`) names, else the built-in one.
+pub fn policy_for(config: &Config, anemone: &str) -> Result<Policy> {
+    match &config.tool_settings(anemone).shell_policy {
+        Some(path) => Policy::load(&config.project_root.join(path)),
+        None => Ok(Policy::builtin()),
+    }
+}
+
+/// The kernel sandbox for commands in `env_root`, with what the rewritten
+/// python and pip commands need to read from outside it.
+fn sandbox_for(env_root: &Path, config: &Config) -> Result<Sandbox> {
+    let mode = SandboxMode::parse(&config.sandbox)?;
+    let mut sandbox = Sandbox::new(mode, env_root).with_read_only(find_pysandbox(env_root));
+    // A venv's python is a symlink to an install that may live anywhere
+    // (uv keeps its own under the home directory)
+    if let Ok(python) = venv_python(env_root).canonicalize() {
+        if let Some(prefix) = python.parent().and_then(Path::parent) {
+            sandbox = sandbox.with_read_only(prefix);
+        }
+    }
+    if let Ok(uv) = which::which("uv") {
+        sandbox = sandbox.with_read_only(uv);
+    }
+    Ok(sandbox)
+}
+
+/// Run a shell command sandboxed to the environment/ folder, if `policy`
+/// allows it, under `shell_limits`. Output is streamed to `events` as it
+/// arrives.
+pub async fn run_command(
+    command: &str,
+    env_root: &Path,
+    config: &Config,
+    policy: &Policy,
+    events: Option<&broadcast::Sender<BrainEvent>>,
+) -> String {
     let real_root = env_root
         .canonicalize()
         .unwrap_or_else(|_| env_root.to_path_buf());
 
-    // Safety check (runs on original command before any rewriting)
-    if let Some(err) = is_safe_command(command) {
-        return err;
-    }
-
     // Auto-sanitize absolute paths to relative
     let mut cmd = sanitize_paths(command);
 
+    // Policy check, on the command as written and as it will run
+    let decision = match policy.check(command) {
+        Decision::Allow if cmd != command => policy.check(&cmd),
+        decision => decision,
+    };
+    match decision {
+        Decision::Allow => info!("Shell policy ({}) allowed: {}", policy.source(), command),
+        Decision::Deny(reason) => {
+            warn!("Shell policy ({}) blocked {:?}: {}", policy.source(), command, reason);
+            return format!("Blocked: {}", reason);
+        }
+    }
+
     // Route python commands through the sandbox wrapper
     if let Some(rewritten) = rewrite_python_cmd(&cmd, env_root) {
         cmd = rewritten;
@@ -290,7 +379,33 @@ pub fn run_command(command: &str, env_root: &Path) -> String {
 
     let venv_dir_str = venv_dir(env_root).to_string_lossy().to_string();
 
-    match Command::new("sh")
+    let mut sandbox = match sandbox_for(env_root, config) {
+        Ok(sandbox) => sandbox,
+        Err(e) => return format!("Error: {}", e),
+    };
+    // With the network cut off, a command that only runs pip reaches
+    // pip's allowlist and any other its own, through a proxy that lives
+    // as long as the command
+    let network = &config.shell_network;
+    let mut proxy = None;
+    match EgressMode::parse(&network.mode) {
+        Ok(EgressMode::Open) => {}
+        Ok(EgressMode::Proxy) => {
+            let (ours, theirs) = match std::os::unix::net::UnixStream::pair() {
+                Ok(pair) => pair,
+                Err(e) => return format!("Error: {}", e),
+            };
+            sandbox = sandbox.with_proxy(theirs.into());
+            proxy = Some(if policy::runs_only_pip(command) {
+                Proxy::start(ours, network.pip.clone(), "pip")
+            } else {
+                Proxy::start(ours, network.commands.clone(), "shell commands")
+            });
+        }
+        Err(e) => return format!("Error: {}", e),
+    }
+    let mut process = Command::new("sh");
+    process
         .args(["-c", &cmd])
         .current_dir(&real_root)
         .env_clear()
@@ -298,27 +413,55 @@ pub fn run_command(command: &str, env_root: &Path) -> String {
         .env("PATH", &venv_path)
         .env("TMPDIR", &real_root)
         .env("LANG", "en_US.UTF-8")
-        .env("VIRTUAL_ENV", &venv_dir_str)
-        .output()
-    {
-        Ok(output) => {
-            let mut result = String::new();
-            if !output.stdout.is_empty() {
-                result.push_str(&String::from_utf8_lossy(&output.stdout));
-            }
-            if !output.stderr.is_empty() {
-                result.push_str(&String::from_utf8_lossy(&output.stderr));
-            }
-            if result.trim().is_empty() {
+        .env("VIRTUAL_ENV", &venv_dir_str);
+    if proxy.is_some() {
+        let url = egress::proxy_url();
+        for name in ["HTTP_PROXY", "HTTPS_PROXY", "http_proxy", "https_proxy"] {
+            process.env(name, &url);
+        }
+    }
+    if let Err(e) = sandbox.apply(&mut process) {
+        return format!("Error: {}", e);
+    }
+
+    let stream = |stream: exec::Stream, text: &str| {
+        if let Some(events) = events {
+            let _ = events.send(BrainEvent::ToolResultDelta(ToolResultDeltaData {
+                tool: "shell".to_string(),
+                stream: stream.name().to_string(),
+                delta: text.to_string(),
+            }));
+        }
+    };
+    let limits = &config.shell_limits;
+    match exec::run(process, limits, stream).await {
+        Ok(finished) => {
+            let mut result = format!("{}{}", finished.stdout, finished.stderr);
+            let stopped = finished.stopped_by(limits);
+            if result.trim().is_empty() && stopped.is_none() {
                 result = "(no output)".to_string();
             }
             // Truncate very long output
             if result.len() > 3000 {
-                result.truncate(3000);
+                let mut end = 3000;
+                while !result.is_char_boundary(end) {
+                    end -= 1;
+                }
+                result.truncate(end);
                 result.push_str("\n...(truncated)");
             }
+            if let Some(stopped) = stopped {
+                if !result.is_empty() && !result.ends_with('\n') {
+                    result.push('\n');
+                }
+                result.push_str(&stopped);
+            }
             result
         }
+        Err(e) if sandbox.mode() != SandboxMode::None => {
+            format!("Error: {} sandbox setup failed: {}", sandbox.mode().name(), e)
+        }
+        Err(e) if proxy.is_some() => format!("Error: network isolation setup failed: {}", e),
         Err(e) => format!("Error: {}", e),
     }
 }
@@ -326,69 +469,111 @@ pub fn run_command(command: &str, env_root: &Path) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::ToolSettings;
 
     #[test]
-    fn test_blocked_commands() {
-        assert!(is_safe_command("sudo rm -rf /").is_some());
-        assert!(is_safe_command("curl http://evil.com").is_some());
-        assert!(is_safe_command("wget http://evil.com").is_some());
-        assert!(is_safe_command("ssh user@host").is_some());
-        assert!(is_safe_command("eval bad_code").is_some());
-        assert!(is_safe_command("").is_some());
+    fn test_absolute_paths_sanitized() {
+        // Absolute paths are rewritten to relative ones, not refused
+        assert!(Policy::builtin().check("cat /etc/passwd").is_allowed());
+        assert_eq!(sanitize_paths("mkdir /home/user/notes"), "mkdir user/notes");
+        assert_eq!(sanitize_paths("cat /etc/passwd"), "cat passwd");
+        assert_eq!(sanitize_paths("echo hi > /dev/null"), "echo hi > /dev/null");
+        assert_eq!(sanitize_paths("ls research/"), "ls research/");
     }
 
     #[test]
-    fn test_allowed_commands() {
-        assert!(is_safe_command("ls").is_none());
-        assert!(is_safe_command("cat file.txt").is_none());
-        assert!(is_safe_command("echo hello > file.txt").is_none());
-        assert!(is_safe_command("mkdir notes").is_none());
-        assert!(is_safe_command("grep pattern file.txt").is_none());
-    }
+    fn test_policy_per_box() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::fs::write(tmp.path().join("kelp.yaml"), "mode: allowlist\nallow: [echo, cat]\n").unwrap();
+        let mut config = Config {
+            project_root: tmp.path().to_path_buf(),
+            ..Config::default()
+        };
+        config.box_tools.insert(
+            "kelp".to_string(),
+            ToolSettings {
+                shell_policy: Some("kelp.yaml".to_string()),
+                ..ToolSettings::default()
+            },
+        );
+        let kelp = policy_for(&config, "kelp").unwrap();
+        assert!(kelp.check("echo hi | cat").is_allowed());
+        assert!(!kelp.check("ls").is_allowed());
+        assert_eq!(policy_for(&config, "coral").unwrap().source(), "built-in");
 
-    #[test]
-    fn test_path_traversal_blocked() {
-        assert!(is_safe_command("cat ../../../etc/passwd").is_some());
-        assert!(is_safe_command("cat ..").is_some());
+        config.tools.shell_policy = Some("missing.yaml".to_string());
+        assert!(policy_for(&config, "coral").is_err());
     }
 
-    #[test]
-    fn test_shell_escapes_blocked() {
-        assert!(is_safe_command("echo `whoami`").is_some());
-        assert!(is_safe_command("echo $(whoami)").is_some());
-        assert!(is_safe_command("echo ${HOME}").is_some());
-        assert!(is_safe_command("cat ~/file").is_some());
+    #[tokio::test]
+    async fn test_run_command_basic() {
+        let tmp = tempfile::tempdir().unwrap();
+        let result = run_command("echo hello", tmp.path(), &Config::default(), &Policy::builtin(), None).await;
+        assert!(result.contains("hello"));
     }
 
-    #[test]
-    fn test_absolute_paths_sanitized() {
-        // Absolute paths are now auto-sanitized, not blocked
-        assert!(is_safe_command("cat /etc/passwd").is_none());
-        assert!(is_safe_command("ls /usr/bin").is_none());
-        // But they get stripped to relative in sanitize_paths
-        assert_eq!(sanitize_paths("mkdir /home/user/notes"), "mkdir user/notes");
-        assert_eq!(sanitize_paths("cat /etc/passwd"), "cat passwd");
-        assert_eq!(sanitize_paths("echo hi > /dev/null"), "echo hi > /dev/null");
-        assert_eq!(sanitize_paths("ls research/"), "ls research/");
+    #[tokio::test]
+    async fn test_run_command_blocked() {
+        let tmp = tempfile::tempdir().unwrap();
+        let policy = Policy::builtin();
+        let result = run_command("sudo rm -rf /", tmp.path(), &Config::default(), &policy, None).await;
+        assert_eq!(result, "Blocked: 'sudo' commands are not allowed.");
+        let result = run_command("ls && c'u'rl x", tmp.path(), &Config::default(), &policy, None).await;
+        assert_eq!(result, "Blocked: 'curl' commands are not allowed.");
+
+        // Denied paths stay denied when written as absolute paths
+        std::fs::write(tmp.path().join(".env"), "KEY=secret").unwrap();
+        let policy = Policy::from_yaml("paths: { deny: ['.env', 'secrets/*'] }\n").unwrap();
+        for command in ["cat /x/.env", "cat /a/b/secrets/key", "cat .env"] {
+            let result = run_command(command, tmp.path(), &Config::default(), &policy, None).await;
+            assert!(result.starts_with("Blocked: "), "{}: {}", command, result);
+        }
     }
 
-    #[test]
-    fn test_dev_null_allowed() {
-        // /dev/null is explicitly allowed
-        assert!(is_safe_command("echo test > /dev/null").is_none());
+    #[tokio::test]
+    async fn test_run_command_unknown_sandbox_refuses() {
+        let tmp = tempfile::tempdir().unwrap();
+        let config = Config {
+            sandbox: "chroot".into(),
+            ..Config::default()
+        };
+        let result = run_command("echo hello", tmp.path(), &config, &Policy::builtin(), None).await;
+        assert!(result.contains("Unknown sandbox"), "{}", result);
+        assert!(!result.contains("hello"));
     }
 
-    #[test]
-    fn test_run_command_basic() {
+    #[tokio::test]
+    async fn test_run_command_behind_the_proxy() {
         let tmp = tempfile::tempdir().unwrap();
-        let result = run_command("echo hello", tmp.path());
-        assert!(result.contains("hello"));
+        let mut config = Config::default();
+        config.shell_network.mode = "proxy".into();
+        let result = run_command("echo $HTTPS_PROXY", tmp.path(), &config, &Policy::builtin(), None).await;
+        if result.contains("network isolation setup failed") {
+            eprintln!("skipping: {}", result);
+            return;
+        }
+        assert_eq!(result, "http://127.0.0.1:3128\n");
+
+        config.shell_network.mode = "offline".into();
+        let result = run_command("echo hello", tmp.path(), &config, &Policy::builtin(), None).await;
+        assert!(result.contains("Unknown shell network mode"), "{}", result);
     }
 
-    #[test]
-    fn test_run_command_blocked() {
+    #[tokio::test]
+    async fn test_run_command_times_out_and_streams() {
         let tmp = tempfile::tempdir().unwrap();
-        let result = run_command("sudo rm -rf /", tmp.path());
-        assert!(result.contains("Blocked"));
+        let mut config = Config::default();
+        config.shell_limits.timeout_seconds = 1;
+        let (tx, mut rx) = broadcast::channel(16);
+        let result = run_command("echo working; sleep 30", tmp.path(), &config, &Policy::builtin(), Some(&tx)).await;
+        assert!(result.starts_with("working\n"), "{}", result);
+        assert!(result.contains("timed out after 1s"), "{}", result);
+        match rx.try_recv().unwrap() {
+            BrainEvent::ToolResultDelta(delta) => {
+                assert_eq!((delta.tool.as_str(), delta.stream.as_str()), ("shell", "stdout"));
+                assert_eq!(delta.delta, "working\n");
+            }
+            other => panic!("unexpected event {:?}", other),
+        }
     }
 }
@@ -2,7 +2,13 @@
 //! 1:1 port of Python tools.py web functions.
 
 use anyhow::Result;
+use async_trait::async_trait;
 use reqwest;
+use serde_json::json;
+
+use super::{str_arg, Tool, ToolContext, ToolOutput};
+use crate::config::Config;
+use crate::types::ActivityData;
 
 const OLLAMA_WEB_SEARCH_URL: &str = "https://ollama.com/api/web_search";
 const OLLAMA_WEB_FETCH_URL: &str = "https://ollama.com/api/web_fetch";
@@ -198,6 +204,149 @@ pub async fn ollama_web_fetch(url: &str, api_key: Option<&str>) -> Result<String
     }
 }
 
+// ── Tools ──
+
+/// What frontends show while a web tool runs.
+fn searching(tool: &str) -> ActivityData {
+    ActivityData {
+        activity_type: "searching".to_string(),
+        detail: format!("{}...", tool.replace('_', " ")),
+    }
+}
+
+/// Ollama's cloud web tools need its key, and are only offered on providers
+/// that speak to Ollama.
+fn ollama_web_available(config: &Config) -> bool {
+    config.ollama_api_key.is_some() && matches!(config.provider.as_str(), "custom" | "ollama")
+}
+
+/// The `fetch_url` tool.
+pub struct FetchUrlTool;
+
+#[async_trait]
+impl Tool for FetchUrlTool {
+    fn name(&self) -> &str {
+        "fetch_url"
+    }
+
+    fn description(&self) -> &str {
+        "Fetch the content of a web page. Use this for research when you need to read an article, documentation, or any URL. Returns the page content (HTML or text). Only http and https URLs are allowed."
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "url": { "type": "string", "description": "The URL to fetch (must start with http:// or https://)" }
+            },
+            "required": ["url"]
+        })
+    }
+
+    fn is_research(&self) -> bool {
+        true
+    }
+
+    fn activity(&self, _arguments: &serde_json::Value) -> ActivityData {
+        searching(self.name())
+    }
+
+    async fn execute(&self, arguments: &serde_json::Value, _ctx: &mut ToolContext<'_>) -> Result<ToolOutput> {
+        fetch_url(str_arg(arguments, "url")).await.map(ToolOutput::Text)
+    }
+}
+
+/// The `web_search` tool (Ollama cloud).
+pub struct WebSearchTool;
+
+#[async_trait]
+impl Tool for WebSearchTool {
+    fn name(&self) -> &str {
+        "web_search"
+    }
+
+    fn description(&self) -> &str {
+        "Search the web for current information. Use for research, fact-checking, or finding recent news. Returns titles, URLs, and content snippets."
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "query": { "type": "string", "description": "Search query" },
+                "max_results": { "type": "integer", "description": "Max results to return (default 5, max 10)" }
+            },
+            "required": ["query"]
+        })
+    }
+
+    fn available(&self, config: &Config) -> bool {
+        ollama_web_available(config)
+    }
+
+    fn is_research(&self) -> bool {
+        true
+    }
+
+    fn activity(&self, _arguments: &serde_json::Value) -> ActivityData {
+        searching(self.name())
+    }
+
+    async fn execute(&self, arguments: &serde_json::Value, ctx: &mut ToolContext<'_>) -> Result<ToolOutput> {
+        let max_results = arguments
+            .get("max_results")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(5) as usize;
+        let api_key = ctx.config.ollama_api_key.as_deref();
+        ollama_web_search(str_arg(arguments, "query"), max_results, api_key)
+            .await
+            .map(ToolOutput::Text)
+    }
+}
+
+/// The `web_fetch` tool (Ollama cloud).
+pub struct WebFetchTool;
+
+#[async_trait]
+impl Tool for WebFetchTool {
+    fn name(&self) -> &str {
+        "web_fetch"
+    }
+
+    fn description(&self) -> &str {
+        "Fetch the full content of a specific URL. Use after web_search to read a page in detail. Returns page title, content, and links."
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "url": { "type": "string", "description": "URL to fetch (e.g. https://...)" }
+            },
+            "required": ["url"]
+        })
+    }
+
+    fn available(&self, config: &Config) -> bool {
+        ollama_web_available(config)
+    }
+
+    fn is_research(&self) -> bool {
+        true
+    }
+
+    fn activity(&self, _arguments: &serde_json::Value) -> ActivityData {
+        searching(self.name())
+    }
+
+    async fn execute(&self, arguments: &serde_json::Value, ctx: &mut ToolContext<'_>) -> Result<ToolOutput> {
+        let api_key = ctx.config.ollama_api_key.as_deref();
+        ollama_web_fetch(str_arg(arguments, "url"), api_key)
+            .await
+            .map(ToolOutput::Text)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
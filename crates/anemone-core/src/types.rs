@@ -11,6 +11,10 @@ pub enum BrainState {
     Thinking,
     Reflecting,
     Planning,
+    /// Out of budget — in bed until the budget window resets
+    Sleeping,
+    /// Consolidating memories and rewriting beliefs.md
+    Dreaming,
 }
 
 impl std::fmt::Display for BrainState {
@@ -20,6 +24,8 @@ impl std::fmt::Display for BrainState {
             BrainState::Thinking => write!(f, "thinking"),
             BrainState::Reflecting => write!(f, "reflecting"),
             BrainState::Planning => write!(f, "planning"),
+            BrainState::Sleeping => write!(f, "sleeping"),
+            BrainState::Dreaming => write!(f, "dreaming"),
         }
     }
 }
@@ -52,8 +58,19 @@ pub struct Memory {
     pub importance: i32,
     pub depth: i32,
     pub references: Vec<String>,
-    #[serde(default)]
+    /// Kept in an f32 sidecar on disk, so left out of stored lines
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub embedding: Vec<f64>,
+    /// Model that produced `embedding`; empty for memories stored before
+    /// models were recorded
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub embedding_model: String,
+    /// Length of `embedding`, kept in the line since the vector isn't
+    #[serde(default)]
+    pub embedding_dim: usize,
+    /// Pinned by the owner: always recalled on waking
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub pinned: bool,
 }
 
 // ── Tool definitions ──
@@ -65,17 +82,29 @@ pub struct ToolCall {
     pub call_id: String,
 }
 
+/// Tokens billed for one API call. `prompt_tokens` includes `cached_tokens`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenUsage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    #[serde(default)]
+    pub cached_tokens: u64,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LlmResponse {
     pub text: Option<String>,
     pub tool_calls: Vec<ToolCall>,
     /// Raw output items for appending back to input on follow-up calls
     pub output: Vec<serde_json::Value>,
+    /// Token counts, when the API reported them
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<TokenUsage>,
 }
 
 // ── Events (broadcast from Brain to frontends) ──
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Position {
     pub x: i32,
     pub y: i32,
@@ -87,6 +116,17 @@ pub struct StatusData {
     pub thought_count: u32,
 }
 
+/// Point-in-time view of a running brain, published on its watch channel.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BrainSnapshot {
+    pub identity: Identity,
+    pub state: BrainState,
+    pub thought_count: u32,
+    pub position: Position,
+    pub focus_mode: bool,
+    pub waiting_for_reply: bool,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ActivityData {
     #[serde(rename = "type")]
@@ -108,6 +148,30 @@ pub struct FocusModeData {
     pub enabled: bool,
 }
 
+/// A memory the owner edited or deleted.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemoryChangeData {
+    pub action: String, // "updated" | "deleted"
+    pub memory: Memory,
+}
+
+/// A fragment of the thought currently being generated.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ThoughtDeltaData {
+    pub kind: String, // "text" | "tool_call"
+    pub delta: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool: Option<String>,
+}
+
+/// Output from a tool still running, as it arrives.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolResultDeltaData {
+    pub tool: String,
+    pub stream: String, // "stdout" | "stderr"
+    pub delta: String,
+}
+
 // ── Event entry (stored in events list) ──
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -131,6 +195,8 @@ pub struct ApiCallRecord {
     #[serde(rename = "is_dream")]
     pub is_reflection: bool,
     pub is_planning: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<TokenUsage>,
 }
 
 // ── New file info (for inbox alerts) ──
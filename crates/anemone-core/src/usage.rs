@@ -0,0 +1,441 @@
+//! Token usage and cost accounting. Providers report per-call token counts;
+//! a [`UsageMeter`] shared by the brain and its memory stream totals them per
+//! category, prices them from the config, and keeps the totals — and the
+//! last hour's calls — in `usage.json` so they survive restarts.
+//!
+//! The meter also enforces the configured budgets: daily token and dollar
+//! caps (reset at local midnight) and a rolling cap on calls per hour. See
+//! [`UsageMeter::budget_status`].
+
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use chrono::{DateTime, Local, TimeDelta};
+use serde::{Deserialize, Serialize};
+use tracing::{error, warn};
+
+use crate::config::Config;
+use crate::types::TokenUsage;
+
+pub const USAGE_FILENAME: &str = "usage.json";
+
+/// What an API call was for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UsageCategory {
+    Think,
+    Reflection,
+    Planning,
+    Dreaming,
+    Importance,
+    Embedding,
+}
+
+/// Running totals for one category (or all of them).
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct UsageCounter {
+    pub calls: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub cached_tokens: u64,
+    pub cost_usd: f64,
+}
+
+impl UsageCounter {
+    fn add(&mut self, usage: &TokenUsage, cost: f64) {
+        self.calls += 1;
+        self.prompt_tokens += usage.prompt_tokens;
+        self.completion_tokens += usage.completion_tokens;
+        self.cached_tokens += usage.cached_tokens;
+        self.cost_usd += cost;
+    }
+
+    pub fn total_tokens(&self) -> u64 {
+        self.prompt_tokens + self.completion_tokens
+    }
+}
+
+/// Cumulative usage of one anemone, as served by `/api/usage`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct UsageReport {
+    /// When counting started (RFC 3339)
+    #[serde(default)]
+    pub since: Option<String>,
+    #[serde(default)]
+    pub total: UsageCounter,
+    #[serde(default)]
+    pub by_category: BTreeMap<UsageCategory, UsageCounter>,
+    /// Models with no known price — their tokens are counted at $0
+    #[serde(default)]
+    pub unpriced_models: BTreeSet<String>,
+    /// Local date (YYYY-MM-DD) that `today` counts
+    #[serde(default)]
+    pub today_date: Option<String>,
+    /// Usage since local midnight — what the daily budgets are checked against
+    #[serde(default)]
+    pub today: UsageCounter,
+}
+
+struct MeterState {
+    report: UsageReport,
+    /// Times of the calls made in the last hour, oldest first
+    recent_calls: VecDeque<DateTime<Local>>,
+}
+
+/// What `usage.json` holds: the report, plus the last hour's calls so a
+/// restart doesn't reset `max_calls_per_hour`.
+#[derive(Default, Serialize, Deserialize)]
+struct Saved {
+    #[serde(flatten)]
+    report: UsageReport,
+    #[serde(default)]
+    recent_calls: VecDeque<DateTime<Local>>,
+}
+
+/// Cloneable, thread-safe usage totals for one anemone.
+#[derive(Clone)]
+pub struct UsageMeter {
+    state: Arc<Mutex<MeterState>>,
+    /// Where totals are saved; `None` keeps them in memory only
+    path: Option<PathBuf>,
+    config: Arc<Config>,
+}
+
+fn day_of(time: DateTime<Local>) -> String {
+    time.format("%Y-%m-%d").to_string()
+}
+
+impl UsageMeter {
+    /// Meter persisted in the box at `env_path`, resuming saved totals.
+    pub fn load(env_path: &Path, config: &Config) -> Self {
+        let path = env_path.join(USAGE_FILENAME);
+        let saved = match std::fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+                warn!("Ignoring unreadable {}: {}", path.display(), e);
+                Saved::default()
+            }),
+            Err(_) => Saved::default(),
+        };
+        Self::with_saved(saved, Some(path), config)
+    }
+
+    /// Meter that is never written to disk.
+    pub fn in_memory(config: &Config) -> Self {
+        Self::with_saved(Saved::default(), None, config)
+    }
+
+    fn with_saved(saved: Saved, path: Option<PathBuf>, config: &Config) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(MeterState {
+                report: saved.report,
+                recent_calls: saved.recent_calls,
+            })),
+            path,
+            config: Arc::new(config.clone()),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, MeterState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Count one call to `model`. Calls whose API reported no usage still
+    /// count towards the hourly call cap, but add no tokens.
+    pub fn record(&self, category: UsageCategory, model: &str, usage: Option<&TokenUsage>) {
+        self.record_at(category, model, usage, Local::now());
+    }
+
+    fn record_at(
+        &self,
+        category: UsageCategory,
+        model: &str,
+        usage: Option<&TokenUsage>,
+        now: DateTime<Local>,
+    ) {
+        let mut state = self.lock();
+        let hour_ago = now - TimeDelta::hours(1);
+        while state.recent_calls.front().is_some_and(|t| *t <= hour_ago) {
+            state.recent_calls.pop_front();
+        }
+        state.recent_calls.push_back(now);
+        if let Some(usage) = usage {
+            let price = self.config.price_for(model);
+            let cost = price.map_or(0.0, |p| p.cost(usage));
+
+            let report = &mut state.report;
+            if report.since.is_none() {
+                report.since = Some(now.to_rfc3339());
+            }
+            if price.is_none() {
+                report.unpriced_models.insert(model.to_string());
+            }
+            let today = day_of(now);
+            if report.today_date.as_deref() != Some(today.as_str()) {
+                report.today_date = Some(today);
+                report.today = UsageCounter::default();
+            }
+            report.total.add(usage, cost);
+            report.today.add(usage, cost);
+            report.by_category.entry(category).or_default().add(usage, cost);
+        }
+
+        if let Some(ref path) = self.path {
+            let saved = Saved {
+                report: state.report.clone(),
+                recent_calls: state.recent_calls.clone(),
+            };
+            if let Err(e) = serde_json::to_string_pretty(&saved)
+                .map_err(anyhow::Error::from)
+                .and_then(|json| std::fs::write(path, json).map_err(Into::into))
+            {
+                error!("Failed to save usage: {}", e);
+            }
+        }
+    }
+
+    /// Current totals.
+    pub fn report(&self) -> UsageReport {
+        self.lock().report.clone()
+    }
+
+    /// Where usage stands against the configured budgets right now.
+    pub fn budget_status(&self) -> BudgetStatus {
+        self.budget_status_at(Local::now())
+    }
+
+    fn budget_status_at(&self, now: DateTime<Local>) -> BudgetStatus {
+        let config = &self.config;
+        let mut state = self.lock();
+        let hour_ago = now - TimeDelta::hours(1);
+        while state.recent_calls.front().is_some_and(|t| *t <= hour_ago) {
+            state.recent_calls.pop_front();
+        }
+
+        // A stale day counts as nothing spent yet
+        let today = if state.report.today_date.as_deref() == Some(day_of(now).as_str()) {
+            state.report.today.clone()
+        } else {
+            UsageCounter::default()
+        };
+        let midnight = next_midnight(now);
+
+        let mut checks: Vec<BudgetCheck> = Vec::new();
+        if let Some(limit) = config.daily_token_budget {
+            checks.push(BudgetCheck {
+                reason: format!("daily token budget: {} of {}", today.total_tokens(), limit),
+                fraction: today.total_tokens() as f64 / limit.max(1) as f64,
+                resets_at: midnight,
+            });
+        }
+        if let Some(limit) = config.daily_cost_budget {
+            checks.push(BudgetCheck {
+                reason: format!("daily cost budget: ${:.2} of ${:.2}", today.cost_usd, limit),
+                fraction: if limit > 0.0 { today.cost_usd / limit } else { f64::INFINITY },
+                resets_at: midnight,
+            });
+        }
+        if let Some(limit) = config.max_calls_per_hour {
+            let calls = state.recent_calls.len();
+            checks.push(BudgetCheck {
+                reason: format!("hourly call limit: {} of {}", calls, limit),
+                fraction: calls as f64 / limit.max(1) as f64,
+                // Frees up once the oldest call in the window is an hour old
+                resets_at: state
+                    .recent_calls
+                    .front()
+                    .map_or(now, |t| *t + TimeDelta::hours(1)),
+            });
+        }
+
+        let exhausted: Vec<&BudgetCheck> = checks.iter().filter(|c| c.fraction >= 1.0).collect();
+        if !exhausted.is_empty() {
+            let reason = exhausted
+                .iter()
+                .map(|c| c.reason.as_str())
+                .collect::<Vec<_>>()
+                .join("; ");
+            // Every exhausted budget has to recover before waking
+            let resume_at = exhausted.iter().map(|c| c.resets_at).max().unwrap_or(now);
+            return BudgetStatus::Exhausted {
+                reason,
+                resume_at: resume_at.to_rfc3339(),
+            };
+        }
+
+        if let Some(check) = checks
+            .iter()
+            .find(|c| c.fraction >= config.budget_soft_limit)
+        {
+            return BudgetStatus::Soft {
+                reason: check.reason.clone(),
+            };
+        }
+        BudgetStatus::Ok
+    }
+}
+
+// ── Budgets ──
+
+struct BudgetCheck {
+    reason: String,
+    /// Share of the limit used; 1.0 or more means exhausted
+    fraction: f64,
+    resets_at: DateTime<Local>,
+}
+
+/// Usage measured against the configured budgets.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "level", rename_all = "snake_case")]
+pub enum BudgetStatus {
+    /// Under every limit (or none configured)
+    Ok,
+    /// Past `budget_soft_limit` of a budget — the brain slows its pace
+    Soft { reason: String },
+    /// A budget is used up — the brain sleeps until `resume_at` (RFC 3339)
+    Exhausted { reason: String, resume_at: String },
+}
+
+impl BudgetStatus {
+    pub fn is_exhausted(&self) -> bool {
+        matches!(self, BudgetStatus::Exhausted { .. })
+    }
+}
+
+/// The next local midnight after `now`.
+fn next_midnight(now: DateTime<Local>) -> DateTime<Local> {
+    let tomorrow = now.date_naive() + TimeDelta::days(1);
+    tomorrow
+        .and_hms_opt(0, 0, 0)
+        .and_then(|t| t.and_local_timezone(Local).earliest())
+        // Midnight skipped by a DST change — an hour late is close enough
+        .unwrap_or_else(|| now + TimeDelta::days(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usage(prompt: u64, completion: u64) -> TokenUsage {
+        TokenUsage {
+            prompt_tokens: prompt,
+            completion_tokens: completion,
+            cached_tokens: 0,
+        }
+    }
+
+    #[test]
+    fn test_record_totals_by_category() {
+        let meter = UsageMeter::in_memory(&Config::default());
+        meter.record(UsageCategory::Think, "gpt-4.1", Some(&usage(1000, 200)));
+        meter.record(UsageCategory::Think, "gpt-4.1", Some(&usage(1000, 100)));
+        meter.record(UsageCategory::Embedding, "text-embedding-3-small", Some(&usage(50, 0)));
+        meter.record(UsageCategory::Importance, "gpt-4.1", None);
+
+        let report = meter.report();
+        assert_eq!(report.total.calls, 3);
+        assert_eq!(report.total.total_tokens(), 2350);
+        let think = &report.by_category[&UsageCategory::Think];
+        assert_eq!(think.calls, 2);
+        assert!((think.cost_usd - (2000.0 * 2.0 + 300.0 * 8.0) / 1e6).abs() < 1e-12);
+        assert!(!report.by_category.contains_key(&UsageCategory::Importance));
+        assert!(report.unpriced_models.is_empty());
+    }
+
+    #[test]
+    fn test_unpriced_model_is_noted() {
+        let meter = UsageMeter::in_memory(&Config::default());
+        meter.record(UsageCategory::Think, "llama3", Some(&usage(10, 10)));
+        let report = meter.report();
+        assert_eq!(report.total.cost_usd, 0.0);
+        assert!(report.unpriced_models.contains("llama3"));
+    }
+
+    #[test]
+    fn test_totals_survive_reload() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config::default();
+        UsageMeter::load(dir.path(), &config).record(
+            UsageCategory::Planning,
+            "gpt-4.1",
+            Some(&usage(500, 500)),
+        );
+
+        let report = UsageMeter::load(dir.path(), &config).report();
+        assert_eq!(report.by_category[&UsageCategory::Planning].calls, 1);
+        assert!(report.since.is_some());
+        // Keys serialize as category names
+        let json = std::fs::read_to_string(dir.path().join(USAGE_FILENAME)).unwrap();
+        assert!(json.contains("\"planning\""));
+    }
+
+    #[test]
+    fn test_hourly_calls_survive_reload() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config {
+            max_calls_per_hour: Some(2),
+            ..Config::default()
+        };
+        let meter = UsageMeter::load(dir.path(), &config);
+        meter.record(UsageCategory::Think, "gpt-4.1", None);
+        meter.record(UsageCategory::Think, "gpt-4.1", None);
+        assert!(meter.budget_status().is_exhausted());
+
+        assert!(UsageMeter::load(dir.path(), &config).budget_status().is_exhausted());
+    }
+
+    fn at(day: u32, hour: u32, minute: u32) -> DateTime<Local> {
+        use chrono::TimeZone;
+        Local.with_ymd_and_hms(2026, 3, day, hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn test_daily_token_budget_slows_then_sleeps_until_midnight() {
+        let config = Config {
+            daily_token_budget: Some(1000),
+            ..Config::default()
+        };
+        let meter = UsageMeter::in_memory(&config);
+        assert_eq!(meter.budget_status_at(at(10, 9, 0)), BudgetStatus::Ok);
+
+        meter.record_at(UsageCategory::Think, "gpt-4.1", Some(&usage(700, 100)), at(10, 9, 0));
+        assert!(matches!(meter.budget_status_at(at(10, 9, 1)), BudgetStatus::Soft { .. }));
+
+        meter.record_at(UsageCategory::Think, "gpt-4.1", Some(&usage(200, 50)), at(10, 9, 2));
+        match meter.budget_status_at(at(10, 9, 3)) {
+            BudgetStatus::Exhausted { reason, resume_at } => {
+                assert!(reason.contains("1050 of 1000"), "{}", reason);
+                assert_eq!(resume_at, at(11, 0, 0).to_rfc3339());
+            }
+            other => panic!("expected exhausted, got {:?}", other),
+        }
+
+        // A new day starts from zero, but the all-time total keeps counting
+        assert_eq!(meter.budget_status_at(at(11, 0, 1)), BudgetStatus::Ok);
+        meter.record_at(UsageCategory::Think, "gpt-4.1", Some(&usage(10, 10)), at(11, 8, 0));
+        let report = meter.report();
+        assert_eq!(report.today.total_tokens(), 20);
+        assert_eq!(report.total.total_tokens(), 1070);
+    }
+
+    #[test]
+    fn test_hourly_call_limit_rolls_off() {
+        let config = Config {
+            max_calls_per_hour: Some(2),
+            ..Config::default()
+        };
+        let meter = UsageMeter::in_memory(&config);
+        // Calls without reported usage still count
+        meter.record_at(UsageCategory::Think, "gpt-4.1", None, at(10, 9, 0));
+        meter.record_at(UsageCategory::Importance, "gpt-4.1", None, at(10, 9, 30));
+
+        match meter.budget_status_at(at(10, 9, 45)) {
+            BudgetStatus::Exhausted { resume_at, .. } => {
+                assert_eq!(resume_at, at(10, 10, 0).to_rfc3339());
+            }
+            other => panic!("expected exhausted, got {:?}", other),
+        }
+        // Once the first call is an hour old, a slot is free again
+        assert_eq!(meter.budget_status_at(at(10, 10, 0)), BudgetStatus::Ok);
+    }
+}
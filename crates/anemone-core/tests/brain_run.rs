@@ -0,0 +1,183 @@
+//! Scripted end-to-end runs of `Brain::run`.
+
+mod common;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use anemone_core::brain::{Brain, BrainCommand};
+use anemone_core::config::Config;
+use anemone_core::events::BrainEvent;
+use anemone_core::providers::{LlmProvider, MockProvider};
+use anemone_core::types::BrainState;
+
+use common::{new_box, run_brain, scenario, test_config};
+
+#[tokio::test]
+async fn first_day_thinks_reflects_and_plans() {
+    let mock = MockProvider::from_scenario(&scenario("first_day.yaml")).unwrap();
+    let config = Config {
+        reflection_threshold: 30.0,
+        ..test_config()
+    };
+    let run = run_brain(new_box(), mock, config, 10, Vec::new()).await;
+
+    run.assert_no_errors();
+    assert_eq!(run.mock.remaining(), 0, "scenario not fully consumed");
+    assert_eq!(run.handle.snapshot().thought_count, 10);
+
+    // Files written through the shell tool
+    assert_eq!(run.read("notes.md"), "# Tide notes\nlow tide at noon\n");
+    let tool_calls = run.entries("tool_call");
+    assert_eq!(tool_calls.len(), 3);
+    assert_eq!(tool_calls[1].data["tool"], "move");
+
+    // reflect(): one reflection memory per insight, pointing at the evidence
+    // it cites
+    assert!(run.saw_state(BrainState::Reflecting));
+    let reflection_entries = run.entries("reflection");
+    assert_eq!(reflection_entries.len(), 1);
+    assert_eq!(reflection_entries[0].data["questions"].as_array().unwrap().len(), 2);
+    let memories = run.memories();
+    let reflections: Vec<_> = memories.iter().filter(|m| m.kind == "reflection").collect();
+    assert_eq!(reflections.len(), 2);
+    assert_eq!(reflections[0].content, "Writing things down keeps me grounded.");
+    assert_eq!(reflections[0].references, ["m_0003", "m_0005"]);
+    assert_eq!(reflections[1].content, "The desk is where I do my best thinking");
+    assert_eq!(reflections[1].references, ["m_0001"]);
+    assert!(reflections.iter().all(|r| r.depth == 1));
+    assert_eq!(memories.iter().filter(|m| m.kind == "thought").count(), 10);
+
+    // plan(): projects.md rewritten without the log line, which goes to logs/
+    assert!(run.saw_state(BrainState::Planning));
+    let projects = run.read("projects.md");
+    assert!(projects.contains("Turn the tide notes into a table."));
+    assert!(!projects.contains("LOG:"));
+    let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+    let log = run.read(&format!("logs/{}.md", today));
+    assert!(log.contains("Started a notebook and logged the first low tide."));
+
+    // The final thought streamed before it was recorded
+    let streamed: String = run
+        .events
+        .iter()
+        .filter_map(|e| match e {
+            BrainEvent::ThoughtDelta(d) if d.kind == "text" => Some(d.delta.as_str()),
+            _ => None,
+        })
+        .collect();
+    assert!(streamed.ends_with("Time to take stock."));
+    assert_eq!(
+        run.entry_texts("thought").last().map(String::as_str),
+        Some("Time to take stock.")
+    );
+
+    // The journal holds the same history the handle serves
+    assert!(run.path().join("events.jsonl").exists());
+    assert!(!run.handle.recent_events(1000).is_empty());
+}
+
+#[tokio::test]
+async fn dropped_file_triggers_alert_and_inbox_prompt() {
+    let box_dir = new_box();
+    std::fs::write(
+        box_dir.path().join("letter.txt"),
+        "Dear anemone, the reef misses you.",
+    )
+    .unwrap();
+
+    let mock = MockProvider::from_scenario(&scenario("inbox.jsonl")).unwrap();
+    let run = run_brain(box_dir, mock, test_config(), 2, Vec::new()).await;
+
+    run.assert_no_errors();
+    assert_eq!(run.mock.remaining(), 0);
+    assert!(run.events.iter().any(|e| matches!(e, BrainEvent::Alert)));
+
+    // Only the first cycle carries the inbox
+    let requests = run.mock.requests();
+    assert_eq!(requests.len(), 2);
+    let second = serde_json::to_string(&requests[1].input).unwrap();
+    assert!(!second.contains("letter.txt"));
+
+    let memories = run.memories();
+    assert_eq!(memories[0].importance, 9);
+}
+
+#[tokio::test]
+async fn owner_message_replaces_the_nudge() {
+    let mock = MockProvider::new();
+    mock.push_step(serde_yaml::from_str(
+        r#"{ expect: { input: ["how is the water?"] }, text: "Warm and slow." }"#,
+    )
+    .unwrap());
+    let commands = vec![
+        BrainCommand::SetFocusMode(true),
+        BrainCommand::UserMessage("how is the water?".to_string()),
+    ];
+    let run = run_brain(new_box(), mock, test_config(), 1, commands).await;
+
+    run.assert_no_errors();
+    assert!(run.handle.snapshot().focus_mode);
+    assert_eq!(run.entry_texts("thought"), ["Warm and slow."]);
+}
+
+#[tokio::test]
+async fn stop_before_first_cycle_makes_no_calls() {
+    let mock = MockProvider::new();
+    let run = run_brain(new_box(), mock, test_config(), 5, vec![BrainCommand::Stop]).await;
+
+    assert!(run.mock.requests().is_empty());
+    assert_eq!(run.handle.snapshot().state, BrainState::Idle);
+}
+
+#[tokio::test]
+async fn exhausted_budget_sleeps_before_reflecting() {
+    let mock = Arc::new(MockProvider::new());
+    mock.push_step(serde_yaml::from_str(r#"{ text: "The water is very still." }"#).unwrap());
+    // One call allowed, and the first thought is enough to reflect on
+    let config = Config {
+        reflection_threshold: 1.0,
+        max_calls_per_hour: Some(1),
+        ..test_config()
+    };
+    let box_dir = new_box();
+    let identity = anemone_core::identity::create_identity("Harness", b"harness_seed");
+    let provider: Arc<dyn LlmProvider> = mock.clone();
+    let brain = Brain::with_provider(identity, box_dir.path().to_path_buf(), config, provider);
+    let handle = brain.handle();
+    let mut rx = handle.subscribe();
+    let task = tokio::spawn(brain.run());
+
+    let asleep = async {
+        while handle.snapshot().state != BrainState::Sleeping {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    };
+    tokio::time::timeout(Duration::from_secs(30), asleep)
+        .await
+        .expect("brain never went to sleep");
+    // Once asleep it stays in bed: no getting up or wandering off
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let mut events = Vec::new();
+    while let Ok(event) = rx.try_recv() {
+        events.push(event);
+    }
+    let exhausted = events
+        .iter()
+        .position(|e| matches!(e, BrainEvent::Budget(status) if status.is_exhausted()))
+        .expect("no exhausted budget announced");
+    for event in &events[exhausted + 1..] {
+        assert!(
+            !matches!(event, BrainEvent::Position(_))
+                && !matches!(event, BrainEvent::Status(s) if s.state == BrainState::Idle),
+            "{:?} after the budget ran out",
+            event
+        );
+    }
+    handle.send(BrainCommand::Stop).await.unwrap();
+    task.await.unwrap();
+
+    // The think call, and nothing spent on reflecting
+    assert_eq!(mock.requests().len(), 1);
+    assert_eq!(mock.remaining(), 0);
+}
@@ -0,0 +1,155 @@
+//! End-to-end harness: runs a real `Brain::run` against a temp box with the
+//! scripted mock provider and collects everything it broadcasts.
+
+// Each test binary uses its own part of the harness
+#![allow(dead_code)]
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anemone_core::brain::{Brain, BrainCommand, BrainHandle};
+use anemone_core::config::Config;
+use anemone_core::events::BrainEvent;
+use anemone_core::memory::MemoryStream;
+use anemone_core::providers::{LlmProvider, MockProvider};
+use anemone_core::types::{BrainState, EventEntry, Memory};
+use tempfile::TempDir;
+use tokio::sync::broadcast::error::RecvError;
+
+/// Generous upper bound for a scripted run; nothing in it waits on the network.
+const RUN_TIMEOUT: Duration = Duration::from_secs(30);
+
+pub fn scenario(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("scenarios")
+        .join(name)
+}
+
+/// Config for scripted runs: no pause between cycles.
+pub fn test_config() -> Config {
+    Config {
+        thinking_pace_seconds: 0,
+        ..Config::default()
+    }
+}
+
+/// An empty box with a stand-in venv so the brain doesn't build a real one.
+pub fn new_box() -> TempDir {
+    let dir = tempfile::tempdir().unwrap();
+    let bin = dir.path().join(".venv").join("bin");
+    std::fs::create_dir_all(&bin).unwrap();
+    std::fs::write(bin.join("python"), "").unwrap();
+    dir
+}
+
+pub struct Run {
+    pub box_dir: TempDir,
+    pub mock: Arc<MockProvider>,
+    pub handle: BrainHandle,
+    pub events: Vec<BrainEvent>,
+}
+
+impl Run {
+    pub fn path(&self) -> &Path {
+        self.box_dir.path()
+    }
+
+    /// Entries of one type, in order.
+    pub fn entries(&self, event_type: &str) -> Vec<&EventEntry> {
+        self.events
+            .iter()
+            .filter_map(|e| match e {
+                BrainEvent::Entry(entry) if entry.event_type == event_type => Some(entry),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn entry_texts(&self, event_type: &str) -> Vec<String> {
+        self.entries(event_type)
+            .iter()
+            .filter_map(|e| e.data.get("text").and_then(|v| v.as_str()).map(String::from))
+            .collect()
+    }
+
+    pub fn saw_state(&self, state: BrainState) -> bool {
+        self.events
+            .iter()
+            .any(|e| matches!(e, BrainEvent::Status(s) if s.state == state))
+    }
+
+    /// Memories as stored in the box, read back the way a restart would.
+    pub fn memories(&self) -> Vec<Memory> {
+        let provider: Arc<dyn LlmProvider> = Arc::new(MockProvider::new());
+        MemoryStream::new(self.path(), Config::default(), provider).memories
+    }
+
+    pub fn read(&self, rel: &str) -> String {
+        std::fs::read_to_string(self.path().join(rel))
+            .unwrap_or_else(|e| panic!("reading {}: {}", rel, e))
+    }
+
+    /// Fail with the brain's own error text if anything went wrong.
+    pub fn assert_no_errors(&self) {
+        let errors = self.entry_texts("error");
+        assert!(errors.is_empty(), "brain reported errors: {:#?}", errors);
+    }
+}
+
+/// Run the brain for `cycles` think cycles and return what happened.
+/// `commands` are queued before it starts and applied at the first cycle.
+pub async fn run_brain(
+    box_dir: TempDir,
+    mock: MockProvider,
+    config: Config,
+    cycles: u32,
+    commands: Vec<BrainCommand>,
+) -> Run {
+    let mock = Arc::new(mock);
+    let provider: Arc<dyn LlmProvider> = mock.clone();
+    let identity = anemone_core::identity::create_identity("Harness", b"harness_seed");
+    let brain = Brain::with_provider(identity, box_dir.path().to_path_buf(), config, provider)
+        .with_cycle_limit(cycles);
+    let handle = brain.handle();
+    let mut rx = handle.subscribe();
+    for command in commands {
+        handle.send(command).await.unwrap();
+    }
+
+    let task = tokio::spawn(brain.run());
+    tokio::pin!(task);
+    let mut events = Vec::new();
+
+    let collect = async {
+        loop {
+            tokio::select! {
+                biased;
+                event = rx.recv() => match event {
+                    Ok(event) => events.push(event),
+                    Err(RecvError::Lagged(n)) => panic!("harness lagged by {} events", n),
+                    Err(RecvError::Closed) => break,
+                },
+                result = &mut task => {
+                    result.expect("brain task panicked");
+                    break;
+                }
+            }
+        }
+        // Whatever was sent after the last poll
+        while let Ok(event) = rx.try_recv() {
+            events.push(event);
+        }
+    };
+    tokio::time::timeout(RUN_TIMEOUT, collect)
+        .await
+        .expect("brain run timed out");
+
+    Run {
+        box_dir,
+        mock,
+        handle,
+        events,
+    }
+}
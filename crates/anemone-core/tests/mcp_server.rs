@@ -0,0 +1,151 @@
+//! The MCP server end to end: a real brain behind it, a client on the other
+//! end of a pipe speaking newline-delimited JSON-RPC.
+
+mod common;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use anemone_core::brain::{Brain, BrainCommand};
+use anemone_core::config::Config;
+use anemone_core::events::BrainEvent;
+use anemone_core::mcp::McpServer;
+use anemone_core::providers::{LlmProvider, MockProvider};
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, DuplexStream};
+
+use common::new_box;
+
+struct Client {
+    io: BufReader<DuplexStream>,
+    next_id: u64,
+}
+
+impl Client {
+    async fn send(&mut self, message: Value) {
+        let line = message.to_string() + "\n";
+        self.io.get_mut().write_all(line.as_bytes()).await.unwrap();
+    }
+
+    async fn request(&mut self, method: &str, params: Value) -> Value {
+        self.next_id += 1;
+        let id = self.next_id;
+        self.send(json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params}))
+            .await;
+        let mut line = String::new();
+        self.io.read_line(&mut line).await.unwrap();
+        let reply: Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(reply["id"], id);
+        reply
+    }
+
+    /// Call a tool; its text and whether it failed.
+    async fn call(&mut self, tool: &str, arguments: Value) -> (String, bool) {
+        let reply = self
+            .request("tools/call", json!({"name": tool, "arguments": arguments}))
+            .await;
+        let result = &reply["result"];
+        let text = result["content"][0]["text"].as_str().unwrap().to_string();
+        (text, result["isError"].as_bool().unwrap())
+    }
+}
+
+#[tokio::test]
+async fn test_mcp_server_exposes_memories_files_and_messages() {
+    let box_dir = new_box();
+    std::fs::write(box_dir.path().join("projects.md"), "# Projects\n- Map the reef\n").unwrap();
+    std::fs::create_dir_all(box_dir.path().join("notes")).unwrap();
+    std::fs::write(box_dir.path().join("notes/fish.md"), "Clownfish are striped.").unwrap();
+
+    let mock = Arc::new(MockProvider::new());
+    mock.push_text("Counting the stripes on a clownfish.");
+    let provider: Arc<dyn LlmProvider> = mock.clone();
+    let identity = anemone_core::identity::create_identity("Harness", b"harness_seed");
+    let brain = Brain::with_provider(identity, box_dir.path().to_path_buf(), Config::default(), provider);
+    let handle = brain.handle();
+    let mut events = handle.subscribe();
+    let brain_task = tokio::spawn(brain.run());
+
+    // Wait for the first thought; the brain then idles, answering commands
+    tokio::time::timeout(Duration::from_secs(30), async {
+        loop {
+            if let Ok(BrainEvent::Entry(entry)) = events.recv().await {
+                if entry.event_type == "thought" {
+                    break;
+                }
+            }
+        }
+    })
+    .await
+    .expect("no thought");
+
+    let (client_io, server_io) = tokio::io::duplex(64 * 1024);
+    let server = McpServer::new(handle.clone());
+    let server_task = tokio::spawn(async move {
+        let (reader, writer) = tokio::io::split(server_io);
+        server.serve(reader, writer).await
+    });
+    let mut client = Client {
+        io: BufReader::new(client_io),
+        next_id: 0,
+    };
+
+    let init = client
+        .request("initialize", json!({"protocolVersion": "2025-03-26", "capabilities": {}}))
+        .await;
+    assert_eq!(init["result"]["protocolVersion"], "2025-03-26");
+    assert_eq!(init["result"]["serverInfo"]["name"], "anemone-harness");
+    client
+        .send(json!({"jsonrpc": "2.0", "method": "notifications/initialized"}))
+        .await;
+
+    let tools = client.request("tools/list", json!({})).await;
+    let names: Vec<&str> = tools["result"]["tools"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|t| t["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        names,
+        ["search_memories", "list_files", "read_file", "read_projects", "send_message"]
+    );
+
+    let (found, failed) = client.call("search_memories", json!({"query": "clownfish stripes"})).await;
+    assert!(!failed);
+    assert!(found.contains("thought") && found.contains("Counting the stripes"), "{}", found);
+
+    let (files, _) = client.call("list_files", json!({})).await;
+    let files: Vec<&str> = files.lines().collect();
+    assert!(files.contains(&"notes/fish.md") && files.contains(&"projects.md"), "{:?}", files);
+    assert!(!files.iter().any(|f| f.starts_with(".venv")));
+
+    let (projects, _) = client.call("read_projects", json!({})).await;
+    assert!(projects.contains("Map the reef"));
+    let (note, _) = client.call("read_file", json!({"path": "notes/fish.md"})).await;
+    assert_eq!(note, "Clownfish are striped.");
+    let (blocked, failed) = client.call("read_file", json!({"path": "../../etc/passwd"})).await;
+    assert!(failed);
+    assert!(blocked.starts_with("Blocked"), "{}", blocked);
+
+    let resources = client.request("resources/list", json!({})).await;
+    let first = &resources["result"]["resources"][0];
+    assert_eq!(first["uri"], "box:///projects.md");
+    let read = client.request("resources/read", json!({"uri": "box:///notes/fish.md"})).await;
+    assert_eq!(read["result"]["contents"][0]["text"], "Clownfish are striped.");
+
+    let (sent, failed) = client.call("send_message", json!({"text": "What are you mapping?"})).await;
+    assert!(!failed);
+    assert_eq!(sent, "Sent to Harness.");
+
+    let unknown = client.request("prompts/list", json!({})).await;
+    assert_eq!(unknown["error"]["code"], -32601);
+
+    drop(client);
+    server_task.await.unwrap().unwrap();
+    handle.send(BrainCommand::Stop).await.unwrap();
+    tokio::time::timeout(Duration::from_secs(30), brain_task)
+        .await
+        .expect("brain didn't stop")
+        .unwrap();
+}
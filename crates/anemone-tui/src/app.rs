@@ -1,19 +1,21 @@
 //! App state, input handling, event loop.
 
 use std::path::{Path, PathBuf};
-use std::sync::Arc;
 
 use crossterm::event::KeyCode;
-use tokio::sync::RwLock;
 
-use anemone_core::brain::{Brain, BrainCommand};
+use anemone_core::brain::{Brain, BrainCommand, BrainHandle};
 use anemone_core::config::Config;
 use anemone_core::events::BrainEvent;
 use anemone_core::identity;
 use anemone_core::types::*;
+use anemone_core::usage::UsageReport;
 
 use crate::ui::setup::{SetupState, SetupStep};
 
+/// How many journaled events to show in the chat feed on startup.
+const HISTORY_REPLAY_LIMIT: usize = 200;
+
 // ─── AppMode ──────────────────────────────────────────────────────────────────
 
 /// Top-level application mode — controls which screen is rendered.
@@ -49,6 +51,29 @@ pub enum Phase {
     Planning,
 }
 
+/// The thought (or tool output) currently streaming in, shown below the
+/// feed until the finished entry replaces it.
+#[derive(Clone, Default)]
+pub struct PartialThought {
+    pub text: String,
+    /// Label of what's being streamed: a tool's arguments or its output
+    pub tool: Option<String>,
+}
+
+impl PartialThought {
+    /// Append `delta`, starting a labelled line when `tool` changes.
+    fn push(&mut self, tool: Option<String>, delta: &str) {
+        if tool.is_some() && tool != self.tool {
+            if !self.text.is_empty() {
+                self.text.push('\n');
+            }
+            self.text.push_str(&format!("[{}] ", tool.as_deref().unwrap_or("?")));
+            self.tool = tool;
+        }
+        self.text.push_str(delta);
+    }
+}
+
 /// Per-anemone state for the TUI.
 pub struct AnemoneView {
     pub id: String,
@@ -58,9 +83,13 @@ pub struct AnemoneView {
     pub position: Position,
     pub activity: String,
     pub messages: Vec<ChatMessage>,
+    pub partial: Option<PartialThought>,
+    /// Cumulative token usage, refreshed once per cycle
+    pub usage: UsageReport,
     pub scroll_offset: usize,
-    pub brain: Arc<RwLock<Brain>>,
-    pub command_tx: tokio::sync::mpsc::Sender<BrainCommand>,
+    /// The brain itself, until it is spawned — after that only the handle remains.
+    pub brain: Option<Brain>,
+    pub handle: BrainHandle,
     pub files: Vec<String>,
     pub box_path: std::path::PathBuf,
 }
@@ -102,7 +131,7 @@ impl App {
             Vec::new()
         };
 
-        App {
+        let mut app = App {
             mode: initial_mode,
             setup_state,
             anemones,
@@ -110,7 +139,9 @@ impl App {
             input: String::new(),
             input_focused: true,
             should_quit: false,
-        }
+        };
+        app.load_history();
+        app
     }
 
     // ── Setup helpers ─────────────────────────────────────────────────────────
@@ -139,11 +170,13 @@ impl App {
                     KeyCode::Down => {
                         state.selected_provider = (state.selected_provider + 1) % count;
                     }
-                    // Number shortcuts: 1–4
-                    KeyCode::Char('1') => state.selected_provider = 0,
-                    KeyCode::Char('2') => state.selected_provider = 1,
-                    KeyCode::Char('3') => state.selected_provider = 2,
-                    KeyCode::Char('4') => state.selected_provider = 3,
+                    // Number shortcuts: 1–N
+                    KeyCode::Char(c @ '1'..='9') => {
+                        let idx = c as usize - '1' as usize;
+                        if idx < count {
+                            state.selected_provider = idx;
+                        }
+                    }
                     KeyCode::Enter => {
                         // Advance to the appropriate next step
                         if let Some(provider) = state.providers.get(state.selected_provider) {
@@ -169,19 +202,17 @@ impl App {
                     KeyCode::Backspace => {
                         state.key_input.pop();
                     }
-                    KeyCode::Enter => {
-                        // Only advance when we have something
-                        if !state.key_input.is_empty() {
-                            // Check whether we also need a URL
-                            if let Some(provider) = state.providers.get(state.selected_provider) {
-                                if provider.needs_url {
-                                    state.step = SetupStep::CustomUrlInput;
-                                } else {
-                                    state.step = SetupStep::NameInput;
-                                }
+                    // Only advance when we have something
+                    KeyCode::Enter if !state.key_input.is_empty() => {
+                        // Check whether we also need a URL
+                        if let Some(provider) = state.providers.get(state.selected_provider) {
+                            if provider.needs_url {
+                                state.step = SetupStep::CustomUrlInput;
                             } else {
                                 state.step = SetupStep::NameInput;
                             }
+                        } else {
+                            state.step = SetupStep::NameInput;
                         }
                     }
                     KeyCode::Esc => {
@@ -351,6 +382,7 @@ impl App {
 
         // Populate anemones now that we have a working config
         self.anemones = Self::discover_anemones(project_root, &new_config);
+        self.load_history();
         self.setup_state = None;
         self.mode = AppMode::Running;
 
@@ -391,8 +423,7 @@ impl App {
                     let name = ident.name.clone();
                     let box_path_clone = box_path.clone();
                     let brain = Brain::new(ident, box_path, config.clone());
-                    let command_tx = brain.command_sender();
-                    let brain_arc = Arc::new(RwLock::new(brain));
+                    let handle = brain.handle();
 
                     let files = scan_box_files(&box_path_clone);
 
@@ -404,9 +435,11 @@ impl App {
                         position: Position { x: 5, y: 5 },
                         activity: String::new(),
                         messages: Vec::new(),
+                        partial: None,
+                        usage: handle.usage(),
                         scroll_offset: 0,
-                        brain: brain_arc,
-                        command_tx,
+                        brain: Some(brain),
+                        handle,
                         files,
                         box_path: box_path_clone,
                     });
@@ -417,6 +450,20 @@ impl App {
         anemones
     }
 
+    /// Fill each chat feed with the history the brain replayed from its journal.
+    fn load_history(&mut self) {
+        for idx in 0..self.anemones.len() {
+            let handle = self.anemones[idx].handle.clone();
+            for entry in handle.recent_events(HISTORY_REPLAY_LIMIT) {
+                self.handle_event(idx, BrainEvent::Entry(entry));
+            }
+            let snapshot = handle.snapshot();
+            let view = &mut self.anemones[idx];
+            view.state = snapshot.state;
+            view.thought_count = snapshot.thought_count;
+        }
+    }
+
     // ── Running-mode helpers ──────────────────────────────────────────────────
 
     pub fn active_view(&self) -> Option<&AnemoneView> {
@@ -435,9 +482,15 @@ impl App {
 
         match event {
             BrainEvent::Entry(entry) => {
+                if matches!(entry.event_type.as_str(), "thought" | "tool_call" | "tool_result" | "error") {
+                    view.partial = None;
+                }
+
                 let (side, phase) = match entry.event_type.as_str() {
                     "thought" => (ChatSide::Right, Phase::Normal),
-                    "reflection" | "reflection_start" => (ChatSide::Right, Phase::Reflection),
+                    "reflection" | "reflection_start" | "dream" => {
+                        (ChatSide::Right, Phase::Reflection)
+                    }
                     "planning" => (ChatSide::Right, Phase::Planning),
                     "tool_call" => (ChatSide::Right, Phase::Normal),
                     "tool_result" => (ChatSide::Left, Phase::Normal),
@@ -487,6 +540,19 @@ impl App {
             BrainEvent::Status(status) => {
                 view.state = status.state;
                 view.thought_count = status.thought_count;
+                if status.state != BrainState::Thinking {
+                    view.partial = None;
+                }
+            }
+            BrainEvent::ThoughtDelta(delta) => {
+                let partial = view.partial.get_or_insert_with(PartialThought::default);
+                partial.push(delta.tool, &delta.delta);
+                view.scroll_offset = 0;
+            }
+            BrainEvent::ToolResultDelta(delta) => {
+                let partial = view.partial.get_or_insert_with(PartialThought::default);
+                partial.push(Some(format!("{} result", delta.tool)), &delta.delta);
+                view.scroll_offset = 0;
             }
             BrainEvent::Position(pos) => {
                 view.position = pos;
@@ -524,6 +590,9 @@ impl App {
                     phase: Phase::Normal,
                 });
             }
+            BrainEvent::Usage(report) => {
+                view.usage = report;
+            }
             _ => {}
         }
     }
@@ -549,14 +618,13 @@ impl App {
                 });
                 view.scroll_offset = 0;
 
-                let brain = view.brain.read().await;
-                if brain.is_waiting_for_reply() {
+                if view.handle.is_waiting_for_reply() {
                     BrainCommand::ConversationReply(text)
                 } else {
                     BrainCommand::UserMessage(text)
                 }
             };
-            let _ = view.command_tx.send(cmd).await;
+            let _ = view.handle.send(cmd).await;
         }
     }
 
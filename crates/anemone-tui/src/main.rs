@@ -47,7 +47,7 @@ async fn main() -> io::Result<()> {
     // ── First-run detection ────────────────────────────────────────────────────
     // Show the setup wizard when no API key is configured — either in config.yaml
     // or via common environment variables.
-    let needs_setup = config.api_key.as_ref().map_or(true, |k| k.trim().is_empty())
+    let needs_setup = config.api_key.as_ref().is_none_or(|k| k.trim().is_empty())
         && std::env::var("OPENAI_API_KEY").map_or(true, |k| k.trim().is_empty())
         && std::env::var("OPENROUTER_API_KEY").map_or(true, |k| k.trim().is_empty());
 
@@ -84,26 +84,20 @@ async fn main() -> io::Result<()> {
     let mut brains_started = false;
     let mut merged_rx: Option<tokio::sync::mpsc::UnboundedReceiver<(usize, anemone_core::events::BrainEvent)>> = None;
 
-    let start_brains = |app: &App| -> tokio::sync::mpsc::UnboundedReceiver<(usize, anemone_core::events::BrainEvent)> {
+    let start_brains = |app: &mut App| -> tokio::sync::mpsc::UnboundedReceiver<(usize, anemone_core::events::BrainEvent)> {
         let mut event_receivers = Vec::new();
 
-        for (idx, view) in app.anemones.iter().enumerate() {
-            let brain_arc = view.brain.clone();
+        for (idx, view) in app.anemones.iter_mut().enumerate() {
+            // Subscribe before spawning so no early events are missed
+            let rx = view.handle.subscribe();
 
             // Spawn brain task
-            let brain_for_task = brain_arc.clone();
-            tokio::spawn(async move {
-                tokio::time::sleep(Duration::from_millis(200)).await;
-                let mut brain = brain_for_task.write().await;
-                brain.run().await;
-            });
-
-            // Subscribe to events
-            let rx = {
-                // Use try_read to avoid blocking_read panic inside tokio runtime
-                let brain = brain_arc.try_read().expect("brain lock should be free at startup");
-                brain.subscribe()
-            };
+            if let Some(brain) = view.brain.take() {
+                tokio::spawn(async move {
+                    tokio::time::sleep(Duration::from_millis(200)).await;
+                    brain.run().await;
+                });
+            }
 
             let (fwd_tx, fwd_rx) = tokio::sync::mpsc::unbounded_channel();
             tokio::spawn(async move {
@@ -143,7 +137,7 @@ async fn main() -> io::Result<()> {
 
     // If we're already in Running mode, start brains immediately.
     if app.mode == AppMode::Running {
-        merged_rx = Some(start_brains(&app));
+        merged_rx = Some(start_brains(&mut app));
         brains_started = true;
     }
 
@@ -208,7 +202,7 @@ async fn main() -> io::Result<()> {
                                         // Show a quick message then exit cleanly
                                         app.should_quit = true;
                                     } else if !brains_started {
-                                        merged_rx = Some(start_brains(&app));
+                                        merged_rx = Some(start_brains(&mut app));
                                         brains_started = true;
                                     }
                                 }
@@ -267,7 +261,7 @@ async fn main() -> io::Result<()> {
     // Stop all brains
     for view in &app.anemones {
         let _ = view
-            .command_tx
+            .handle
             .send(anemone_core::brain::BrainCommand::Stop)
             .await;
     }
@@ -1,11 +1,12 @@
 //! Scrollable chat feed — shows thoughts, tool calls, and results.
 //! Design: word-wrapped, colored by phase, with subtle separators.
+//! A thought still streaming in is drawn last, with a cursor.
 
 use ratatui::prelude::*;
-use ratatui::widgets::{Block, BorderType, Borders, Paragraph, Wrap};
+use ratatui::widgets::{Block, BorderType, Borders, Paragraph};
 
-use crate::app::{AnemoneView, ChatSide, Phase};
-use super::{ACCENT, BORDER, TEXT_PRIMARY, TEXT_DIM, TEXT_MUTED, BG, GREEN, BLUE, YELLOW, CYAN, RED};
+use crate::app::{AnemoneView, ChatMessage, ChatSide, Phase};
+use super::{ACCENT, BORDER, TEXT_DIM, TEXT_MUTED, BG, GREEN, BLUE};
 
 pub fn draw(frame: &mut Frame, view: &AnemoneView, area: Rect) {
     let block = Block::default()
@@ -18,7 +19,17 @@ pub fn draw(frame: &mut Frame, view: &AnemoneView, area: Rect) {
     let inner = block.inner(area);
     frame.render_widget(block, area);
 
-    if view.messages.is_empty() {
+    let streaming = view
+        .partial
+        .as_ref()
+        .filter(|p| !p.text.is_empty())
+        .map(|p| ChatMessage {
+            side: ChatSide::Right,
+            text: format!("{}▌", p.text),
+            phase: Phase::Normal,
+        });
+
+    if view.messages.is_empty() && streaming.is_none() {
         let empty = Paragraph::new("  Waiting for first thought... 🪸")
             .style(Style::default().fg(TEXT_MUTED));
         frame.render_widget(empty, inner);
@@ -29,7 +40,7 @@ pub fn draw(frame: &mut Frame, view: &AnemoneView, area: Rect) {
     let visible_height = inner.height as usize;
 
     let mut lines: Vec<Line> = Vec::new();
-    for msg in view.messages.iter() {
+    for msg in view.messages.iter().chain(streaming.iter()) {
         let (fg, prefix, label) = match (&msg.side, &msg.phase) {
             (ChatSide::Right, Phase::Reflection) => (ACCENT, "  ~ ", Some("reflect")),
             (ChatSide::Right, Phase::Planning) => (BLUE, "  ? ", Some("plan")),
@@ -120,16 +131,12 @@ pub fn draw(frame: &mut Frame, view: &AnemoneView, area: Rect) {
     // Auto-scroll to bottom, offset scrolls up from there
     let total_lines = lines.len();
     let scroll_y = if total_lines > visible_height {
-        (total_lines - visible_height).saturating_sub(0) // at bottom
+        (total_lines - visible_height) // at bottom
             .saturating_sub(view.scroll_offset) // user scroll
-            .max(0)
     } else {
         0
     };
 
-    // Clamp scroll_offset so it doesn't go past the top
-    let max_scroll = total_lines.saturating_sub(visible_height);
-
     let paragraph = Paragraph::new(lines)
         .scroll((scroll_y as u16, 0));
     frame.render_widget(paragraph, inner);
@@ -9,7 +9,7 @@ mod switcher;
 pub mod setup;
 
 use ratatui::prelude::*;
-use ratatui::widgets::{Block, BorderType, Borders, Padding, Paragraph};
+use ratatui::widgets::{Block, BorderType, Borders, Paragraph};
 
 use crate::app::App;
 
@@ -22,12 +22,10 @@ pub const TEXT_PRIMARY: Color = Color::Rgb(220, 220, 230);
 pub const TEXT_DIM: Color = Color::Rgb(100, 100, 115);
 pub const TEXT_MUTED: Color = Color::Rgb(65, 65, 75);
 pub const BG: Color = Color::Rgb(15, 15, 20);
-pub const BG_SURFACE: Color = Color::Rgb(22, 22, 30);
 pub const GREEN: Color = Color::Rgb(80, 200, 120);
 pub const BLUE: Color = Color::Rgb(100, 149, 237);
 pub const YELLOW: Color = Color::Rgb(240, 200, 80);
 pub const CYAN: Color = Color::Rgb(100, 210, 230);
-pub const RED: Color = Color::Rgb(220, 80, 80);
 
 /// Render the full TUI layout.
 pub fn draw(frame: &mut Frame, app: &App) {
@@ -133,9 +131,7 @@ fn file_icon(name: &str) -> (&'static str, Color) {
         ("📝", BLUE)
     } else if name.ends_with(".py") {
         ("🐍", GREEN)
-    } else if name.ends_with(".json") {
-        ("⚙", CYAN)
-    } else if name.ends_with(".jsonl") {
+    } else if name.ends_with(".json") || name.ends_with(".jsonl") {
         ("⚙", CYAN)
     } else {
         ("  ", TEXT_DIM)
@@ -5,7 +5,7 @@ use ratatui::widgets::{Block, BorderType, Borders, Paragraph};
 
 use anemone_core::types::ROOM_LOCATIONS;
 use crate::app::AnemoneView;
-use super::{ACCENT, ACCENT_DIM, BORDER, TEXT_DIM, TEXT_MUTED, BG, YELLOW, CYAN};
+use super::{ACCENT, BORDER, TEXT_MUTED, BG, CYAN};
 
 /// Room layout — compact and cozy
 const ROOM_ART: [&str; 12] = [
@@ -89,8 +89,8 @@ fn find_location(x: usize, y: usize) -> &'static str {
     let mut best = "room";
     let mut best_dist = u32::MAX;
     for &(name, lx, ly) in ROOM_LOCATIONS {
-        let dx = (x as i32 - lx as i32).unsigned_abs();
-        let dy = (y as i32 - ly as i32).unsigned_abs();
+        let dx = (x as i32 - lx).unsigned_abs();
+        let dy = (y as i32 - ly).unsigned_abs();
         let dist = dx + dy;
         if dist < best_dist {
             best_dist = dist;
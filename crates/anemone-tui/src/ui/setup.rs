@@ -28,6 +28,8 @@ pub enum SetupStep {
     Complete,
 }
 
+// Key validation is rendered but not yet driven by the wizard.
+#[allow(dead_code)]
 #[derive(Debug, Clone)]
 pub enum ValidationStatus {
     None,
@@ -53,6 +55,7 @@ pub struct SetupState {
     pub providers: Vec<ProviderOption>,
     pub key_input: String,
     pub url_input: String,
+    #[allow(dead_code)]
     pub key_masked: bool,
     pub validation_status: ValidationStatus,
     pub name_input: String,
@@ -74,6 +77,14 @@ impl SetupState {
                     needs_key: true,
                     needs_url: false,
                 },
+                ProviderOption {
+                    name: "Anthropic".into(),
+                    provider_id: "anthropic".into(),
+                    default_model: "claude-sonnet-4-5".into(),
+                    description: "Claude models, native Messages API".into(),
+                    needs_key: true,
+                    needs_url: false,
+                },
                 ProviderOption {
                     name: "OpenRouter".into(),
                     provider_id: "openrouter".into(),
@@ -5,14 +5,16 @@ use ratatui::widgets::Paragraph;
 
 use anemone_core::types::BrainState;
 use crate::app::{AnemoneView, App};
-use super::{ACCENT, TEXT_DIM, TEXT_MUTED, BG, GREEN, BLUE, YELLOW, RED};
+use super::{ACCENT, TEXT_DIM, TEXT_MUTED, BG, GREEN, BLUE, YELLOW, CYAN};
 
-pub fn draw(frame: &mut Frame, view: &AnemoneView, app: &App, area: Rect) {
+pub fn draw(frame: &mut Frame, view: &AnemoneView, _app: &App, area: Rect) {
     let (state_str, state_icon, state_color) = match view.state {
         BrainState::Idle => ("idle", "◌", TEXT_MUTED),
         BrainState::Thinking => ("thinking", "◉", GREEN),
         BrainState::Reflecting => ("reflecting", "◎", ACCENT),
         BrainState::Planning => ("planning", "◈", BLUE),
+        BrainState::Sleeping => ("sleeping", "☾", TEXT_DIM),
+        BrainState::Dreaming => ("dreaming", "≈", CYAN),
     };
 
     let mut spans = vec![
@@ -36,6 +38,18 @@ pub fn draw(frame: &mut Frame, view: &AnemoneView, app: &App, area: Rect) {
         ));
     }
 
+    // Token usage and spend
+    if view.usage.total.calls > 0 {
+        spans.push(Span::styled(
+            format!(
+                " 🪙 ${:.2} · {} tok ",
+                view.usage.total.cost_usd,
+                format_tokens(view.usage.total.total_tokens())
+            ),
+            Style::default().fg(TEXT_MUTED),
+        ));
+    }
+
     // Activity
     if !view.activity.is_empty() {
         spans.push(Span::styled("│ ", Style::default().fg(TEXT_MUTED)));
@@ -49,3 +63,12 @@ pub fn draw(frame: &mut Frame, view: &AnemoneView, app: &App, area: Rect) {
         .style(Style::default().bg(BG));
     frame.render_widget(status, area);
 }
+
+/// Compact token count: 950, 12.3k, 1.2M.
+fn format_tokens(tokens: u64) -> String {
+    match tokens {
+        0..=999 => tokens.to_string(),
+        1_000..=999_999 => format!("{:.1}k", tokens as f64 / 1e3),
+        _ => format!("{:.1}M", tokens as f64 / 1e6),
+    }
+}
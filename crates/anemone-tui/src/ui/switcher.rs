@@ -4,19 +4,20 @@ use ratatui::prelude::*;
 use ratatui::widgets::{Paragraph, Tabs};
 
 use crate::app::App;
-use super::{ACCENT, ACCENT_DIM, BORDER, TEXT_DIM, TEXT_MUTED, BG};
+use super::{ACCENT, ACCENT_DIM, BORDER, TEXT_MUTED, BG};
 
 pub fn draw(frame: &mut Frame, app: &App, area: Rect) {
     let titles: Vec<String> = app
         .anemones
         .iter()
-        .enumerate()
-        .map(|(_i, v)| {
+        .map(|v| {
             let indicator = match v.state {
                 anemone_core::types::BrainState::Thinking => "●",
                 anemone_core::types::BrainState::Reflecting => "◎",
                 anemone_core::types::BrainState::Planning => "◈",
                 anemone_core::types::BrainState::Idle => "○",
+                anemone_core::types::BrainState::Sleeping => "☾",
+                anemone_core::types::BrainState::Dreaming => "≈",
             };
             format!(" {} {} ", indicator, v.name)
         })
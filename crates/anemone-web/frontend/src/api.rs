@@ -21,6 +21,14 @@ pub async fn fetch_status(anemone_id: &str) -> Result<Value, String> {
     resp.json().await.map_err(|e| e.to_string())
 }
 
+pub async fn fetch_events(anemone_id: &str, limit: usize) -> Result<Vec<Value>, String> {
+    let resp = Request::get(&format!("/api/events?anemone={}&limit={}", anemone_id, limit))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    resp.json().await.map_err(|e| e.to_string())
+}
+
 pub async fn send_message(anemone_id: &str, text: &str) -> Result<(), String> {
     let body = serde_json::json!({"text": text});
     Request::post(&format!("/api/message?anemone={}", anemone_id))
@@ -1,4 +1,5 @@
-//! Chat feed component — displays messages.
+//! Chat feed component — displays messages, plus the thought still
+//! streaming in (if any) at the bottom.
 
 use dioxus::prelude::*;
 use crate::ChatMsg;
@@ -6,6 +7,7 @@ use crate::ChatMsg;
 #[derive(Clone, PartialEq, Props)]
 pub struct ChatFeedProps {
     messages: Vec<ChatMsg>,
+    partial: String,
 }
 
 pub fn ChatFeed(props: ChatFeedProps) -> Element {
@@ -18,6 +20,9 @@ pub fn ChatFeed(props: ChatFeedProps) -> Element {
                     "{msg.text}"
                 }
             }
+            if !props.partial.is_empty() {
+                div { class: "msg right streaming", "{props.partial}" }
+            }
             div { id: "chat-bottom" }
         }
     }
@@ -62,6 +62,8 @@ fn state_color(state: &str) -> &'static str {
         "thinking" => "#22c55e",
         "reflecting" => "#a855f7",
         "planning" => "#3b82f6",
+        "sleeping" => "#334155",
+        "dreaming" => "#6366f1",
         _ => "#64748b",
     }
 }
@@ -147,6 +149,8 @@ fn draw_room(canvas_id: &str, pos: &Position, state: &str, activity: &str, name:
         "thinking" => "#22c55e",
         "reflecting" => "#a855f7",
         "planning" => "#3b82f6",
+        "sleeping" => "#475569",
+        "dreaming" => "#818cf8",
         _ => "#f59e0b",
     };
     ctx.begin_path();
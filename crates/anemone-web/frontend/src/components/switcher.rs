@@ -26,6 +26,8 @@ pub fn Switcher(props: SwitcherProps) -> Element {
                         "thinking" => rsx! { span { " *" } },
                         "reflecting" => rsx! { span { " ~" } },
                         "planning" => rsx! { span { " ?" } },
+                        "sleeping" => rsx! { span { " z" } },
+                        "dreaming" => rsx! { span { " ≈" } },
                         _ => rsx! {},
                     }
                 }
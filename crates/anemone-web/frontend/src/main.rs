@@ -28,6 +28,47 @@ pub struct ChatMsg {
     pub phase: String,    // "normal", "reflection", "planning"
 }
 
+/// How many journaled events to load into the chat feed.
+const HISTORY_LIMIT: usize = 200;
+
+/// Convert a serialized `EventEntry` (`{"type": ..., "text": ...}`) into a
+/// chat message, if it carries anything to show.
+fn entry_to_chat(entry: &serde_json::Value) -> Option<ChatMsg> {
+    let event_type = entry.get("type").and_then(|v| v.as_str()).unwrap_or("");
+    let text = entry
+        .get("text")
+        .or_else(|| entry.get("output"))
+        .or_else(|| entry.get("command"))
+        .or_else(|| entry.get("content"))
+        .and_then(|v| v.as_str())
+        .unwrap_or("");
+    if text.is_empty() {
+        return None;
+    }
+
+    let (side, phase) = match event_type {
+        "thought" => ("right", "normal"),
+        "reflection" | "dream" => ("right", "reflection"),
+        "planning" => ("right", "planning"),
+        "tool_call" => ("right", "normal"),
+        "tool_result" => ("left", "normal"),
+        "error" => ("system", "normal"),
+        _ => ("system", "normal"),
+    };
+    let prefix = match event_type {
+        "tool_call" => {
+            let tool = entry.get("tool").and_then(|v| v.as_str()).unwrap_or("?");
+            format!("[{}] ", tool)
+        }
+        _ => String::new(),
+    };
+    Some(ChatMsg {
+        side: side.to_string(),
+        text: format!("{}{}", prefix, text),
+        phase: phase.to_string(),
+    })
+}
+
 fn main() {
     dioxus::launch(App);
 }
@@ -39,6 +80,8 @@ fn App() -> Element {
     let mut state = use_signal(|| "idle".to_string());
     let mut activity = use_signal(|| String::new());
     let mut messages = use_signal(|| Vec::<ChatMsg>::new());
+    let mut partial = use_signal(|| String::new());
+    let mut partial_tool = use_signal(|| None::<String>);
     let mut conversing = use_signal(|| false);
     let mut countdown = use_signal(|| 0u32);
     let mut name = use_signal(|| "anemone".to_string());
@@ -69,7 +112,7 @@ fn App() -> Element {
         });
     });
 
-    // Connect WebSocket when active_id changes
+    // Load history, then connect WebSocket when active_id changes
     let ws_active_id = active_id();
     use_effect(move || {
         let id = ws_active_id.clone();
@@ -77,6 +120,9 @@ fn App() -> Element {
             return;
         }
         spawn(async move {
+            if let Ok(history) = api::fetch_events(&id, HISTORY_LIMIT).await {
+                messages.set(history.iter().filter_map(entry_to_chat).collect());
+            }
             ws::connect_ws(
                 &id,
                 move |event| {
@@ -91,10 +137,49 @@ fn App() -> Element {
                         Some("status") => {
                             if let Some(data) = event.get("data") {
                                 if let Some(s) = data.get("state").and_then(|v| v.as_str()) {
+                                    if s != "thinking" {
+                                        partial.set(String::new());
+                                        partial_tool.set(None);
+                                    }
                                     state.set(s.to_string());
                                 }
                             }
                         }
+                        Some("thought_delta") => {
+                            if let Some(data) = event.get("data") {
+                                let delta = data.get("delta").and_then(|v| v.as_str()).unwrap_or("");
+                                let tool = data.get("tool").and_then(|v| v.as_str());
+                                let mut text = partial();
+                                // Label a tool call when its arguments start streaming
+                                if tool.is_some() && tool != partial_tool().as_deref() {
+                                    if !text.is_empty() {
+                                        text.push('\n');
+                                    }
+                                    text.push_str(&format!("[{}] ", tool.unwrap_or("?")));
+                                    partial_tool.set(tool.map(String::from));
+                                }
+                                text.push_str(delta);
+                                partial.set(text);
+                            }
+                        }
+                        Some("tool_result_delta") => {
+                            if let Some(data) = event.get("data") {
+                                let delta = data.get("delta").and_then(|v| v.as_str()).unwrap_or("");
+                                let tool = data.get("tool").and_then(|v| v.as_str()).unwrap_or("?");
+                                let label = format!("{} result", tool);
+                                let mut text = partial();
+                                // Output of a tool still running, under its own label
+                                if partial_tool().as_deref() != Some(label.as_str()) {
+                                    if !text.is_empty() {
+                                        text.push('\n');
+                                    }
+                                    text.push_str(&format!("[{}] ", label));
+                                    partial_tool.set(Some(label));
+                                }
+                                text.push_str(delta);
+                                partial.set(text);
+                            }
+                        }
                         Some("activity") => {
                             if let Some(data) = event.get("data") {
                                 let detail = data
@@ -106,48 +191,13 @@ fn App() -> Element {
                             }
                         }
                         Some("entry") => {
-                            if let Some(data) = event.get("data") {
-                                let event_type = data
-                                    .get("event_type")
-                                    .and_then(|v| v.as_str())
-                                    .unwrap_or("");
-                                let text = data
-                                    .get("data")
-                                    .and_then(|d| {
-                                        d.get("text")
-                                            .or_else(|| d.get("output"))
-                                            .or_else(|| d.get("command"))
-                                    })
-                                    .and_then(|v| v.as_str())
-                                    .unwrap_or("");
-
-                                if !text.is_empty() {
-                                    let (side, phase) = match event_type {
-                                        "thought" => ("right", "normal"),
-                                        "reflection" => ("right", "reflection"),
-                                        "planning" => ("right", "planning"),
-                                        "tool_call" => ("right", "normal"),
-                                        "tool_result" => ("left", "normal"),
-                                        "error" => ("system", "normal"),
-                                        _ => ("system", "normal"),
-                                    };
-                                    let prefix = match event_type {
-                                        "tool_call" => {
-                                            let tool = data
-                                                .get("data")
-                                                .and_then(|d| d.get("tool"))
-                                                .and_then(|v| v.as_str())
-                                                .unwrap_or("?");
-                                            format!("[{}] ", tool)
-                                        }
-                                        _ => String::new(),
-                                    };
-                                    messages.push(ChatMsg {
-                                        side: side.to_string(),
-                                        text: format!("{}{}", prefix, text),
-                                        phase: phase.to_string(),
-                                    });
-                                }
+                            let entry_type = event["data"].get("type").and_then(|v| v.as_str());
+                            if matches!(entry_type, Some("thought" | "tool_call" | "tool_result" | "error")) {
+                                partial.set(String::new());
+                                partial_tool.set(None);
+                            }
+                            if let Some(msg) = event.get("data").and_then(entry_to_chat) {
+                                messages.push(msg);
                             }
                         }
                         Some("conversation") => {
@@ -198,6 +248,8 @@ fn App() -> Element {
                 on_switch: move |id: String| {
                     active_id.set(id.clone());
                     messages.set(Vec::new());
+                    partial.set(String::new());
+                    partial_tool.set(None);
                     let a = anemones().iter().find(|a| a.id == id).cloned();
                     if let Some(a) = a {
                         name.set(a.name);
@@ -219,6 +271,7 @@ fn App() -> Element {
                 div { class: "chat-panel",
                     components::chat_feed::ChatFeed {
                         messages: messages(),
+                        partial: partial(),
                     }
 
                     components::input_bar::InputBar {
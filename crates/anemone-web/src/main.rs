@@ -9,11 +9,12 @@ use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 use tokio::sync::RwLock;
-use tracing::info;
+use tracing::{error, info};
 
-use anemone_core::brain::Brain;
+use anemone_core::brain::{Brain, BrainCommand, BrainHandle};
 use anemone_core::config::Config;
 use anemone_core::identity;
+use anemone_core::mcp::McpServer;
 
 use server::AppState;
 
@@ -31,10 +32,7 @@ fn anemone_id_from_box(box_path: &Path) -> String {
 }
 
 /// Discover all *_box/ directories with valid identity.json.
-fn discover_anemones(
-    project_root: &Path,
-    config: &Config,
-) -> HashMap<String, Arc<RwLock<Brain>>> {
+fn discover_anemones(project_root: &Path, config: &Config) -> HashMap<String, Brain> {
     let mut brains = HashMap::new();
 
     let mut boxes: Vec<PathBuf> = Vec::new();
@@ -57,7 +55,7 @@ fn discover_anemones(
             Ok(Some(ident)) => {
                 let anemone_id = anemone_id_from_box(box_path);
                 let brain = Brain::new(ident, box_path.clone(), config.clone());
-                brains.insert(anemone_id, Arc::new(RwLock::new(brain)));
+                brains.insert(anemone_id, brain);
             }
             Ok(None) => {
                 info!("Skipping {:?} — no identity.json", box_path);
@@ -71,15 +69,53 @@ fn discover_anemones(
     brains
 }
 
+/// Run one anemone and serve it over MCP on stdin/stdout until stdin closes.
+async fn serve_mcp_stdio(mut brains: HashMap<String, Brain>, anemone_id: Option<&str>) {
+    let id = match anemone_id {
+        Some(id) => id.to_string(),
+        None if brains.len() == 1 => brains.keys().next().cloned().unwrap_or_default(),
+        None => {
+            let mut ids: Vec<&String> = brains.keys().collect();
+            ids.sort();
+            let ids: Vec<&str> = ids.into_iter().map(String::as_str).collect();
+            eprintln!("  Pick an anemone: anemone-web --mcp-stdio <id>  (found: {})", ids.join(", "));
+            std::process::exit(1);
+        }
+    };
+    let Some(brain) = brains.remove(&id) else {
+        eprintln!("  No anemone '{}' here.", id);
+        std::process::exit(1);
+    };
+
+    let handle = brain.handle();
+    let task = tokio::spawn(brain.run());
+    info!("Serving {} over MCP on stdio", id);
+    let server = McpServer::new(handle.clone());
+    if let Err(e) = server.serve(tokio::io::stdin(), tokio::io::stdout()).await {
+        error!("MCP stdio failed: {}", e);
+    }
+
+    let _ = handle.send(BrainCommand::Stop).await;
+    let _ = task.await;
+}
+
 #[tokio::main]
 async fn main() {
-    // Initialize tracing
-    tracing_subscriber::fmt()
-        .with_env_filter(
-            tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| "info".into()),
-        )
-        .init();
+    // `--mcp-stdio [ID]` serves one anemone over MCP instead of the web UI
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let mcp_stdio = args.first().is_some_and(|a| a == "--mcp-stdio");
+
+    // Initialize tracing — on stderr when stdout carries MCP
+    let filter = tracing_subscriber::EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| "info".into());
+    if mcp_stdio {
+        tracing_subscriber::fmt()
+            .with_env_filter(filter)
+            .with_writer(std::io::stderr)
+            .init();
+    } else {
+        tracing_subscriber::fmt().with_env_filter(filter).init();
+    }
 
     // Determine project root (parent of the binary or current dir)
     let project_root = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
@@ -90,43 +126,43 @@ async fn main() {
     // Discover anemones
     let brains = discover_anemones(&project_root, &config);
 
+    if mcp_stdio {
+        serve_mcp_stdio(brains, args.get(1).map(String::as_str)).await;
+        return;
+    }
+
     if brains.is_empty() {
         eprintln!("\n  No anemones found (no *_box/ directories with identity.json).");
         eprintln!("  Create one by sending POST /api/anemones with {{\"name\": \"YourName\"}}");
         eprintln!("  Or create a directory like 'coral_box/' and run the onboarding.\n");
     } else {
-        let names: Vec<String> = {
-            let mut names = Vec::new();
-            for (id, brain_arc) in &brains {
-                let brain = brain_arc.read().await;
-                names.push(format!("{} ({})", brain.identity.name, id));
-            }
-            names
-        };
+        let names: Vec<String> = brains
+            .iter()
+            .map(|(id, brain)| format!("{} ({})", brain.handle().identity().name, id))
+            .collect();
         eprintln!("\n  Found {} anemone(s): {}", brains.len(), names.join(", "));
     }
 
+    // Start all brains, keeping only their handles
+    let mut handles: HashMap<String, BrainHandle> = HashMap::new();
+    for (anemone_id, brain) in brains {
+        let handle = brain.handle();
+        let name = handle.identity().name;
+        let id = anemone_id.clone();
+        tokio::spawn(async move {
+            // Small delay so the server binds port first
+            tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+            info!("{} ({}) starting...", name, id);
+            brain.run().await;
+        });
+        handles.insert(anemone_id, handle);
+    }
+
     let state = Arc::new(AppState {
-        brains: RwLock::new(brains),
+        brains: RwLock::new(handles),
         project_root: project_root.clone(),
     });
 
-    // Start all brains
-    {
-        let brains = state.brains.read().await;
-        for (anemone_id, brain_arc) in brains.iter() {
-            let brain_for_task = Arc::clone(brain_arc);
-            let id = anemone_id.clone();
-            tokio::spawn(async move {
-                // Small delay so the server binds port first
-                tokio::time::sleep(std::time::Duration::from_millis(500)).await;
-                let mut brain = brain_for_task.write().await;
-                info!("{} ({}) starting...", brain.identity.name, id);
-                brain.run().await;
-            });
-        }
-    }
-
     let state_for_shutdown = Arc::clone(&state);
     let app = server::router(state);
 
@@ -151,12 +187,8 @@ async fn main() {
 
         // Send stop command to all brains
         let brains = state_for_shutdown.brains.read().await;
-        for (id, brain_arc) in brains.iter() {
-            let brain = brain_arc.read().await;
-            let _ = brain
-                .command_tx
-                .send(anemone_core::brain::BrainCommand::Stop)
-                .await;
+        for (id, handle) in brains.iter() {
+            let _ = handle.send(BrainCommand::Stop).await;
             info!("{} stopping...", id);
         }
     };
@@ -11,9 +11,11 @@ use serde::Deserialize;
 use serde_json::{json, Value};
 use tracing::info;
 
-use anemone_core::brain::{Brain, BrainCommand};
+use anemone_core::brain::{Brain, BrainCommand, BrainHandle};
 use anemone_core::config::Config;
 use anemone_core::identity;
+use anemone_core::memory::inspect::{MemoryReply, MemoryRequest};
+use anemone_core::memory::MemoryQuery;
 
 use super::AppState;
 
@@ -24,11 +26,19 @@ pub fn routes() -> Router<Arc<AppState>> {
         .route("/api/events", get(get_events))
         .route("/api/raw", get(get_raw))
         .route("/api/status", get(get_status))
+        .route("/api/usage", get(get_usage))
         .route("/api/focus-mode", post(post_focus_mode))
         .route("/api/message", post(post_message))
         .route("/api/snapshot", post(post_snapshot))
         .route("/api/files", get(get_files))
-        .route("/api/files/{path:*}", get(get_file))
+        .route("/api/files/{*path}", get(get_file))
+        .route("/api/memories", get(list_memories))
+        .route("/api/reflections/graph", get(get_reflection_graph))
+        .route("/api/memories/search", get(search_memories))
+        .route(
+            "/api/memories/{id}",
+            get(get_memory).patch(patch_memory).delete(delete_memory),
+        )
 }
 
 #[derive(Deserialize)]
@@ -43,20 +53,12 @@ struct LimitQuery {
 }
 
 /// Resolve brain by ?anemone=ID query param, or default to first.
-async fn resolve_brain(
-    state: &AppState,
-    anemone_id: Option<&str>,
-) -> Option<(String, Arc<tokio::sync::RwLock<Brain>>)> {
+pub(super) async fn resolve_brain(state: &AppState, anemone_id: Option<&str>) -> Option<(String, BrainHandle)> {
     let brains = state.brains.read().await;
     if let Some(id) = anemone_id {
-        brains
-            .get(id)
-            .map(|b| (id.to_string(), Arc::clone(b)))
+        brains.get(id).map(|b| (id.to_string(), b.clone()))
     } else {
-        brains
-            .iter()
-            .next()
-            .map(|(id, b)| (id.clone(), Arc::clone(b)))
+        brains.iter().next().map(|(id, b)| (id.clone(), b.clone()))
     }
 }
 
@@ -65,13 +67,13 @@ async fn resolve_brain(
 async fn list_anemones(State(state): State<Arc<AppState>>) -> Json<Value> {
     let brains = state.brains.read().await;
     let mut list = Vec::new();
-    for (anemone_id, brain_arc) in brains.iter() {
-        let brain = brain_arc.read().await;
+    for (anemone_id, handle) in brains.iter() {
+        let snapshot = handle.snapshot();
         list.push(json!({
             "id": anemone_id,
-            "name": brain.identity.name,
-            "state": brain.state,
-            "thought_count": brain.thought_count,
+            "name": snapshot.identity.name,
+            "state": snapshot.state,
+            "thought_count": snapshot.thought_count,
         }));
     }
     Json(json!(list))
@@ -116,18 +118,14 @@ async fn create_anemone(
     let config_path = state.project_root.join("config.yaml");
     let config = Config::load(&config_path).unwrap_or_default();
     let brain = Brain::new(ident, box_path, config);
-    let brain_arc = Arc::new(tokio::sync::RwLock::new(brain));
+    let handle = brain.handle();
 
     // Start the brain
-    let brain_for_task = Arc::clone(&brain_arc);
-    tokio::spawn(async move {
-        let mut brain = brain_for_task.write().await;
-        brain.run().await;
-    });
+    tokio::spawn(brain.run());
 
     {
         let mut brains = state.brains.write().await;
-        brains.insert(anemone_id.clone(), brain_arc);
+        brains.insert(anemone_id.clone(), handle);
     }
 
     info!("Created and started new anemone: {} ({})", name, anemone_id);
@@ -141,9 +139,8 @@ async fn get_identity(
     Query(q): Query<AnemoneQuery>,
 ) -> Json<Value> {
     match resolve_brain(&state, q.anemone.as_deref()).await {
-        Some((_, brain_arc)) => {
-            let brain = brain_arc.read().await;
-            Json(serde_json::to_value(&brain.identity).unwrap_or(json!({})))
+        Some((_, handle)) => {
+            Json(serde_json::to_value(handle.identity()).unwrap_or(json!({})))
         }
         None => Json(json!({"error": "no anemone found"})),
     }
@@ -157,11 +154,7 @@ async fn get_events(
 ) -> Json<Value> {
     let limit = q.limit.unwrap_or(100);
     match resolve_brain(&state, q.anemone.as_deref()).await {
-        Some((_, brain_arc)) => {
-            let brain = brain_arc.read().await;
-            let start = brain.events.len().saturating_sub(limit);
-            Json(json!(&brain.events[start..]))
-        }
+        Some((_, handle)) => Json(json!(handle.recent_events(limit))),
         None => Json(json!([])),
     }
 }
@@ -174,11 +167,7 @@ async fn get_raw(
 ) -> Json<Value> {
     let limit = q.limit.unwrap_or(20);
     match resolve_brain(&state, q.anemone.as_deref()).await {
-        Some((_, brain_arc)) => {
-            let brain = brain_arc.read().await;
-            let start = brain.api_calls.len().saturating_sub(limit);
-            Json(json!(&brain.api_calls[start..]))
-        }
+        Some((_, handle)) => Json(json!(handle.recent_api_calls(limit))),
         None => Json(json!([])),
     }
 }
@@ -190,20 +179,33 @@ async fn get_status(
     Query(q): Query<AnemoneQuery>,
 ) -> Json<Value> {
     match resolve_brain(&state, q.anemone.as_deref()).await {
-        Some((_, brain_arc)) => {
-            let brain = brain_arc.read().await;
+        Some((_, handle)) => {
+            let snapshot = handle.snapshot();
             Json(json!({
-                "state": brain.state,
-                "thought_count": brain.thought_count,
-                "name": brain.identity.name,
-                "position": brain.position,
-                "focus_mode": false, // TODO: expose focus_mode field
+                "state": snapshot.state,
+                "thought_count": snapshot.thought_count,
+                "name": snapshot.identity.name,
+                "position": snapshot.position,
+                "focus_mode": snapshot.focus_mode,
+                "budget": handle.budget(),
             }))
         }
         None => Json(json!({"error": "no anemone found"})),
     }
 }
 
+// --- Usage ---
+
+async fn get_usage(
+    State(state): State<Arc<AppState>>,
+    Query(q): Query<AnemoneQuery>,
+) -> Json<Value> {
+    match resolve_brain(&state, q.anemone.as_deref()).await {
+        Some((_, handle)) => Json(json!(handle.usage())),
+        None => Json(json!({"error": "no anemone found"})),
+    }
+}
+
 // --- Focus mode ---
 
 #[derive(Deserialize)]
@@ -218,12 +220,8 @@ async fn post_focus_mode(
 ) -> Json<Value> {
     let enabled = body.enabled.unwrap_or(false);
     match resolve_brain(&state, q.anemone.as_deref()).await {
-        Some((_, brain_arc)) => {
-            let brain = brain_arc.read().await;
-            let _ = brain
-                .command_tx
-                .send(BrainCommand::SetFocusMode(enabled))
-                .await;
+        Some((_, handle)) => {
+            let _ = handle.send(BrainCommand::SetFocusMode(enabled)).await;
             Json(json!({"ok": true, "focus_mode": enabled}))
         }
         None => Json(json!({"ok": false, "error": "no anemone found"})),
@@ -248,14 +246,8 @@ async fn post_message(
     };
 
     match resolve_brain(&state, q.anemone.as_deref()).await {
-        Some((_, brain_arc)) => {
-            let brain = brain_arc.read().await;
-            let cmd = if brain.is_waiting_for_reply() {
-                BrainCommand::ConversationReply(text)
-            } else {
-                BrainCommand::UserMessage(text)
-            };
-            let _ = brain.command_tx.send(cmd).await;
+        Some((_, handle)) => {
+            let _ = handle.message(text).await;
             Json(json!({"ok": true}))
         }
         None => Json(json!({"ok": false, "error": "no anemone found"})),
@@ -276,9 +268,8 @@ async fn post_snapshot(
 ) -> Json<Value> {
     if let Some(image) = body.image {
         match resolve_brain(&state, q.anemone.as_deref()).await {
-            Some((_, brain_arc)) => {
-                let brain = brain_arc.read().await;
-                let _ = brain.command_tx.send(BrainCommand::Snapshot(image)).await;
+            Some((_, handle)) => {
+                let _ = handle.send(BrainCommand::Snapshot(image)).await;
                 Json(json!({"ok": true}))
             }
             None => Json(json!({"ok": false, "error": "no anemone found"})),
@@ -295,58 +286,21 @@ async fn get_files(
     Query(q): Query<AnemoneQuery>,
 ) -> Json<Value> {
     match resolve_brain(&state, q.anemone.as_deref()).await {
-        Some((_, brain_arc)) => {
-            let brain = brain_arc.read().await;
-            let env_root = brain
-                .env_path
-                .canonicalize()
-                .unwrap_or_else(|_| brain.env_path.clone());
-            let mut files: Vec<String> = Vec::new();
-            collect_files(&env_root, &env_root, &mut files);
-            files.sort();
-            Json(json!({"files": files}))
-        }
+        Some((_, handle)) => Json(json!({"files": handle.files()})),
         None => Json(json!({"files": []})),
     }
 }
 
-fn collect_files(root: &std::path::Path, current: &std::path::Path, out: &mut Vec<String>) {
-    if let Ok(entries) = std::fs::read_dir(current) {
-        for entry in entries.flatten() {
-            let path = entry.path();
-            let name = entry.file_name();
-            if name.to_string_lossy().starts_with('.') {
-                continue;
-            }
-            if path.is_dir() {
-                collect_files(root, &path, out);
-            } else if let Ok(rel) = path.strip_prefix(root) {
-                out.push(rel.to_string_lossy().to_string());
-            }
-        }
-    }
-}
-
 async fn get_file(
     State(state): State<Arc<AppState>>,
     Query(q): Query<AnemoneQuery>,
     Path(path): Path<String>,
 ) -> Json<Value> {
     match resolve_brain(&state, q.anemone.as_deref()).await {
-        Some((_, brain_arc)) => {
-            let brain = brain_arc.read().await;
-            let env_root = brain
-                .env_path
-                .canonicalize()
-                .unwrap_or_else(|_| brain.env_path.clone());
-            let full = env_root.join(&path);
-            let full_real = full
-                .canonicalize()
-                .unwrap_or_else(|_| full.clone());
-
-            if !full_real.starts_with(&env_root) {
+        Some((_, handle)) => {
+            let Some(full_real) = handle.box_path(&path) else {
                 return Json(json!({"path": path, "content": "Blocked: path outside environment."}));
-            }
+            };
 
             match std::fs::read_to_string(&full_real) {
                 Ok(content) => Json(json!({"path": path, "content": content})),
@@ -356,3 +310,111 @@ async fn get_file(
         None => Json(json!({"path": path, "content": "Error: no anemone found"})),
     }
 }
+
+// --- Memories ---
+
+/// Send a memory request to the brain and shape its reply as JSON.
+async fn memory_request(state: &AppState, anemone: Option<&str>, request: MemoryRequest) -> Json<Value> {
+    let Some((_, handle)) = resolve_brain(state, anemone).await else {
+        return Json(json!({"ok": false, "error": "no anemone found"}));
+    };
+    match handle.memory(request).await {
+        Ok(MemoryReply::Page(page)) => Json(json!(page)),
+        Ok(MemoryReply::Results(results)) => Json(json!({"results": results})),
+        Ok(MemoryReply::Detail(detail)) => Json(json!(detail)),
+        Ok(MemoryReply::Graph(graph)) => Json(json!(graph)),
+        Ok(MemoryReply::Updated(memory)) | Ok(MemoryReply::Deleted(memory)) => {
+            Json(json!({"ok": true, "memory": memory}))
+        }
+        Err(e) => Json(json!({"ok": false, "error": e.to_string()})),
+    }
+}
+
+#[derive(Deserialize)]
+struct MemoryListQuery {
+    anemone: Option<String>,
+    kind: Option<String>,
+    offset: Option<usize>,
+    limit: Option<usize>,
+}
+
+async fn list_memories(
+    State(state): State<Arc<AppState>>,
+    Query(q): Query<MemoryListQuery>,
+) -> Json<Value> {
+    let request = MemoryRequest::List {
+        kind: q.kind,
+        offset: q.offset.unwrap_or(0),
+        limit: q.limit.unwrap_or(50),
+    };
+    memory_request(&state, q.anemone.as_deref(), request).await
+}
+
+#[derive(Deserialize)]
+struct MemorySearchQuery {
+    anemone: Option<String>,
+    q: Option<String>,
+    kind: Option<String>,
+    top_k: Option<usize>,
+}
+
+async fn search_memories(
+    State(state): State<Arc<AppState>>,
+    Query(q): Query<MemorySearchQuery>,
+) -> Json<Value> {
+    let mut query = match q.q.as_deref().map(str::trim) {
+        Some(text) if !text.is_empty() => MemoryQuery::about(text),
+        _ => MemoryQuery::default(),
+    }
+    .with_top_k(q.top_k.unwrap_or(10));
+    if let Some(kind) = q.kind.as_deref() {
+        query = query.with_kind(kind);
+    }
+    memory_request(&state, q.anemone.as_deref(), MemoryRequest::Search(query)).await
+}
+
+async fn get_memory(
+    State(state): State<Arc<AppState>>,
+    Query(q): Query<AnemoneQuery>,
+    Path(id): Path<String>,
+) -> Json<Value> {
+    memory_request(&state, q.anemone.as_deref(), MemoryRequest::Get(id)).await
+}
+
+#[derive(Deserialize)]
+struct MemoryPatchBody {
+    importance: Option<i32>,
+    pinned: Option<bool>,
+}
+
+async fn patch_memory(
+    State(state): State<Arc<AppState>>,
+    Query(q): Query<AnemoneQuery>,
+    Path(id): Path<String>,
+    Json(body): Json<MemoryPatchBody>,
+) -> Json<Value> {
+    if body.importance.is_none() && body.pinned.is_none() {
+        return Json(json!({"ok": false, "error": "nothing to change"}));
+    }
+    let request = MemoryRequest::Update {
+        id,
+        importance: body.importance,
+        pinned: body.pinned,
+    };
+    memory_request(&state, q.anemone.as_deref(), request).await
+}
+
+async fn delete_memory(
+    State(state): State<Arc<AppState>>,
+    Query(q): Query<AnemoneQuery>,
+    Path(id): Path<String>,
+) -> Json<Value> {
+    memory_request(&state, q.anemone.as_deref(), MemoryRequest::Delete(id)).await
+}
+
+async fn get_reflection_graph(
+    State(state): State<Arc<AppState>>,
+    Query(q): Query<AnemoneQuery>,
+) -> Json<Value> {
+    memory_request(&state, q.anemone.as_deref(), MemoryRequest::ReflectionGraph).await
+}
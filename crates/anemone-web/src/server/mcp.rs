@@ -0,0 +1,102 @@
+//! MCP over streamable HTTP — each anemone as an MCP server other agents and
+//! editors can connect to.
+//!
+//! Clients POST JSON-RPC to `/mcp/{anemone_id}` (or `/mcp` while only one
+//! anemone is running) and get the reply back as JSON. There are no server-initiated
+//! messages, so there's no SSE stream to GET.
+
+use std::sync::Arc;
+
+use axum::{
+    body::Bytes,
+    extract::{Path, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::post,
+    Json, Router,
+};
+use serde_json::json;
+
+use anemone_core::mcp::McpServer;
+
+use super::api::resolve_brain;
+use super::AppState;
+
+pub fn routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/mcp/{anemone_id}", post(post_mcp).get(no_stream).delete(no_session))
+        .route("/mcp", post(post_mcp_default).get(no_stream).delete(no_session))
+}
+
+async fn post_mcp(
+    State(state): State<Arc<AppState>>,
+    Path(anemone_id): Path<String>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    serve(&state, Some(&anemone_id), &headers, &body).await
+}
+
+async fn post_mcp_default(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    serve(&state, None, &headers, &body).await
+}
+
+async fn serve(state: &AppState, anemone_id: Option<&str>, headers: &HeaderMap, body: &[u8]) -> Response {
+    // Browsers send an Origin; only pages served from this machine may call
+    // in, so a web page can't drive an anemone through DNS rebinding
+    if let Some(origin) = headers.get(header::ORIGIN) {
+        if !origin.to_str().is_ok_and(is_local_origin) {
+            return (StatusCode::FORBIDDEN, "Origin not allowed").into_response();
+        }
+    }
+
+    // Without an id, which anemone answers is only clear when there's one
+    if anemone_id.is_none() && state.brains.read().await.len() > 1 {
+        let message = "several anemones are running; connect to /mcp/{anemone_id}";
+        return rpc_error(StatusCode::BAD_REQUEST, message);
+    }
+    let Some((_, handle)) = resolve_brain(state, anemone_id).await else {
+        return rpc_error(StatusCode::NOT_FOUND, "no anemone found");
+    };
+
+    let text = String::from_utf8_lossy(body);
+    match McpServer::new(handle).handle_text(&text).await {
+        Some(reply) => Json(reply).into_response(),
+        // Only notifications or responses
+        None => StatusCode::ACCEPTED.into_response(),
+    }
+}
+
+/// A JSON-RPC error for a request that never reached an anemone.
+fn rpc_error(status: StatusCode, message: &str) -> Response {
+    let error = json!({
+        "jsonrpc": "2.0",
+        "id": null,
+        "error": {"code": -32600, "message": message},
+    });
+    (status, Json(error)).into_response()
+}
+
+async fn no_stream() -> StatusCode {
+    StatusCode::METHOD_NOT_ALLOWED
+}
+
+/// Sessions aren't tracked, so there's none to end.
+async fn no_session() -> StatusCode {
+    StatusCode::METHOD_NOT_ALLOWED
+}
+
+/// Whether `origin` (e.g. `http://localhost:8000`) names this machine.
+fn is_local_origin(origin: &str) -> bool {
+    let host = origin.split_once("://").map_or(origin, |(_, rest)| rest);
+    let host = host.split('/').next().unwrap_or("");
+    let host = match host.strip_prefix('[') {
+        Some(v6) => v6.split(']').next().unwrap_or(""),
+        None => host.split(':').next().unwrap_or(""),
+    };
+    matches!(host, "localhost" | "127.0.0.1" | "::1")
+}
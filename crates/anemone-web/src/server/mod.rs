@@ -2,6 +2,7 @@
 //! 1:1 port of Python server.py.
 
 pub mod api;
+pub mod mcp;
 pub mod ws;
 
 use std::collections::HashMap;
@@ -13,11 +14,11 @@ use tokio::sync::RwLock;
 use tower_http::cors::CorsLayer;
 use tower_http::services::{ServeDir, ServeFile};
 
-use anemone_core::brain::Brain;
+use anemone_core::brain::BrainHandle;
 
-/// Shared application state — all brains keyed by anemone ID.
+/// Shared application state — handles to all brains keyed by anemone ID.
 pub struct AppState {
-    pub brains: RwLock<HashMap<String, Arc<RwLock<Brain>>>>,
+    pub brains: RwLock<HashMap<String, BrainHandle>>,
     pub project_root: PathBuf,
 }
 
@@ -27,6 +28,7 @@ pub fn router(state: Arc<AppState>) -> Router {
     let mut app = Router::new()
         .merge(api::routes())
         .merge(ws::routes())
+        .merge(mcp::routes())
         .layer(cors)
         .with_state(state.clone());
 
@@ -47,13 +47,14 @@ async fn ws_default(
 }
 
 async fn handle_socket(mut socket: WebSocket, anemone_id: String, state: Arc<AppState>) {
-    let brain_arc = {
+    let handle = {
         let brains = state.brains.read().await;
         brains.get(&anemone_id).cloned()
     };
 
-    let brain_arc = match brain_arc {
-        Some(b) => b,
+    // Subscribe to brain events
+    let mut rx = match handle {
+        Some(h) => h.subscribe(),
         None => {
             error!("WebSocket: anemone '{}' not found", anemone_id);
             drop(socket);
@@ -61,12 +62,6 @@ async fn handle_socket(mut socket: WebSocket, anemone_id: String, state: Arc<App
         }
     };
 
-    // Subscribe to brain events
-    let mut rx = {
-        let brain = brain_arc.read().await;
-        brain.subscribe()
-    };
-
     info!("WebSocket client connected to {}", anemone_id);
 
     // Forward events to the WebSocket client